@@ -1,6 +1,6 @@
 use std::net::{IpAddr, ToSocketAddrs};
 
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use gamedig::{
     games::*,
     protocols::types::{CommonResponse, ExtraRequestSettings, TimeoutSettings},
@@ -38,49 +38,8 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Action {
     /// Query game server information
-    Query {
-        /// Unique identifier of the game for which server information is being
-        /// queried.
-        #[arg(short, long)]
-        game: String,
-
-        /// Hostname or IP address of the server.
-        #[arg(short, long)]
-        ip: String,
-
-        /// Optional query port number for the server. If not provided the
-        /// default port for the game is used.
-        #[arg(short, long)]
-        port: Option<u16>,
-
-        /// Specifies the output format
-        #[arg(short, long, default_value = "debug", value_enum)]
-        format: OutputFormat,
-
-        /// Which response variant to use when outputting
-        #[arg(short, long, default_value = "generic")]
-        output_mode: OutputMode,
-
-        /// Optional file path for packet capture file writer
-        ///
-        /// When set a PCAP file will be written to the location. This file can
-        /// be read with a tool like wireshark. The PCAP contains a log of the
-        /// TCP and UDP data sent/recieved by the gamedig library, it does not
-        /// contain an accurate representation of the real packets sent on the
-        /// wire as some information has to be hallucinated in order for it to
-        /// display nicely.
-        #[cfg(feature = "packet_capture")]
-        #[arg(short, long)]
-        capture: Option<std::path::PathBuf>,
-
-        /// Optional timeout settings for the server query
-        #[command(flatten, next_help_heading = "Timeouts")]
-        timeout_settings: Option<TimeoutSettings>,
-
-        /// Optional extra settings for the server query
-        #[command(flatten, next_help_heading = "Query options")]
-        extra_options: Option<ExtraRequestSettings>,
-    },
+    // Boxed, its settings being much larger than the other actions.
+    Query(Box<QueryArgs>),
 
     /// Check out the source code
     Source,
@@ -88,6 +47,54 @@ enum Action {
     License,
 }
 
+#[derive(Args, Debug)]
+struct QueryArgs {
+    /// Unique identifier of the game for which server information is being
+    /// queried.
+    #[arg(short, long)]
+    game: String,
+
+    /// Hostname or IP address of the server.
+    #[arg(short, long)]
+    ip: String,
+
+    /// Optional query port number for the server. If not provided the
+    /// default port for the game is used.
+    #[arg(short, long)]
+    port: Option<u16>,
+
+    /// Specifies the output format
+    #[arg(short, long, default_value = "debug", value_enum)]
+    format: OutputFormat,
+
+    /// Which response variant to use when outputting
+    #[arg(short, long, default_value = "generic")]
+    output_mode: OutputMode,
+
+    /// Optional file path for packet capture file writer
+    ///
+    /// When set a PCAP file will be written to the location. This file can
+    /// be read with a tool like wireshark. The PCAP contains a log of the
+    /// TCP and UDP data sent/recieved by the gamedig library, it does not
+    /// contain an accurate representation of the real packets sent on the
+    /// wire as some information has to be hallucinated in order for it to
+    /// display nicely.
+    ///
+    /// The HTTP based queries are recorded next to it instead, as an HTTP
+    /// Archive (with a `har` extension).
+    #[cfg(feature = "packet_capture")]
+    #[arg(short, long)]
+    capture: Option<std::path::PathBuf>,
+
+    /// Optional timeout settings for the server query
+    #[command(flatten, next_help_heading = "Timeouts")]
+    timeout_settings: Option<TimeoutSettings>,
+
+    /// Optional extra settings for the server query
+    #[command(flatten, next_help_heading = "Query options")]
+    extra_options: Option<ExtraRequestSettings>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, ValueEnum)]
 enum OutputMode {
     /// A generalised response that maps common fields from all game types to
@@ -423,27 +430,68 @@ fn main() -> Result<()> {
     let args = Cli::parse();
 
     match args.action {
-        Action::Query {
-            game,
-            ip,
-            port,
-            format,
-            output_mode,
-            #[cfg(feature = "packet_capture")]
-            capture,
-            timeout_settings,
-            extra_options,
-        } => {
+        Action::Query(query) => {
+            let QueryArgs {
+                game,
+                ip,
+                port,
+                format,
+                output_mode,
+                #[cfg(feature = "packet_capture")]
+                capture,
+                timeout_settings,
+                extra_options,
+            } = *query;
+
             // Process the query command
+            #[cfg(feature = "packet_capture")]
+            let game_id = game.clone();
             let game = find_game(&game)?;
             let mut extra_options = extra_options;
+            #[cfg(feature = "packet_capture")]
+            let host = ip.clone();
             let ip = resolve_ip_or_domain(&ip, &mut extra_options)?;
 
             #[cfg(feature = "packet_capture")]
-            gamedig::capture::setup_capture(capture);
+            let capturing = capture.is_some();
+            #[cfg(feature = "packet_capture")]
+            gamedig::capture::setup_http_capture(capture.clone(), Default::default())?;
+            #[cfg(feature = "packet_capture")]
+            gamedig::capture::setup_capture_with_options(
+                capture,
+                gamedig::capture::CaptureOptions {
+                    annotate_hostnames: true,
+                    name_resolution: true,
+                    ..Default::default()
+                },
+            )?;
+            // Note the hostname the address was resolved from, and the game
+            #[cfg(feature = "packet_capture")]
+            if host.parse::<IpAddr>().is_err() {
+                gamedig::capture::annotate_hostname(&host, &ip);
+            }
+            #[cfg(feature = "packet_capture")]
+            gamedig::capture::annotate_game(&game_id, &ip);
+            // Don't lose the capture when interrupted
+            #[cfg(all(feature = "packet_capture", unix))]
+            if capturing {
+                gamedig::capture::install_signal_flush()?;
+            }
+
+            let result = query_with_timeout_and_extra_settings(game, &ip, port, timeout_settings, extra_options);
+
+            // Embed the outcome of the query at the end of the capture
+            #[cfg(feature = "packet_capture")]
+            if capturing {
+                gamedig::capture::finalize_summary(&gamedig::capture::QuerySummary {
+                    game: game.name.to_string(),
+                    players_online: result.as_ref().ok().map(|r| r.players_online()),
+                    ping: result.as_ref().ok().and_then(|r| r.ping()),
+                    errors: result.as_ref().err().map(ToString::to_string).into_iter().collect(),
+                })?;
+            }
 
-            let result = query_with_timeout_and_extra_settings(game, &ip, port, timeout_settings, extra_options)?;
-            output_result(output_mode, format, result.as_ref());
+            output_result(output_mode, format, result?.as_ref());
         }
         Action::Source => {
             println!("{}", GAMEDIG_HEADER);
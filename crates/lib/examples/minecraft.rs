@@ -21,6 +21,7 @@ fn main() {
         Some(RequestSettings {
             hostname: "mc.hypixel.net".to_string(),
             protocol_version: -1,
+            proxy_protocol_header: None,
         }),
     );
 
@@ -12,6 +12,7 @@ fn main() {
         players: GatherToggle::Enforce, // We want to query for players
         rules: GatherToggle::Skip,      // We don't want to query for rules
         check_app_id: false,            // Loosen up the query a bit by not checking app id
+        ..GatheringSettings::default()
     };
 
     let read_timeout = Duration::from_secs(2);
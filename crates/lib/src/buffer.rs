@@ -1,6 +1,6 @@
 use crate::GDErrorKind::PacketBad;
-use crate::GDErrorKind::PacketUnderflow;
 use crate::GDResult;
+use crate::PacketTooShort;
 use byteorder::{BigEndian, ByteOrder, LittleEndian};
 use std::{convert::TryInto, marker::PhantomData};
 
@@ -109,9 +109,12 @@ impl<'a, B: ByteOrder> Buffer<'a, B> {
         // If the size of `T` is larger than the remaining length, return an error
         // because we don't have enough data left to read.
         if size > remaining {
-            return Err(PacketUnderflow.context(format!(
-                "Size requested {size} was larger than remaining bytes {remaining}"
-            )));
+            return Err(PacketTooShort {
+                expected: size,
+                got: remaining,
+                offset: self.cursor,
+            }
+            .into());
         }
 
         // Slice the data array from the current cursor position for `size` amount of
@@ -132,14 +135,13 @@ impl<'a, B: ByteOrder> Buffer<'a, B> {
     /// # Type Parameters
     ///
     /// * `D` - The type of string decoder to use. This type must implement the
-    /// `StringDecoder` trait with the same byte order as the buffer.
+    ///   `StringDecoder` trait with the same byte order as the buffer.
     ///
     /// # Arguments
     ///
     /// * `until` - An optional delimiter. If provided, the method will read
-    ///   until this
-    /// delimiter is encountered. If not provided, the method will read until
-    /// the default delimiter of the decoder.
+    ///   until this delimiter is encountered. If not provided, the method will
+    ///   read until the default delimiter of the decoder.
     ///
     /// # Errors
     ///
@@ -156,6 +158,9 @@ impl<'a, B: ByteOrder> Buffer<'a, B> {
         // passing in the remaining data slice, the mutable reference to the
         // cursor, and the delimiter.
         let result = D::decode_string(data_slice, &mut self.cursor, delimiter)?;
+        // A string that isn't terminated ends with the data, the cursor
+        // skipping the missing delimiter mustn't go past it.
+        self.cursor = self.cursor.min(self.data.len());
 
         // If decoding was successful, return the decoded string. The cursor
         // position has been updated within the decode_string call to reflect
@@ -291,6 +296,7 @@ impl_buffer_read!(u32, read_u32);
 impl_buffer_read!(i32, read_i32);
 impl_buffer_read!(u64, read_u64);
 impl_buffer_read!(i64, read_i64);
+impl_buffer_read!(u128, read_u128);
 impl_buffer_read!(f32, read_f32);
 impl_buffer_read!(f64, read_f64);
 
@@ -595,10 +601,26 @@ mod tests {
         let data: &[u8] = &[1, 2];
         let mut buffer = Buffer::<LittleEndian>::new(data);
 
-        let result: Result<u32, _> = buffer.read();
+        buffer.move_cursor(1).unwrap();
+        let error = buffer.read::<u32>().unwrap_err();
+        assert_eq!(error, crate::GDErrorKind::PacketUnderflow.into());
         assert_eq!(
-            result.unwrap_err(),
-            crate::GDErrorKind::PacketUnderflow.into()
+            error.packet_too_short(),
+            Some(PacketTooShort {
+                expected: 4,
+                got: 1,
+                offset: 1,
+            })
         );
     }
+
+    #[test]
+    fn test_unterminated_string_stays_within_the_data() {
+        let data: &[u8] = b"Hello";
+        let mut buffer = Buffer::<LittleEndian>::new(data);
+
+        assert_eq!(buffer.read_string::<Utf8Decoder>(None).unwrap(), "Hello");
+        assert_eq!(buffer.remaining_length(), 0);
+        assert!(buffer.read::<u8>().is_err());
+    }
 }
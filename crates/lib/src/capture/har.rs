@@ -0,0 +1,257 @@
+//! HTTP Archive (HAR) captures of the HTTP based queries.
+//!
+//! Synthesizing the TCP packets of an HTTP exchange isn't of much help when
+//! debugging these queries, the requests and responses are recorded as the
+//! entries of a [HAR 1.2](http://www.softwareishard.com/blog/har-12-spec/)
+//! document instead, that browsers' developer tools can open.
+
+use crate::{GDErrorKind::Capture, GDResult};
+use base64::Engine;
+use serde_json::{json, Value};
+use std::{
+    fs::File,
+    io::{Seek, SeekFrom, Write},
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// The HTTP capture, if one was set up.
+pub(crate) static HTTP_CAPTURE_WRITER: Mutex<Option<HttpCaptureWriter<File>>> = Mutex::new(None);
+
+/// Writes the recorded HTTP exchanges as a HAR document.
+///
+/// A HAR document being a single JSON object, the whole document is written
+/// again after every exchange, so that the output is always complete.
+pub(crate) struct HttpCaptureWriter<W: Write + Seek> {
+    output: W,
+    entries: Vec<Value>,
+}
+
+impl<W: Write + Seek> HttpCaptureWriter<W> {
+    pub(crate) fn new(output: W) -> Self {
+        Self {
+            output,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The HAR document of the entries recorded so far.
+    pub(crate) fn document(&self) -> Value {
+        json!({
+            "log": {
+                "version": "1.2",
+                "creator": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+                "entries": self.entries,
+            },
+        })
+    }
+
+    /// Add an entry to the document and write it.
+    pub(crate) fn record(&mut self, entry: Value) -> GDResult<()> {
+        self.entries.push(entry);
+
+        // Entries are only ever added, so the new document always overwrites
+        // the previous one entirely.
+        let document = self.document();
+        self.output
+            .seek(SeekFrom::Start(0))
+            .map_err(|e| Capture.context(e))?;
+        serde_json::to_writer(&mut self.output, &document).map_err(|e| Capture.context(e))?;
+        self.output.flush().map_err(|e| Capture.context(e))
+    }
+
+    pub(crate) fn finish(&mut self) -> GDResult<()> { self.output.flush().map_err(|e| Capture.context(e)) }
+}
+
+/// An HTTP exchange being recorded.
+pub(crate) struct HttpExchange {
+    started: SystemTime,
+    timer: Instant,
+    request: Value,
+    response: Option<Value>,
+}
+
+impl HttpExchange {
+    /// Start recording an exchange, if an HTTP capture was set up.
+    pub(crate) fn begin(request: &ureq::Request, body: Option<&[u8]>) -> Option<Self> {
+        if HTTP_CAPTURE_WRITER.lock().unwrap().is_none() {
+            return None;
+        }
+
+        Some(Self::new(request, body))
+    }
+
+    pub(crate) fn new(request: &ureq::Request, body: Option<&[u8]>) -> Self {
+        let headers = request
+            .header_names()
+            .iter()
+            .flat_map(|name| request.all(name).into_iter().map(move |value| header(name, value)))
+            .collect::<Vec<_>>();
+
+        let mut request_entry = json!({
+            "method": request.method(),
+            "url": request.url(),
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": headers,
+            "queryString": [],
+            "headersSize": -1,
+            "bodySize": body.map_or(0, <[u8]>::len),
+        });
+        if let Some(body) = body {
+            request_entry["postData"] = json!({
+                "mimeType": request.header("Content-Type").unwrap_or_default(),
+                "text": String::from_utf8_lossy(body),
+            });
+        }
+
+        Self {
+            started: SystemTime::now(),
+            timer: Instant::now(),
+            request: request_entry,
+            response: None,
+        }
+    }
+
+    /// Note the status and headers of the response, before its body is read.
+    pub(crate) fn respond(&mut self, response: &ureq::Response) {
+        let headers = response
+            .headers_names()
+            .iter()
+            .flat_map(|name| response.all(name).into_iter().map(move |value| header(name, value)))
+            .collect::<Vec<_>>();
+
+        self.response = Some(json!({
+            "status": response.status(),
+            "statusText": response.status_text(),
+            "httpVersion": response.http_version(),
+            "cookies": [],
+            "headers": headers,
+            "redirectURL": response.header("Location").unwrap_or_default(),
+            "headersSize": -1,
+            "mimeType": response.content_type(),
+        }));
+    }
+
+    /// The HAR entry of the exchange, the response's body being `body`.
+    pub(crate) fn into_entry(self, body: &[u8]) -> Value {
+        let time = self.timer.elapsed().as_secs_f64() * 1000.0;
+
+        let mut response = self.response.unwrap_or_else(|| json!({}));
+        let mime_type = response
+            .as_object_mut()
+            .and_then(|response| response.remove("mimeType"))
+            .unwrap_or_else(|| json!(""));
+        response["bodySize"] = json!(body.len());
+        // Binary bodies can't be stored as text.
+        response["content"] = match std::str::from_utf8(body) {
+            Ok(text) => json!({ "size": body.len(), "mimeType": mime_type, "text": text }),
+            Err(_) => json!({
+                "size": body.len(),
+                "mimeType": mime_type,
+                "text": base64::engine::general_purpose::STANDARD.encode(body),
+                "encoding": "base64",
+            }),
+        };
+
+        json!({
+            "startedDateTime": iso8601(self.started),
+            "time": time,
+            "request": self.request,
+            "response": response,
+            "cache": {},
+            "timings": { "send": 0, "wait": time, "receive": 0 },
+        })
+    }
+
+    /// Write the exchange to the HTTP capture.
+    pub(crate) fn record(self, body: &[u8]) {
+        if let Some(writer) = HTTP_CAPTURE_WRITER.lock().unwrap().as_mut() {
+            let _ = writer.record(self.into_entry(body));
+        }
+    }
+}
+
+fn header(name: &str, value: &str) -> Value { json!({ "name": name, "value": value }) }
+
+/// Format a time as an ISO 8601 UTC date, with milliseconds.
+fn iso8601(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (hours, minutes, seconds_of_minute) = ((seconds / 3600) % 24, (seconds / 60) % 60, seconds % 60);
+
+    // Civil date from the days since the epoch, see
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (seconds / 86400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{hours:02}:{minutes:02}:{seconds_of_minute:02}.{:03}Z",
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io::Cursor, time::Duration};
+
+    #[test]
+    fn iso8601_dates() {
+        assert_eq!(iso8601(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            iso8601(UNIX_EPOCH + Duration::from_millis(1_709_251_199_250)),
+            "2024-02-29T23:59:59.250Z"
+        );
+    }
+
+    #[test]
+    fn exchanges_are_written_as_har() {
+        let request = ureq::get("http://127.0.0.1:8080/status").set("Accept", "application/json");
+        let response = ureq::Response::new(200, "OK", "").unwrap();
+
+        let mut exchange = HttpExchange::new(&request, None);
+        exchange.respond(&response);
+
+        let mut writer = HttpCaptureWriter::new(Cursor::new(Vec::new()));
+        writer.record(exchange.into_entry(b"{\"online\":3}")).unwrap();
+
+        let mut exchange = HttpExchange::new(&request.set("Content-Type", "text/plain"), Some(b"ping"));
+        exchange.respond(&response);
+        writer.record(exchange.into_entry(&[0xFF, 0x00])).unwrap();
+
+        let har: Value = serde_json::from_slice(writer.output.get_ref()).unwrap();
+        let entries = har["log"]["entries"].as_array().unwrap();
+        assert_eq!(har["log"]["version"], "1.2");
+        assert_eq!(entries.len(), 2);
+
+        assert_eq!(entries[0]["request"]["method"], "GET");
+        assert_eq!(entries[0]["request"]["url"], "http://127.0.0.1:8080/status");
+        assert_eq!(
+            entries[0]["request"]["headers"][0],
+            json!({ "name": "accept", "value": "application/json" })
+        );
+        assert!(entries[0]["request"].get("postData").is_none());
+        assert_eq!(entries[0]["response"]["status"], 200);
+        assert_eq!(entries[0]["response"]["content"]["text"], "{\"online\":3}");
+
+        assert_eq!(entries[1]["request"]["postData"]["text"], "ping");
+        assert_eq!(entries[1]["request"]["postData"]["mimeType"], "text/plain");
+        assert_eq!(entries[1]["response"]["content"]["encoding"], "base64");
+        assert_eq!(entries[1]["response"]["content"]["text"], "/wA=");
+    }
+}
@@ -1,39 +1,686 @@
+//! Capture of the exchanged data, to debug queries.
+//!
+//! The queries over UDP or TCP sockets (most protocols, such as Valve,
+//! GameSpy, Quake, Unreal 2 or the Minecraft ones) are captured as the
+//! packets they exchange, in a pcapng file (see [setup_capture]) or any other
+//! output (see [capture_to_writer]).
+//!
+//! The queries over HTTP (Eco, the Epic Online Services, the JSON over HTTP
+//! games such as Vintage Story and the Minetest master server) aren't part of
+//! the packet captures, their requests and responses are recorded in an HTTP
+//! Archive (HAR) file instead, see [setup_http_capture].
+
+pub(crate) mod har;
 pub(crate) mod packet;
 mod pcap;
+#[cfg(unix)]
+mod signal;
 pub(crate) mod socket;
+mod summary;
 pub(crate) mod writer;
 
-use self::{pcap::Pcap, writer::Writer};
-use pcap_file::pcapng::{blocks::interface_description::InterfaceDescriptionBlock, PcapNgBlock, PcapNgWriter};
-use std::path::PathBuf;
+pub use packet::{Direction, OwnedCapturePacket, Protocol};
+#[cfg(unix)]
+pub use signal::install_signal_flush;
+pub use summary::{QuerySummary, SUMMARY_PEN};
+
+use self::{
+    pcap::{interface_block, new_section_writer, Pcap},
+    writer::{with_writer, SamplingWriter, TeeWriter, Writer, CAPTURE_WRITER, SCOPED_WRITER},
+};
+use crate::{
+    transport::{ExchangedPacket, PacketDirection},
+    GDErrorKind::{Capture, InvalidInput},
+    GDResult,
+};
+use pcap_file::pcapng::{PcapNgBlock, PcapNgWriter};
+use std::{
+    fs::File,
+    io::Write,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+/// Start capturing to a new file at `file_path` (with a `pcap` extension), if
+/// any.
+///
+/// # Errors
+/// Same as [setup_capture_with_options] with the default options.
+pub fn setup_capture(file_path: Option<PathBuf>) -> GDResult<()> {
+    setup_capture_with_options(file_path, CaptureOptions::default())
+}
+
+/// Settings of the packets written to a capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureOptions {
+    /// When set, each written packet is annotated with comments describing the
+    /// computed header fields of every layer (IP lengths and checksum, TCP
+    /// sequence/acknowledgement numbers...).
+    pub verbose: bool,
+    /// The TTL (IPv4) or hop limit (IPv6) of the received packets, lower than
+    /// the one of the sent packets (64) to reflect the hops they went through.
+    pub receive_ttl: u8,
+    /// Which of the exchanged packets are written, to limit the disk writes
+    /// of high volume deployments.
+    pub sampling: Sampling,
+    /// When set, the missing parent directories of the capture file are
+    /// created, instead of failing the setup.
+    pub ensure_dirs: bool,
+    /// When set, the hostnames given to [annotate_hostname] are written as a
+    /// comment on the first packet of the following stream.
+    pub annotate_hostnames: bool,
+    /// When set, every packet is stamped with a `query_id=N` comment, `N`
+    /// being the id of the query it belongs to (increasing with each query),
+    /// so that the packets of a query can be filtered even if it overlaps
+    /// with other queries to the same server. Each query having a stream of
+    /// its own, this is the reliable stream identifier: the stream count
+    /// written in the IP headers (the IPv4 stream identifier option, the IPv6
+    /// flow label) wraps around within their 16 and 20 bits.
+    pub query_ids: bool,
+    /// When set, a Name Resolution Block is written before the first stream
+    /// to each address, mapping it to the hostname it was resolved from and
+    /// the id of the game it was queried for (see [annotate_hostname] and
+    /// [annotate_game]), so that Wireshark shows these instead of the bare
+    /// address.
+    pub name_resolution: bool,
+    /// The maximum number of bytes written of each packet (the snapshot
+    /// length), the packets being written in full if `None`. A truncated
+    /// packet keeps its original length, as tools handling oversized packets
+    /// poorly only need its start.
+    pub snaplen: Option<u32>,
+    /// Which interfaces the packets are written on.
+    pub interfaces: Interfaces,
+    /// When set, the capture rolls over to a new file once the current one is
+    /// full, see [Rotation].
+    pub rotation: Option<Rotation>,
+}
+
+impl Default for CaptureOptions {
+    /// `verbose`: false
+    /// `receive_ttl`: 54
+    /// `sampling`: [Sampling::All]
+    /// `ensure_dirs`: false
+    /// `annotate_hostnames`: false
+    /// `query_ids`: false
+    /// `name_resolution`: false
+    /// `snaplen`: None
+    /// `interfaces`: [Interfaces::Single]
+    /// `rotation`: None
+    fn default() -> Self {
+        Self {
+            verbose: false,
+            receive_ttl: 54,
+            sampling: Sampling::All,
+            ensure_dirs: false,
+            annotate_hostnames: false,
+            query_ids: false,
+            name_resolution: false,
+            snaplen: None,
+            interfaces: Interfaces::Single,
+            rotation: None,
+        }
+    }
+}
+
+/// Which of the exchanged packets are written to a capture.
+///
+/// The connection establishment and teardown are always written, so that the
+/// structure of the streams is preserved even if some of their packets aren't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sampling {
+    /// Every packet is written.
+    All,
+    /// Only one packet out of every given number is written (starting with the
+    /// first one), 0 behaving as 1.
+    OneIn(u32),
+    /// At most the given number of packets are written per second.
+    PerSecond(u32),
+}
+
+/// Which interfaces (pcapng Interface Description Blocks) the packets of a
+/// capture are written on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interfaces {
+    /// Every packet is written on a single interface.
+    Single,
+    /// The packets are written on an interface per transport protocol and
+    /// address family (named `udp/ipv4`, `tcp/ipv6`...), described on its first
+    /// packet, so that the UDP and TCP parts of a query can be told apart.
+    PerFlow,
+    /// The packets are written on an interface per local address (named after
+    /// it, with the address as `if_IPv4addr`/`if_IPv6addr`), so that the
+    /// queries made from several local addresses show where they went out
+    /// from. The sockets bound to an unspecified address share its interface.
+    PerLocalAddress,
+}
+
+/// When a capture rolls over to a new file, for long running processes.
+///
+/// The files are named after the capture's, numbered from 1 (`capture-0001.pcap`,
+/// `capture-0002.pcap`...), each starting with the description of the
+/// interfaces so that it can be read on its own. The stream count (the IP
+/// identification and flow label of the packets) keeps increasing across the
+/// files, so that streams can be told apart when the files are merged back,
+/// while the Name Resolution Blocks aren't written again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// After the packets written to a file reach the given size, in bytes.
+    Size(u64),
+    /// After the given number of packets were written to a file, 0 behaving
+    /// as 1.
+    Packets(u64),
+}
+
+/// Same as [setup_capture], but when `verbose` is set each written packet is
+/// annotated with comments describing the computed header fields of every
+/// layer (IP lengths and checksum, TCP sequence/acknowledgement numbers...).
+pub fn setup_capture_with_verbosity(file_path: Option<PathBuf>, verbose: bool) -> GDResult<()> {
+    setup_capture_with_options(
+        file_path,
+        CaptureOptions {
+            verbose,
+            ..Default::default()
+        },
+    )
+}
+
+/// Same as [setup_capture], with the given [options](CaptureOptions).
+///
+/// # Errors
+/// Returns a [Capture] error if the parent directory of the file doesn't exist
+/// (and `ensure_dirs` isn't set), if the file couldn't be created (it already
+/// exists for example) or its header written, or if a capture was already set
+/// up.
+pub fn setup_capture_with_options(file_path: Option<PathBuf>, options: CaptureOptions) -> GDResult<()> {
+    if let Some(file_path) = file_path {
+        match options.rotation {
+            None => setup_capture_to_file(create_capture_file(file_path.with_extension("pcap"), options)?, options)?,
+            Some(rotation) => setup_rotating_capture(file_path, rotation, options)?,
+        }
+    }
 
-pub fn setup_capture(file_path: Option<PathBuf>) {
+    Ok(())
+}
+
+/// Start recording the HTTP based queries to a new HTTP Archive file at
+/// `file_path` (with a `har` extension), if any. Of the options, only
+/// `ensure_dirs` applies.
+///
+/// # Errors
+/// Same as [setup_capture_with_options].
+pub fn setup_http_capture(file_path: Option<PathBuf>, options: CaptureOptions) -> GDResult<()> {
     if let Some(file_path) = file_path {
-        let file = std::fs::OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .open(file_path.with_extension("pcap"))
-            .unwrap();
-
-        let mut pcap_writer = PcapNgWriter::new(file).unwrap();
-
-        // Write headers
-        let _ = pcap_writer.write_block(
-            &InterfaceDescriptionBlock {
-                linktype: pcap_file::DataLink::ETHERNET,
-                snaplen: 0xFFFF,
-                options: vec![],
-            }
-            .into_block(),
-        );
+        let file = create_capture_file(file_path.with_extension("har"), options)?;
+        *har::HTTP_CAPTURE_WRITER.lock().unwrap() = Some(har::HttpCaptureWriter::new(file));
+    }
+
+    Ok(())
+}
+
+fn create_capture_file(file_path: PathBuf, options: CaptureOptions) -> GDResult<File> {
+    if let Some(directory) = file_path
+        .parent()
+        .filter(|directory| !directory.as_os_str().is_empty() && !directory.exists())
+    {
+        if !options.ensure_dirs {
+            return Err(Capture.context(format!(
+                "The capture directory {} doesn't exist",
+                directory.display()
+            )));
+        }
+
+        std::fs::create_dir_all(directory).map_err(|e| Capture.context(e))?;
+    }
+
+    std::fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&file_path)
+        .map_err(|e| Capture.context(e))
+}
+
+/// Start capturing to an already open file descriptor (for example one handed
+/// over by a supervisor, such as systemd), instead of opening a file by path.
+///
+/// # Safety
+/// `fd` must be an open file descriptor that is writable and isn't owned by
+/// anything else, as it will be closed by the capture.
+///
+/// # Errors
+/// Returns a [Capture] error if the capture's header couldn't be written, or
+/// if a capture was already set up.
+#[cfg(unix)]
+pub unsafe fn begin_capture_from_raw_fd(fd: std::os::unix::io::RawFd, options: CaptureOptions) -> GDResult<()> {
+    use std::os::unix::io::FromRawFd;
+
+    setup_capture_to_file(File::from_raw_fd(fd), options)
+}
+
+/// Start capturing to an already open file handle, see
+/// `begin_capture_from_raw_fd` on unix.
+///
+/// # Safety
+/// `handle` must be an open file handle that is writable and isn't owned by
+/// anything else, as it will be closed by the capture.
+///
+/// # Errors
+/// Same as `begin_capture_from_raw_fd`.
+#[cfg(windows)]
+pub unsafe fn begin_capture_from_raw_handle(
+    handle: std::os::windows::io::RawHandle,
+    options: CaptureOptions,
+) -> GDResult<()> {
+    use std::os::windows::io::FromRawHandle;
+
+    setup_capture_to_file(File::from_raw_handle(handle), options)
+}
+
+/// Write the given packets (of a single query, for example) to a new,
+/// standalone capture file at `file_path` (with a `pcap` extension), to share
+/// them without the rest of a capture.
+///
+/// The packets are written in order, each stream (the packets of a query id)
+/// being established before its first packet (with a handshake for the TCP
+/// ones) and torn down after the last packet. This doesn't involve the
+/// capture set up with [setup_capture], if any.
+///
+/// # Errors
+/// Returns a [Capture] error if the file couldn't be created (it already
+/// exists for example) or written.
+pub fn export_query(file_path: PathBuf, packets: &[(OwnedCapturePacket, Vec<u8>)]) -> GDResult<()> {
+    let options = CaptureOptions::default();
+    let file = create_capture_file(file_path.with_extension("pcap"), options)?;
+    let mut pcap = Pcap::new(new_pcap_writer(file, options)?, options);
+
+    // The streams are described from our side, as the sockets do.
+    let mut streams: Vec<OwnedCapturePacket> = Vec::new();
+    for (info, data) in packets {
+        if !streams.iter().any(|stream| stream.query_id == info.query_id) {
+            let stream = OwnedCapturePacket {
+                direction: Direction::Send,
+                ..*info
+            };
+            pcap.new_connect(&stream.as_packet())?;
+            streams.push(stream);
+        }
+
+        pcap.write(&info.as_packet(), data)?;
+    }
+
+    for stream in streams {
+        pcap.close_connection(&stream.as_packet())?;
+    }
+
+    pcap.finish()
+}
 
-        let writer = Box::new(Pcap::new(pcap_writer));
-        attach(writer)
+/// Describe the packets recorded during a query (such as the ones
+/// [valve::query_debug](crate::protocols::valve::query_debug) returns) as
+/// packets of `stream`, to [export](export_query) them: their protocol,
+/// addresses, query id and hint are the stream's, their directions the
+/// recorded ones.
+pub fn recorded_packets(stream: OwnedCapturePacket, packets: &[ExchangedPacket]) -> Vec<(OwnedCapturePacket, Vec<u8>)> {
+    packets
+        .iter()
+        .map(|packet| {
+            let direction = match packet.direction {
+                PacketDirection::Sent => Direction::Send,
+                PacketDirection::Received => Direction::Receive,
+            };
+
+            (OwnedCapturePacket { direction, ..stream }, packet.data.clone())
+        })
+        .collect()
+}
+
+/// The path of the file of a rotating capture with the given index.
+fn rotating_file_path(file_path: &Path, index: u32) -> PathBuf {
+    let stem = file_path.file_stem().unwrap_or_default().to_string_lossy();
+
+    file_path.with_file_name(format!("{stem}-{index:04}.pcap"))
+}
+
+fn setup_rotating_capture(file_path: PathBuf, rotation: Rotation, options: CaptureOptions) -> GDResult<()> {
+    let file = create_capture_file(rotating_file_path(&file_path, 1), options)?;
+    let open_next = Box::new(move |index| create_capture_file(rotating_file_path(&file_path, index), options));
+
+    attach_pcap(
+        Pcap::new(new_pcap_writer(file, options)?, options).with_rotation(rotation, open_next),
+        options,
+    )
+}
+
+fn setup_capture_to_file(file: File, options: CaptureOptions) -> GDResult<()> {
+    attach_pcap(Pcap::new(new_pcap_writer(file, options)?, options), options)
+}
+
+/// Start capturing to `writer` (an in-memory buffer, a pipe...) instead of a
+/// file, with the default options, see [capture_to_writer_with_options].
+///
+/// # Errors
+/// Same as [capture_to_writer_with_options].
+pub fn capture_to_writer<W: Write + Send + Sync + 'static>(writer: W) -> GDResult<()> {
+    capture_to_writer_with_options(writer, CaptureOptions::default())
+}
+
+/// Same as [capture_to_writer], with the given [options](CaptureOptions),
+/// except for the [rotation](CaptureOptions::rotation), which needs files to
+/// roll over to.
+///
+/// The capture is written as it goes, [finish_capture] flushing the rest of
+/// it.
+///
+/// # Errors
+/// Returns an [InvalidInput] error if a rotation is set, a [Capture] error
+/// if the capture's header couldn't be written or if a capture was already
+/// set up.
+pub fn capture_to_writer_with_options<W: Write + Send + Sync + 'static>(
+    writer: W,
+    options: CaptureOptions,
+) -> GDResult<()> {
+    if options.rotation.is_some() {
+        return Err(InvalidInput.context("A capture to a writer can't be rotated"));
+    }
+
+    attach_pcap(Pcap::new(new_pcap_writer(writer, options)?, options), options)
+}
+
+/// Same as [capture_to_writer_with_options], the capture being written to
+/// each of the `writers` (a file and a live analyzer, for example), each
+/// getting a whole capture of its own.
+///
+/// A writer failing doesn't keep the others from getting the packets, the
+/// query then failing with the first error.
+///
+/// # Errors
+/// Same as [capture_to_writer_with_options], or an [InvalidInput] error if
+/// there are no writers.
+pub fn capture_to_writers<I: IntoIterator<Item = Box<dyn Write + Send + Sync>>>(
+    writers: I,
+    options: CaptureOptions,
+) -> GDResult<()> {
+    if options.rotation.is_some() {
+        return Err(InvalidInput.context("A capture to a writer can't be rotated"));
+    }
+
+    let writers = writers
+        .into_iter()
+        .map(|writer| Ok(sampled(Pcap::new(new_pcap_writer(writer, options)?, options), options)))
+        .collect::<GDResult<Vec<_>>>()?;
+    if writers.is_empty() {
+        return Err(InvalidInput.context("A capture needs at least a writer"));
+    }
+
+    attach(Box::new(TeeWriter::new(writers)))
+}
+
+/// A writer of the capture to `output`, its headers written.
+fn new_pcap_writer<W: Write>(output: W, options: CaptureOptions) -> GDResult<PcapNgWriter<W>> {
+    let mut pcap_writer = new_section_writer(output)?;
+
+    // Write headers, the other interfaces are described on their first packet
+    if options.interfaces == Interfaces::Single {
+        pcap_writer
+            .write_block(&interface_block(&options, vec![]).into_block())
+            .map_err(|e| Capture.context(e))?;
+    }
+
+    Ok(pcap_writer)
+}
+
+fn attach_pcap<W: Write + Send + Sync + 'static>(pcap: Pcap<W>, options: CaptureOptions) -> GDResult<()> {
+    attach(sampled(pcap, options))
+}
+
+/// The writer of `pcap`, only forwarding the sample of the packets of the
+/// options.
+fn sampled<W: Write + Send + Sync + 'static>(pcap: Pcap<W>, options: CaptureOptions) -> Box<dyn Writer + Send + Sync> {
+    match options.sampling {
+        Sampling::All => Box::new(pcap),
+        sampling => Box::new(SamplingWriter::new(pcap, sampling)),
     }
 }
 
 /// Attaches a writer to the capture module.
 ///
 /// # Errors
-/// Returns an Error if the writer is already set.
-fn attach(writer: Box<dyn Writer + Send + Sync>) { crate::capture::socket::set_writer(writer); }
+/// Returns a [Capture] error if a writer is already set.
+fn attach(writer: Box<dyn Writer + Send + Sync>) -> GDResult<()> { crate::capture::socket::set_writer(writer) }
+
+/// Write a summary of the query outcome at the current position of the
+/// capture, as a pcapng Custom Block (see [SUMMARY_PEN]).
+///
+/// # Errors
+/// Returns an Error if no capture was set up or the block couldn't be written.
+pub fn finalize_summary(summary: &QuerySummary) -> GDResult<()> {
+    with_writer(|writer| writer.finalize_summary(summary))
+        .unwrap_or_else(|| Err(InvalidInput.context("No capture has been set up")))
+}
+
+/// Record that the address of the next query was resolved from `hostname`,
+/// so that the capture notes it ("querying play.example.com (resolved
+/// 1.2.3.4)") on the first packet of the next stream, if the capture was set
+/// up with [annotate_hostnames](CaptureOptions::annotate_hostnames).
+pub fn annotate_hostname(hostname: &str, address: &IpAddr) {
+    with_writer(|writer| writer.annotate_hostname(hostname, address));
+}
+
+/// Record that the next query to `address` is for the game `game_id`, which
+/// the capture maps the address to if it was set up with
+/// [name_resolution](CaptureOptions::name_resolution).
+pub fn annotate_game(game_id: &str, address: &IpAddr) {
+    with_writer(|writer| writer.annotate_game(game_id, address));
+}
+
+/// Capture the queries `scope` makes on the current thread to `writer`, with
+/// the given [options](CaptureOptions) (except for the rotation), instead of
+/// to the capture set up for the whole process, if any.
+///
+/// Each thread having its own scope, tests (or independent queries) running
+/// in parallel each get their capture, and scopes can be nested. The queries
+/// `scope` makes on other threads (like [query_many](crate::query_many)'s)
+/// and the HTTP based queries aren't part of it. The capture is flushed once
+/// `scope` returns, the previous capture of the thread (if any) being
+/// restored, even if `scope` panics.
+///
+/// # Errors
+/// Returns an [InvalidInput] error if a rotation is set, a [Capture] error if
+/// the capture's header couldn't be written or it couldn't be flushed.
+pub fn with_capture<W: Write + Send + Sync + 'static, T>(
+    writer: W,
+    options: CaptureOptions,
+    scope: impl FnOnce() -> T,
+) -> GDResult<T> {
+    if options.rotation.is_some() {
+        return Err(InvalidInput.context("A capture to a writer can't be rotated"));
+    }
+
+    let writer = sampled(Pcap::new(new_pcap_writer(writer, options)?, options), options);
+    let guard = RestoreWriter(Some(SCOPED_WRITER.with(|scoped| scoped.replace(Some(writer)))));
+    let result = scope();
+
+    if let Some(mut writer) = guard.restore() {
+        writer.finish()?;
+    }
+
+    Ok(result)
+}
+
+/// Puts back the writer a [with_capture] scope replaced, even if the scope
+/// panics.
+struct RestoreWriter(Option<Option<Box<dyn Writer + Send + Sync>>>);
+
+impl RestoreWriter {
+    /// Put the previous writer back, returning the one of the scope.
+    fn restore(mut self) -> Option<Box<dyn Writer + Send + Sync>> {
+        let previous = self.0.take().flatten();
+        SCOPED_WRITER.with(|scoped| scoped.replace(previous))
+    }
+}
+
+impl Drop for RestoreWriter {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            SCOPED_WRITER.with(|scoped| scoped.replace(previous));
+        }
+    }
+}
+
+/// Flush and close the packet and HTTP captures, the following queries aren't
+/// captured anymore.
+///
+/// # Errors
+/// Returns an Error if no capture was set up or one couldn't be flushed.
+pub fn finish_capture() -> GDResult<()> {
+    let writer = CAPTURE_WRITER.lock().unwrap().take();
+    let http_writer = har::HTTP_CAPTURE_WRITER.lock().unwrap().take();
+
+    if writer.is_none() && http_writer.is_none() {
+        return Err(InvalidInput.context("No capture has been set up"));
+    }
+
+    if let Some(mut writer) = writer {
+        writer.finish()?;
+    }
+
+    match http_writer {
+        Some(mut writer) => writer.finish(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_directory_is_reported() {
+        let directory = std::env::temp_dir().join(format!("gamedig-missing-{}", std::process::id()));
+
+        let error = setup_capture_with_options(Some(directory.join("out")), CaptureOptions::default()).unwrap_err();
+        assert_eq!(error.kind, Capture);
+        assert!(error
+            .source
+            .unwrap()
+            .to_string()
+            .contains("gamedig-missing-"));
+        assert!(!directory.exists());
+    }
+
+    #[test]
+    fn existing_file_is_reported() {
+        let file_path = std::env::temp_dir().join(format!("gamedig-existing-{}.pcap", std::process::id()));
+        std::fs::write(&file_path, b"kept").unwrap();
+
+        let error = setup_capture_with_options(Some(file_path.clone()), CaptureOptions::default()).unwrap_err();
+        assert_eq!(error.kind, Capture);
+        assert_eq!(std::fs::read(&file_path).unwrap(), b"kept");
+
+        std::fs::remove_file(file_path).unwrap();
+    }
+
+    #[test]
+    fn exported_queries_are_standalone() {
+        use pcap_file::pcapng::{Block, PcapNgReader};
+        use pnet_packet::{ipv4::Ipv4Packet, tcp::TcpPacket, Packet};
+
+        let file_path = std::env::temp_dir().join(format!("gamedig-export-{}.pcap", std::process::id()));
+        let packet = |direction| {
+            OwnedCapturePacket {
+                direction,
+                protocol: Protocol::Tcp,
+                remote_address: "127.0.0.2:25565".parse().unwrap(),
+                local_address: "127.0.0.1:50000".parse().unwrap(),
+                query_id: 7,
+                protocol_hint: Some("minecraft java"),
+            }
+        };
+
+        export_query(
+            file_path.clone(),
+            &[
+                (packet(Direction::Send), vec![0x01, 0x00]),
+                (packet(Direction::Receive), vec![0x02, 0x00, 0x00]),
+            ],
+        )
+        .unwrap();
+        let data = std::fs::read(&file_path).unwrap();
+        std::fs::remove_file(file_path).unwrap();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let mut flags = Vec::new();
+        while let Some(block) = reader.next_block() {
+            if let Block::EnhancedPacket(packet) = block.unwrap() {
+                let ip = Ipv4Packet::new(&packet.data[14 ..]).unwrap();
+                flags.push(TcpPacket::new(ip.payload()).unwrap().get_flags());
+            }
+        }
+
+        // SYN, SYN|ACK, ACK, the payloads acknowledged, then FIN|ACK, FIN|ACK,
+        // ACK.
+        assert_eq!(flags, [0x02, 0x12, 0x10, 0x18, 0x10, 0x18, 0x10, 0x11, 0x11, 0x10]);
+    }
+
+    #[test]
+    fn debug_queries_are_exported() {
+        use crate::protocols::valve::{query_debug, Engine};
+        use pcap_file::pcapng::{Block, PcapNgReader};
+        use pnet_packet::{ipv4::Ipv4Packet, udp::UdpPacket, Packet};
+
+        // An info reply cut short, which fails the query.
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let responder = std::thread::spawn(move || {
+            let mut buffer = [0; 64];
+            let (_, from) = server.recv_from(&mut buffer).unwrap();
+            server.send_to(&[0xFF, 0xFF, 0xFF, 0xFF, 0x49], from).unwrap();
+        });
+
+        let (result, exchanged) = query_debug(&address, Engine::new(440), None, None);
+        responder.join().unwrap();
+        assert!(result.is_err());
+
+        let stream = OwnedCapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Udp,
+            remote_address: address,
+            local_address: "127.0.0.1:50000".parse().unwrap(),
+            query_id: 1,
+            protocol_hint: Some("valve"),
+        };
+        let packets = recorded_packets(stream, &exchanged);
+        assert_eq!(packets[0].0.direction, Direction::Send);
+        assert_eq!(packets[1].0.direction, Direction::Receive);
+
+        let file_path = std::env::temp_dir().join(format!("gamedig-export-debug-{}.pcap", std::process::id()));
+        export_query(file_path.clone(), &packets).unwrap();
+        let data = std::fs::read(&file_path).unwrap();
+        std::fs::remove_file(file_path).unwrap();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let mut payloads = Vec::new();
+        while let Some(block) = reader.next_block() {
+            if let Block::EnhancedPacket(packet) = block.unwrap() {
+                let ip = Ipv4Packet::new(&packet.data[14 ..]).unwrap();
+                payloads.push(UdpPacket::new(ip.payload()).unwrap().payload().to_vec());
+            }
+        }
+
+        let exchanged: Vec<_> = exchanged.into_iter().map(|packet| packet.data).collect();
+        assert_eq!(payloads, exchanged);
+    }
+
+    #[test]
+    fn rotating_files_are_numbered() {
+        assert_eq!(
+            rotating_file_path(Path::new("captures/monitor.pcap"), 12),
+            Path::new("captures/monitor-0012.pcap")
+        );
+        assert_eq!(rotating_file_path(Path::new("monitor"), 1), Path::new("monitor-0001.pcap"));
+    }
+
+    #[test]
+    fn finishing_without_capture_fails() {
+        assert_eq!(finish_capture().unwrap_err().kind, InvalidInput);
+    }
+}
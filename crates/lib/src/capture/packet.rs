@@ -9,11 +9,15 @@ pub(crate) const HEADER_SIZE_IP4: usize = 20;
 /// Size of an IPv6 header.
 pub(crate) const HEADER_SIZE_IP6: usize = 40;
 /// Size of a UDP header.
-pub(crate) const HEADER_SIZE_UDP: usize = 4;
+pub(crate) const HEADER_SIZE_UDP: usize = 8;
+/// Size of a TCP header, without options.
+pub(crate) const HEADER_SIZE_TCP: usize = 20;
+/// Size of an IPv6 fragment extension header.
+pub(crate) const HEADER_SIZE_IP6_FRAGMENT: usize = 8;
 
 /// Represents the direction of a network packet.
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) enum Direction {
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
     /// Packet is outgoing (sent by us).
     Send,
     /// Packet is incoming (received by us).
@@ -21,8 +25,8 @@ pub(crate) enum Direction {
 }
 
 /// Defines the protocol of a network packet.
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub(crate) enum Protocol {
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
     /// Transmission Control Protocol.
     Tcp,
     /// User Datagram Protocol.
@@ -47,6 +51,59 @@ pub(crate) struct CapturePacket<'a> {
     pub(crate) remote_address: &'a SocketAddr,
     /// Local socket address.
     pub(crate) local_address: &'a SocketAddr,
+    /// Id of the logical query (the socket) the packet belongs to, telling
+    /// apart overlapping conversations with the same server.
+    pub(crate) query_id: u64,
+    /// The game protocol spoken over the socket (`valve`, `minecraft
+    /// java`...), if the query named it.
+    pub(crate) protocol_hint: Option<&'static str>,
+}
+
+/// A description of an exchanged packet, which isn't tied to the lifetime of
+/// the socket addresses it was created from and can thus be sent to another
+/// thread (for example through a channel to a background writer) or kept to
+/// [export](super::export_query) later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OwnedCapturePacket {
+    /// Direction of the packet (Send/Receive).
+    pub direction: Direction,
+    /// Protocol of the packet (Tcp/UDP).
+    pub protocol: Protocol,
+    /// Remote socket address.
+    pub remote_address: SocketAddr,
+    /// Local socket address.
+    pub local_address: SocketAddr,
+    /// Id of the logical query the packet belongs to.
+    pub query_id: u64,
+    /// The game protocol spoken over the socket, if the query named it.
+    pub protocol_hint: Option<&'static str>,
+}
+
+impl OwnedCapturePacket {
+    /// Borrows this packet as a [CapturePacket], to be handed to a [Writer](super::writer::Writer).
+    pub(crate) const fn as_packet(&self) -> CapturePacket<'_> {
+        CapturePacket {
+            direction: self.direction,
+            protocol: self.protocol,
+            remote_address: &self.remote_address,
+            local_address: &self.local_address,
+            query_id: self.query_id,
+            protocol_hint: self.protocol_hint,
+        }
+    }
+}
+
+impl From<&CapturePacket<'_>> for OwnedCapturePacket {
+    fn from(packet: &CapturePacket<'_>) -> Self {
+        Self {
+            direction: packet.direction,
+            protocol: packet.protocol,
+            remote_address: *packet.remote_address,
+            local_address: *packet.local_address,
+            query_id: packet.query_id,
+            protocol_hint: packet.protocol_hint,
+        }
+    }
 }
 
 impl CapturePacket<'_> {
@@ -131,6 +188,28 @@ mod tests {
     // Helper function to create a SocketAddr from a string
     fn socket_addr(addr: &str) -> SocketAddr { SocketAddr::from_str(addr).unwrap() }
 
+    #[test]
+    fn test_owned_round_trip() {
+        let local_address = socket_addr("127.0.0.1:8080");
+        let remote_address = socket_addr("[::1]:80");
+        let packet = CapturePacket {
+            direction: Direction::Receive,
+            protocol: Protocol::Udp,
+            local_address: &local_address,
+            remote_address: &remote_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let owned = OwnedCapturePacket::from(&packet);
+        assert_eq!(owned.remote_address, remote_address);
+        assert_eq!(owned.query_id, 1);
+
+        // The owned packet outlives the addresses it was created from.
+        let owned = std::thread::spawn(move || owned).join().unwrap();
+        assert_eq!(owned.as_packet(), packet);
+    }
+
     #[test]
     fn test_ports_by_direction() {
         let packet_send = CapturePacket {
@@ -138,6 +217,8 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            query_id: 1,
+            protocol_hint: None,
         };
 
         let packet_receive = CapturePacket {
@@ -145,6 +226,8 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            query_id: 1,
+            protocol_hint: None,
         };
 
         assert_eq!(packet_send.ports_by_direction(), (8080, 80));
@@ -158,6 +241,8 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            query_id: 1,
+            protocol_hint: None,
         };
 
         let packet_receive = CapturePacket {
@@ -165,6 +250,8 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            query_id: 1,
+            protocol_hint: None,
         };
 
         assert_eq!(
@@ -190,6 +277,8 @@ mod tests {
             protocol: Protocol::Tcp,
             local_address: &socket_addr("127.0.0.1:8080"),
             remote_address: &socket_addr("192.168.1.1:80"),
+            query_id: 1,
+            protocol_hint: None,
         };
 
         let ipv4_result: Result<(Ipv4Addr, Ipv4Addr), _> =
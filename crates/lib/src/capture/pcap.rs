@@ -1,14 +1,41 @@
-use pcap_file::pcapng::{blocks::enhanced_packet::EnhancedPacketOption, PcapNgBlock, PcapNgWriter};
+use pcap_file::{
+    pcapng::{
+        blocks::{
+            enhanced_packet::EnhancedPacketOption,
+            interface_description::{InterfaceDescriptionBlock, InterfaceDescriptionOption},
+            name_resolution::{Ipv4Record, Ipv6Record, NameResolutionBlock, Record},
+            section_header::{SectionHeaderBlock, SectionHeaderOption},
+            unknown::UnknownBlock,
+        },
+        PcapNgBlock,
+        PcapNgWriter,
+    },
+    Endianness,
+};
 use pnet_packet::{
     ethernet::{EtherType, MutableEthernetPacket},
     ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
-    ipv4::MutableIpv4Packet,
-    ipv6::MutableIpv6Packet,
-    tcp::{MutableTcpPacket, TcpFlags},
-    udp::MutableUdpPacket,
+    ipv4::{Ipv4Packet, MutableIpv4Packet},
+    ipv6::{Ipv6Packet, MutableIpv6Packet},
+    tcp::{self, MutableTcpPacket, TcpFlags, TcpPacket},
+    udp::{self, MutableUdpPacket, UdpPacket},
+    MutablePacket,
+    Packet,
     PacketSize,
 };
-use std::{io::Write, net::IpAddr, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use super::summary::{QuerySummary, CUSTOM_BLOCK_TYPE, SUMMARY_PEN};
+use super::{CaptureOptions, Interfaces, Rotation};
+use crate::{
+    GDErrorKind::{Capture, PacketSend},
+    GDResult,
+};
 
 use super::packet::{
     CapturePacket,
@@ -17,34 +44,192 @@ use super::packet::{
     HEADER_SIZE_ETHERNET,
     HEADER_SIZE_IP4,
     HEADER_SIZE_IP6,
+    HEADER_SIZE_IP6_FRAGMENT,
+    HEADER_SIZE_TCP,
     HEADER_SIZE_UDP,
     PACKET_SIZE,
 };
 
 const BUFFER_SIZE: usize = PACKET_SIZE - HEADER_SIZE_IP6 - HEADER_SIZE_ETHERNET;
+/// The largest payload of a written TCP segment, longer payloads being split
+/// over as many segments as needed.
+const MAX_SEGMENT_SIZE: usize = BUFFER_SIZE - HEADER_SIZE_TCP;
+/// The largest part of a UDP datagram carried by an IP fragment, a multiple
+/// of 8 bytes as the fragment offsets are.
+const MAX_FRAGMENT_SIZE: usize = (BUFFER_SIZE - HEADER_SIZE_IP6_FRAGMENT) & !7;
+/// The TTL (or hop limit) of the sent packets.
+const SEND_TTL: u8 = 64;
+/// IPv6 flow labels are 20 bits wide.
+const FLOW_LABEL_MASK: u32 = 0xF_FFFF;
+/// The `if_tsresol` of the interfaces, 10^-9 seconds, as the timestamps are
+/// written in nanoseconds (instead of the default microseconds).
+const TIMESTAMP_RESOLUTION: u8 = 9;
 
 pub(crate) struct Pcap<W: Write> {
     writer: PcapNgWriter<W>,
     pub(crate) state: State,
+    pub(crate) options: CaptureOptions,
+    rotating: Option<Rotating<W>>,
+}
+
+/// Opens the file of a rotating capture with the given index (2 for the one
+/// following the first).
+pub(crate) type OpenNext<W> = Box<dyn FnMut(u32) -> GDResult<W> + Send + Sync>;
+
+/// The state of a capture rolling over to new files.
+struct Rotating<W> {
+    rotation: Rotation,
+    open_next: OpenNext<W>,
+    /// The index of the current file, starting at 1.
+    index: u32,
+    /// The size of the packets written to the current file.
+    bytes: u64,
+    /// The number of packets written to the current file.
+    packets: u64,
+}
+
+impl<W> Rotating<W> {
+    const fn is_full(&self) -> bool {
+        self.packets > 0
+            && match self.rotation {
+                Rotation::Size(size) => self.bytes >= size,
+                Rotation::Packets(packets) => self.packets >= packets,
+            }
+    }
 }
 
 pub(crate) struct State {
+    /// When the capture started, by the system clock, the packets being
+    /// stamped with their wall-clock time.
+    pub(crate) start_wall_time: SystemTime,
+    /// When the capture started, by the monotonic clock, so that the packets
+    /// stay in order even if the system clock is adjusted meanwhile.
     pub(crate) start_time: Instant,
+    /// The TCP streams that were written to, by the query id of their socket,
+    /// a retry reconnecting on a new socket getting a stream of its own.
+    pub(crate) tcp_streams: HashMap<u64, TcpStream>,
+    /// The number of streams started so far, which the IP headers carry as a
+    /// hint of the stream a packet is part of: in the stream identifier
+    /// option of IPv4 and the flow label of IPv6, wrapping around within
+    /// their 16 and 20 bits. See [query_ids](super::CaptureOptions::query_ids)
+    /// to identify the streams reliably.
+    pub(crate) stream_count: u32,
+    /// A comment to write on the next packet, the first of its stream.
+    pub(crate) stream_comment: Option<String>,
+    /// The names (hostnames, game ids) noted for the addresses of the next
+    /// streams, not written yet.
+    pub(crate) pending_names: Vec<(IpAddr, String)>,
+    /// The addresses a Name Resolution Block was written for.
+    pub(crate) resolved_addresses: HashSet<IpAddr>,
+    /// The ids of the interfaces described so far, by name, when not writing
+    /// on a [single](Interfaces::Single) one.
+    pub(crate) interfaces: HashMap<String, u32>,
+    /// The number of datagrams split over IP fragments so far, which their
+    /// fragments are identified by.
+    pub(crate) fragmented_datagrams: u32,
+}
+
+/// The part of a datagram carried by an IP fragment.
+#[derive(Debug, Clone, Copy)]
+struct Fragment {
+    /// The identification shared by the fragments of the datagram.
+    id: u32,
+    /// Where the part starts in the datagram, in bytes.
+    offset: usize,
+    /// Whether other fragments follow this one.
+    more: bool,
+}
+
+/// The sequence numbers of a TCP stream.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct TcpStream {
     pub(crate) send_seq: u32,
     pub(crate) rec_seq: u32,
     pub(crate) has_sent_handshake: bool,
-    pub(crate) stream_count: u32,
 }
 
 impl<W: Write> Pcap<W> {
-    pub(crate) fn new(writer: PcapNgWriter<W>) -> Self {
+    pub(crate) fn new(writer: PcapNgWriter<W>, options: CaptureOptions) -> Self {
         Self {
             writer,
             state: State::default(),
+            options,
+            rotating: None,
+        }
+    }
+
+    /// Roll over to a new file, opened by `open_next`, once the current one
+    /// is full.
+    pub(crate) fn with_rotation(mut self, rotation: Rotation, open_next: OpenNext<W>) -> Self {
+        self.rotating = Some(Rotating {
+            rotation,
+            open_next,
+            index: 1,
+            bytes: 0,
+            packets: 0,
+        });
+
+        self
+    }
+
+    /// Switch to the next file if the current one is full, describing the
+    /// interfaces again so that it can be read on its own. Failing to open it
+    /// keeps writing to the current one.
+    fn rotate_if_full(&mut self) {
+        let rotating = match &mut self.rotating {
+            Some(rotating) if rotating.is_full() => rotating,
+            _ => return,
+        };
+
+        let writer = match (rotating.open_next)(rotating.index + 1)
+            .and_then(new_section_writer)
+        {
+            Ok(writer) => writer,
+            Err(_) => return,
+        };
+        rotating.index += 1;
+        rotating.bytes = 0;
+        rotating.packets = 0;
+        self.writer = writer;
+
+        match self.options.interfaces {
+            Interfaces::Single => {
+                let _ = self
+                    .writer
+                    .write_block(&interface_block(&self.options, vec![]).into_block());
+            }
+            Interfaces::PerFlow | Interfaces::PerLocalAddress => {
+                let mut interfaces: Vec<_> = self
+                    .state
+                    .interfaces
+                    .iter()
+                    .map(|(name, id)| (*id, name.clone()))
+                    .collect();
+                interfaces.sort();
+
+                for (_, name) in interfaces {
+                    let block = interface_block(&self.options, self.interface_options(name));
+                    let _ = self.writer.write_block(&block.into_block());
+                }
+            }
         }
     }
 
-    pub(crate) fn write_transport_packet(&mut self, info: &CapturePacket, payload: &[u8]) {
+    /// The TTL (or hop limit) of a packet, depending on its direction.
+    const fn ttl(&self, direction: Direction) -> u8 {
+        match direction {
+            Direction::Send => SEND_TTL,
+            Direction::Receive => self.options.receive_ttl,
+        }
+    }
+
+    /// Write a TCP segment (and the ACK answering it) or a UDP datagram
+    /// carrying the payload.
+    ///
+    /// TCP payloads too long for a single packet are split over many
+    /// segments, and UDP ones over many IP fragments. A UDP payload too long
+    /// for a datagram is an error.
+    pub(crate) fn write_transport_packet(&mut self, info: &CapturePacket, payload: &[u8]) -> GDResult<()> {
         let mut buffer_array: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
         let buf: &mut [u8] = &mut buffer_array[..];
 
@@ -52,38 +237,52 @@ impl<W: Write> Pcap<W> {
 
         match info.protocol {
             Protocol::Tcp => {
-                let buf_size = {
-                    let mut tcp = MutableTcpPacket::new(buf).unwrap();
-                    tcp.set_source(source_port);
-                    tcp.set_destination(dest_port);
-                    tcp.set_payload(payload);
-                    tcp.set_data_offset(5);
-                    tcp.set_window(43440);
-                    match info.direction {
-                        Direction::Send => {
-                            tcp.set_sequence(self.state.send_seq);
-                            tcp.set_acknowledgement(self.state.rec_seq);
+                let mut stream = self.state.tcp_streams.remove(&info.query_id).unwrap_or_default();
+                // Each segment advances the sequence number by its length,
+                // the last one pushing the data.
+                let segments: Vec<&[u8]> = if payload.is_empty() {
+                    vec![payload]
+                } else {
+                    payload.chunks(MAX_SEGMENT_SIZE).collect()
+                };
+                for (index, segment) in segments.iter().enumerate() {
+                    let buf_size = {
+                        let mut tcp = MutableTcpPacket::new(buf).unwrap();
+                        tcp.set_source(source_port);
+                        tcp.set_destination(dest_port);
+                        tcp.set_payload(segment);
+                        tcp.set_data_offset(5);
+                        tcp.set_window(43440);
+                        match info.direction {
+                            Direction::Send => {
+                                tcp.set_sequence(stream.send_seq);
+                                tcp.set_acknowledgement(stream.rec_seq);
 
-                            self.state.send_seq = self.state.send_seq.wrapping_add(payload.len() as u32);
-                        }
-                        Direction::Receive => {
-                            tcp.set_sequence(self.state.rec_seq);
-                            tcp.set_acknowledgement(self.state.send_seq);
+                                stream.send_seq = stream.send_seq.wrapping_add(segment.len() as u32);
+                            }
+                            Direction::Receive => {
+                                tcp.set_sequence(stream.rec_seq);
+                                tcp.set_acknowledgement(stream.send_seq);
 
-                            self.state.rec_seq = self.state.rec_seq.wrapping_add(payload.len() as u32);
+                                stream.rec_seq = stream.rec_seq.wrapping_add(segment.len() as u32);
+                            }
+                        }
+                        if index + 1 == segments.len() {
+                            tcp.set_flags(TcpFlags::PSH | TcpFlags::ACK);
+                        } else {
+                            tcp.set_flags(TcpFlags::ACK);
                         }
-                    }
-                    tcp.set_flags(TcpFlags::PSH | TcpFlags::ACK);
 
-                    tcp.packet_size()
-                };
+                        tcp.packet_size()
+                    };
 
-                self.write_transport_payload(
-                    info,
-                    IpNextHeaderProtocols::Tcp,
-                    &buf[.. buf_size + payload.len()],
-                    vec![],
-                );
+                    self.write_transport_payload(
+                        info,
+                        IpNextHeaderProtocols::Tcp,
+                        &buf[.. buf_size + segment.len()],
+                        vec![],
+                    );
+                }
 
                 let mut info = info.clone();
                 let buf_size = {
@@ -94,14 +293,14 @@ impl<W: Write> Pcap<W> {
                     tcp.set_window(43440);
                     match &info.direction {
                         Direction::Send => {
-                            tcp.set_sequence(self.state.rec_seq);
-                            tcp.set_acknowledgement(self.state.send_seq);
+                            tcp.set_sequence(stream.rec_seq);
+                            tcp.set_acknowledgement(stream.send_seq);
 
                             info.direction = Direction::Receive;
                         }
                         Direction::Receive => {
-                            tcp.set_sequence(self.state.send_seq);
-                            tcp.set_acknowledgement(self.state.rec_seq);
+                            tcp.set_sequence(stream.send_seq);
+                            tcp.set_acknowledgement(stream.rec_seq);
 
                             info.direction = Direction::Send;
                         }
@@ -117,35 +316,77 @@ impl<W: Write> Pcap<W> {
                     &buf[.. buf_size],
                     vec![EnhancedPacketOption::Comment("Generated TCP ACK".into())],
                 );
+
+                self.state.tcp_streams.insert(info.query_id, stream);
             }
             Protocol::Udp => {
-                let buf_size = {
-                    let mut udp = MutableUdpPacket::new(buf).unwrap();
+                let length = u16::try_from(payload.len() + HEADER_SIZE_UDP).map_err(|_| {
+                    Capture.context(format!(
+                        "A {} bytes payload doesn't fit in a UDP datagram",
+                        payload.len()
+                    ))
+                })?;
+
+                let mut datagram = vec![0; usize::from(length)];
+                {
+                    let mut udp = MutableUdpPacket::new(&mut datagram).unwrap();
                     udp.set_source(source_port);
                     udp.set_destination(dest_port);
-                    udp.set_length((payload.len() + HEADER_SIZE_UDP) as u16);
+                    udp.set_length(length);
                     udp.set_payload(payload);
+                }
 
-                    udp.packet_size()
-                };
+                if datagram.len() <= BUFFER_SIZE {
+                    self.write_transport_payload(info, IpNextHeaderProtocols::Udp, &datagram, vec![]);
+                } else {
+                    self.write_fragmented_datagram(info, &mut datagram);
+                }
+            }
+        }
 
-                self.write_transport_payload(
-                    info,
-                    IpNextHeaderProtocols::Udp,
-                    &buf[.. buf_size + payload.len()],
-                    vec![],
-                );
+        Ok(())
+    }
+
+    /// Write a UDP datagram too long for a single packet over as many IP
+    /// fragments as needed, its checksum covering the whole datagram.
+    fn write_fragmented_datagram(&mut self, info: &CapturePacket, datagram: &mut [u8]) {
+        let protocol = IpNextHeaderProtocols::Udp;
+        match info.ip_addr() {
+            (IpAddr::V4(_), IpAddr::V4(_)) => {
+                let (source, destination) = info.ipvt_by_direction();
+                set_ipv4_transport_checksum(datagram, protocol, &source, &destination);
+            }
+            (IpAddr::V6(_), IpAddr::V6(_)) => {
+                let (source, destination) = info.ipvt_by_direction();
+                set_ipv6_transport_checksum(datagram, protocol, &source, &destination);
             }
+            _ => unreachable!(),
+        }
+
+        let id = self.state.fragmented_datagrams;
+        self.state.fragmented_datagrams = id.wrapping_add(1);
+
+        let fragments = datagram.chunks(MAX_FRAGMENT_SIZE).count();
+        for (index, part) in datagram.chunks(MAX_FRAGMENT_SIZE).enumerate() {
+            let fragment = Fragment {
+                id,
+                offset: index * MAX_FRAGMENT_SIZE,
+                more: index + 1 < fragments,
+            };
+
+            self.write_network_payload(info, protocol, part, Some(fragment), vec![]);
         }
     }
 
-    /// Encode a network layer (IP) packet with a payload.
+    /// Encode a network layer (IP) packet with a payload, which is the part
+    /// of a datagram when encoding a fragment.
     fn encode_ip_packet(
         &self,
         buf: &mut [u8],
         info: &CapturePacket,
         protocol: IpNextHeaderProtocol,
         payload: &[u8],
+        fragment: Option<Fragment>,
     ) -> (usize, EtherType) {
         match info.ip_addr() {
             (IpAddr::V4(_), IpAddr::V4(_)) => {
@@ -163,8 +404,21 @@ impl<W: Write> Pcap<W> {
                 ip.set_source(source);
                 ip.set_destination(destination);
                 ip.set_payload(payload);
-                ip.set_ttl(64);
-                ip.set_flags(pnet_packet::ipv4::Ipv4Flags::DontFragment);
+                ip.set_ttl(self.ttl(info.direction));
+                match fragment {
+                    // The identification field only keeps the low 16 bits.
+                    Some(fragment) => {
+                        ip.set_identification(fragment.id as u16);
+                        if fragment.more {
+                            ip.set_flags(pnet_packet::ipv4::Ipv4Flags::MoreFragments);
+                        }
+                        ip.set_fragment_offset((fragment.offset / 8) as u16);
+                    }
+                    None => {
+                        set_ipv4_transport_checksum(ip.payload_mut(), protocol, &source, &destination);
+                        ip.set_flags(pnet_packet::ipv4::Ipv4Flags::DontFragment);
+                    }
+                }
 
                 let mut options_writer =
                     pnet_packet::ipv4::MutableIpv4OptionPacket::new(ip.get_options_raw_mut()).unwrap();
@@ -172,6 +426,7 @@ impl<W: Write> Pcap<W> {
                 options_writer.set_class(0);
                 options_writer.set_number(pnet_packet::ipv4::Ipv4OptionNumbers::SID);
                 options_writer.set_length(&[4]);
+                // The stream count wraps around within the option's 16 bits.
                 options_writer.set_data(&(self.state.stream_count as u16).to_be_bytes());
 
                 ip.set_checksum(pnet_packet::ipv4::checksum(&ip.to_immutable()));
@@ -183,15 +438,44 @@ impl<W: Write> Pcap<W> {
 
                 let mut ip = MutableIpv6Packet::new(buf).unwrap();
                 ip.set_version(6);
-                ip.set_payload_length(payload.len() as u16);
-                ip.set_next_header(protocol);
                 ip.set_source(source);
                 ip.set_destination(destination);
-                ip.set_hop_limit(64);
-                ip.set_payload(payload);
-                ip.set_flow_label(self.state.stream_count);
+                ip.set_hop_limit(self.ttl(info.direction));
+                // The stream count wraps around within the flow label, instead
+                // of spilling over the traffic class.
+                ip.set_flow_label(self.state.stream_count & FLOW_LABEL_MASK);
+
+                match fragment {
+                    // The part follows a fragment extension header: the
+                    // protocol of the datagram, its offset in 8 bytes units
+                    // (along with the more fragments flag) and its id.
+                    Some(fragment) => {
+                        let offset = (fragment.offset / 8) as u16;
+                        let header = [
+                            &[protocol.0, 0][..],
+                            &(offset << 3 | u16::from(fragment.more)).to_be_bytes(),
+                            &fragment.id.to_be_bytes(),
+                        ]
+                        .concat();
 
-                (ip.packet_size(), pnet_packet::ethernet::EtherTypes::Ipv6)
+                        ip.set_payload_length((HEADER_SIZE_IP6_FRAGMENT + payload.len()) as u16);
+                        ip.set_next_header(IpNextHeaderProtocols::Ipv6Frag);
+                        ip.set_payload(&[&header[..], payload].concat());
+
+                        (
+                            ip.packet_size() + HEADER_SIZE_IP6_FRAGMENT,
+                            pnet_packet::ethernet::EtherTypes::Ipv6,
+                        )
+                    }
+                    None => {
+                        ip.set_payload_length(payload.len() as u16);
+                        ip.set_next_header(protocol);
+                        ip.set_payload(payload);
+                        set_ipv6_transport_checksum(ip.payload_mut(), protocol, &source, &destination);
+
+                        (ip.packet_size(), pnet_packet::ethernet::EtherTypes::Ipv6)
+                    }
+                }
             }
             _ => unreachable!(),
         }
@@ -213,6 +497,7 @@ impl<W: Write> Pcap<W> {
 
     /// Write a TCP handshake.
     pub(crate) fn write_tcp_handshake(&mut self, info: &CapturePacket) {
+        let mut stream = TcpStream::default();
         let (source_port, dest_port) = (info.local_address.port(), info.remote_address.port());
 
         let mut info = info.clone();
@@ -227,8 +512,8 @@ impl<W: Write> Pcap<W> {
         // SYN
         let buf_size = {
             let mut tcp = MutableTcpPacket::new(buf).unwrap();
-            self.state.send_seq = 500;
-            tcp.set_sequence(self.state.send_seq);
+            stream.send_seq = 500;
+            tcp.set_sequence(stream.send_seq);
             tcp.set_flags(TcpFlags::SYN);
             tcp.set_source(source_port);
             tcp.set_destination(dest_port);
@@ -248,10 +533,10 @@ impl<W: Write> Pcap<W> {
         info.direction = Direction::Receive;
         let buf_size = {
             let mut tcp = MutableTcpPacket::new(buf).unwrap();
-            self.state.send_seq = self.state.send_seq.wrapping_add(1);
-            tcp.set_acknowledgement(self.state.send_seq);
-            self.state.rec_seq = 1000;
-            tcp.set_sequence(self.state.rec_seq);
+            stream.send_seq = stream.send_seq.wrapping_add(1);
+            tcp.set_acknowledgement(stream.send_seq);
+            stream.rec_seq = 1000;
+            tcp.set_sequence(stream.rec_seq);
             tcp.set_flags(TcpFlags::SYN | TcpFlags::ACK);
             tcp.set_source(dest_port);
             tcp.set_destination(source_port);
@@ -271,9 +556,9 @@ impl<W: Write> Pcap<W> {
         info.direction = Direction::Send;
         let buf_size = {
             let mut tcp = MutableTcpPacket::new(buf).unwrap();
-            tcp.set_sequence(self.state.send_seq);
-            self.state.rec_seq = self.state.rec_seq.wrapping_add(1);
-            tcp.set_acknowledgement(self.state.rec_seq);
+            tcp.set_sequence(stream.send_seq);
+            stream.rec_seq = stream.rec_seq.wrapping_add(1);
+            tcp.set_acknowledgement(stream.rec_seq);
             tcp.set_flags(TcpFlags::ACK);
             tcp.set_source(source_port);
             tcp.set_destination(dest_port);
@@ -289,12 +574,40 @@ impl<W: Write> Pcap<W> {
             options,
         );
 
-        self.state.has_sent_handshake = true;
+        stream.has_sent_handshake = true;
+        self.state.tcp_streams.insert(info.query_id, stream);
+    }
+
+    /// Write the teardown of a TCP stream, as closed by us: our FIN, the
+    /// server's FIN + ACK and our final ACK.
+    ///
+    /// Streams without a handshake (never connected) have nothing to close.
+    pub(crate) fn write_tcp_teardown(&mut self, info: &CapturePacket) {
+        let mut stream = match self.state.tcp_streams.remove(&info.query_id) {
+            Some(stream) if stream.has_sent_handshake => stream,
+            _ => return,
+        };
+
+        // FIN
+        self.write_tcp_control(info, &stream, Direction::Send, TcpFlags::FIN | TcpFlags::ACK);
+        stream.send_seq = stream.send_seq.wrapping_add(1);
+
+        // FIN + ACK
+        self.write_tcp_control(info, &stream, Direction::Receive, TcpFlags::FIN | TcpFlags::ACK);
+        stream.rec_seq = stream.rec_seq.wrapping_add(1);
+
+        // ACK
+        self.write_tcp_control(info, &stream, Direction::Send, TcpFlags::ACK);
     }
 
-    pub(crate) fn send_tcp_fin(&mut self, info: &CapturePacket) {
+    /// Write a payload-less TCP segment of the teardown, in the given
+    /// direction.
+    fn write_tcp_control(&mut self, info: &CapturePacket, stream: &TcpStream, direction: Direction, flags: u8) {
         let mut buffer_array: [u8; BUFFER_SIZE] = [0; BUFFER_SIZE];
         let buf: &mut [u8] = &mut buffer_array[..];
+
+        let mut info = info.clone();
+        info.direction = direction;
         let (source_port, dest_port) = info.ports_by_direction();
 
         let buf_size = {
@@ -304,80 +617,1312 @@ impl<W: Write> Pcap<W> {
             tcp.set_data_offset(5);
             tcp.set_window(43440);
 
-            match info.direction {
+            match direction {
                 Direction::Send => {
-                    tcp.set_sequence(self.state.send_seq);
-                    tcp.set_acknowledgement(self.state.rec_seq);
+                    tcp.set_sequence(stream.send_seq);
+                    tcp.set_acknowledgement(stream.rec_seq);
                 }
                 Direction::Receive => {
-                    tcp.set_sequence(self.state.rec_seq);
-                    tcp.set_acknowledgement(self.state.send_seq);
+                    tcp.set_sequence(stream.rec_seq);
+                    tcp.set_acknowledgement(stream.send_seq);
                 }
             }
 
-            tcp.set_flags(TcpFlags::FIN | TcpFlags::ACK);
+            tcp.set_flags(flags);
             tcp.packet_size()
         };
 
         self.write_transport_payload(
-            info,
+            &info,
             IpNextHeaderProtocols::Tcp,
             &buf[.. buf_size],
-            vec![EnhancedPacketOption::Comment("Generated TCP FIN".into())],
+            vec![EnhancedPacketOption::Comment("Generated TCP teardown".into())],
         );
+    }
 
-        // Update sequence number
-        match info.direction {
-            Direction::Send => {
-                self.state.send_seq = self.state.send_seq.wrapping_add(1);
+    /// The id of the interface a packet is written on, describing it first if
+    /// it is new.
+    fn interface_id(&mut self, info: &CapturePacket) -> u32 {
+        let name = match self.options.interfaces {
+            Interfaces::Single => return 0,
+            Interfaces::PerFlow => {
+                let protocol = match info.protocol {
+                    Protocol::Tcp => "tcp",
+                    Protocol::Udp => "udp",
+                };
+                let family = match info.remote_address.is_ipv4() {
+                    true => "ipv4",
+                    false => "ipv6",
+                };
+
+                format!("{protocol}/{family}")
             }
-            Direction::Receive => {
-                self.state.rec_seq = self.state.rec_seq.wrapping_add(1);
+            Interfaces::PerLocalAddress => info.local_address.ip().to_string(),
+        };
+
+        if let Some(id) = self.state.interfaces.get(&name) {
+            return *id;
+        }
+
+        let id = self.state.interfaces.len() as u32;
+        let block = interface_block(&self.options, self.interface_options(name.clone()));
+        let _ = self.writer.write_block(&block.into_block());
+        self.state.interfaces.insert(name, id);
+
+        id
+    }
+
+    /// The options describing the interface of the given name: its name, and
+    /// its address for the interfaces per local address.
+    fn interface_options(&self, name: String) -> Vec<InterfaceDescriptionOption<'static>> {
+        let address = match self.options.interfaces {
+            Interfaces::PerLocalAddress => name.parse().ok().and_then(address_option),
+            _ => None,
+        };
+
+        let mut interface_options = vec![InterfaceDescriptionOption::IfName(name.into())];
+        interface_options.extend(address);
+
+        interface_options
+    }
+
+    /// Write a Custom Block containing the [SUMMARY_PEN] followed by the JSON
+    /// encoded summary.
+    pub(crate) fn write_summary(&mut self, summary: &QuerySummary) -> GDResult<()> {
+        let mut value = match self.writer.section().endianness {
+            Endianness::Big => SUMMARY_PEN.to_be_bytes(),
+            Endianness::Little => SUMMARY_PEN.to_le_bytes(),
+        }
+        .to_vec();
+        value.extend(summary.to_json_bytes());
+
+        self.writer
+            .write_block(&UnknownBlock::new(CUSTOM_BLOCK_TYPE, 0, &value).into_block())
+            .map_err(|e| PacketSend.context(e))?;
+
+        Ok(())
+    }
+
+    /// Note a name of an address, written in a Name Resolution Block before
+    /// the first stream to it, if the capture was set up with
+    /// [name_resolution](CaptureOptions::name_resolution).
+    pub(crate) fn note_name(&mut self, address: &IpAddr, name: &str) {
+        if self.options.name_resolution && !self.state.resolved_addresses.contains(address) {
+            self.state.pending_names.push((*address, name.to_string()));
+        }
+    }
+
+    /// Write a Name Resolution Block with the names noted for an address, if
+    /// there are some and none was written for it yet (once per address).
+    pub(crate) fn write_name_resolution(&mut self, address: &IpAddr) -> GDResult<()> {
+        let mut names = Vec::new();
+        self.state.pending_names.retain(|(pending, name)| {
+            if pending == address && !names.contains(name) {
+                names.push(name.clone());
             }
+
+            pending != address
+        });
+        if names.is_empty() || !self.state.resolved_addresses.insert(*address) {
+            return Ok(());
         }
+
+        let names = names.into_iter().map(Into::into).collect();
+        let record = match address {
+            IpAddr::V4(ip) => {
+                Record::Ipv4(Ipv4Record {
+                    ip_addr: ip.octets().to_vec().into(),
+                    names,
+                })
+            }
+            IpAddr::V6(ip) => {
+                Record::Ipv6(Ipv6Record {
+                    ip_addr: ip.octets().to_vec().into(),
+                    names,
+                })
+            }
+        };
+
+        self.writer
+            .write_block(
+                &NameResolutionBlock {
+                    records: vec![record],
+                    options: vec![],
+                }
+                .into_block(),
+            )
+            .map_err(|e| Capture.context(e))?;
+
+        Ok(())
     }
 
+    /// Flush the blocks written so far to the underlying writer.
+    pub(crate) fn flush(&mut self) -> GDResult<()> { self.writer.get_mut().flush().map_err(|e| Capture.context(e)) }
+
     fn write_transport_payload(
         &mut self,
         info: &CapturePacket,
         protocol: IpNextHeaderProtocol,
         payload: &[u8],
         options: Vec<pcap_file::pcapng::blocks::enhanced_packet::EnhancedPacketOption>,
+    ) {
+        self.write_network_payload(info, protocol, payload, None, options);
+    }
+
+    /// Write an IP packet carrying the payload, or the fragment of a datagram.
+    fn write_network_payload(
+        &mut self,
+        info: &CapturePacket,
+        protocol: IpNextHeaderProtocol,
+        payload: &[u8],
+        fragment: Option<Fragment>,
+        mut options: Vec<pcap_file::pcapng::blocks::enhanced_packet::EnhancedPacketOption>,
     ) {
         let mut network_packet = vec![0; PACKET_SIZE - HEADER_SIZE_ETHERNET];
-        let (network_size, ethertype) = self.encode_ip_packet(&mut network_packet, info, protocol, payload);
+        let (network_size, ethertype) = self.encode_ip_packet(&mut network_packet, info, protocol, payload, fragment);
         let network_size = network_size + payload.len();
         network_packet.truncate(network_size);
 
+        if let Some(comment) = self.state.stream_comment.take() {
+            options.insert(0, EnhancedPacketOption::Comment(comment.into()));
+        }
+
+        if self.options.query_ids {
+            options.push(EnhancedPacketOption::Comment(format!("query_id={}", info.query_id).into()));
+        }
+
+        if let Some(protocol_hint) = info.protocol_hint {
+            options.push(EnhancedPacketOption::Comment(format!("protocol={protocol_hint}").into()));
+        }
+
+        if self.options.verbose {
+            options.extend(
+                describe_layers(&network_packet, ethertype)
+                    .into_iter()
+                    .map(|comment| EnhancedPacketOption::Comment(comment.into())),
+            );
+        }
+
+        self.rotate_if_full();
+        let interface_id = self.interface_id(info);
         let mut physical_packet = vec![0; PACKET_SIZE];
         let physical_size =
             self.encode_ethernet_packet(&mut physical_packet, ethertype, &network_packet) + network_size;
 
         physical_packet.truncate(physical_size);
+        if let Some(snaplen) = self.options.snaplen {
+            physical_packet.truncate(snaplen as usize);
+        }
 
-        self.writer
+        let written = self
+            .writer
             .write_block(
                 &pcap_file::pcapng::blocks::enhanced_packet::EnhancedPacketBlock {
                     original_len: physical_size as u32,
                     data: physical_packet.into(),
-                    interface_id: 0,
-                    timestamp: self.state.start_time.elapsed(),
+                    interface_id,
+                    timestamp: self.state.timestamp(),
                     options,
                 }
                 .into_block(),
             )
             .unwrap();
+
+        if let Some(rotating) = &mut self.rotating {
+            rotating.bytes += written as u64;
+            rotating.packets += 1;
+        }
+    }
+}
+
+/// Start a section of the capture, its header naming the application
+/// (`rust-gamedig <version>`), the operating system and the architecture it
+/// was written on.
+pub(crate) fn new_section_writer<W: Write>(output: W) -> GDResult<PcapNgWriter<W>> {
+    let section = SectionHeaderBlock {
+        options: vec![
+            SectionHeaderOption::UserApplication(format!("rust-gamedig {}", env!("CARGO_PKG_VERSION")).into()),
+            SectionHeaderOption::OS(std::env::consts::OS.into()),
+            SectionHeaderOption::Hardware(std::env::consts::ARCH.into()),
+        ],
+        ..Default::default()
+    };
+
+    PcapNgWriter::with_section_header(output, section).map_err(|e| Capture.context(e))
+}
+
+/// The description of an Ethernet interface of the capture.
+pub(crate) fn interface_block(
+    options: &CaptureOptions,
+    mut interface_options: Vec<InterfaceDescriptionOption<'static>>,
+) -> InterfaceDescriptionBlock<'static> {
+    interface_options.push(InterfaceDescriptionOption::IfTsResol(TIMESTAMP_RESOLUTION));
+
+    InterfaceDescriptionBlock {
+        linktype: pcap_file::DataLink::ETHERNET,
+        snaplen: options.snaplen.unwrap_or(0xFFFF),
+        options: interface_options,
+    }
+}
+
+/// The `if_IPv4addr`/`if_IPv6addr` option of an interface having a single
+/// (host) address, `None` for an unspecified address, which doesn't tell
+/// the interface.
+fn address_option(address: IpAddr) -> Option<InterfaceDescriptionOption<'static>> {
+    if address.is_unspecified() {
+        return None;
+    }
+
+    Some(match address {
+        IpAddr::V4(address) => {
+            let mut value = address.octets().to_vec();
+            value.extend([0xFF; 4]);
+            InterfaceDescriptionOption::IfIpv4Addr(value.into())
+        }
+        IpAddr::V6(address) => {
+            let mut value = address.octets().to_vec();
+            value.push(128);
+            InterfaceDescriptionOption::IfIpv6Addr(value.into())
+        }
+    })
+}
+
+/// Set the checksum of a TCP or UDP segment sent over IPv4, which covers the
+/// pseudo header made of the IP addresses.
+fn set_ipv4_transport_checksum(
+    segment: &mut [u8],
+    protocol: IpNextHeaderProtocol,
+    source: &Ipv4Addr,
+    destination: &Ipv4Addr,
+) {
+    match protocol {
+        IpNextHeaderProtocols::Tcp => {
+            let mut tcp = MutableTcpPacket::new(segment).unwrap();
+            tcp.set_checksum(tcp::ipv4_checksum(&tcp.to_immutable(), source, destination));
+        }
+        IpNextHeaderProtocols::Udp => {
+            let mut udp = MutableUdpPacket::new(segment).unwrap();
+            udp.set_checksum(udp::ipv4_checksum(&udp.to_immutable(), source, destination));
+        }
+        _ => {}
+    }
+}
+
+/// Same as [set_ipv4_transport_checksum], over IPv6.
+fn set_ipv6_transport_checksum(
+    segment: &mut [u8],
+    protocol: IpNextHeaderProtocol,
+    source: &Ipv6Addr,
+    destination: &Ipv6Addr,
+) {
+    match protocol {
+        IpNextHeaderProtocols::Tcp => {
+            let mut tcp = MutableTcpPacket::new(segment).unwrap();
+            tcp.set_checksum(tcp::ipv6_checksum(&tcp.to_immutable(), source, destination));
+        }
+        IpNextHeaderProtocols::Udp => {
+            let mut udp = MutableUdpPacket::new(segment).unwrap();
+            udp.set_checksum(udp::ipv6_checksum(&udp.to_immutable(), source, destination));
+        }
+        _ => {}
+    }
+}
+
+/// Describe the computed header fields of an encoded network layer packet and
+/// of the transport layer packet it carries, one line per layer.
+fn describe_layers(network_packet: &[u8], ethertype: EtherType) -> Vec<String> {
+    let mut layers = Vec::with_capacity(2);
+
+    let (protocol, transport) = match ethertype {
+        pnet_packet::ethernet::EtherTypes::Ipv4 => {
+            let Some(ip) = Ipv4Packet::new(network_packet) else {
+                return layers;
+            };
+
+            layers.push(format!(
+                "IPv4: header length {} bytes, total length {}, ttl {}, checksum {:#06x}",
+                ip.get_header_length() as usize * 4,
+                ip.get_total_length(),
+                ip.get_ttl(),
+                ip.get_checksum()
+            ));
+
+            // Only the first fragment of a datagram starts with its header.
+            if ip.get_fragment_offset() != 0 {
+                return layers;
+            }
+
+            (ip.get_next_level_protocol(), ip.payload().to_vec())
+        }
+        pnet_packet::ethernet::EtherTypes::Ipv6 => {
+            let Some(ip) = Ipv6Packet::new(network_packet) else {
+                return layers;
+            };
+
+            layers.push(format!(
+                "IPv6: payload length {}, flow label {}, hop limit {}",
+                ip.get_payload_length(),
+                ip.get_flow_label(),
+                ip.get_hop_limit()
+            ));
+
+            (ip.get_next_header(), ip.payload().to_vec())
+        }
+        _ => return layers,
+    };
+
+    match protocol {
+        IpNextHeaderProtocols::Tcp => {
+            if let Some(tcp) = TcpPacket::new(&transport) {
+                layers.push(format!(
+                    "TCP: seq {}, ack {}, flags {:#04x}, data offset {}, payload {} bytes",
+                    tcp.get_sequence(),
+                    tcp.get_acknowledgement(),
+                    tcp.get_flags(),
+                    tcp.get_data_offset(),
+                    tcp.payload().len()
+                ));
+            }
+        }
+        IpNextHeaderProtocols::Udp => {
+            if let Some(udp) = UdpPacket::new(&transport) {
+                layers.push(format!(
+                    "UDP: length {}, checksum {:#06x}, payload {} bytes",
+                    udp.get_length(),
+                    udp.get_checksum(),
+                    udp.payload().len()
+                ));
+            }
+        }
+        _ => {}
+    }
+
+    layers
+}
+
+impl State {
+    /// The timestamp of a packet written now: the time since the UNIX epoch,
+    /// in nanoseconds (the resolution of the interfaces).
+    pub(crate) fn timestamp(&self) -> Duration {
+        self.start_wall_time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            + self.start_time.elapsed()
     }
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
+            start_wall_time: SystemTime::now(),
             start_time: Instant::now(),
-            send_seq: 0,
-            rec_seq: 0,
-            has_sent_handshake: false,
+            tcp_streams: HashMap::new(),
             stream_count: 0,
+            stream_comment: None,
+            pending_names: Vec::new(),
+            resolved_addresses: HashSet::new(),
+            interfaces: HashMap::new(),
+            fragmented_datagrams: 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pcap_file::pcapng::{Block, PcapNgReader};
+    use std::net::SocketAddr;
+
+    fn verbose(verbose: bool) -> CaptureOptions {
+        CaptureOptions {
+            verbose,
+            ..Default::default()
+        }
+    }
+
+    /// A writer with an Ethernet interface, that packets can be written to.
+    fn ethernet_writer() -> PcapNgWriter<Vec<u8>> {
+        let mut writer = PcapNgWriter::new(Vec::new()).unwrap();
+        writer
+            .write_block(&interface_block(&CaptureOptions::default(), vec![]).into_block())
+            .unwrap();
+
+        writer
+    }
+
+    fn written_comments(options: CaptureOptions, direction: Direction, protocol: Protocol) -> Vec<String> {
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction,
+            protocol,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let mut pcap = Pcap::new(ethernet_writer(), options);
+        pcap.write_transport_packet(&info, &[0xFF, 0xFF, 0xFF, 0xFF, 0x54]).unwrap();
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let mut comments = Vec::new();
+        while let Some(block) = reader.next_block() {
+            if let Block::EnhancedPacket(packet) = block.unwrap() {
+                for option in packet.options {
+                    if let EnhancedPacketOption::Comment(comment) = option {
+                        comments.push(comment.to_string());
+                    }
+                }
+            }
+        }
+
+        comments
+    }
+
+    #[test]
+    fn hostname_is_noted_on_the_first_stream_packet() {
+        use crate::capture::writer::Writer;
+
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Udp,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        for annotate_hostnames in [true, false] {
+            let options = CaptureOptions {
+                annotate_hostnames,
+                ..Default::default()
+            };
+            let mut pcap = Pcap::new(ethernet_writer(), options);
+            pcap.annotate_hostname("play.example.com", &remote_address.ip());
+            pcap.new_connect(&info).unwrap();
+            pcap.write(&info, &[0x01]).unwrap();
+            pcap.write(&info, &[0x02]).unwrap();
+            let data = pcap.writer.into_inner();
+
+            let mut reader = PcapNgReader::new(&data[..]).unwrap();
+            let mut comments = Vec::new();
+            while let Some(block) = reader.next_block() {
+                if let Block::EnhancedPacket(packet) = block.unwrap() {
+                    comments.push(packet.options.len());
+                    if let Some(EnhancedPacketOption::Comment(comment)) = packet.options.first() {
+                        assert_eq!(comment, "querying play.example.com (resolved 127.0.0.2)");
+                    }
+                }
+            }
+
+            match annotate_hostnames {
+                true => assert_eq!(comments, [1, 0]),
+                false => assert_eq!(comments, [0, 0]),
+            }
+        }
+    }
+
+    #[test]
+    fn addresses_are_resolved_once() {
+        use crate::capture::writer::Writer;
+
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let other_address: SocketAddr = "[::2]:27015".parse().unwrap();
+        let info = |remote_address| {
+            CapturePacket {
+                direction: Direction::Send,
+                protocol: Protocol::Udp,
+                remote_address,
+                local_address: &local_address,
+                query_id: 1,
+                protocol_hint: None,
+            }
+        };
+
+        for name_resolution in [true, false] {
+            let options = CaptureOptions {
+                name_resolution,
+                ..Default::default()
+            };
+            let mut pcap = Pcap::new(ethernet_writer(), options);
+            pcap.annotate_hostname("play.example.com", &remote_address.ip());
+            pcap.annotate_game("teamfortress2", &remote_address.ip());
+            pcap.new_connect(&info(&remote_address)).unwrap();
+            pcap.annotate_game("teamfortress2", &remote_address.ip());
+            pcap.new_connect(&info(&remote_address)).unwrap();
+            // Addresses without names aren't resolved.
+            pcap.new_connect(&info(&other_address)).unwrap();
+            let data = pcap.writer.into_inner();
+
+            let mut reader = PcapNgReader::new(&data[..]).unwrap();
+            let mut records = Vec::new();
+            while let Some(block) = reader.next_block() {
+                if let Block::NameResolution(block) = block.unwrap() {
+                    records.extend(block.records.into_iter().map(|record| record.into_owned()));
+                }
+            }
+
+            match name_resolution {
+                true => {
+                    assert_eq!(
+                        records,
+                        [Record::Ipv4(Ipv4Record {
+                            ip_addr: vec![127, 0, 0, 2].into(),
+                            names: vec!["play.example.com".into(), "teamfortress2".into()],
+                        })]
+                    )
+                }
+                false => assert!(records.is_empty()),
+            }
+        }
+    }
+
+    #[test]
+    fn packets_are_stamped_with_their_query_id() {
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let query = |query_id| {
+            CapturePacket {
+                direction: Direction::Send,
+                protocol: Protocol::Tcp,
+                remote_address: &remote_address,
+                local_address: &local_address,
+                query_id,
+                protocol_hint: None,
+            }
+        };
+
+        let options = CaptureOptions {
+            query_ids: true,
+            ..Default::default()
+        };
+        let mut pcap = Pcap::new(ethernet_writer(), options);
+        // Two overlapping queries to the same server.
+        pcap.write_transport_packet(&query(1), &[0x01]).unwrap();
+        pcap.write_transport_packet(&query(2), &[0x02]).unwrap();
+        pcap.write_transport_packet(&query(1), &[0x03]).unwrap();
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let mut comments = Vec::new();
+        while let Some(block) = reader.next_block() {
+            if let Block::EnhancedPacket(packet) = block.unwrap() {
+                if let Some(EnhancedPacketOption::Comment(comment)) = packet.options.last() {
+                    comments.push(comment.to_string());
+                }
+            }
+        }
+
+        // Each payload is followed by its generated ACK.
+        assert_eq!(
+            comments,
+            ["query_id=1", "query_id=1", "query_id=2", "query_id=2", "query_id=1", "query_id=1"]
+        );
+        assert!(written_comments(verbose(false), Direction::Send, Protocol::Udp).is_empty());
+    }
+
+    #[test]
+    fn packets_are_annotated_with_their_protocol() {
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let query = |protocol_hint| {
+            CapturePacket {
+                direction: Direction::Send,
+                protocol: Protocol::Udp,
+                remote_address: &remote_address,
+                local_address: &local_address,
+                query_id: 1,
+                protocol_hint,
+            }
+        };
+
+        let mut pcap = Pcap::new(ethernet_writer(), CaptureOptions::default());
+        pcap.write_transport_packet(&query(Some("valve")), &[0x01]).unwrap();
+        pcap.write_transport_packet(&query(None), &[0x02]).unwrap();
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let mut comments = Vec::new();
+        while let Some(block) = reader.next_block() {
+            if let Block::EnhancedPacket(packet) = block.unwrap() {
+                comments.push(packet.options.iter().find_map(|option| {
+                    match option {
+                        EnhancedPacketOption::Comment(comment) => Some(comment.to_string()),
+                        _ => None,
+                    }
+                }));
+            }
+        }
+
+        assert_eq!(comments, [Some("protocol=valve".to_string()), None]);
+    }
+
+    #[test]
+    fn summary_is_written_as_custom_block() {
+        let mut pcap = Pcap::new(PcapNgWriter::new(Vec::new()).unwrap(), verbose(false));
+        pcap.write_summary(&QuerySummary {
+            game: "teamfortress2".to_string(),
+            players_online: Some(12),
+            ping: Some(std::time::Duration::from_millis(42)),
+            errors: vec![],
+        })
+        .unwrap();
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let endianness = reader.section().endianness;
+        let Some(Ok(Block::Unknown(block))) = reader.next_block() else {
+            panic!("Expected a custom block");
+        };
+
+        let pen = match endianness {
+            Endianness::Big => SUMMARY_PEN.to_be_bytes(),
+            Endianness::Little => SUMMARY_PEN.to_le_bytes(),
+        };
+        assert_eq!(block.type_, CUSTOM_BLOCK_TYPE);
+        assert_eq!(block.value[.. 4], pen);
+
+        // The block is padded to 32 bits with null bytes.
+        let json = block.value[4 ..].split(|&byte| byte == 0).next().unwrap();
+        let summary: serde_json::Value = serde_json::from_slice(json).unwrap();
+        assert_eq!(summary["game"], "teamfortress2");
+        assert_eq!(summary["players_online"], 12);
+        assert_eq!(summary["ping_ms"], 42);
+    }
+
+    #[test]
+    fn verbose_disabled_adds_no_comments() {
+        assert!(written_comments(verbose(false), Direction::Send, Protocol::Udp).is_empty());
+    }
+
+    #[test]
+    fn verbose_describes_udp_layers() {
+        let comments = written_comments(verbose(true), Direction::Send, Protocol::Udp);
+
+        assert_eq!(comments.len(), 2);
+        assert!(comments[0].starts_with("IPv4: header length 24 bytes, total length 37"));
+        assert!(comments[1].starts_with("UDP: length 13"));
+    }
+
+    #[test]
+    fn received_packets_have_a_lower_ttl() {
+        let sent = written_comments(verbose(true), Direction::Send, Protocol::Udp);
+        assert!(sent[0].contains(", ttl 64,"));
+
+        let received = written_comments(verbose(true), Direction::Receive, Protocol::Udp);
+        assert!(received[0].contains(", ttl 54,"));
+
+        let options = CaptureOptions {
+            receive_ttl: 40,
+            ..verbose(true)
+        };
+        let received = written_comments(options, Direction::Receive, Protocol::Udp);
+        assert!(received[0].contains(", ttl 40,"));
+    }
+
+    #[test]
+    fn flow_label_stays_within_20_bits() {
+        let local_address: SocketAddr = "[::1]:50000".parse().unwrap();
+        let remote_address: SocketAddr = "[::2]:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Udp,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let mut pcap = Pcap::new(ethernet_writer(), verbose(false));
+        for stream_count in [FLOW_LABEL_MASK, FLOW_LABEL_MASK + 6, u32::MAX] {
+            pcap.state.stream_count = stream_count;
+            pcap.write_transport_packet(&info, &[0xFF, 0xFF, 0xFF, 0xFF, 0x54]).unwrap();
+        }
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let mut flow_labels = Vec::new();
+        while let Some(block) = reader.next_block() {
+            if let Block::EnhancedPacket(packet) = block.unwrap() {
+                let ip = Ipv6Packet::new(&packet.data[HEADER_SIZE_ETHERNET ..]).unwrap();
+                assert_eq!(ip.get_version(), 6);
+                assert_eq!(ip.get_traffic_class(), 0);
+                assert_eq!(ip.get_payload_length(), 13);
+                assert_eq!(ip.get_source(), local_address.ip());
+                flow_labels.push(ip.get_flow_label());
+            }
+        }
+
+        assert_eq!(flow_labels, [FLOW_LABEL_MASK, 5, FLOW_LABEL_MASK]);
+    }
+
+    #[test]
+    fn stream_identifier_stays_within_16_bits() {
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Udp,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let mut pcap = Pcap::new(ethernet_writer(), verbose(false));
+        for stream_count in [0xFFFF, 0x1_0003] {
+            pcap.state.stream_count = stream_count;
+            pcap.write_transport_packet(&info, &[0xFF, 0xFF, 0xFF, 0xFF, 0x54]).unwrap();
+        }
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let mut stream_identifiers = Vec::new();
+        while let Some(block) = reader.next_block() {
+            if let Block::EnhancedPacket(packet) = block.unwrap() {
+                let ip = Ipv4Packet::new(&packet.data[HEADER_SIZE_ETHERNET ..]).unwrap();
+                let option = ip.get_options_raw();
+                assert_eq!(option[.. 2], [0x88, 4]);
+                stream_identifiers.push(u16::from_be_bytes([option[2], option[3]]));
+            }
+        }
+
+        assert_eq!(stream_identifiers, [0xFFFF, 3]);
+    }
+
+    /// The source port, flags, sequence and acknowledgement numbers of the
+    /// written TCP segments (over IPv4).
+    fn tcp_segments(data: &[u8]) -> Vec<(u16, u8, u32, u32)> {
+        let mut reader = PcapNgReader::new(data).unwrap();
+        let mut segments = Vec::new();
+        while let Some(block) = reader.next_block() {
+            if let Block::EnhancedPacket(packet) = block.unwrap() {
+                let ip = Ipv4Packet::new(&packet.data[HEADER_SIZE_ETHERNET ..]).unwrap();
+                let tcp = TcpPacket::new(ip.payload()).unwrap();
+                segments.push((
+                    tcp.get_source(),
+                    tcp.get_flags(),
+                    tcp.get_sequence(),
+                    tcp.get_acknowledgement(),
+                ));
+            }
+        }
+
+        segments
+    }
+
+    #[test]
+    fn tcp_streams_are_torn_down_once() {
+        use crate::capture::writer::Writer;
+
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Tcp,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let mut pcap = Pcap::new(ethernet_writer(), CaptureOptions::default());
+        // Never connected, so nothing to close.
+        pcap.close_connection(&info).unwrap();
+        pcap.new_connect(&info).unwrap();
+        pcap.write(&info, &[0x01, 0x02]).unwrap();
+        pcap.close_connection(&info).unwrap();
+        pcap.close_connection(&info).unwrap();
+        let data = pcap.writer.into_inner();
+
+        let segments = tcp_segments(&data);
+
+        // The handshake, the payload (and its ACK), then the teardown.
+        assert_eq!(segments.len(), 8);
+        assert_eq!(
+            segments[5 ..],
+            [
+                (50000, TcpFlags::FIN | TcpFlags::ACK, 503, 1001),
+                (27015, TcpFlags::FIN | TcpFlags::ACK, 1001, 504),
+                (50000, TcpFlags::ACK, 504, 1002),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_tcp_payloads_are_split_over_segments() {
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Tcp,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let mut pcap = Pcap::new(ethernet_writer(), CaptureOptions::default());
+        pcap.write_transport_packet(&info, &vec![0x20; MAX_SEGMENT_SIZE * 2 + 10]).unwrap();
+        let data = pcap.writer.into_inner();
+
+        let size = MAX_SEGMENT_SIZE as u32;
+        assert_eq!(
+            tcp_segments(&data),
+            [
+                (50000, TcpFlags::ACK, 0, 0),
+                (50000, TcpFlags::ACK, size, 0),
+                (50000, TcpFlags::PSH | TcpFlags::ACK, size * 2, 0),
+                (27015, TcpFlags::ACK, 0, size * 2 + 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_udp_payloads_are_fragmented() {
+        let payload: Vec<u8> = (0 .. MAX_FRAGMENT_SIZE * 2).map(|i| i as u8).collect();
+        let written = |local_address: &str, remote_address: &str| {
+            let local_address: SocketAddr = local_address.parse().unwrap();
+            let remote_address: SocketAddr = remote_address.parse().unwrap();
+            let info = CapturePacket {
+                direction: Direction::Receive,
+                protocol: Protocol::Udp,
+                remote_address: &remote_address,
+                local_address: &local_address,
+                query_id: 1,
+                protocol_hint: None,
+            };
+
+            let mut pcap = Pcap::new(ethernet_writer(), CaptureOptions::default());
+            pcap.write_transport_packet(&info, &payload).unwrap();
+            assert!(pcap.write_transport_packet(&info, &[0; 0x1_0000]).is_err());
+            let data = pcap.writer.into_inner();
+
+            let mut reader = PcapNgReader::new(&data[..]).unwrap();
+            let mut packets = Vec::new();
+            while let Some(block) = reader.next_block() {
+                if let Block::EnhancedPacket(packet) = block.unwrap() {
+                    packets.push(packet.data[HEADER_SIZE_ETHERNET ..].to_vec());
+                }
+            }
+
+            packets
+        };
+
+        // The UDP header pushes the end of the datagram to a third fragment.
+        let packets = written("127.0.0.1:50000", "127.0.0.2:27015");
+        assert_eq!(packets.len(), 3);
+        let mut datagram = Vec::new();
+        for (index, packet) in packets.iter().enumerate() {
+            let ip = Ipv4Packet::new(packet).unwrap();
+            assert_eq!(ip.get_identification(), 0);
+            assert_eq!(usize::from(ip.get_fragment_offset()) * 8, datagram.len());
+            let more = ip.get_flags() & pnet_packet::ipv4::Ipv4Flags::MoreFragments != 0;
+            assert_eq!(more, index < 2);
+            assert_eq!(ip.get_checksum(), pnet_packet::ipv4::checksum(&ip));
+            datagram.extend_from_slice(ip.payload());
+        }
+        let udp = UdpPacket::new(&datagram).unwrap();
+        assert_eq!(udp.payload(), payload);
+        assert_eq!(usize::from(udp.get_length()), datagram.len());
+        let (source, destination) = ("127.0.0.2".parse().unwrap(), "127.0.0.1".parse().unwrap());
+        assert_eq!(udp.get_checksum(), udp::ipv4_checksum(&udp, &source, &destination));
+
+        let packets = written("[::1]:50000", "[::2]:27015");
+        assert_eq!(packets.len(), 3);
+        let mut datagram = Vec::new();
+        for (index, packet) in packets.iter().enumerate() {
+            let ip = Ipv6Packet::new(packet).unwrap();
+            assert_eq!(ip.get_next_header(), IpNextHeaderProtocols::Ipv6Frag);
+            let (header, part) = ip.payload().split_at(HEADER_SIZE_IP6_FRAGMENT);
+            assert_eq!(header[0], IpNextHeaderProtocols::Udp.0);
+            let offset_and_flag = u16::from_be_bytes([header[2], header[3]]);
+            assert_eq!(usize::from(offset_and_flag >> 3) * 8, datagram.len());
+            assert_eq!(offset_and_flag & 1 == 1, index < 2);
+            datagram.extend_from_slice(part);
+        }
+        let udp = UdpPacket::new(&datagram).unwrap();
+        assert_eq!(udp.payload(), payload);
+        let (source, destination) = ("::2".parse().unwrap(), "::1".parse().unwrap());
+        assert_eq!(udp.get_checksum(), udp::ipv6_checksum(&udp, &source, &destination));
+    }
+
+    #[test]
+    fn retried_tcp_streams_have_their_own_state() {
+        use crate::capture::writer::Writer;
+
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let (first_address, retry_address) = ("127.0.0.1:50000".parse().unwrap(), "127.0.0.1:50001".parse().unwrap());
+        let info = |local_address, query_id, direction| {
+            CapturePacket {
+                direction,
+                protocol: Protocol::Tcp,
+                remote_address: &remote_address,
+                local_address,
+                query_id,
+                protocol_hint: None,
+            }
+        };
+        let first = info(&first_address, 1, Direction::Send);
+        let retry = info(&retry_address, 2, Direction::Send);
+
+        // The first attempt times out after sending its request, its socket
+        // being dropped once the retry connected.
+        let mut pcap = Pcap::new(ethernet_writer(), CaptureOptions::default());
+        pcap.new_connect(&first).unwrap();
+        pcap.write(&first, &[0x01, 0x02, 0x03, 0x04]).unwrap();
+        pcap.new_connect(&retry).unwrap();
+        pcap.close_connection(&first).unwrap();
+        pcap.write(&retry, &[0x01, 0x02, 0x03, 0x04]).unwrap();
+        pcap.write(&info(&retry_address, 2, Direction::Receive), &[0x05, 0x06])
+            .unwrap();
+        pcap.close_connection(&retry).unwrap();
+        let segments = tcp_segments(&pcap.writer.into_inner());
+
+        let first_segments: Vec<_> = segments
+            .iter()
+            .filter(|segment| segment.0 == 50000)
+            .collect();
+        let retry_segments: Vec<_> = segments
+            .iter()
+            .filter(|segment| segment.0 == 50001)
+            .collect();
+
+        // Both attempts start with their own SYN, and close from their own
+        // sequence numbers.
+        assert_eq!(first_segments[0], &(50000, TcpFlags::SYN, 500, 0));
+        assert_eq!(retry_segments[0], &(50001, TcpFlags::SYN, 500, 0));
+        assert_eq!(first_segments[3], &(50000, TcpFlags::FIN | TcpFlags::ACK, 505, 1001));
+        assert_eq!(retry_segments[2], &(50001, TcpFlags::PSH | TcpFlags::ACK, 501, 1001));
+        assert_eq!(retry_segments[4], &(50001, TcpFlags::FIN | TcpFlags::ACK, 505, 1003));
+    }
+
+    #[test]
+    fn transport_checksums_are_valid() {
+        let addresses = [("127.0.0.1:50000", "127.0.0.2:27015"), ("[::1]:50000", "[::2]:27015")];
+        for (local_address, remote_address) in addresses {
+            let local_address: SocketAddr = local_address.parse().unwrap();
+            let remote_address: SocketAddr = remote_address.parse().unwrap();
+
+            for protocol in [Protocol::Tcp, Protocol::Udp] {
+                let info = CapturePacket {
+                    direction: Direction::Receive,
+                    protocol,
+                    remote_address: &remote_address,
+                    local_address: &local_address,
+                    query_id: 1,
+                    protocol_hint: None,
+                };
+                let mut pcap = Pcap::new(ethernet_writer(), CaptureOptions::default());
+                pcap.write_transport_packet(&info, &[0xFF, 0xFF, 0xFF, 0xFF, 0x49, 0x11]).unwrap();
+                let data = pcap.writer.into_inner();
+
+                let mut reader = PcapNgReader::new(&data[..]).unwrap();
+                while let Some(block) = reader.next_block() {
+                    let packet = match block.unwrap() {
+                        Block::EnhancedPacket(packet) => packet,
+                        _ => continue,
+                    };
+                    let network = &packet.data[HEADER_SIZE_ETHERNET ..];
+                    let (checksum, expected) = match (protocol, local_address) {
+                        (Protocol::Tcp, SocketAddr::V4(_)) => {
+                            let ip = Ipv4Packet::new(network).unwrap();
+                            let tcp = TcpPacket::new(ip.payload()).unwrap();
+                            let expected = tcp::ipv4_checksum(&tcp, &ip.get_source(), &ip.get_destination());
+                            (tcp.get_checksum(), expected)
+                        }
+                        (Protocol::Udp, SocketAddr::V4(_)) => {
+                            let ip = Ipv4Packet::new(network).unwrap();
+                            let udp = UdpPacket::new(ip.payload()).unwrap();
+                            let expected = udp::ipv4_checksum(&udp, &ip.get_source(), &ip.get_destination());
+                            (udp.get_checksum(), expected)
+                        }
+                        (Protocol::Tcp, SocketAddr::V6(_)) => {
+                            let ip = Ipv6Packet::new(network).unwrap();
+                            let tcp = TcpPacket::new(ip.payload()).unwrap();
+                            let expected = tcp::ipv6_checksum(&tcp, &ip.get_source(), &ip.get_destination());
+                            (tcp.get_checksum(), expected)
+                        }
+                        (Protocol::Udp, SocketAddr::V6(_)) => {
+                            let ip = Ipv6Packet::new(network).unwrap();
+                            let udp = UdpPacket::new(ip.payload()).unwrap();
+                            let expected = udp::ipv6_checksum(&udp, &ip.get_source(), &ip.get_destination());
+                            (udp.get_checksum(), expected)
+                        }
+                    };
+
+                    assert_ne!(checksum, 0);
+                    assert_eq!(checksum, expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn packets_are_truncated_to_the_snaplen() {
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction: Direction::Receive,
+            protocol: Protocol::Udp,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let written_lengths = |snaplen| {
+            let options = CaptureOptions {
+                snaplen,
+                ..Default::default()
+            };
+            let mut pcap = Pcap::new(ethernet_writer(), options);
+            pcap.write_transport_packet(&info, &[0x20; 200]).unwrap();
+            let data = pcap.writer.into_inner();
+
+            let mut reader = PcapNgReader::new(&data[..]).unwrap();
+            let mut lengths = Vec::new();
+            while let Some(block) = reader.next_block() {
+                if let Block::EnhancedPacket(packet) = block.unwrap() {
+                    lengths.push((packet.data.len(), packet.original_len as usize));
+                }
+            }
+
+            lengths
+        };
+
+        let (full_length, original_length) = written_lengths(None)[0];
+        assert_eq!(full_length, original_length);
+        assert!(full_length > 200);
+        assert_eq!(written_lengths(Some(64)), [(64, original_length)]);
+    }
+
+    #[test]
+    fn packets_are_stamped_with_the_wall_clock_time() {
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Udp,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let since_epoch = || SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let before = since_epoch();
+        let mut pcap = Pcap::new(ethernet_writer(), CaptureOptions::default());
+        pcap.write_transport_packet(&info, &[0x01]).unwrap();
+        let after = since_epoch();
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let (mut resolutions, mut timestamps) = (Vec::new(), Vec::new());
+        while let Some(block) = reader.next_block() {
+            match block.unwrap() {
+                Block::InterfaceDescription(interface) => {
+                    resolutions.extend(interface.options.iter().filter_map(|option| {
+                        match option {
+                            InterfaceDescriptionOption::IfTsResol(resolution) => Some(*resolution),
+                            _ => None,
+                        }
+                    }));
+                }
+                Block::EnhancedPacket(packet) => timestamps.push(packet.timestamp),
+                _ => {}
+            }
+        }
+
+        assert_eq!(resolutions, [TIMESTAMP_RESOLUTION]);
+        assert_eq!(timestamps.len(), 1);
+        assert!(before <= timestamps[0] && timestamps[0] <= after);
+    }
+
+    #[test]
+    fn flows_are_written_on_their_own_interface() {
+        let local_v4: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_v4: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let local_v6: SocketAddr = "[::1]:50000".parse().unwrap();
+        let remote_v6: SocketAddr = "[::2]:27015".parse().unwrap();
+        let info = |protocol, local_address, remote_address| {
+            CapturePacket {
+                direction: Direction::Send,
+                protocol,
+                remote_address,
+                local_address,
+                query_id: 1,
+                protocol_hint: None,
+            }
+        };
+
+        let options = CaptureOptions {
+            interfaces: Interfaces::PerFlow,
+            ..Default::default()
+        };
+        // The interfaces are all described by the writer.
+        let mut pcap = Pcap::new(PcapNgWriter::new(Vec::new()).unwrap(), options);
+        pcap.write_transport_packet(&info(Protocol::Udp, &local_v4, &remote_v4), &[0x01]).unwrap();
+        pcap.write_transport_packet(&info(Protocol::Tcp, &local_v4, &remote_v4), &[0x02]).unwrap();
+        pcap.write_transport_packet(&info(Protocol::Udp, &local_v4, &remote_v4), &[0x03]).unwrap();
+        pcap.write_transport_packet(&info(Protocol::Udp, &local_v6, &remote_v6), &[0x04]).unwrap();
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let (mut interfaces, mut interface_ids) = (Vec::new(), Vec::new());
+        while let Some(block) = reader.next_block() {
+            match block.unwrap() {
+                Block::InterfaceDescription(interface) => {
+                    if let Some(InterfaceDescriptionOption::IfName(name)) = interface.options.first() {
+                        interfaces.push(name.to_string());
+                    }
+                }
+                Block::EnhancedPacket(packet) => interface_ids.push(packet.interface_id),
+                _ => {}
+            }
+        }
+
+        assert_eq!(interfaces, ["udp/ipv4", "tcp/ipv4", "udp/ipv6"]);
+        // The TCP payload being followed by its generated ACK.
+        assert_eq!(interface_ids, [0, 1, 1, 0, 2]);
+    }
+
+    #[test]
+    fn local_addresses_are_written_on_their_own_interface() {
+        let remote: SocketAddr = "192.0.2.1:27015".parse().unwrap();
+        let first: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+        let second: SocketAddr = "10.0.0.2:50001".parse().unwrap();
+        let unspecified: SocketAddr = "0.0.0.0:50002".parse().unwrap();
+        let info = |local_address| {
+            CapturePacket {
+                direction: Direction::Send,
+                protocol: Protocol::Udp,
+                remote_address: &remote,
+                local_address,
+                query_id: 1,
+                protocol_hint: None,
+            }
+        };
+
+        let options = CaptureOptions {
+            interfaces: Interfaces::PerLocalAddress,
+            ..Default::default()
+        };
+        let mut pcap = Pcap::new(PcapNgWriter::new(Vec::new()).unwrap(), options);
+        pcap.write_transport_packet(&info(&first), &[0x01]).unwrap();
+        pcap.write_transport_packet(&info(&second), &[0x02]).unwrap();
+        pcap.write_transport_packet(&info(&first), &[0x03]).unwrap();
+        pcap.write_transport_packet(&info(&unspecified), &[0x04]).unwrap();
+        let data = pcap.writer.into_inner();
+
+        let mut reader = PcapNgReader::new(&data[..]).unwrap();
+        let (mut interfaces, mut interface_ids) = (Vec::new(), Vec::new());
+        while let Some(block) = reader.next_block() {
+            match block.unwrap() {
+                Block::InterfaceDescription(interface) => {
+                    let (mut name, mut address) = (String::new(), Vec::new());
+                    for option in interface.options {
+                        match option {
+                            InterfaceDescriptionOption::IfName(value) => name = value.to_string(),
+                            InterfaceDescriptionOption::IfIpv4Addr(value) => address = value.to_vec(),
+                            _ => {}
+                        }
+                    }
+                    interfaces.push((name, address));
+                }
+                Block::EnhancedPacket(packet) => interface_ids.push(packet.interface_id),
+                _ => {}
+            }
+        }
+
+        assert_eq!(
+            interfaces,
+            [
+                ("10.0.0.1".to_string(), vec![10, 0, 0, 1, 0xFF, 0xFF, 0xFF, 0xFF]),
+                ("10.0.0.2".to_string(), vec![10, 0, 0, 2, 0xFF, 0xFF, 0xFF, 0xFF]),
+                ("0.0.0.0".to_string(), vec![]),
+            ]
+        );
+        assert_eq!(interface_ids, [0, 1, 0, 2]);
+    }
+
+    /// A file of a rotating capture, that stays readable once rotated.
+    #[derive(Clone, Default)]
+    struct SharedFile(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedFile {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> { self.0.lock().unwrap().write(buf) }
+        fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+    }
+
+    #[test]
+    fn rotated_files_are_readable_on_their_own() {
+        let local_address: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let remote_address: SocketAddr = "127.0.0.2:27015".parse().unwrap();
+        let info = CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Udp,
+            remote_address: &remote_address,
+            local_address: &local_address,
+            query_id: 1,
+            protocol_hint: None,
+        };
+
+        let files = std::sync::Arc::new(std::sync::Mutex::new(vec![SharedFile::default()]));
+        let opened = files.clone();
+        let open_next: OpenNext<SharedFile> = Box::new(move |index| {
+            let mut files = opened.lock().unwrap();
+            assert_eq!(files.len() + 1, index as usize);
+            files.push(SharedFile::default());
+
+            Ok(files.last().unwrap().clone())
+        });
+
+        let first = files.lock().unwrap()[0].clone();
+        let mut writer = PcapNgWriter::new(first).unwrap();
+        writer
+            .write_block(&interface_block(&CaptureOptions::default(), vec![]).into_block())
+            .unwrap();
+        let mut pcap = Pcap::new(writer, CaptureOptions::default()).with_rotation(Rotation::Packets(2), open_next);
+        for _ in 0 .. 5 {
+            pcap.write_transport_packet(&info, &[0xFF, 0xFF, 0xFF, 0xFF, 0x54]).unwrap();
+        }
+
+        let files = files.lock().unwrap();
+        let packets: Vec<_> = files
+            .iter()
+            .map(|file| {
+                let data = file.0.lock().unwrap();
+                let mut reader = PcapNgReader::new(&data[..]).unwrap();
+                let (mut interfaces, mut packets) = (0, 0);
+                while let Some(block) = reader.next_block() {
+                    match block.unwrap() {
+                        Block::InterfaceDescription(_) => interfaces += 1,
+                        Block::EnhancedPacket(packet) => {
+                            assert_eq!(packet.interface_id, 0);
+                            packets += 1;
+                        }
+                        _ => {}
+                    }
+                }
+                assert_eq!(interfaces, 1);
+
+                packets
+            })
+            .collect();
+        assert_eq!(packets, [2, 2, 1]);
+    }
+
+    #[test]
+    fn verbose_describes_tcp_layers() {
+        let comments = written_comments(verbose(true), Direction::Send, Protocol::Tcp);
+
+        assert!(comments.iter().any(|c| c.starts_with("TCP: seq 0, ack 0")));
+        assert!(comments.iter().any(|c| c == "Generated TCP ACK"));
+    }
+}
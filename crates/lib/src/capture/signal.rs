@@ -0,0 +1,108 @@
+//! Finishing the capture when the process is interrupted.
+
+use super::finish_capture;
+use crate::{GDErrorKind::Capture, GDResult};
+use std::{
+    io,
+    sync::atomic::{AtomicI32, Ordering},
+    thread,
+};
+
+/// The write end of the pipe the signal handler notifies the flushing thread
+/// through, -1 until the handler is installed.
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+/// Only async-signal-safe functions can be called from a signal handler, so
+/// the signal is just forwarded to the flushing thread.
+extern "C" fn on_signal(signal: libc::c_int) {
+    let signal = signal as u8;
+
+    // SAFETY: the pipe is never closed once installed, and writing to it is
+    // async-signal-safe.
+    unsafe {
+        libc::write(
+            SIGNAL_PIPE.load(Ordering::Relaxed),
+            &signal as *const u8 as *const libc::c_void,
+            1,
+        );
+    }
+}
+
+/// Wait for a signal to be forwarded, then finish the capture and terminate
+/// the process by the signal, as it would have without the handler.
+fn wait_for_signal(pipe: libc::c_int) {
+    let mut signal = 0u8;
+
+    loop {
+        // SAFETY: the pipe is open and the buffer is a single byte.
+        match unsafe { libc::read(pipe, &mut signal as *mut u8 as *mut libc::c_void, 1) } {
+            1 => break,
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            _ => return,
+        }
+    }
+
+    let _ = finish_capture();
+
+    // SAFETY: restoring the default disposition and raising a signal have no
+    // memory safety requirements.
+    unsafe {
+        libc::signal(signal.into(), libc::SIG_DFL);
+        libc::raise(signal.into());
+    }
+}
+
+/// Register a handler that finishes the active capture (see
+/// [finish_capture]) when the process receives `SIGINT` or `SIGTERM`, before
+/// letting the signal terminate the process.
+///
+/// This relies on the capture writer being kept in the capture module (rather
+/// than leaked), so that it can be taken back and closed from the handler's
+/// thread. Installing it more than once has no effect.
+///
+/// # Errors
+/// Returns a [Capture] error if the handler couldn't be registered.
+pub fn install_signal_flush() -> GDResult<()> {
+    let mut pipe = [0; 2];
+    // SAFETY: the array has room for the two file descriptors.
+    if unsafe { libc::pipe(pipe.as_mut_ptr()) } != 0 {
+        return Err(Capture.context(io::Error::last_os_error()));
+    }
+
+    let [read_end, write_end] = pipe;
+    if SIGNAL_PIPE
+        .compare_exchange(-1, write_end, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        // Already installed.
+        // SAFETY: the pipe was just created and isn't used.
+        unsafe {
+            libc::close(read_end);
+            libc::close(write_end);
+        }
+
+        return Ok(());
+    }
+
+    thread::Builder::new()
+        .name("gamedig-capture-flush".to_string())
+        .spawn(move || wait_for_signal(read_end))
+        .map_err(|e| Capture.context(e))?;
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: the action is fully initialized before being registered, and
+        // the handler only calls async-signal-safe functions.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as *const () as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) != 0 {
+                return Err(Capture.context(io::Error::last_os_error()));
+            }
+        }
+    }
+
+    Ok(())
+}
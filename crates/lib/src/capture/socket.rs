@@ -1,33 +1,44 @@
-use std::{marker::PhantomData, net::SocketAddr};
+use std::{
+    marker::PhantomData,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 use crate::{
     capture::{
         packet::CapturePacket,
         packet::{Direction, Protocol},
-        writer::{Writer, CAPTURE_WRITER},
+        writer::{with_writer, Writer, CAPTURE_WRITER},
     },
     protocols::types::TimeoutSettings,
     socket::{Socket, TcpSocketImpl, UdpSocketImpl},
+    GDErrorKind::Capture,
     GDResult,
 };
 
 /// Sets a global capture writer for handling all packet data.
 ///
-/// # Panics
-/// Panics if a capture writer is already set.
+/// # Errors
+/// Returns a [Capture] error if a capture writer is already set.
 ///
 /// # Arguments
 /// * `writer` - A boxed writer that implements the `Writer` trait.
-pub(crate) fn set_writer(writer: Box<dyn Writer + Send + Sync>) {
+pub(crate) fn set_writer(writer: Box<dyn Writer + Send + Sync>) -> GDResult<()> {
     let mut lock = CAPTURE_WRITER.lock().unwrap();
 
     if lock.is_some() {
-        panic!("Capture writer already set");
+        return Err(Capture.context("Capture writer already set"));
     }
 
     *lock = Some(writer);
+
+    Ok(())
 }
 
+/// The number of captured sockets created so far, each socket being one
+/// logical query whose packets are stamped with its id.
+static QUERY_COUNT: AtomicU64 = AtomicU64::new(0);
+
 /// A trait representing a provider of a network protocol.
 pub(crate) trait ProtocolProvider {
     /// Returns the protocol used by the provider.
@@ -55,6 +66,8 @@ impl ProtocolProvider for ProtocolUDP {
 pub(crate) struct WrappedCaptureSocket<I: Socket, P: ProtocolProvider> {
     inner: I,
     remote_address: SocketAddr,
+    query_id: u64,
+    protocol_hint: Option<&'static str>,
     _protocol: PhantomData<P>,
 }
 
@@ -76,6 +89,8 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
         let v = Self {
             inner: I::new(address, timeout_settings)?,
             remote_address: *address,
+            query_id: QUERY_COUNT.fetch_add(1, Ordering::Relaxed) + 1,
+            protocol_hint: None,
             _protocol: PhantomData,
         };
 
@@ -84,11 +99,11 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
             protocol: P::protocol(),
             remote_address: address,
             local_address: &v.local_addr().unwrap(),
+            query_id: v.query_id,
+            protocol_hint: v.protocol_hint,
         };
 
-        if let Some(writer) = CAPTURE_WRITER.lock().unwrap().as_mut() {
-            writer.new_connect(&info)?;
-        }
+        with_writer(|writer| writer.new_connect(&info)).transpose()?;
 
         Ok(v)
     }
@@ -109,11 +124,11 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
             protocol: P::protocol(),
             remote_address: &self.remote_address,
             local_address: &self.local_addr().unwrap(),
+            query_id: self.query_id,
+            protocol_hint: self.protocol_hint,
         };
 
-        if let Some(writer) = CAPTURE_WRITER.lock().unwrap().as_mut() {
-            writer.write(&info, data)?;
-        }
+        with_writer(|writer| writer.write(&info, data)).transpose()?;
 
         self.inner.send(data)
     }
@@ -135,11 +150,28 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
             protocol: P::protocol(),
             remote_address: &self.remote_address,
             local_address: &self.local_addr().unwrap(),
+            query_id: self.query_id,
+            protocol_hint: self.protocol_hint,
+        };
+
+        with_writer(|writer| writer.write(&info, &data)).transpose()?;
+
+        Ok(data)
+    }
+
+    /// Receives exactly `size` bytes from the socket and captures them.
+    fn receive_exact(&mut self, size: usize) -> crate::GDResult<Vec<u8>> {
+        let data = self.inner.receive_exact(size)?;
+        let info = CapturePacket {
+            direction: Direction::Receive,
+            protocol: P::protocol(),
+            remote_address: &self.remote_address,
+            local_address: &self.local_addr().unwrap(),
+            query_id: self.query_id,
+            protocol_hint: self.protocol_hint,
         };
 
-        if let Some(writer) = CAPTURE_WRITER.lock().unwrap().as_mut() {
-            writer.write(&info, &data)?;
-        }
+        with_writer(|writer| writer.write(&info, &data)).transpose()?;
 
         Ok(data)
     }
@@ -175,6 +207,13 @@ impl<I: Socket, P: ProtocolProvider> Socket for WrappedCaptureSocket<I, P> {
     /// # Returns
     /// The local SocketAddr.
     fn local_addr(&self) -> std::io::Result<SocketAddr> { self.inner.local_addr() }
+
+    /// Returns the ping of the wrapped socket.
+    fn ping(&self) -> Option<std::time::Duration> { self.inner.ping() }
+
+    /// Notes the game protocol, which the following packets are annotated
+    /// with.
+    fn set_protocol_hint(&mut self, hint: &'static str) { self.protocol_hint = Some(hint); }
 }
 
 // this seems a bad way to do this, but its safe
@@ -188,12 +227,12 @@ impl<I: Socket, P: ProtocolProvider> Drop for WrappedCaptureSocket<I, P> {
             local_address: &self
                 .local_addr()
                 .unwrap_or_else(|_| SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED), 0)),
+            query_id: self.query_id,
+            protocol_hint: self.protocol_hint,
         };
 
         // If a capture writer is set, close the connection and capture the packet.
-        if let Some(writer) = CAPTURE_WRITER.lock().unwrap().as_mut() {
-            let _ = writer.close_connection(&info);
-        }
+        let _ = with_writer(|writer| writer.close_connection(&info));
     }
 }
 
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+/// Private Enterprise Number written at the start of the summary Custom Block.
+///
+/// Gamedig doesn't own a registered number, so the one reserved for
+/// documentation use ([RFC 5612](https://datatracker.ietf.org/doc/html/rfc5612))
+/// is used, readers should check it along the block type.
+pub const SUMMARY_PEN: u32 = 32473;
+
+/// The pcapng block type of a Custom Block that may be copied along with the
+/// rest of the file.
+pub(crate) const CUSTOM_BLOCK_TYPE: u32 = 0x0000_0BAD;
+
+/// The high-level outcome of a query, embedded in the capture file as a
+/// Custom Block so that tools can read it along the raw packets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuerySummary {
+    /// The game (or protocol) that was queried.
+    pub game: String,
+    /// The final number of players, if the query succeeded.
+    pub players_online: Option<u32>,
+    /// The round trip time of the query's first request, if the query
+    /// succeeded and measured it (see
+    /// [CommonResponse::ping](crate::protocols::types::CommonResponse::ping)).
+    pub ping: Option<Duration>,
+    /// The errors that were encountered.
+    pub errors: Vec<String>,
+}
+
+impl QuerySummary {
+    /// Encode the summary as the JSON document stored after the PEN.
+    pub(crate) fn to_json_bytes(&self) -> Vec<u8> {
+        serde_json::json!({
+            "game": self.game,
+            "players_online": self.players_online,
+            "ping_ms": self.ping.map(|ping| ping.as_millis() as u64),
+            "errors": self.errors,
+        })
+        .to_string()
+        .into_bytes()
+    }
+}
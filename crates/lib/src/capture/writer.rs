@@ -1,8 +1,16 @@
-use std::{io::Write, sync::Mutex};
+use std::{
+    cell::RefCell,
+    io::Write,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
 use super::{
     packet::{CapturePacket, Protocol},
     pcap::Pcap,
+    summary::QuerySummary,
+    Sampling,
 };
 use crate::GDResult;
 use lazy_static::lazy_static;
@@ -14,6 +22,28 @@ lazy_static! {
     pub(crate) static ref CAPTURE_WRITER: Mutex<Option<Box<dyn Writer + Send + Sync>>> = Mutex::new(None);
 }
 
+thread_local! {
+    /// The writer of the [with_capture](super::with_capture) scope running on
+    /// the thread, if any, used instead of [CAPTURE_WRITER].
+    pub(crate) static SCOPED_WRITER: RefCell<Option<Box<dyn Writer + Send + Sync>>> = const { RefCell::new(None) };
+}
+
+/// Make `call` with the capture writer of the thread's scope, or else with
+/// the global one, `None` if there is no capture.
+pub(crate) fn with_writer<R>(call: impl FnOnce(&mut dyn Writer) -> R) -> Option<R> {
+    SCOPED_WRITER.with(|scoped| {
+        if let Some(writer) = scoped.borrow_mut().as_mut() {
+            return Some(call(writer.as_mut()));
+        }
+
+        CAPTURE_WRITER
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(|writer| call(writer.as_mut()))
+    })
+}
+
 /// Trait defining the functionality for a writer that handles network packet
 /// captures. This trait includes methods for writing packet data, handling new
 /// connections, and closing connections.
@@ -49,19 +79,47 @@ pub(crate) trait Writer {
     /// A `GDResult` indicating the success or failure of the connection closure
     /// operation.
     fn close_connection(&mut self, packet: &CapturePacket) -> GDResult<()>;
+
+    /// Records the outcome of a query alongside the captured packets.
+    ///
+    /// # Arguments
+    /// * `summary` - The summary of the query.
+    ///
+    /// # Returns
+    /// A `GDResult` indicating the success or failure of the write operation.
+    fn finalize_summary(&mut self, summary: &QuerySummary) -> GDResult<()>;
+
+    /// Notes that the next stream is to an address resolved from a hostname.
+    ///
+    /// # Arguments
+    /// * `hostname` - The hostname that was resolved.
+    /// * `address` - The address it was resolved to.
+    fn annotate_hostname(&mut self, hostname: &str, address: &IpAddr);
+
+    /// Notes the id of the game queried at an address.
+    ///
+    /// # Arguments
+    /// * `game_id` - The id of the game.
+    /// * `address` - The address of the server.
+    fn annotate_game(&mut self, game_id: &str, address: &IpAddr);
+
+    /// Flushes everything written so far to the underlying storage, before
+    /// the writer is dropped.
+    ///
+    /// # Returns
+    /// A `GDResult` indicating the success or failure of the flush.
+    fn finish(&mut self) -> GDResult<()>;
 }
 
 /// Implementation of the `Writer` trait for the `Pcap` struct.
 /// This implementation enables writing, connection handling, and closure
 /// specific to PCAP (Packet Capture) format.
 impl<W: Write> Writer for Pcap<W> {
-    fn write(&mut self, info: &CapturePacket, data: &[u8]) -> GDResult<()> {
-        self.write_transport_packet(info, data);
-
-        Ok(())
-    }
+    fn write(&mut self, info: &CapturePacket, data: &[u8]) -> GDResult<()> { self.write_transport_packet(info, data) }
 
     fn new_connect(&mut self, packet: &CapturePacket) -> GDResult<()> {
+        self.write_name_resolution(&packet.remote_address.ip())?;
+
         match packet.protocol {
             Protocol::Tcp => {
                 self.write_tcp_handshake(packet);
@@ -77,10 +135,275 @@ impl<W: Write> Writer for Pcap<W> {
     fn close_connection(&mut self, packet: &CapturePacket) -> GDResult<()> {
         match packet.protocol {
             Protocol::Tcp => {
-                self.send_tcp_fin(packet);
+                self.write_tcp_teardown(packet);
             }
             Protocol::Udp => {}
         }
         Ok(())
     }
+
+    fn finalize_summary(&mut self, summary: &QuerySummary) -> GDResult<()> { self.write_summary(summary) }
+    fn annotate_hostname(&mut self, hostname: &str, address: &IpAddr) {
+        if self.options.annotate_hostnames {
+            self.state.stream_comment = Some(format!("querying {hostname} (resolved {address})"));
+        }
+        self.note_name(address, hostname);
+    }
+    fn annotate_game(&mut self, game_id: &str, address: &IpAddr) { self.note_name(address, game_id) }
+    fn finish(&mut self) -> GDResult<()> { self.flush() }
+}
+
+/// A writer that only forwards a [sample](Sampling) of the packets to its
+/// inner writer, the connection establishment/teardown and the summary always
+/// being forwarded.
+pub(crate) struct SamplingWriter<W: Writer> {
+    inner: W,
+    sampling: Sampling,
+    /// Number of packets seen (written or not) so far.
+    seen: u64,
+    /// Start of the current one second window, and the number of packets
+    /// written within it.
+    window: (Instant, u32),
+}
+
+impl<W: Writer> SamplingWriter<W> {
+    pub(crate) fn new(inner: W, sampling: Sampling) -> Self {
+        Self {
+            inner,
+            sampling,
+            seen: 0,
+            window: (Instant::now(), 0),
+        }
+    }
+
+    /// Whether the next packet is part of the sample.
+    fn sample(&mut self) -> bool {
+        self.seen += 1;
+
+        match self.sampling {
+            Sampling::All => true,
+            Sampling::OneIn(every) => (self.seen - 1) % u64::from(every.max(1)) == 0,
+            Sampling::PerSecond(limit) => {
+                let now = Instant::now();
+                if now.duration_since(self.window.0) >= Duration::from_secs(1) {
+                    self.window = (now, 0);
+                }
+
+                let written = self.window.1 < limit;
+                if written {
+                    self.window.1 += 1;
+                }
+
+                written
+            }
+        }
+    }
+}
+
+impl<W: Writer> Writer for SamplingWriter<W> {
+    fn write(&mut self, packet: &CapturePacket, data: &[u8]) -> GDResult<()> {
+        match self.sample() {
+            true => self.inner.write(packet, data),
+            false => Ok(()),
+        }
+    }
+
+    fn new_connect(&mut self, packet: &CapturePacket) -> GDResult<()> { self.inner.new_connect(packet) }
+    fn close_connection(&mut self, packet: &CapturePacket) -> GDResult<()> { self.inner.close_connection(packet) }
+    fn finalize_summary(&mut self, summary: &QuerySummary) -> GDResult<()> { self.inner.finalize_summary(summary) }
+    fn annotate_hostname(&mut self, hostname: &str, address: &IpAddr) { self.inner.annotate_hostname(hostname, address) }
+    fn annotate_game(&mut self, game_id: &str, address: &IpAddr) { self.inner.annotate_game(game_id, address) }
+    fn finish(&mut self) -> GDResult<()> { self.inner.finish() }
+}
+
+/// A writer forwarding everything to each of its inner writers (the same
+/// capture written to a file and an in-process analyzer, for example).
+///
+/// An inner writer failing doesn't keep the others from getting the call,
+/// the first error being returned once all of them got it.
+pub(crate) struct TeeWriter {
+    writers: Vec<Box<dyn Writer + Send + Sync>>,
+}
+
+impl TeeWriter {
+    pub(crate) fn new(writers: Vec<Box<dyn Writer + Send + Sync>>) -> Self { Self { writers } }
+
+    fn forward(&mut self, mut call: impl FnMut(&mut dyn Writer) -> GDResult<()>) -> GDResult<()> {
+        let mut result = Ok(());
+        for writer in &mut self.writers {
+            let written = call(writer.as_mut());
+            if result.is_ok() {
+                result = written;
+            }
+        }
+
+        result
+    }
+}
+
+impl Writer for TeeWriter {
+    fn write(&mut self, packet: &CapturePacket, data: &[u8]) -> GDResult<()> {
+        self.forward(|writer| writer.write(packet, data))
+    }
+
+    fn new_connect(&mut self, packet: &CapturePacket) -> GDResult<()> {
+        self.forward(|writer| writer.new_connect(packet))
+    }
+
+    fn close_connection(&mut self, packet: &CapturePacket) -> GDResult<()> {
+        self.forward(|writer| writer.close_connection(packet))
+    }
+
+    fn finalize_summary(&mut self, summary: &QuerySummary) -> GDResult<()> {
+        self.forward(|writer| writer.finalize_summary(summary))
+    }
+
+    fn annotate_hostname(&mut self, hostname: &str, address: &IpAddr) {
+        for writer in &mut self.writers {
+            writer.annotate_hostname(hostname, address);
+        }
+    }
+
+    fn annotate_game(&mut self, game_id: &str, address: &IpAddr) {
+        for writer in &mut self.writers {
+            writer.annotate_game(game_id, address);
+        }
+    }
+
+    fn finish(&mut self) -> GDResult<()> { self.forward(|writer| writer.finish()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::packet::Direction;
+    use crate::GDErrorKind::Capture;
+    use std::net::SocketAddr;
+
+    /// Records the calls it gets.
+    #[derive(Default)]
+    struct Recorder {
+        written: Vec<Vec<u8>>,
+        connections: usize,
+        closed: usize,
+    }
+
+    impl Writer for Recorder {
+        fn write(&mut self, _packet: &CapturePacket, data: &[u8]) -> GDResult<()> {
+            self.written.push(data.to_vec());
+
+            Ok(())
+        }
+
+        fn new_connect(&mut self, _packet: &CapturePacket) -> GDResult<()> {
+            self.connections += 1;
+
+            Ok(())
+        }
+
+        fn close_connection(&mut self, _packet: &CapturePacket) -> GDResult<()> {
+            self.closed += 1;
+
+            Ok(())
+        }
+
+        fn finalize_summary(&mut self, _summary: &QuerySummary) -> GDResult<()> { Ok(()) }
+        fn annotate_hostname(&mut self, _hostname: &str, _address: &IpAddr) {}
+        fn annotate_game(&mut self, _game_id: &str, _address: &IpAddr) {}
+        fn finish(&mut self) -> GDResult<()> { Ok(()) }
+    }
+
+    /// Fails every call.
+    struct Failing;
+
+    impl Writer for Failing {
+        fn write(&mut self, _packet: &CapturePacket, _data: &[u8]) -> GDResult<()> { Err(Capture.into()) }
+        fn new_connect(&mut self, _packet: &CapturePacket) -> GDResult<()> { Err(Capture.into()) }
+        fn close_connection(&mut self, _packet: &CapturePacket) -> GDResult<()> { Err(Capture.into()) }
+        fn finalize_summary(&mut self, _summary: &QuerySummary) -> GDResult<()> { Err(Capture.into()) }
+        fn annotate_hostname(&mut self, _hostname: &str, _address: &IpAddr) {}
+        fn annotate_game(&mut self, _game_id: &str, _address: &IpAddr) {}
+        fn finish(&mut self) -> GDResult<()> { Err(Capture.into()) }
+    }
+
+    /// Records into a recorder that is still readable once handed over.
+    struct Shared(std::sync::Arc<Mutex<Recorder>>);
+
+    impl Writer for Shared {
+        fn write(&mut self, packet: &CapturePacket, data: &[u8]) -> GDResult<()> {
+            self.0.lock().unwrap().write(packet, data)
+        }
+        fn new_connect(&mut self, packet: &CapturePacket) -> GDResult<()> { self.0.lock().unwrap().new_connect(packet) }
+        fn close_connection(&mut self, packet: &CapturePacket) -> GDResult<()> {
+            self.0.lock().unwrap().close_connection(packet)
+        }
+        fn finalize_summary(&mut self, _summary: &QuerySummary) -> GDResult<()> { Ok(()) }
+        fn annotate_hostname(&mut self, _hostname: &str, _address: &IpAddr) {}
+        fn annotate_game(&mut self, _game_id: &str, _address: &IpAddr) {}
+        fn finish(&mut self) -> GDResult<()> { Ok(()) }
+    }
+
+    fn packet(address: &SocketAddr) -> CapturePacket<'_> {
+        CapturePacket {
+            direction: Direction::Send,
+            protocol: Protocol::Tcp,
+            remote_address: address,
+            local_address: address,
+            query_id: 1,
+            protocol_hint: None,
+        }
+    }
+
+    fn exchange(sampling: Sampling, count: u8) -> Recorder {
+        let address: SocketAddr = "127.0.0.1:27015".parse().unwrap();
+        let packet = packet(&address);
+
+        let mut writer = SamplingWriter::new(Recorder::default(), sampling);
+        writer.new_connect(&packet).unwrap();
+        for data in 0 .. count {
+            writer.write(&packet, &[data]).unwrap();
+        }
+        writer.close_connection(&packet).unwrap();
+
+        writer.inner
+    }
+
+    #[test]
+    fn one_in_n_packets_are_written() {
+        let recorder = exchange(Sampling::OneIn(3), 7);
+
+        assert_eq!(recorder.written, [[0], [3], [6]]);
+        assert_eq!(recorder.connections, 1);
+        assert_eq!(recorder.closed, 1);
+    }
+
+    #[test]
+    fn one_in_zero_writes_everything() {
+        assert_eq!(exchange(Sampling::OneIn(0), 4).written.len(), 4);
+    }
+
+    #[test]
+    fn packets_per_second_are_limited() {
+        let recorder = exchange(Sampling::PerSecond(2), 10);
+
+        assert_eq!(recorder.written, [[0], [1]]);
+        assert_eq!(recorder.connections, 1);
+        assert_eq!(recorder.closed, 1);
+    }
+
+    #[test]
+    fn tee_forwards_past_a_failing_writer() {
+        let address: SocketAddr = "127.0.0.1:27015".parse().unwrap();
+        let recorder = std::sync::Arc::new(Mutex::new(Recorder::default()));
+        let mut writer = TeeWriter::new(vec![Box::new(Failing), Box::new(Shared(recorder.clone()))]);
+
+        assert_eq!(writer.new_connect(&packet(&address)).unwrap_err().kind, Capture);
+        assert!(writer.write(&packet(&address), b"data").is_err());
+        assert!(writer.close_connection(&packet(&address)).is_err());
+
+        let recorder = recorder.lock().unwrap();
+        assert_eq!(recorder.written, [b"data"]);
+        assert_eq!(recorder.connections, 1);
+        assert_eq!(recorder.closed, 1);
+    }
 }
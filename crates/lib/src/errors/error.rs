@@ -1,7 +1,7 @@
 use crate::GDErrorKind;
 use std::error::Error;
 use std::fmt::Formatter;
-use std::{backtrace, fmt};
+use std::{backtrace, fmt, io};
 
 pub(crate) type ErrorSource = Box<dyn Error + 'static + Send + Sync>;
 
@@ -91,6 +91,114 @@ impl GDError {
     pub fn from_error<E: Into<ErrorSource>>(kind: GDErrorKind, source: E) -> Self {
         Self::new(kind, Some(source.into()))
     }
+
+    /// Tells if the error is transient, meaning that trying again might
+    /// succeed: failing to send or receive a packet, unless it was caused
+    /// by an IO error other than a timeout (`TimedOut`/`WouldBlock`), a
+    /// `ConnectionReset` or an interruption.
+    ///
+    /// Everything else (invalid input, a host that couldn't be found, a
+    /// bad response...) is permanent and won't be retried.
+    pub fn is_retryable(&self) -> bool {
+        if self.kind != GDErrorKind::PacketSend && self.kind != GDErrorKind::PacketReceive {
+            return false;
+        }
+
+        match self
+            .source
+            .as_ref()
+            .and_then(|source| source.downcast_ref::<io::Error>())
+        {
+            Some(error) => {
+                matches!(
+                    error.kind(),
+                    io::ErrorKind::TimedOut
+                        | io::ErrorKind::WouldBlock
+                        | io::ErrorKind::ConnectionReset
+                        | io::ErrorKind::Interrupted
+                )
+            }
+            None => true,
+        }
+    }
+
+    /// Tells where the query stalled if the error is a timeout: connecting
+    /// (the connect timeout of a TCP socket elapsed), reading or writing
+    /// (the read or write timeout elapsed), `None` if it isn't one.
+    ///
+    /// A refused connection isn't a timeout, the host answered.
+    pub fn timeout_phase(&self) -> Option<TimeoutPhase> {
+        let phase = match self.kind {
+            GDErrorKind::SocketConnect => TimeoutPhase::Connect,
+            GDErrorKind::PacketReceive => TimeoutPhase::Read,
+            GDErrorKind::PacketSend => TimeoutPhase::Write,
+            _ => return None,
+        };
+
+        let error = self.source.as_ref()?.downcast_ref::<io::Error>()?;
+        matches!(error.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock).then_some(phase)
+    }
+
+    /// Tells how short the packet was if the error is a
+    /// [PacketUnderflow](GDErrorKind::PacketUnderflow) raised while parsing
+    /// it, `None` otherwise.
+    ///
+    /// ```
+    /// use gamedig::{GDError, PacketTooShort};
+    /// fn describe(error: &GDError) -> String {
+    ///     match error.packet_too_short() {
+    ///         Some(PacketTooShort { expected, got, offset }) => {
+    ///             format!("Truncated at byte {offset}: {got} of {expected} bytes")
+    ///         }
+    ///         None => error.to_string(),
+    ///     }
+    /// }
+    /// ```
+    pub fn packet_too_short(&self) -> Option<PacketTooShort> {
+        self.source
+            .as_ref()?
+            .downcast_ref::<PacketTooShort>()
+            .copied()
+    }
+}
+
+/// The source of the [PacketUnderflow](GDErrorKind::PacketUnderflow) errors
+/// raised by the parsers, see [GDError::packet_too_short].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketTooShort {
+    /// The number of bytes that were needed.
+    pub expected: usize,
+    /// The number of bytes that were left.
+    pub got: usize,
+    /// Where the bytes were needed, from the start of the data being parsed.
+    pub offset: usize,
+}
+
+impl fmt::Display for PacketTooShort {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Expected {} bytes at offset {}, but only {} were left",
+            self.expected, self.offset, self.got
+        )
+    }
+}
+
+impl Error for PacketTooShort {}
+
+impl From<PacketTooShort> for GDError {
+    fn from(value: PacketTooShort) -> Self { GDErrorKind::PacketUnderflow.context(value) }
+}
+
+/// The step of a query that timed out, see [GDError::timeout_phase].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    /// Connecting to the server (TCP only).
+    Connect,
+    /// Waiting for the server's response.
+    Read,
+    /// Sending the request.
+    Write,
 }
 
 #[cfg(test)]
@@ -132,6 +240,60 @@ mod tests {
         assert!(error_from_context.source.is_some());
     }
 
+    // Test classifying errors as transient or permanent
+    #[test]
+    fn test_is_retryable() {
+        let timeout = io::Error::new(io::ErrorKind::WouldBlock, "timed out");
+        assert!(GDErrorKind::PacketReceive.context(timeout).is_retryable());
+        assert!(GDErrorKind::PacketSend.context("test").is_retryable());
+
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        assert!(!GDErrorKind::PacketReceive.context(refused).is_retryable());
+        assert!(!GDErrorKind::HostLookup.context("No socket addresses found for host").is_retryable());
+        assert!(!GDError::from(GDErrorKind::InvalidInput).is_retryable());
+    }
+
+    // Test telling where a query timed out
+    #[test]
+    fn test_timeout_phase() {
+        let timeout = || io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        assert_eq!(
+            GDErrorKind::SocketConnect.context(timeout()).timeout_phase(),
+            Some(TimeoutPhase::Connect)
+        );
+        assert_eq!(
+            GDErrorKind::PacketReceive.context(timeout()).timeout_phase(),
+            Some(TimeoutPhase::Read)
+        );
+        assert_eq!(
+            GDErrorKind::PacketSend.context(timeout()).timeout_phase(),
+            Some(TimeoutPhase::Write)
+        );
+
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        assert_eq!(GDErrorKind::SocketConnect.context(refused).timeout_phase(), None);
+        assert_eq!(GDErrorKind::PacketBad.context(timeout()).timeout_phase(), None);
+    }
+
+    #[test]
+    fn test_packet_too_short() {
+        let too_short = PacketTooShort {
+            expected: 4,
+            got: 2,
+            offset: 6,
+        };
+        let error = GDError::from(too_short);
+        assert_eq!(error.kind, GDErrorKind::PacketUnderflow);
+        assert_eq!(error.packet_too_short(), Some(too_short));
+        assert_eq!(
+            error.source().unwrap().to_string(),
+            "Expected 4 bytes at offset 6, but only 2 were left"
+        );
+
+        assert_eq!(GDErrorKind::PacketUnderflow.context("short").packet_too_short(), None);
+        assert_eq!(GDError::from(GDErrorKind::PacketBad).packet_too_short(), None);
+    }
+
     // Test creating GDError with From<GDErrorKind> for GDError
     #[test]
     fn test_create_into() {
@@ -10,6 +10,9 @@ pub enum GDErrorKind {
     PacketUnderflow,
     /// The received packet is badly formatted.
     PacketBad,
+    /// The received packets are well formatted, but don't lead to a response
+    /// (such as redirects looping).
+    PacketParse,
     /// Couldn't send the packet.
     PacketSend,
     /// Couldn't receieve data when it was expected.
@@ -38,6 +41,10 @@ pub enum GDErrorKind {
     TypeParse,
     /// Couldn't find the host specified.
     HostLookup,
+    /// Couldn't set up or write a packet capture.
+    Capture,
+    /// The server refused the password (of an RCON connection).
+    BadPassword,
 }
 
 impl GDErrorKind {
@@ -0,0 +1,514 @@
+//! Querying many servers at once.
+
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::games::query::query_sendable;
+use crate::games::types::Game;
+use crate::protocols::types::{CommonResponse, ExtraRequestSettings, TimeoutSettings};
+use crate::socket::exchanged_bytes;
+use crate::GDErrorKind::{self, PacketReceive};
+use crate::{GDError, GDResult};
+
+/// The default maximum number of queries made at the same time.
+pub const DEFAULT_MAX_CONCURRENT: usize = 16;
+
+/// How a query went, handed to a [QueryHook].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryOutcome<'a> {
+    /// The game that was queried.
+    pub game: &'a Game,
+    /// The address that was queried (the game's default port being filled
+    /// in).
+    pub address: SocketAddr,
+    /// How long the query took.
+    pub duration: Duration,
+    /// The number of bytes sent over the UDP and TCP sockets (the HTTP based
+    /// queries' aren't counted).
+    pub bytes_sent: usize,
+    /// The number of bytes received over the UDP and TCP sockets.
+    pub bytes_received: usize,
+    /// `Ok` if the query succeeded, the kind of its error otherwise.
+    pub result: Result<(), GDErrorKind>,
+}
+
+/// Called once each query is done (whether it succeeded or not), from the
+/// thread that made it, for metrics or tracing to be recorded without
+/// depending on a specific library, see [query_many_with_hook] and
+/// [query_batch_with_hook].
+pub type QueryHook = Arc<dyn Fn(&QueryOutcome) + Send + Sync>;
+
+/// A server to query, see [query_many].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryRequest {
+    /// The game of the server.
+    pub game: Game,
+    /// The address of the server.
+    pub address: IpAddr,
+    /// The port of the server, the game's default one if `None`.
+    pub port: Option<u16>,
+    /// Optional timeout settings for the query.
+    pub timeout_settings: Option<TimeoutSettings>,
+    /// Optional extra settings for the query.
+    pub extra_settings: Option<ExtraRequestSettings>,
+}
+
+impl QueryRequest {
+    /// Query a server on the given port (or the game's default one), with the
+    /// default settings.
+    pub fn new(game: &Game, address: IpAddr, port: Option<u16>) -> Self {
+        Self {
+            game: game.clone(),
+            address,
+            port,
+            timeout_settings: None,
+            extra_settings: None,
+        }
+    }
+
+    /// Make the query (see [QueryRequest::query_before]), `on_complete` being
+    /// called with its outcome.
+    fn query_with_hook(
+        &self,
+        deadline: Option<Instant>,
+        on_complete: Option<&QueryHook>,
+    ) -> GDResult<Box<dyn CommonResponse + Send>> {
+        let Some(on_complete) = on_complete else {
+            return self.query_before(deadline);
+        };
+
+        let start = Instant::now();
+        let (sent_before, received_before) = exchanged_bytes();
+
+        let result = self.query_before(deadline);
+
+        let (sent_after, received_after) = exchanged_bytes();
+        on_complete(&QueryOutcome {
+            game: &self.game,
+            address: SocketAddr::new(self.address, self.port.unwrap_or(self.game.default_port)),
+            duration: start.elapsed(),
+            bytes_sent: sent_after.wrapping_sub(sent_before),
+            bytes_received: received_after.wrapping_sub(received_before),
+            result: result
+                .as_ref()
+                .map(|_| ())
+                .map_err(|error| error.kind.clone()),
+        });
+
+        result
+    }
+
+    /// Make the query, its deadline being shortened to end by `deadline`.
+    fn query_before(&self, deadline: Option<Instant>) -> GDResult<Box<dyn CommonResponse + Send>> {
+        let mut timeout_settings = self.timeout_settings;
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(batch_timed_out("The batch deadline passed before the query was made"));
+            }
+
+            timeout_settings = Some(
+                timeout_settings
+                    .unwrap_or_default()
+                    .start_deadline_ending_by(deadline),
+            );
+        }
+
+        query_sendable(
+            &self.game,
+            &self.address,
+            self.port,
+            timeout_settings,
+            self.extra_settings.clone(),
+        )
+    }
+}
+
+/// The result of a query, along with the index of its request.
+pub type IndexedResult = (usize, GDResult<Box<dyn CommonResponse + Send>>);
+
+/// Query the servers concurrently, the results being in the order of the
+/// requests once all of them completed, see [query_many_streaming] to get
+/// them as they complete instead.
+///
+/// At most [DEFAULT_MAX_CONCURRENT] queries are made at the same time, see
+/// [query_many_with_limit].
+pub fn query_many<I: IntoIterator<Item = QueryRequest>>(requests: I) -> Vec<GDResult<Box<dyn CommonResponse + Send>>> {
+    query_many_with_limit(requests, DEFAULT_MAX_CONCURRENT)
+}
+
+/// Same as [query_many], making at most `max_concurrent` queries at the same
+/// time.
+pub fn query_many_with_limit<I: IntoIterator<Item = QueryRequest>>(
+    requests: I,
+    max_concurrent: usize,
+) -> Vec<GDResult<Box<dyn CommonResponse + Send>>> {
+    let mut results: Vec<_> = query_many_streaming_with_limit(requests, max_concurrent).collect();
+    results.sort_by_key(|(index, _)| *index);
+
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Same as [query_many_with_limit], `on_complete` being called with the
+/// outcome of each query.
+pub fn query_many_with_hook<I: IntoIterator<Item = QueryRequest>>(
+    requests: I,
+    max_concurrent: usize,
+    on_complete: QueryHook,
+) -> Vec<GDResult<Box<dyn CommonResponse + Send>>> {
+    let requests = requests.into_iter().collect();
+    let mut results: Vec<_> = stream_queries(requests, max_concurrent, None, Some(on_complete))
+        .into_iter()
+        .collect();
+    results.sort_by_key(|(index, _)| *index);
+
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Query the servers concurrently, yielding each result with the index of
+/// its request as soon as it is ready, so that the fastest servers don't
+/// wait for the slowest ones.
+///
+/// The queries are made by a pool of worker threads, the iterator ends once
+/// every request has been answered (or failed). At most
+/// [DEFAULT_MAX_CONCURRENT] queries are made at the same time, see
+/// [query_many_streaming_with_limit].
+pub fn query_many_streaming<I: IntoIterator<Item = QueryRequest>>(requests: I) -> impl Iterator<Item = IndexedResult> {
+    query_many_streaming_with_limit(requests, DEFAULT_MAX_CONCURRENT)
+}
+
+/// Same as [query_many_streaming], making at most `max_concurrent` queries
+/// at the same time (0 behaving as 1), to not overwhelm the network or the
+/// servers.
+///
+/// The requests beyond the limit are queued, not dropped: each is made once
+/// a previous query completes. The workers also wait for the results to be
+/// consumed once `max_concurrent` of them are pending, so that a slow
+/// consumer slows the queries down instead of piling the results up.
+pub fn query_many_streaming_with_limit<I: IntoIterator<Item = QueryRequest>>(
+    requests: I,
+    max_concurrent: usize,
+) -> impl Iterator<Item = IndexedResult> {
+    stream_queries(requests.into_iter().collect(), max_concurrent, None, None).into_iter()
+}
+
+/// Query the servers concurrently (at most `max_concurrent` at the same
+/// time), returning each request along with its result, in the order of the
+/// requests. A failed query doesn't stop the others.
+///
+/// The timeout settings of each request still apply to its query, see
+/// [query_batch_with_deadline] to also bound the whole batch.
+pub fn query_batch<I: IntoIterator<Item = QueryRequest>>(
+    requests: I,
+    max_concurrent: usize,
+) -> Vec<(QueryRequest, GDResult<Box<dyn CommonResponse + Send>>)> {
+    batch(requests.into_iter().collect(), max_concurrent, None, None)
+}
+
+/// Same as [query_batch], the batch ending by `deadline` from now: the
+/// deadline of the queries made is shortened to what is left of it, and the
+/// ones that couldn't be made (or didn't finish) in time fail with a read
+/// timeout.
+pub fn query_batch_with_deadline<I: IntoIterator<Item = QueryRequest>>(
+    requests: I,
+    max_concurrent: usize,
+    deadline: Duration,
+) -> Vec<(QueryRequest, GDResult<Box<dyn CommonResponse + Send>>)> {
+    batch(
+        requests.into_iter().collect(),
+        max_concurrent,
+        Some(Instant::now() + deadline),
+        None,
+    )
+}
+
+/// Same as [query_batch] (or [query_batch_with_deadline] if a deadline is
+/// given), `on_complete` being called with the outcome of each query, even
+/// of the ones still being made once the deadline passed (after the batch
+/// returned).
+pub fn query_batch_with_hook<I: IntoIterator<Item = QueryRequest>>(
+    requests: I,
+    max_concurrent: usize,
+    deadline: Option<Duration>,
+    on_complete: QueryHook,
+) -> Vec<(QueryRequest, GDResult<Box<dyn CommonResponse + Send>>)> {
+    batch(
+        requests.into_iter().collect(),
+        max_concurrent,
+        deadline.map(|deadline| Instant::now() + deadline),
+        Some(on_complete),
+    )
+}
+
+fn batch(
+    requests: Vec<QueryRequest>,
+    max_concurrent: usize,
+    deadline: Option<Instant>,
+    on_complete: Option<QueryHook>,
+) -> Vec<(QueryRequest, GDResult<Box<dyn CommonResponse + Send>>)> {
+    let receiver = stream_queries(requests.clone(), max_concurrent, deadline, on_complete);
+    let mut results: Vec<_> = requests.iter().map(|_| None).collect();
+
+    // The results are only waited for until the deadline, a query that is
+    // still being made then (however its own settings bound it) times out.
+    loop {
+        let received = match deadline {
+            Some(deadline) => {
+                receiver
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .ok()
+            }
+            None => receiver.recv().ok(),
+        };
+        let Some((index, result)) = received else {
+            break;
+        };
+
+        results[index] = Some(result);
+    }
+
+    requests
+        .into_iter()
+        .zip(results)
+        .map(|(request, result)| {
+            (
+                request,
+                result.unwrap_or_else(|| Err(batch_timed_out("The batch deadline passed before the query ended"))),
+            )
+        })
+        .collect()
+}
+
+/// The error of a query the batch deadline didn't leave the time for.
+fn batch_timed_out(message: &str) -> GDError {
+    PacketReceive.context(io::Error::new(io::ErrorKind::TimedOut, message.to_string()))
+}
+
+fn stream_queries(
+    requests: Vec<QueryRequest>,
+    max_concurrent: usize,
+    deadline: Option<Instant>,
+    on_complete: Option<QueryHook>,
+) -> mpsc::Receiver<IndexedResult> {
+    let max_concurrent = max_concurrent.max(1);
+    let requests: Vec<_> = requests.into_iter().enumerate().collect();
+    let workers = requests.len().min(max_concurrent);
+    let queue = Arc::new(Mutex::new(requests.into_iter()));
+
+    let (sender, receiver) = mpsc::sync_channel(max_concurrent);
+    for _ in 0 .. workers {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        let on_complete = on_complete.clone();
+
+        thread::spawn(move || {
+            loop {
+                // The lock is only held to take the next request, not while
+                // it is being made.
+                let (index, request) = match queue.lock().unwrap().next() {
+                    Some(next) => next,
+                    None => break,
+                };
+
+                // The receiver being dropped means nobody wants the results
+                // anymore.
+                let result = request.query_with_hook(deadline, on_complete.as_ref());
+                if sender.send((index, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::types::Protocol;
+    use crate::protocols::valve::Engine;
+    use crate::GDErrorKind::{PacketReceive, PacketUnderflow};
+    use std::net::{Ipv4Addr, UdpSocket};
+    use std::time::Duration;
+
+    fn game() -> Game {
+        Game {
+            name: "Test",
+            default_port: 0,
+            protocol: Protocol::Valve(Engine::new(440)),
+            request_settings: ExtraRequestSettings::default(),
+        }
+    }
+
+    /// A request to a server that never answers, until its socket is dropped.
+    fn request(socket: &UdpSocket, timeout: Duration) -> QueryRequest {
+        QueryRequest {
+            timeout_settings: TimeoutSettings::new(Some(timeout), None, None, 0).ok(),
+            ..QueryRequest::new(
+                &game(),
+                Ipv4Addr::LOCALHOST.into(),
+                Some(socket.local_addr().unwrap().port()),
+            )
+        }
+    }
+
+    #[test]
+    fn results_are_streamed_as_they_complete() {
+        let slow = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let fast = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+
+        let results: Vec<_> = query_many_streaming([
+            request(&slow, Duration::from_millis(500)),
+            request(&fast, Duration::from_millis(50)),
+        ])
+        .map(|(index, result)| (index, result.is_err()))
+        .collect();
+
+        assert_eq!(results, [(1, true), (0, true)]);
+    }
+
+    #[test]
+    fn collected_results_keep_the_request_order() {
+        let slow = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        // Answers once with a reply that can't be parsed.
+        let fast = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let responder = fast.try_clone().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0; 64];
+            let (_, address) = responder.recv_from(&mut buffer).unwrap();
+            responder.send_to(b"\xFF\xFF\xFF\xFFI", address).unwrap();
+        });
+
+        let results = query_many([
+            request(&slow, Duration::from_millis(200)),
+            request(&fast, Duration::from_millis(200)),
+        ]);
+
+        assert_eq!(results.len(), 2);
+        let kinds: Vec<_> = results
+            .iter()
+            .map(|result| result.as_ref().err().map(|e| e.kind.clone()))
+            .collect();
+        assert_eq!(kinds[0], Some(PacketReceive));
+        assert!(matches!(&kinds[1], Some(kind) if *kind != PacketReceive));
+        assert!(query_many(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn limited_queries_are_queued() {
+        const TIMEOUT: Duration = Duration::from_millis(50);
+
+        let sockets: Vec<_> = (0 .. 4)
+            .map(|_| UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap())
+            .collect();
+
+        // With a single query at a time, every timeout is waited for in turn.
+        let start = std::time::Instant::now();
+        let results = query_many_with_limit(sockets.iter().map(|socket| request(socket, TIMEOUT)), 0);
+        assert!(start.elapsed() >= TIMEOUT * 4);
+        assert_eq!(results.len(), 4);
+    }
+
+    #[test]
+    fn hook_is_called_with_each_outcome() {
+        const REPLY: &[u8] = b"\xFF\xFF\xFF\xFFI";
+
+        let silent = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        // Answers once with a reply that can't be parsed.
+        let server = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let responder = server.try_clone().unwrap();
+        let responder = thread::spawn(move || {
+            let mut buffer = [0; 64];
+            let (received, address) = responder.recv_from(&mut buffer).unwrap();
+            responder.send_to(REPLY, address).unwrap();
+            received
+        });
+
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&outcomes);
+        let on_complete: QueryHook = Arc::new(move |outcome| {
+            assert_eq!(outcome.game, &game());
+            recorded.lock().unwrap().push((
+                outcome.address,
+                outcome.bytes_sent,
+                outcome.bytes_received,
+                outcome.result.clone(),
+            ));
+        });
+
+        let results = query_batch_with_hook(
+            [
+                request(&server, Duration::from_millis(200)),
+                request(&silent, Duration::from_millis(100)),
+            ],
+            2,
+            None,
+            Arc::clone(&on_complete),
+        );
+        let request_size = responder.join().unwrap();
+        assert_eq!(results.len(), 2);
+
+        // The silent server times out last.
+        assert_eq!(
+            outcomes.lock().unwrap().as_slice(),
+            [
+                (server.local_addr().unwrap(), request_size, REPLY.len(), Err(PacketUnderflow)),
+                (silent.local_addr().unwrap(), request_size, 0, Err(PacketReceive)),
+            ]
+        );
+
+        outcomes.lock().unwrap().clear();
+        let results = query_many_with_hook([request(&silent, Duration::from_millis(50))], 1, on_complete);
+        assert!(results[0].is_err());
+        assert_eq!(outcomes.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn batches_end_by_their_deadline() {
+        let sockets: Vec<_> = (0 .. 3)
+            .map(|_| UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap())
+            .collect();
+        let requests: Vec<_> = sockets
+            .iter()
+            .map(|socket| request(socket, Duration::from_secs(2)))
+            .collect();
+
+        // A query at a time, the first one using the whole deadline.
+        let start = std::time::Instant::now();
+        let results = query_batch_with_deadline(requests.clone(), 1, Duration::from_millis(200));
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        assert_eq!(results.len(), 3);
+        for ((request, result), expected) in results.iter().zip(&requests) {
+            assert_eq!(request, expected);
+            assert_eq!(result.as_ref().err().map(|e| e.kind.clone()), Some(PacketReceive));
+        }
+        assert!(query_batch(Vec::new(), 4).is_empty());
+    }
+
+    #[test]
+    fn batches_dont_wait_for_queries_past_their_deadline() {
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        // The hook holding the query up, it ends well after the deadline.
+        let on_complete: QueryHook = Arc::new(|_| thread::sleep(Duration::from_secs(1)));
+
+        let start = std::time::Instant::now();
+        let results = query_batch_with_hook(
+            [request(&socket, Duration::from_millis(50))],
+            1,
+            Some(Duration::from_millis(200)),
+            on_complete,
+        );
+        assert!(start.elapsed() < Duration::from_millis(800));
+
+        let error = results[0].1.as_ref().err().unwrap();
+        assert_eq!(error.kind, PacketReceive);
+        assert!(format!("{error:?}").contains("before the query ended"));
+    }
+}
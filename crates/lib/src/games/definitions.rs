@@ -36,6 +36,7 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
     "minecraftbedrock" => game!("Minecraft (bedrock)", 19132, Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Bedrock)))),
     "minecraftpocket" => game!("Minecraft (pocket)", 19132, Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Bedrock)))),
     "minecraftjava" => game!("Minecraft (java)", 25565, Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Java)))),
+    "minecraftquery" => game!("Minecraft (query)", 25565, Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Query)))),
     "minecraftlegacy16" => game!("Minecraft (legacy 1.6)", 25565, Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Legacy(LegacyGroup::V1_6))))),
     "minecraftlegacy14" => game!("Minecraft (legacy 1.4)", 25565, Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Legacy(LegacyGroup::V1_4))))),
     "minecraftlegacyb18" => game!("Minecraft (legacy b1.8)", 25565, Protocol::PROPRIETARY(ProprietaryProtocol::Minecraft(Some(Server::Legacy(LegacyGroup::VB1_8))))),
@@ -43,6 +44,7 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
         players: GatherToggle::Enforce,
         rules: GatherToggle::Skip,
         check_app_id: true,
+        ..GatheringSettings::default()
     }.into_extra()),
     "abioticfactor" => game!("Abiotic Factor", 27015, Protocol::Valve(Engine::new(427_410))),
     "alienswarm" => game!("Alien Swarm", 27015, Protocol::Valve(Engine::new(630))),
@@ -54,6 +56,7 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
         players: GatherToggle::Enforce,
         rules: GatherToggle::Enforce,
         check_app_id: false,
+        ..GatheringSettings::default()
     }.into_extra()),
     "atlas" => game!("ATLAS", 57561, Protocol::Valve(Engine::new(834_910))),
     "avorion" => game!("Avorion", 27020, Protocol::Valve(Engine::new(445_220))),
@@ -63,6 +66,7 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
         players: GatherToggle::Enforce,
         rules: GatherToggle::Skip,
         check_app_id: true,
+        ..GatheringSettings::default()
     }.into_extra()),
     "battalion1944" => game!("Battalion 1944", 7780, Protocol::Valve(Engine::new(489_940))),
     "brainbread2" => game!("BrainBread 2", 27015, Protocol::Valve(Engine::new(346_330))),
@@ -76,6 +80,7 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
         players: GatherToggle::Skip,
         rules: GatherToggle::Enforce,
         check_app_id: true,
+        ..GatheringSettings::default()
     }.into_extra()),
     "counterstrike" => game!("Counter-Strike", 27015, Protocol::Valve(Engine::new_gold_src(false))),
     "counterstrike2" => game!("Counter-Strike 2", 27015, Protocol::Valve(Engine::new(730))),
@@ -107,13 +112,16 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
     "postscriptum" => game!("Post Scriptum", 10037, Protocol::Valve(Engine::new(736_220))),
     "projectzomboid" => game!("Project Zomboid", 16261, Protocol::Valve(Engine::new(108_600))),
     "pvak2" => game!("Pirates, Vikings, and Knights II", 27015, Protocol::Valve(Engine::new(17_570))),
+    "netquake" => game!("NetQuake", 26000, Protocol::Quake(QuakeVersion::NetQuake)),
     "quake1" => game!("Quake 1", 27500, Protocol::Quake(QuakeVersion::One)),
     "quake2" => game!("Quake 2", 27910, Protocol::Quake(QuakeVersion::Two)),
     "q3a" => game!("Quake 3 Arena", 27960, Protocol::Quake(QuakeVersion::Three)),
+    "quakelive" => game!("Quake Live", 27960, Protocol::Valve(Engine::new(282_440))),
     "risingworld" => game!("Rising World", 4254, Protocol::Valve(Engine::new(324_080)), GatheringSettings {
         players: GatherToggle::Enforce,
         rules: GatherToggle::Skip,
         check_app_id: true,
+        ..GatheringSettings::default()
     }.into_extra()),
     "ror2" => game!("Risk of Rain 2", 27016, Protocol::Valve(Engine::new(632_360))),
     "rust" => game!("Rust", 27015, Protocol::Valve(Engine::new(252_490))),
@@ -123,18 +131,20 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
     "sof2" => game!("Soldier of Fortune 2", 20100, Protocol::Quake(QuakeVersion::Three)),
     "soulmask" => game!("Soulmask", 27015, Protocol::Valve(Engine::new(2_646_460))),
     "serioussam" => game!("Serious Sam", 25601, Protocol::Gamespy(GameSpyVersion::One)),
+    "groundbranch" => game!("Ground Branch", 27015, Protocol::Valve(Engine::new_with_dedicated(16900, 476_400))),
     "squad" => game!("Squad", 27165, Protocol::Valve(Engine::new(393_380))),
     "theforest" => game!("The Forest", 27016, Protocol::Valve(Engine::new(556_450))),
     "thefront" => game!("The Front", 27015, Protocol::Valve(Engine::new(2_285_150))),
     "teamfortress2" => game!("Team Fortress 2", 27015, Protocol::Valve(Engine::new(440))),
     "tfc" => game!("Team Fortress Classic", 27015, Protocol::Valve(Engine::new_gold_src(false))),
     "theship" => game!("The Ship", 27015, Protocol::PROPRIETARY(ProprietaryProtocol::TheShip)),
-    "unturned" => game!("Unturned", 27015, Protocol::Valve(Engine::new(304_930))),
+    "unturned" => game!("Unturned", 27016, Protocol::Valve(Engine::new(304_930))),
     "unrealtournament" => game!("Unreal Tournament", 7778, Protocol::Gamespy(GameSpyVersion::One)),
     "valheim" => game!("Valheim", 2457, Protocol::Valve(Engine::new(892_970)), GatheringSettings {
         players: GatherToggle::Enforce,
         rules: GatherToggle::Skip,
         check_app_id: true,
+        ..GatheringSettings::default()
     }.into_extra()),
     "vrising" => game!("V Rising", 27016, Protocol::Valve(Engine::new(1_604_030))),
     "jc2m" => game!("Just Cause 2: Multiplayer", 7777, Protocol::PROPRIETARY(ProprietaryProtocol::JC2M)),
@@ -151,4 +161,7 @@ pub static GAMES: Map<&'static str, Game> = phf_map! {
     "mordhau" => game!("Mordhau", 27015, Protocol::Valve(Engine::new(629_760))),
     "mindustry" => game!("Mindustry", crate::games::mindustry::DEFAULT_PORT, Protocol::PROPRIETARY(ProprietaryProtocol::Mindustry)),
     "nla" => game!("Nova-Life: Amboise", 27015, Protocol::Valve(Engine::new(885_570))),
+    "vintagestory" => game!("Vintage Story", crate::games::vintagestory::DEFAULT_PORT, Protocol::PROPRIETARY(ProprietaryProtocol::VintageStory)),
+    "wreckfest" => game!("Wreckfest", crate::games::wreckfest::DEFAULT_PORT, Protocol::PROPRIETARY(ProprietaryProtocol::Wreckfest)),
+    "factorio" => game!("Factorio", crate::games::factorio::DEFAULT_PORT, Protocol::PROPRIETARY(ProprietaryProtocol::Factorio)),
 };
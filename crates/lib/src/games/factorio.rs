@@ -0,0 +1,283 @@
+//! Factorio headless server UDP query.
+//!
+//! The server answers a single datagram request (message type `0x02`, followed
+//! by the client version and a request id) with a reply (message type `0x03`)
+//! laid out as:
+//! - version: major `u8`, minor `u8`, patch `u8`, build `u16`
+//! - the echoed request id `u32`
+//! - server name (space optimized string)
+//! - flags `u8`: bit 0 is set when a password is required, bit 1 when the
+//!   player list is hidden
+//! - players online `u16`, players maximum `u16` (0 meaning unlimited)
+//! - when the player list isn't hidden: a space optimized string per online
+//!   player
+//!
+//! All numbers are little endian, a space optimized string is prefixed by a
+//! `u8` length, or by `0xFF` followed by a `u32` length for longer strings.
+
+use crate::buffer::Buffer;
+use crate::protocols::types::{CommonPlayer, CommonResponse, GenericPlayer, TimeoutSettings};
+use crate::protocols::GenericResponse;
+use crate::socket::{Socket, UdpSocket};
+use crate::utils::retry_on_timeout;
+use crate::GDErrorKind::{PacketBad, TypeParse};
+use crate::{GDResult, PacketTooShort};
+use byteorder::LittleEndian;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Default Factorio server port.
+pub const DEFAULT_PORT: u16 = 34197;
+
+/// The message type of the query request.
+const REQUEST_KIND: u8 = 0x02;
+/// The message type of the query reply.
+const REPLY_KIND: u8 = 0x03;
+/// The id sent along the request and echoed back by the server, an arbitrary
+/// value only used to tell the reply apart from unrelated datagrams.
+const REQUEST_ID: u32 = 0x4744_4947;
+/// The client version sent along the request (major, minor, patch and build),
+/// an arbitrary one as the server replies with its own version.
+const CLIENT_VERSION: [u8; 5] = [1, 1, 0, 0, 0];
+
+const FLAG_HAS_PASSWORD: u8 = 0b01;
+const FLAG_PLAYERS_HIDDEN: u8 = 0b10;
+
+/// A player connected to the server.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Player {
+    pub name: String,
+}
+
+impl CommonPlayer for Player {
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::Factorio(self) }
+
+    fn name(&self) -> &str { &self.name }
+}
+
+/// The query response.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Response {
+    /// Name of the server.
+    pub name: String,
+    /// The game version (`major.minor.patch`).
+    pub game_version: String,
+    /// The build number of the game version.
+    pub build: u16,
+    /// Indicates whether the server requires a password.
+    pub has_password: bool,
+    /// Number of players on the server.
+    pub players_online: u32,
+    /// Maximum number of players, `None` if the server doesn't limit it.
+    pub players_maximum: Option<u32>,
+    /// Connected players, `None` if the server hides its player list.
+    pub players: Option<Vec<Player>>,
+    /// The round trip time of the request (to its reply), `None` if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ping: Option<Duration>,
+}
+
+impl CommonResponse for Response {
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Factorio(self) }
+
+    fn name(&self) -> Option<&str> { Some(&self.name) }
+    fn game_version(&self) -> Option<&str> { Some(&self.game_version) }
+    fn has_password(&self) -> Option<bool> { Some(self.has_password) }
+    fn players_maximum(&self) -> u32 { self.players_maximum.unwrap_or(0) }
+    fn players_online(&self) -> u32 { self.players_online }
+    fn ping(&self) -> Option<Duration> { self.ping }
+
+    fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
+        self.players
+            .as_ref()
+            .map(|players| players.iter().map(|p| p as &dyn CommonPlayer).collect())
+    }
+}
+
+/// Read a space optimized string.
+fn read_string(buffer: &mut Buffer<LittleEndian>) -> GDResult<String> {
+    let length = match buffer.read::<u8>()? {
+        0xFF => buffer.read::<u32>()? as usize,
+        length => length as usize,
+    };
+
+    if buffer.remaining_length() < length {
+        return Err(PacketTooShort {
+            expected: length,
+            got: buffer.remaining_length(),
+            offset: buffer.current_position(),
+        }
+        .into());
+    }
+
+    let value = std::str::from_utf8(&buffer.remaining_bytes()[.. length])
+        .map_err(|e| TypeParse.context(e))?
+        .to_string();
+    buffer.move_cursor(length as isize)?;
+
+    Ok(value)
+}
+
+fn request_payload() -> Vec<u8> {
+    let mut payload = vec![REQUEST_KIND];
+    payload.extend_from_slice(&CLIENT_VERSION);
+    payload.extend_from_slice(&REQUEST_ID.to_le_bytes());
+
+    payload
+}
+
+/// Parse a query reply, the ping being left unknown.
+pub fn parse_response(data: &[u8]) -> GDResult<Response> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
+
+    let kind = buffer.read::<u8>()?;
+    if kind != REPLY_KIND {
+        return Err(PacketBad.context(format!("Expected message type {REPLY_KIND:#04x}, got {kind:#04x}")));
+    }
+
+    let game_version = format!(
+        "{}.{}.{}",
+        buffer.read::<u8>()?,
+        buffer.read::<u8>()?,
+        buffer.read::<u8>()?
+    );
+    let build = buffer.read::<u16>()?;
+
+    if buffer.read::<u32>()? != REQUEST_ID {
+        return Err(PacketBad.context("Reply doesn't match the request id"));
+    }
+
+    let name = read_string(&mut buffer)?;
+    let flags = buffer.read::<u8>()?;
+    let players_online = buffer.read::<u16>()?.into();
+    let players_maximum = match buffer.read::<u16>()? {
+        0 => None,
+        maximum => Some(maximum.into()),
+    };
+
+    let players = if flags & FLAG_PLAYERS_HIDDEN == 0 {
+        let mut players = Vec::with_capacity(players_online as usize);
+        for _ in 0 .. players_online {
+            players.push(Player {
+                name: read_string(&mut buffer)?,
+            });
+        }
+
+        Some(players)
+    } else {
+        None
+    };
+
+    Ok(Response {
+        name,
+        game_version,
+        build,
+        has_password: flags & FLAG_HAS_PASSWORD != 0,
+        players_online,
+        players_maximum,
+        players,
+        ping: None,
+    })
+}
+
+pub fn query(address: &IpAddr, port: Option<u16>) -> GDResult<Response> { query_with_timeout(address, port, None) }
+
+pub fn query_with_timeout(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let address = SocketAddr::new(*address, port.unwrap_or(DEFAULT_PORT));
+    let mut socket = UdpSocket::new(&address, &timeout_settings)?;
+    socket.set_protocol_hint("factorio");
+
+    let response = retry_on_timeout(
+        TimeoutSettings::get_retries_or_default(&timeout_settings),
+        || {
+            socket.send(&request_payload())?;
+            parse_response(&socket.receive(None)?)
+        },
+    )?;
+
+    Ok(Response {
+        ping: socket.ping(),
+        ..response
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(flags: u8, players: &[&str]) -> Vec<u8> {
+        let mut data = vec![REPLY_KIND, 1, 1, 107];
+        data.extend_from_slice(&59233u16.to_le_bytes());
+        data.extend_from_slice(&REQUEST_ID.to_le_bytes());
+        data.push(7);
+        data.extend_from_slice(b"Factory");
+        data.push(flags);
+        data.extend_from_slice(&(players.len() as u16).to_le_bytes());
+        data.extend_from_slice(&16u16.to_le_bytes());
+        if flags & FLAG_PLAYERS_HIDDEN == 0 {
+            for player in players {
+                data.push(player.len() as u8);
+                data.extend_from_slice(player.as_bytes());
+            }
+        }
+
+        data
+    }
+
+    #[test]
+    fn parses_players() {
+        let response = parse_response(&reply(FLAG_HAS_PASSWORD, &["alice", "bob"])).unwrap();
+
+        assert_eq!(response.name, "Factory");
+        assert_eq!(response.game_version, "1.1.107");
+        assert_eq!(response.build, 59233);
+        assert!(response.has_password);
+        assert_eq!(response.players_online, 2);
+        assert_eq!(response.players_maximum, Some(16));
+        assert_eq!(
+            response.players,
+            Some(vec![
+                Player {
+                    name: "alice".to_string()
+                },
+                Player {
+                    name: "bob".to_string()
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn hidden_players_only_report_counts() {
+        let response = parse_response(&reply(FLAG_PLAYERS_HIDDEN, &["alice", "bob"])).unwrap();
+
+        assert!(!response.has_password);
+        assert_eq!(response.players_online, 2);
+        assert_eq!(response.players, None);
+    }
+
+    #[test]
+    fn rejects_other_message_types() {
+        let mut data = reply(0, &[]);
+        data[0] |= 0x20;
+
+        assert_eq!(parse_response(&data).unwrap_err().kind, PacketBad);
+    }
+
+    #[test]
+    fn rejects_other_request_ids() {
+        let mut data = reply(0, &[]);
+        data[6] ^= 0xFF;
+
+        assert!(parse_response(&data).is_err());
+    }
+}
@@ -37,7 +37,7 @@ pub fn query_with_timeout(
     let players_online = buffer.read::<u8>()?;
     let players_maximum = buffer.read::<u8>()?;
     let server_type = Server::from_gldsrc(buffer.read::<u8>()?)?;
-    let environment_type = Environment::from_gldsrc(buffer.read::<u8>()?)?;
+    let environment_type = Environment::from_gldsrc(buffer.read::<u8>()?);
     let has_password = buffer.read::<u8>()? == 1;
     let vac_secured = buffer.read::<u8>()? == 1;
     buffer.move_cursor(1)?; //average fps
@@ -62,5 +62,6 @@ pub fn query_with_timeout(
         round,
         rounds_maximum,
         time_left,
+        ping: client.ping(),
     })
 }
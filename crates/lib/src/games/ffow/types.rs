@@ -3,6 +3,7 @@ use crate::protocols::valve::{Environment, Server};
 use crate::protocols::GenericResponse;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// The query response.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -40,10 +41,14 @@ pub struct Response {
     pub rounds_maximum: u8,
     /// Time left for the current round in seconds.
     pub time_left: u16,
+    /// The round trip time of the request (to the first packet of its reply),
+    /// `None` if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ping: Option<Duration>,
 }
 
 impl CommonResponse for Response {
-    fn as_original(&self) -> GenericResponse { GenericResponse::FFOW(self) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::FFOW(self) }
 
     fn name(&self) -> Option<&str> { Some(&self.name) }
     fn game_mode(&self) -> Option<&str> { Some(&self.game_mode) }
@@ -53,4 +58,5 @@ impl CommonResponse for Response {
     fn has_password(&self) -> Option<bool> { Some(self.has_password) }
     fn players_maximum(&self) -> u32 { self.players_maximum.into() }
     fn players_online(&self) -> u32 { self.players_online.into() }
+    fn ping(&self) -> Option<Duration> { self.ping }
 }
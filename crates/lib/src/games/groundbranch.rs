@@ -0,0 +1,95 @@
+//! Ground Branch reports its current level and game mode in its keywords (as
+//! `level:<name>` and `mode:<name>`), see
+//! [query_level](crate::games::groundbranch::query_level). Servers often don't
+//! answer the players request, the query then only has the server info (and
+//! `players_details` is empty).
+//!
+//! The query port is set by `QueryPort`, 27015 by default (the game port being
+//! 7777).
+
+use crate::protocols::valve::{game, keyword_value};
+use crate::GDResult;
+use std::net::IpAddr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Default Ground Branch query port (the default game port being 7777).
+pub const DEFAULT_PORT: u16 = 27015;
+
+/// The query response.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// The valve response.
+    pub server: game::Response,
+    /// The level being played, the map if it isn't in the keywords.
+    pub level: String,
+    /// The game mode (example: "TDM").
+    pub game_mode: Option<String>,
+}
+
+impl Response {
+    /// Extract the level and game mode from the keywords.
+    pub fn from_valve_response(server: game::Response) -> Self {
+        let keywords = server.keywords.as_deref().unwrap_or_default();
+
+        Self {
+            level: keyword_value(keywords, "level")
+                .unwrap_or(&server.map)
+                .to_string(),
+            game_mode: keyword_value(keywords, "mode").map(str::to_string),
+            server,
+        }
+    }
+}
+
+pub use crate::games::valve::groundbranch::{query, query_game_port};
+
+/// Same as [query], the level and game mode being parsed out of the response.
+pub fn query_level(address: &IpAddr, port: Option<u16>) -> GDResult<Response> {
+    Ok(Response::from_valve_response(query(address, port)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::valve::{test_server_info, ServerInfo};
+
+    fn valve_response(keywords: &str) -> game::Response {
+        let info = ServerInfo {
+            map: "Depot".to_string(),
+            appid: 16900,
+            ..test_server_info(keywords)
+        };
+
+        // The players request went unanswered.
+        game::Response::from_parts(info, None, None)
+    }
+
+    #[cfg(feature = "game_defs")]
+    #[test]
+    fn definition_has_the_query_port() {
+        let game = crate::games::GAMES.get("groundbranch").unwrap();
+
+        assert_eq!(game.default_port, DEFAULT_PORT);
+        assert_eq!(game.query_port_for(7777), DEFAULT_PORT);
+    }
+
+    #[test]
+    fn parses_level_and_mode() {
+        let response = Response::from_valve_response(valve_response("level:Ministry, mode:TDM"));
+
+        assert_eq!(response.level, "Ministry");
+        assert_eq!(response.game_mode.as_deref(), Some("TDM"));
+        assert!(response.server.players_details.is_empty());
+    }
+
+    #[test]
+    fn level_defaults_to_the_map() {
+        let response = Response::from_valve_response(valve_response(""));
+
+        assert_eq!(response.level, "Depot");
+        assert_eq!(response.game_mode, None);
+    }
+}
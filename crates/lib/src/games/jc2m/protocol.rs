@@ -71,5 +71,6 @@ pub fn query_with_timeout(
         players,
         players_maximum,
         players_online,
+        ping: client.ping(),
     })
 }
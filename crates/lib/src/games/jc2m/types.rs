@@ -2,6 +2,7 @@ use crate::protocols::types::{CommonPlayer, CommonResponse, GenericPlayer};
 use crate::protocols::GenericResponse;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -12,7 +13,7 @@ pub struct Player {
 }
 
 impl CommonPlayer for Player {
-    fn as_original(&self) -> GenericPlayer { GenericPlayer::JCMP2(self) }
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::JCMP2(self) }
 
     fn name(&self) -> &str { &self.name }
 }
@@ -27,10 +28,14 @@ pub struct Response {
     pub players: Vec<Player>,
     pub players_maximum: u32,
     pub players_online: u32,
+    /// The round trip time of the first request (to the first packet of its
+    /// reply), `None` if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ping: Option<Duration>,
 }
 
 impl CommonResponse for Response {
-    fn as_original(&self) -> GenericResponse { GenericResponse::JC2M(self) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::JC2M(self) }
 
     fn game_version(&self) -> Option<&str> { Some(&self.game_version) }
     fn description(&self) -> Option<&str> { Some(&self.description) }
@@ -38,6 +43,7 @@ impl CommonResponse for Response {
     fn has_password(&self) -> Option<bool> { Some(self.has_password) }
     fn players_maximum(&self) -> u32 { self.players_maximum }
     fn players_online(&self) -> u32 { self.players_online }
+    fn ping(&self) -> Option<Duration> { self.ping }
 
     fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
         Some(
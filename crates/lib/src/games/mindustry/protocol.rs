@@ -18,7 +18,7 @@ pub const MAX_BUFFER_SIZE: usize = 500;
 /// [Reference](https://github.com/Anuken/Mindustry/blob/a2e5fbdedb2fc1c8d3c157bf344d10ad6d321442/core/src/mindustry/net/ArcNetProvider.java#L248)
 pub(crate) fn send_ping(socket: &mut UdpSocket) -> GDResult<()> { socket.send(&[-2i8 as u8, 1i8 as u8]) }
 
-/// Parse server data.
+/// Parse server data, the ping being left unknown.
 ///
 /// [Reference](https://github.com/Anuken/Mindustry/blob/a2e5fbdedb2fc1c8d3c157bf344d10ad6d321442/core/src/mindustry/net/NetworkIO.java#L122-L135)
 pub fn parse_server_data<B: byteorder::ByteOrder, D: buffer::StringDecoder>(
@@ -35,24 +35,31 @@ pub fn parse_server_data<B: byteorder::ByteOrder, D: buffer::StringDecoder>(
         player_limit: buffer.read()?,
         description: buffer.read_string::<D>(None)?,
         mode_name: buffer.read_string::<D>(None).ok(),
+        ping: None,
     })
 }
 
 /// Query a Mindustry server (without retries).
 pub fn query(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<ServerData> {
     let mut socket = UdpSocket::new(address, timeout_settings)?;
+    socket.set_protocol_hint("mindustry");
 
     send_ping(&mut socket)?;
 
     let socket_data = socket.receive(Some(MAX_BUFFER_SIZE))?;
     let mut buffer = Buffer::new(&socket_data);
 
-    parse_server_data::<byteorder::BigEndian, buffer::Utf8LengthPrefixedDecoder>(&mut buffer)
+    Ok(ServerData {
+        ping: socket.ping(),
+        ..parse_server_data::<byteorder::BigEndian, buffer::Utf8LengthPrefixedDecoder>(&mut buffer)?
+    })
 }
 
 /// Query a Mindustry server.
 pub fn query_with_retries(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<ServerData> {
     let retries = TimeoutSettings::get_retries_or_default(timeout_settings);
+    // Each retry opens a socket of its own, within the same deadline.
+    let timeout_settings = TimeoutSettings::start_deadline_of(*timeout_settings);
 
-    utils::retry_on_timeout(retries, || query(address, timeout_settings))
+    utils::retry_on_timeout(retries, || query(address, &timeout_settings))
 }
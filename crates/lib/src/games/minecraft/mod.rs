@@ -56,6 +56,12 @@ pub fn query_legacy_specific(group: LegacyGroup, address: &IpAddr, port: Option<
     )
 }
 
+/// Query a Java Server through the Query protocol, this returns the names of
+/// all the online players (the Server List Ping only gives a sample of them).
+pub fn query_full(address: &IpAddr, port: Option<u16>) -> GDResult<QueryResponse> {
+    protocol::query_full(&SocketAddr::new(*address, port_or_java_default(port)), None)
+}
+
 /// Query a Bedrock Server.
 pub fn query_bedrock(address: &IpAddr, port: Option<u16>) -> GDResult<BedrockResponse> {
     protocol::query_bedrock(
@@ -14,6 +14,9 @@ use std::net::SocketAddr;
 
 use byteorder::LittleEndian;
 
+/// The number of fields of a standard pong's status.
+const STATUS_FIELDS: usize = 12;
+
 pub struct Bedrock {
     socket: UdpSocket,
     retry_count: usize,
@@ -21,7 +24,8 @@ pub struct Bedrock {
 
 impl Bedrock {
     fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = UdpSocket::new(address, &timeout_settings)?;
+        let mut socket = UdpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("minecraft bedrock");
 
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
         Ok(Self {
@@ -44,7 +48,12 @@ impl Bedrock {
     /// Send a status request, and parse the response.
     /// This function will retry fetch on timeouts.
     fn get_info(&mut self) -> GDResult<BedrockResponse> {
-        retry_on_timeout(self.retry_count, move || self.get_info_impl())
+        let response = retry_on_timeout(self.retry_count, || self.get_info_impl())?;
+
+        Ok(BedrockResponse {
+            latency: self.socket.ping(),
+            ..response
+        })
     }
 
     /// Send a status request, and parse the response (without retry logic).
@@ -63,9 +72,8 @@ impl Bedrock {
             return Err(PacketBad.context("Invalid nonce"));
         }
 
-        // These 8 bytes are identical to the serverId string we receive in decimal
-        // below
-        buffer.move_cursor(8)?;
+        // The GUID, that the server id (in decimal) of the status repeats.
+        let guid = buffer.switch_endian_chunk(8)?.read::<u64>()?;
 
         // Verifying the magic value (as we need 16 bytes, cast to two u64 values)
         if buffer.read::<u64>()? != 18_374_403_896_610_127_616 {
@@ -80,8 +88,19 @@ impl Bedrock {
 
         error_by_expected_size(remaining_length, buffer.remaining_length())?;
 
-        let binding = buffer.read_string::<Utf8Decoder>(None)?;
-        let status: Vec<&str> = binding.split(';').collect();
+        let status = buffer.read_string::<Utf8Decoder>(None)?;
+        Self::parse_status(guid, &status)
+    }
+
+    /// Parse the `;` separated status of a pong.
+    ///
+    /// Standard servers send [STATUS_FIELDS] fields (followed by an empty one,
+    /// as the status ends with a `;`), while featured servers stop after the
+    /// first ones, the missing (or empty) fields are `None`. The latency is
+    /// left unknown.
+    pub(crate) fn parse_status(guid: u64, raw_status: &str) -> GDResult<BedrockResponse> {
+        let status: Vec<&str> = raw_status.split(';').take(STATUS_FIELDS).collect();
+        let field = |index: usize| status.get(index).copied().filter(|value| !value.is_empty());
 
         // We must have at least 6 values
         if status.len() < 6 {
@@ -95,13 +114,23 @@ impl Bedrock {
             protocol_version: status[2].to_string(),
             players_maximum: status[5].parse().map_err(|e| TypeParse.context(e))?,
             players_online: status[4].parse().map_err(|e| TypeParse.context(e))?,
-            id: status.get(6).map(std::string::ToString::to_string),
-            map: status.get(7).map(std::string::ToString::to_string),
-            game_mode: match status.get(8) {
+            id: field(6).map(str::to_string),
+            map: field(7).map(str::to_string),
+            game_mode: match field(8) {
                 None => None,
-                Some(v) => Some(GameMode::from_bedrock(v)?),
+                Some(v) => Some(GameMode::from_bedrock(&v)?),
             },
+            guid,
+            nintendo_limited: match field(9) {
+                Some("0") => Some(false),
+                Some("1") => Some(true),
+                _ => None,
+            },
+            port_v4: field(10).and_then(|port| port.parse().ok()),
+            port_v6: field(11).and_then(|port| port.parse().ok()),
             server_type: Server::Bedrock,
+            status: raw_status.to_string(),
+            latency: None,
         })
     }
 
@@ -109,3 +138,54 @@ impl Bedrock {
         Self::new(address, timeout_settings)?.get_info()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUID: u64 = 13_253_860_892_328_930_865;
+
+    #[test]
+    fn parses_standard_status() {
+        let response = Bedrock::parse_status(
+            GUID,
+            "MCPE;Dedicated Server;390;1.14.60;3;10;13253860892328930865;Bedrock level;Survival;1;19132;19133;",
+        )
+        .unwrap();
+
+        assert_eq!(response.players_online, 3);
+        assert_eq!(response.id.as_deref(), Some("13253860892328930865"));
+        assert_eq!(response.map.as_deref(), Some("Bedrock level"));
+        assert_eq!(response.game_mode, Some(GameMode::Survival));
+        assert_eq!(response.guid, GUID);
+        assert_eq!(response.nintendo_limited, Some(true));
+        assert_eq!(response.port_v4, Some(19132));
+        assert_eq!(response.port_v6, Some(19133));
+    }
+
+    #[test]
+    fn parses_featured_status() {
+        let response = Bedrock::parse_status(GUID, "MCPE;Featured Server;390;1.14.60;120;500").unwrap();
+
+        assert_eq!(response.players_maximum, 500);
+        assert_eq!(response.id, None);
+        assert_eq!(response.game_mode, None);
+        assert_eq!(response.nintendo_limited, None);
+        assert_eq!(response.port_v4, None);
+
+        assert!(Bedrock::parse_status(GUID, "MCPE;Featured Server;390;1.14.60;120").is_err());
+    }
+
+    #[test]
+    fn empty_fields_are_none() {
+        const STATUS: &str = "MCPE;Old Server;390;1.14.60;0;10;;;;;;;extra;";
+        let response = Bedrock::parse_status(GUID, STATUS).unwrap();
+
+        assert_eq!(response.id, None);
+        assert_eq!(response.map, None);
+        assert_eq!(response.game_mode, None);
+        assert_eq!(response.port_v4, None);
+        // The fields past the known ones are only in the raw status.
+        assert_eq!(response.status, STATUS);
+    }
+}
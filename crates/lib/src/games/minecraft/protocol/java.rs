@@ -1,7 +1,7 @@
 use crate::{
     buffer::Buffer,
     games::minecraft::{as_string, as_varint, get_string, get_varint, JavaResponse, Player, RequestSettings, Server},
-    protocols::types::TimeoutSettings,
+    protocols::types::{ProxyAddr, TimeoutSettings},
     socket::{Socket, TcpSocket},
     utils::retry_on_timeout,
     GDErrorKind::{JsonParse, PacketBad},
@@ -11,11 +11,17 @@ use crate::{
 use byteorder::LittleEndian;
 use serde_json::Value;
 use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The largest size of a packet, its length fitting in 3 bytes.
+const MAX_PACKET_SIZE: usize = (1 << 21) - 1;
 
 pub struct Java {
     socket: TcpSocket,
     request_settings: RequestSettings,
     retry_count: usize,
+    /// The PROXY protocol header, until it is sent along the first packet.
+    proxy_header: Option<Vec<u8>>,
 }
 
 impl Java {
@@ -24,30 +30,50 @@ impl Java {
         timeout_settings: Option<TimeoutSettings>,
         request_settings: Option<RequestSettings>,
     ) -> GDResult<Self> {
-        let socket = TcpSocket::new(address, &timeout_settings)?;
+        let mut socket = TcpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("minecraft java");
 
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
+        let request_settings = request_settings.unwrap_or_default();
         Ok(Self {
             socket,
-            request_settings: request_settings.unwrap_or_default(),
+            proxy_header: request_settings
+                .proxy_protocol_header
+                .as_ref()
+                .map(ProxyAddr::v2_header),
+            request_settings,
             retry_count,
         })
     }
 
     fn send(&mut self, data: Vec<u8>) -> GDResult<()> {
+        // The header is sent in the same write as the handshake, so that a
+        // capture records it as part of the first packet.
+        let proxy_header = self.proxy_header.take().unwrap_or_default();
         self.socket
-            .send(&[as_varint(data.len() as i32), data].concat())
+            .send(&[proxy_header, as_varint(data.len() as i32), data].concat())
     }
 
+    /// Receive a packet (without its length), the connection staying open
+    /// once it is read.
     fn receive(&mut self) -> GDResult<Vec<u8>> {
-        let data = &self.socket.receive(None)?;
-        let mut buffer = Buffer::<LittleEndian>::new(data);
+        // The length is a varint, read a byte at a time to not read past it.
+        let mut length = Vec::with_capacity(5);
+        loop {
+            let byte = self.socket.receive_exact(1)?[0];
+            length.push(byte);
+            if byte & 0x80 == 0 || length.len() == 5 {
+                break;
+            }
+        }
 
-        let _packet_length = get_varint(&mut buffer)? as usize;
-        // this declared 'packet length' from within the packet might be wrong (?), not
-        // checking with it...
+        let length = get_varint(&mut Buffer::<LittleEndian>::new(&length))?;
+        let length = usize::try_from(length).map_err(|e| PacketBad.context(e))?;
+        if length > MAX_PACKET_SIZE {
+            return Err(PacketBad.context(format!("Packet of {length} bytes is too large")));
+        }
 
-        Ok(buffer.remaining_bytes().to_vec())
+        self.socket.receive_exact(length)
     }
 
     fn send_handshake(&mut self) -> GDResult<()> {
@@ -82,10 +108,18 @@ impl Java {
         Ok(())
     }
 
-    fn send_ping_request(&mut self) -> GDResult<()> {
+    /// Send a ping request carrying `payload`, that the pong echoes.
+    fn send_ping_request(&mut self, payload: [u8; 8]) -> GDResult<()> {
         self.send(
-            [0x01] // Packet ID (1)
-                .to_vec(),
+            [
+                &[
+                    // Packet ID (1)
+                    0x01,
+                ],
+                // Payload (any long)
+                &payload[..],
+            ]
+            .concat(),
         )?;
 
         Ok(())
@@ -102,7 +136,6 @@ impl Java {
     fn get_info_impl(&mut self) -> GDResult<JavaResponse> {
         self.send_handshake()?;
         self.send_status_request()?;
-        self.send_ping_request()?;
 
         let socket_data = self.receive()?;
         let mut buffer = Buffer::<LittleEndian>::new(&socket_data);
@@ -154,14 +187,33 @@ impl Java {
             players_maximum: max_players,
             players_online: online_players,
             players,
-            description: value_response["description"].to_string(),
+            description: description_text(&value_response["description"]),
             favicon: value_response["favicon"].as_str().map(str::to_string),
             previews_chat: value_response["previewsChat"].as_bool(),
             enforces_secure_chat: value_response["enforcesSecureChat"].as_bool(),
             server_type: Server::Java,
+            latency: self.get_latency(),
         })
     }
 
+    /// Ping the server once its status was read, so that only the ping and
+    /// its pong are timed, `None` if the pong doesn't come back or doesn't
+    /// echo the ping.
+    fn get_latency(&mut self) -> Option<Duration> {
+        let ping_payload = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        let ping_payload = ping_payload.to_be_bytes();
+
+        let ping_sent = Instant::now();
+        self.send_ping_request(ping_payload).ok()?;
+        let pong = self.receive().ok()?;
+        let latency = ping_sent.elapsed();
+
+        is_pong(&pong, ping_payload).then_some(latency)
+    }
+
     pub fn query(
         address: &SocketAddr,
         timeout_settings: Option<TimeoutSettings>,
@@ -170,3 +222,141 @@ impl Java {
         Self::new(address, timeout_settings, request_settings)?.get_info()
     }
 }
+
+/// The text of a description, either a plain string or a chat component (an
+/// object with a `text` and `extra` components, or an array of components).
+fn description_text(description: &Value) -> String {
+    match description {
+        Value::String(text) => text.clone(),
+        Value::Array(components) => components.iter().map(description_text).collect(),
+        Value::Object(component) => {
+            let mut text = component
+                .get("text")
+                .map(description_text)
+                .unwrap_or_default();
+            if let Some(extra) = component.get("extra") {
+                text += &description_text(extra);
+            }
+
+            text
+        }
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Tells if a packet is the pong echoing the ping's payload.
+fn is_pong(packet: &[u8], ping_payload: [u8; 8]) -> bool {
+    // Its ID (1), then the payload.
+    packet.split_first() == Some((&0x01, &ping_payload[..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use crate::games::minecraft::as_string;
+    use std::io::{Read, Write};
+    use std::net::{Ipv4Addr, TcpListener};
+
+    #[test]
+    fn proxy_header_precedes_the_handshake() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let proxy = ProxyAddr::new("192.168.1.2:51000".parse().unwrap(), address);
+
+        let mut java = Java::new(
+            &address,
+            None,
+            Some(RequestSettings {
+                proxy_protocol_header: Some(proxy),
+                ..Default::default()
+            }),
+        )
+        .unwrap();
+        java.send_handshake().unwrap();
+        java.send_status_request().unwrap();
+        drop(java);
+
+        let mut received = Vec::new();
+        listener
+            .accept()
+            .unwrap()
+            .0
+            .read_to_end(&mut received)
+            .unwrap();
+
+        let header = proxy.v2_header();
+        assert!(received.starts_with(&header));
+        // The handshake follows right after the header.
+        assert_eq!(received[header.len() + 1], 0x00);
+        // Only sent once, the status request following the handshake as is.
+        assert!(received.ends_with(&[0x01, 0x01, 0x00]));
+    }
+
+    #[test]
+    fn descriptions_can_be_chat_components() {
+        let description = serde_json::json!({
+            "text": "A ",
+            "bold": true,
+            "extra": [{ "text": "Minecraft", "color": "green" }, " Server", { "extra": ["!"] }],
+        });
+
+        assert_eq!(description_text(&description), "A Minecraft Server!");
+        assert_eq!(description_text(&serde_json::json!("A plain MOTD")), "A plain MOTD");
+        assert_eq!(description_text(&Value::Null), "");
+    }
+
+    #[test]
+    fn query_reads_the_status_and_the_pong() {
+        const FAVICON: &[u8] = b"\x89PNG\r\n\x1a\n";
+        /// The size of the handshake and status requests, then of the ping.
+        const STATUS_REQUESTS_SIZE: usize = 18 + 2;
+        const PING_SIZE: usize = 10;
+        /// How long the server takes to answer the status.
+        const STATUS_DELAY: Duration = Duration::from_millis(300);
+
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = std::thread::spawn(move || {
+            let mut stream = listener.accept().unwrap().0;
+            let mut requests = vec![0; STATUS_REQUESTS_SIZE];
+            stream.read_exact(&mut requests).unwrap();
+            std::thread::sleep(STATUS_DELAY);
+
+            let status = serde_json::json!({
+                "version": { "name": "1.20.4", "protocol": 765 },
+                "players": { "max": 20, "online": 1, "sample": [{ "name": "alice", "id": "4566e69f" }] },
+                "description": { "text": "Hello ", "extra": [{ "text": "world" }] },
+                "favicon": format!(
+                    "data:image/png;base64,{}",
+                    base64::engine::general_purpose::STANDARD.encode(FAVICON)
+                ),
+            })
+            .to_string();
+            let status = [&[0x00][..], &as_string(&status).unwrap()].concat();
+            stream
+                .write_all(&[as_varint(status.len() as i32), status].concat())
+                .unwrap();
+
+            // The ping only comes once the status was read, the pong echoing
+            // its payload (its last 8 bytes).
+            let mut ping = [0; PING_SIZE];
+            stream.read_exact(&mut ping).unwrap();
+            let pong = [&[0x01][..], &ping[PING_SIZE - 8 ..]].concat();
+            stream
+                .write_all(&[as_varint(pong.len() as i32), pong].concat())
+                .unwrap();
+        });
+
+        let response = Java::query(&address, None, None).unwrap();
+        server.join().unwrap();
+
+        assert_eq!(response.game_version, "1.20.4");
+        assert_eq!(response.description, "Hello world");
+        assert_eq!(response.players.as_ref().unwrap()[0].name, "alice");
+        assert_eq!(response.favicon_data().as_deref(), Some(FAVICON));
+        // Waiting for the status isn't counted.
+        assert!(response.latency.unwrap() < STATUS_DELAY);
+    }
+}
@@ -19,7 +19,8 @@ pub struct LegacyV1_4 {
 
 impl LegacyV1_4 {
     fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = TcpSocket::new(address, &timeout_settings)?;
+        let mut socket = TcpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("minecraft legacy");
 
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
         Ok(Self {
@@ -33,7 +34,12 @@ impl LegacyV1_4 {
     /// Send info request and parse response.
     /// This function will retry fetch on timeouts.
     fn get_info(&mut self) -> GDResult<JavaResponse> {
-        retry_on_timeout(self.retry_count, move || self.get_info_impl())
+        let response = retry_on_timeout(self.retry_count, || self.get_info_impl())?;
+
+        Ok(JavaResponse {
+            latency: self.socket.ping(),
+            ..response
+        })
     }
 
     /// Send info request and parse response (without retry logic).
@@ -74,6 +80,7 @@ impl LegacyV1_4 {
             previews_chat: None,
             enforces_secure_chat: None,
             server_type: Server::Legacy(LegacyGroup::V1_4),
+            latency: None,
         })
     }
 
@@ -18,7 +18,8 @@ pub struct LegacyV1_6 {
 
 impl LegacyV1_6 {
     fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = TcpSocket::new(address, &timeout_settings)?;
+        let mut socket = TcpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("minecraft legacy");
 
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
         Ok(Self {
@@ -80,13 +81,19 @@ impl LegacyV1_6 {
             previews_chat: None,
             enforces_secure_chat: None,
             server_type: Server::Legacy(LegacyGroup::V1_6),
+            latency: None,
         })
     }
 
     /// Send info request and parse response.
     /// This function will retry fetch on timeouts.
     fn get_info(&mut self) -> GDResult<JavaResponse> {
-        retry_on_timeout(self.retry_count, move || self.get_info_impl())
+        let response = retry_on_timeout(self.retry_count, || self.get_info_impl())?;
+
+        Ok(JavaResponse {
+            latency: self.socket.ping(),
+            ..response
+        })
     }
 
     /// Send info request and parse response (without retry logic).
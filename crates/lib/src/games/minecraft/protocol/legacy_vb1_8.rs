@@ -19,7 +19,8 @@ pub struct LegacyVB1_8 {
 
 impl LegacyVB1_8 {
     fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = TcpSocket::new(address, &timeout_settings)?;
+        let mut socket = TcpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("minecraft legacy");
 
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
         Ok(Self {
@@ -33,7 +34,12 @@ impl LegacyVB1_8 {
     /// Send request for info and parse response.
     /// This function will retry fetch on timeouts.
     fn get_info(&mut self) -> GDResult<JavaResponse> {
-        retry_on_timeout(self.retry_count, move || self.get_info_impl())
+        let response = retry_on_timeout(self.retry_count, || self.get_info_impl())?;
+
+        Ok(JavaResponse {
+            latency: self.socket.ping(),
+            ..response
+        })
     }
 
     /// Send request for info and parse response (without retry logic).
@@ -70,6 +76,7 @@ impl LegacyVB1_8 {
             previews_chat: None,
             enforces_secure_chat: None,
             server_type: Server::Legacy(LegacyGroup::VB1_8),
+            latency: None,
         })
     }
 
@@ -7,10 +7,12 @@ use crate::{
             legacy_v1_4::LegacyV1_4,
             legacy_v1_6::LegacyV1_6,
             legacy_vb1_8::LegacyVB1_8,
+            query::Query,
         },
         BedrockResponse,
         JavaResponse,
         LegacyGroup,
+        QueryResponse,
     },
     protocols::types::TimeoutSettings,
     GDErrorKind::AutoQuery,
@@ -23,6 +25,7 @@ mod java;
 mod legacy_v1_4;
 mod legacy_v1_6;
 mod legacy_vb1_8;
+mod query;
 
 /// Queries a Minecraft server with all the protocol variants one by one (Java
 /// -> Bedrock -> Legacy (1.6 -> 1.4 -> Beta 1.8)).
@@ -31,6 +34,8 @@ pub fn query(
     timeout_settings: Option<TimeoutSettings>,
     request_settings: Option<RequestSettings>,
 ) -> GDResult<JavaResponse> {
+    // The variants share the deadline, rather than each getting it whole.
+    let timeout_settings = TimeoutSettings::start_deadline_of(timeout_settings);
     if let Ok(response) = query_java(address, timeout_settings, request_settings) {
         return Ok(response);
     }
@@ -57,6 +62,7 @@ pub fn query_java(
 
 /// Query a (Java) Legacy Server (1.6 -> 1.4 -> Beta 1.8).
 pub fn query_legacy(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<JavaResponse> {
+    let timeout_settings = TimeoutSettings::start_deadline_of(timeout_settings);
     if let Ok(response) = query_legacy_specific(LegacyGroup::V1_6, address, timeout_settings) {
         return Ok(response);
     }
@@ -89,3 +95,9 @@ pub fn query_legacy_specific(
 pub fn query_bedrock(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<BedrockResponse> {
     Bedrock::query(address, timeout_settings)
 }
+
+/// Query a Java Server through the Query protocol (which has to be enabled on
+/// the server), unlike the Server List Ping this lists all the online players.
+pub fn query_full(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<QueryResponse> {
+    Query::query(address, timeout_settings)
+}
@@ -0,0 +1,160 @@
+// The Query protocol is documented at https://wiki.vg/Query
+use crate::{
+    buffer::{Buffer, Utf8Decoder},
+    games::minecraft::{Player, QueryResponse},
+    protocols::{
+        gamespy::three::{data_to_map, GameSpy3},
+        types::TimeoutSettings,
+    },
+    GDErrorKind::{PacketBad, TypeParse},
+    GDResult,
+};
+
+use std::{collections::HashMap, net::SocketAddr};
+
+use byteorder::LittleEndian;
+
+/// Asking for the full stat is done by padding the request with 4 bytes.
+const FULL_STAT_PAYLOAD: [u8; 4] = [0x00, 0x00, 0x00, 0x00];
+/// Separates the key-value section from the player list.
+const PLAYERS_HEADER: &[u8] = b"\x01player_\x00\x00";
+
+pub struct Query;
+
+impl Query {
+    fn take_var(vars: &mut HashMap<String, String>, key: &str) -> GDResult<String> {
+        vars.remove(key)
+            .ok_or_else(|| PacketBad.context(format!("Missing \"{key}\"")))
+    }
+
+    fn parse_players(data: &[u8]) -> GDResult<Vec<Player>> {
+        let data = data
+            .strip_prefix(PLAYERS_HEADER)
+            .ok_or_else(|| PacketBad.context("Expected the player list header"))?;
+        let mut buffer = Buffer::<LittleEndian>::new(data);
+
+        let mut players = Vec::new();
+        while buffer.remaining_length() != 0 {
+            let name = buffer.read_string::<Utf8Decoder>(None)?;
+            if name.is_empty() {
+                break;
+            }
+
+            players.push(Player {
+                name,
+                id: String::new(),
+            });
+        }
+
+        Ok(players)
+    }
+
+    /// Parse a full stat reply (past its `splitnum` padding), the latency being
+    /// left unknown.
+    pub(crate) fn parse_full_stat(packet: &[u8]) -> GDResult<QueryResponse> {
+        let (mut vars, remaining_data) = data_to_map(packet)?;
+        let players = Self::parse_players(&remaining_data)?;
+
+        Ok(QueryResponse {
+            name: Self::take_var(&mut vars, "hostname")?,
+            game_type: Self::take_var(&mut vars, "gametype")?,
+            game_id: Self::take_var(&mut vars, "game_id")?,
+            game_version: Self::take_var(&mut vars, "version")?,
+            plugins: Self::take_var(&mut vars, "plugins")?,
+            map: Self::take_var(&mut vars, "map")?,
+            players_online: Self::take_var(&mut vars, "numplayers")?
+                .parse()
+                .map_err(|e| TypeParse.context(e))?,
+            players_maximum: Self::take_var(&mut vars, "maxplayers")?
+                .parse()
+                .map_err(|e| TypeParse.context(e))?,
+            host_port: vars.remove("hostport").and_then(|port| port.parse().ok()),
+            host_ip: vars.remove("hostip"),
+            players,
+            latency: None,
+        })
+    }
+
+    pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<QueryResponse> {
+        // The full stat reply is never split, so it's read as a single packet.
+        let mut client = GameSpy3::new_custom(address, timeout_settings, FULL_STAT_PAYLOAD, true)?;
+        let packets = client.get_server_packets()?;
+
+        Ok(QueryResponse {
+            latency: client.ping(),
+            ..Self::parse_full_stat(packets.first().ok_or(PacketBad)?)?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full_stat(players: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (key, value) in [
+            ("hostname", "A Minecraft Server"),
+            ("gametype", "SMP"),
+            ("game_id", "MINECRAFT"),
+            ("version", "1.20.4"),
+            ("plugins", ""),
+            ("map", "world"),
+            ("numplayers", "3"),
+            ("maxplayers", "20"),
+            ("hostport", "25565"),
+            ("hostip", "127.0.0.1"),
+        ] {
+            data.extend_from_slice(key.as_bytes());
+            data.push(0);
+            data.extend_from_slice(value.as_bytes());
+            data.push(0);
+        }
+        data.push(0);
+
+        data.extend_from_slice(PLAYERS_HEADER);
+        for player in players {
+            data.extend_from_slice(player.as_bytes());
+            data.push(0);
+        }
+        data.push(0);
+
+        data
+    }
+
+    #[test]
+    fn parses_full_stat() {
+        let response = Query::parse_full_stat(&full_stat(&["alice", "bob", "carol"])).unwrap();
+
+        assert_eq!(response.name, "A Minecraft Server");
+        assert_eq!(response.game_version, "1.20.4");
+        assert_eq!(response.map, "world");
+        assert_eq!(response.players_online, 3);
+        assert_eq!(response.players_maximum, 20);
+        assert_eq!(response.host_port, Some(25565));
+        assert_eq!(
+            response
+                .players
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>(),
+            ["alice", "bob", "carol"]
+        );
+    }
+
+    #[test]
+    fn empty_player_list() {
+        let response = Query::parse_full_stat(&full_stat(&[])).unwrap();
+
+        assert!(response.players.is_empty());
+    }
+
+    #[test]
+    fn missing_player_list_header() {
+        let mut data = full_stat(&["alice"]);
+        let header_start = data.len() - PLAYERS_HEADER.len() - "alice".len() - 2;
+        data[header_start] = 0x02;
+
+        assert!(Query::parse_full_stat(&data).is_err());
+    }
+}
@@ -5,16 +5,18 @@
 use crate::{
     buffer::Buffer,
     protocols::{
-        types::{CommonPlayer, CommonResponse, ExtraRequestSettings, GenericPlayer},
+        types::{CommonPlayer, CommonResponse, ExtraRequestSettings, GenericPlayer, ProxyAddr},
         GenericResponse,
     },
     GDErrorKind::{InvalidInput, PacketBad, UnknownEnumCast},
     GDResult,
 };
 
+use base64::Engine;
 use byteorder::ByteOrder;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// The type of Minecraft Server you want to query.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -26,6 +28,9 @@ pub enum Server {
     Legacy(LegacyGroup),
     /// Bedrock Edition.
     Bedrock,
+    /// Java Edition, through the (GameSpy derived) Query protocol, needs
+    /// `enable-query` to be set on the server.
+    Query,
 }
 
 /// Legacy Java (Versions) Groups.
@@ -49,7 +54,7 @@ pub struct Player {
 }
 
 impl CommonPlayer for Player {
-    fn as_original(&self) -> GenericPlayer { GenericPlayer::Minecraft(self) }
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::Minecraft(self) }
 
     fn name(&self) -> &str { &self.name }
 }
@@ -60,6 +65,7 @@ impl CommonPlayer for Player {
 pub enum VersionedResponse<'a> {
     Bedrock(&'a BedrockResponse),
     Java(&'a JavaResponse),
+    Query(&'a QueryResponse),
 }
 
 /// A Java query response.
@@ -77,9 +83,11 @@ pub struct JavaResponse {
     pub players_online: u32,
     /// Some online players (can be missing).
     pub players: Option<Vec<Player>>,
-    /// Server's description or MOTD.
+    /// Server's description or MOTD, the text of the chat components it can
+    /// be made of.
     pub description: String,
-    /// The favicon (can be missing).
+    /// The favicon, as a `data:image/png;base64,` URL (can be missing), see
+    /// [JavaResponse::favicon_data].
     pub favicon: Option<String>,
     /// Tells if the chat preview is enabled (can be missing).
     pub previews_chat: Option<bool>,
@@ -87,6 +95,10 @@ pub struct JavaResponse {
     pub enforces_secure_chat: Option<bool>,
     /// Tell's the server type.
     pub server_type: Server,
+    /// The time between sending the ping and receiving its pong, `None` if the
+    /// server didn't answer it. The legacy servers having no ping, it is the
+    /// time their status took to be answered.
+    pub latency: Option<Duration>,
 }
 
 /// Java-only additional request settings.
@@ -99,15 +111,21 @@ pub struct RequestSettings {
     /// Specifies the client [protocol version number](https://wiki.vg/Protocol_version_numbers),
     /// `-1` means anything.
     pub protocol_version: i32,
+    /// When the server sits behind a proxy expecting a PROXY protocol
+    /// version 2 header, the addresses to announce in it, the header being
+    /// written right after connecting, before the handshake.
+    pub proxy_protocol_header: Option<ProxyAddr>,
 }
 
 impl Default for RequestSettings {
     /// `hostname`: "gamedig"  
-    /// `protocol_version`: -1
+    /// `protocol_version`: -1  
+    /// `proxy_protocol_header`: None
     fn default() -> Self {
         Self {
             hostname: "gamedig".to_string(),
             protocol_version: -1,
+            proxy_protocol_header: None,
         }
     }
 }
@@ -119,6 +137,7 @@ impl RequestSettings {
         Self {
             hostname,
             protocol_version: -1,
+            proxy_protocol_header: None,
         }
     }
 }
@@ -129,17 +148,19 @@ impl From<ExtraRequestSettings> for RequestSettings {
         Self {
             hostname: value.hostname.unwrap_or(default.hostname),
             protocol_version: value.protocol_version.unwrap_or(default.protocol_version),
+            proxy_protocol_header: default.proxy_protocol_header,
         }
     }
 }
 
 impl CommonResponse for JavaResponse {
-    fn as_original(&self) -> GenericResponse { GenericResponse::Minecraft(VersionedResponse::Java(self)) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Minecraft(VersionedResponse::Java(self)) }
 
     fn description(&self) -> Option<&str> { Some(&self.description) }
     fn players_maximum(&self) -> u32 { self.players_maximum }
     fn players_online(&self) -> u32 { self.players_online }
     fn game_version(&self) -> Option<&str> { Some(&self.game_version) }
+    fn ping(&self) -> Option<Duration> { self.latency }
 
     fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
         self.players
@@ -170,18 +191,91 @@ pub struct BedrockResponse {
     pub map: Option<String>,
     /// Current game mode.
     pub game_mode: Option<GameMode>,
+    /// The server's RakNet GUID, which clients (and the Xbox Live relay) tell
+    /// servers apart by.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub guid: u64,
+    /// Whether the server is limited on Nintendo Switch clients.
+    pub nintendo_limited: Option<bool>,
+    /// The server's IPv4 port.
+    pub port_v4: Option<u16>,
+    /// The server's IPv6 port.
+    pub port_v6: Option<u16>,
     /// Tells the server type.
     pub server_type: Server,
+    /// The whole `;` separated status (the MOTD) the fields were parsed from,
+    /// for the fields this doesn't know of.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub status: String,
+    /// The time between sending the status request and receiving its pong,
+    /// `None` if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub latency: Option<Duration>,
 }
 
 impl CommonResponse for BedrockResponse {
-    fn as_original(&self) -> GenericResponse { GenericResponse::Minecraft(VersionedResponse::Bedrock(self)) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Minecraft(VersionedResponse::Bedrock(self)) }
 
     fn name(&self) -> Option<&str> { Some(&self.name) }
     fn map(&self) -> Option<&str> { self.map.as_deref() }
     fn game_version(&self) -> Option<&str> { Some(&self.version_name) }
     fn players_maximum(&self) -> u32 { self.players_maximum }
     fn players_online(&self) -> u32 { self.players_online }
+    fn ping(&self) -> Option<Duration> { self.latency }
+}
+
+/// A Java Query protocol (full stat) response.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct QueryResponse {
+    /// Server's name or MOTD.
+    pub name: String,
+    /// Game type, always "SMP".
+    pub game_type: String,
+    /// Game id, always "MINECRAFT".
+    pub game_id: String,
+    /// Version name, example: "1.20.4".
+    pub game_version: String,
+    /// The server's mod name and plugins (is empty on vanilla servers).
+    pub plugins: String,
+    /// Name of the world.
+    pub map: String,
+    /// Number of online players.
+    pub players_online: u32,
+    /// Number of server capacity.
+    pub players_maximum: u32,
+    /// The server's game port (can be missing).
+    pub host_port: Option<u16>,
+    /// The server's IP (can be missing).
+    pub host_ip: Option<String>,
+    /// All the online players, the protocol doesn't provide their ids so
+    /// these are empty.
+    pub players: Vec<Player>,
+    /// The time between sending the handshake and receiving its reply, `None`
+    /// if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub latency: Option<Duration>,
+}
+
+impl CommonResponse for QueryResponse {
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Minecraft(VersionedResponse::Query(self)) }
+
+    fn name(&self) -> Option<&str> { Some(&self.name) }
+    fn map(&self) -> Option<&str> { Some(&self.map) }
+    fn game_mode(&self) -> Option<&str> { Some(&self.game_type) }
+    fn game_version(&self) -> Option<&str> { Some(&self.game_version) }
+    fn players_maximum(&self) -> u32 { self.players_maximum }
+    fn players_online(&self) -> u32 { self.players_online }
+    fn ping(&self) -> Option<Duration> { self.latency }
+
+    fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
+        Some(
+            self.players
+                .iter()
+                .map(|p| p as &dyn CommonPlayer)
+                .collect(),
+        )
+    }
 }
 
 impl JavaResponse {
@@ -197,8 +291,21 @@ impl JavaResponse {
             previews_chat: None,
             enforces_secure_chat: None,
             server_type: Server::Bedrock,
+            latency: response.latency,
         }
     }
+
+    /// The PNG image of the favicon, `None` if there is none or it isn't a
+    /// base64 data URL.
+    pub fn favicon_data(&self) -> Option<Vec<u8>> {
+        let (_, data) = self.favicon.as_deref()?.split_once(";base64,")?;
+        // Some servers wrap the base64 data.
+        let data: String = data.split_whitespace().collect();
+
+        base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .ok()
+    }
 }
 
 /// A server's game mode (used only by Bedrock servers.
@@ -334,4 +441,34 @@ mod tests {
         assert_eq!(get_string(&mut buffer), Ok("B".to_string()));
         assert_eq!(buffer.remaining_length(), 0);
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn older_bedrock_responses_are_deserialized() {
+        let response = super::BedrockResponse {
+            edition: "MCPE".to_string(),
+            name: "Dedicated Server".to_string(),
+            version_name: "1.20.50".to_string(),
+            protocol_version: "630".to_string(),
+            players_maximum: 10,
+            players_online: 0,
+            id: None,
+            map: None,
+            game_mode: None,
+            guid: 0,
+            nintendo_limited: None,
+            port_v4: None,
+            port_v6: None,
+            server_type: super::Server::Bedrock,
+            status: String::new(),
+            latency: None,
+        };
+
+        // Serialized before the GUID, the status and the latency were added.
+        let mut json = serde_json::to_value(&response).unwrap();
+        json.as_object_mut().unwrap().remove("guid");
+        json.as_object_mut().unwrap().remove("status");
+        json.as_object_mut().unwrap().remove("latency");
+        assert_eq!(serde_json::from_value::<super::BedrockResponse>(json).unwrap(), response);
+    }
 }
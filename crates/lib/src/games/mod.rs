@@ -18,8 +18,12 @@ pub use valve::*;
 pub mod battalion1944;
 /// Eco
 pub mod eco;
+/// Factorio
+pub mod factorio;
 /// Frontlines: Fuel of War
 pub mod ffow;
+/// Ground Branch
+pub mod groundbranch;
 /// Just Cause 2: Multiplayer
 pub mod jc2m;
 /// Mindustry
@@ -28,8 +32,16 @@ pub mod mindustry;
 pub mod minecraft;
 /// Savage 2
 pub mod savage2;
+/// Squad
+pub mod squad;
 /// The Ship
 pub mod theship;
+/// Unturned
+pub mod unturned;
+/// Vintage Story
+pub mod vintagestory;
+/// Wreckfest
+pub mod wreckfest;
 
 pub mod types;
 pub use types::*;
@@ -37,8 +49,16 @@ pub use types::*;
 pub mod query;
 pub use query::*;
 
+pub mod concurrent;
+pub use concurrent::*;
+
 #[cfg(feature = "game_defs")]
 mod definitions;
 
 #[cfg(feature = "game_defs")]
 pub use definitions::GAMES;
+
+#[cfg(feature = "game_defs")]
+pub mod registry;
+#[cfg(feature = "game_defs")]
+pub use registry::{query_by_id, register_game, registered_game};
@@ -2,6 +2,7 @@
 
 use crate::protocols::quake::game_query_mod;
 
+game_query_mod!(netquake, "NetQuake", netquake, 26000);
 game_query_mod!(quake1, "Quake 1", one, 27500);
 game_query_mod!(quake2, "Quake 2", two, 27910);
 game_query_mod!(q3a, "Quake 3 Arena", three, 27960);
@@ -3,12 +3,12 @@
 use std::net::{IpAddr, SocketAddr};
 
 use crate::games::types::Game;
-use crate::games::{eco, ffow, jc2m, mindustry, minecraft, savage2, theship};
+use crate::games::{eco, factorio, ffow, jc2m, mindustry, minecraft, savage2, theship, vintagestory, wreckfest};
 use crate::protocols;
 use crate::protocols::gamespy::GameSpyVersion;
 use crate::protocols::quake::QuakeVersion;
 use crate::protocols::types::{CommonResponse, ExtraRequestSettings, ProprietaryProtocol, Protocol, TimeoutSettings};
-use crate::GDResult;
+use crate::{GDErrorKind, GDResult};
 
 /// Make a query given a game definition
 #[inline]
@@ -16,6 +16,14 @@ pub fn query(game: &Game, address: &IpAddr, port: Option<u16>) -> GDResult<Box<d
     query_with_timeout_and_extra_settings(game, address, port, None, None)
 }
 
+/// Same as [query], for a server whose game port (rather than its query
+/// port) is known, the query port being derived from it by
+/// [Game::query_port_for].
+#[inline]
+pub fn query_game_port(game: &Game, address: &IpAddr, game_port: u16) -> GDResult<Box<dyn CommonResponse>> {
+    query(game, address, Some(game.query_port_for(game_port)))
+}
+
 /// Make a query given a game definition and timeout settings
 #[inline]
 pub fn query_with_timeout(
@@ -35,19 +43,95 @@ pub fn query_with_timeout_and_extra_settings(
     timeout_settings: Option<TimeoutSettings>,
     extra_settings: Option<ExtraRequestSettings>,
 ) -> GDResult<Box<dyn CommonResponse>> {
+    query_sendable(game, address, port, timeout_settings, extra_settings).map(|response| response as _)
+}
+
+/// Query a game using the Valve protocol given its definition, returning the
+/// Valve response instead of a boxed one: the engine, default port and request
+/// settings of the definition are used, so that querying a Valve game only
+/// takes its definition.
+///
+/// ```rust,no_run
+/// use gamedig::games::{query_valve, GAMES};
+///
+/// let response = query_valve(GAMES.get("tfc").unwrap(), &"127.0.0.1".parse().unwrap(), None);
+/// ```
+///
+/// # Errors
+/// Returns an [InvalidInput](GDErrorKind::InvalidInput) error if the game
+/// doesn't use the Valve protocol.
+#[inline]
+pub fn query_valve(game: &Game, address: &IpAddr, port: Option<u16>) -> GDResult<protocols::valve::game::Response> {
+    query_valve_with_timeout(game, address, port, None)
+}
+
+/// Same as [query_valve], with timeout settings.
+#[inline]
+pub fn query_valve_with_timeout(
+    game: &Game,
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<protocols::valve::game::Response> {
+    query_valve_with_timeout_and_extra_settings(game, address, port, timeout_settings, None)
+}
+
+/// Same as [query_valve], with timeout settings and extra settings, which
+/// replace the request settings of the definition.
+pub fn query_valve_with_timeout_and_extra_settings(
+    game: &Game,
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
+) -> GDResult<protocols::valve::game::Response> {
+    query_valve_protocol(
+        game,
+        &SocketAddr::new(*address, port.unwrap_or(game.default_port)),
+        timeout_settings,
+        extra_settings,
+    )
+    .map(protocols::valve::game::Response::new_from_valve_response)
+}
+
+/// The Valve query of a game definition, which every Valve game (generic
+/// queries included) is queried through.
+fn query_valve_protocol(
+    game: &Game,
+    address: &SocketAddr,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
+) -> GDResult<protocols::valve::Response> {
+    let Protocol::Valve(engine) = &game.protocol else {
+        return Err(GDErrorKind::InvalidInput.context(format!("{} doesn't use the Valve protocol", game.name)));
+    };
+
+    protocols::valve::query(
+        address,
+        *engine,
+        Some(
+            extra_settings
+                .unwrap_or_else(|| game.request_settings.clone())
+                .into(),
+        ),
+        timeout_settings,
+    )
+}
+
+/// Same as [query_with_timeout_and_extra_settings], the response being
+/// `Send` so that it can be handed over from a worker thread.
+pub(crate) fn query_sendable(
+    game: &Game,
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+    extra_settings: Option<ExtraRequestSettings>,
+) -> GDResult<Box<dyn CommonResponse + Send>> {
     let socket_addr = SocketAddr::new(*address, port.unwrap_or(game.default_port));
+    // Every socket of the query ends by the same deadline.
+    let timeout_settings = TimeoutSettings::start_deadline_of(timeout_settings);
     Ok(match &game.protocol {
-        Protocol::Valve(engine) => {
-            protocols::valve::query(
-                &socket_addr,
-                *engine,
-                extra_settings
-                    .or_else(|| Option::from(game.request_settings.clone()))
-                    .map(ExtraRequestSettings::into),
-                timeout_settings,
-            )
-            .map(Box::new)?
-        }
+        Protocol::Valve(_) => query_valve_protocol(game, &socket_addr, timeout_settings, extra_settings).map(Box::new)?,
         #[cfg(feature = "tls")]
         Protocol::Epic(credentials) => {
             protocols::epic::query_with_timeout(credentials.clone(), &socket_addr, timeout_settings).map(Box::new)?
@@ -63,6 +147,9 @@ pub fn query_with_timeout_and_extra_settings(
         }
         Protocol::Quake(version) => {
             match version {
+                QuakeVersion::NetQuake => {
+                    protocols::quake::netquake::query(&socket_addr, timeout_settings).map(Box::new)?
+                }
                 QuakeVersion::One => protocols::quake::one::query(&socket_addr, timeout_settings).map(Box::new)?,
                 QuakeVersion::Two => protocols::quake::two::query(&socket_addr, timeout_settings).map(Box::new)?,
                 QuakeVersion::Three => protocols::quake::three::query(&socket_addr, timeout_settings).map(Box::new)?,
@@ -88,6 +175,15 @@ pub fn query_with_timeout_and_extra_settings(
                 }
                 ProprietaryProtocol::FFOW => ffow::query_with_timeout(address, port, timeout_settings).map(Box::new)?,
                 ProprietaryProtocol::JC2M => jc2m::query_with_timeout(address, port, timeout_settings).map(Box::new)?,
+                ProprietaryProtocol::Factorio => {
+                    factorio::query_with_timeout(address, port, timeout_settings).map(Box::new)?
+                }
+                ProprietaryProtocol::VintageStory => {
+                    vintagestory::query_with_timeout(address, port, &timeout_settings).map(Box::new)?
+                }
+                ProprietaryProtocol::Wreckfest => {
+                    wreckfest::query_with_timeout(address, port, timeout_settings).map(Box::new)?
+                }
                 ProprietaryProtocol::Mindustry => mindustry::query(address, port, &timeout_settings).map(Box::new)?,
                 ProprietaryProtocol::Minecraft(version) => {
                     match version {
@@ -102,6 +198,9 @@ pub fn query_with_timeout_and_extra_settings(
                         Some(minecraft::Server::Bedrock) => {
                             minecraft::protocol::query_bedrock(&socket_addr, timeout_settings).map(Box::new)?
                         }
+                        Some(minecraft::Server::Query) => {
+                            minecraft::protocol::query_full(&socket_addr, timeout_settings).map(Box::new)?
+                        }
                         Some(minecraft::Server::Legacy(group)) => {
                             minecraft::protocol::query_legacy_specific(*group, &socket_addr, timeout_settings)
                                 .map(Box::new)?
@@ -129,3 +228,107 @@ pub fn query_with_timeout_and_extra_settings(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::types::GatherToggle;
+    use crate::protocols::valve::Engine;
+    use crate::GDErrorKind::{InvalidInput, PacketUnderflow};
+    use std::net::{Ipv4Addr, UdpSocket};
+    use std::thread;
+
+    #[test]
+    fn game_ports_are_turned_into_query_ports() {
+        let game = Game {
+            name: "Valheim",
+            default_port: 0,
+            protocol: Protocol::Valve(Engine::new(892_970)),
+            request_settings: ExtraRequestSettings::default(),
+        };
+        // Answers once with a reply that can't be parsed, on the game port
+        // plus one.
+        let server = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0; 64];
+            let (_, client) = server.recv_from(&mut buffer).unwrap();
+            server.send_to(b"\xFF\xFF\xFF\xFFI", client).unwrap();
+        });
+
+        assert_eq!(game.query_port_for(2456), 2457);
+        let error = query_game_port(&game, &address.ip(), address.port() - 1).err().unwrap();
+        assert_eq!(error.kind, PacketUnderflow);
+    }
+
+    /// A Valve server answering `requests` requests (without players nor
+    /// rules), returning their kinds.
+    fn valve_server(requests: usize) -> (SocketAddr, thread::JoinHandle<Vec<u8>>) {
+        let server = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = server.local_addr().unwrap();
+        let responder = thread::spawn(move || {
+            let mut info = b"\xFF\xFF\xFF\xFFI\x11gamedig\0well\0tfc\0Team Fortress Classic\0".to_vec();
+            info.extend_from_slice(&[20, 0, 3, 24, 0, b'd', b'l', 0, 1]);
+            info.extend_from_slice(b"1.1.2.2\0");
+
+            let mut kinds = Vec::new();
+            let mut buffer = [0; 64];
+            for _ in 0 .. requests {
+                let (_, client) = server.recv_from(&mut buffer).unwrap();
+                kinds.push(buffer[4]);
+                let reply = match buffer[4] {
+                    0x54 => info.clone(),
+                    0x55 => b"\xFF\xFF\xFF\xFFD\x00".to_vec(),
+                    _ => b"\xFF\xFF\xFF\xFFE\x00\x00".to_vec(),
+                };
+                server.send_to(&reply, client).unwrap();
+            }
+
+            kinds
+        });
+
+        (address, responder)
+    }
+
+    #[test]
+    fn valve_games_are_queried_from_their_definition() {
+        let (address, responder) = valve_server(1);
+
+        // The default port of the definition is queried.
+        let game = Game {
+            name: "Team Fortress Classic",
+            default_port: address.port(),
+            protocol: Protocol::Valve(Engine::new_gold_src(false)),
+            request_settings: ExtraRequestSettings::default()
+                .set_gather_players(GatherToggle::Skip)
+                .set_gather_rules(GatherToggle::Skip),
+        };
+        let response = query_valve(&game, &address.ip(), None).unwrap();
+        assert_eq!(responder.join().unwrap(), [0x54]);
+        assert_eq!(response.name, "gamedig");
+        assert_eq!(response.map, "well");
+        assert_eq!(response.players_online, 3);
+
+        // The extra settings replace the ones of the definition.
+        let (address, responder) = valve_server(3);
+        let game = Game {
+            default_port: address.port(),
+            ..game
+        };
+        let extra_settings = Some(ExtraRequestSettings::default());
+        query_valve_with_timeout_and_extra_settings(&game, &address.ip(), None, None, extra_settings).unwrap();
+        assert_eq!(responder.join().unwrap(), [0x54, 0x55, 0x56]);
+
+        // The game modules go through their definition too.
+        let (address, responder) = valve_server(3);
+        let response = crate::games::tfc::query(&address.ip(), Some(address.port())).unwrap();
+        assert_eq!(responder.join().unwrap(), [0x54, 0x55, 0x56]);
+        assert_eq!(response.name, "gamedig");
+
+        let game = Game {
+            protocol: Protocol::Gamespy(GameSpyVersion::One),
+            ..game
+        };
+        assert_eq!(query_valve(&game, &address.ip(), None).unwrap_err().kind, InvalidInput);
+    }
+}
@@ -0,0 +1,137 @@
+//! Games registered at runtime, alongside the [built-in ones](GAMES).
+
+use std::{net::IpAddr, sync::Mutex};
+
+use crate::games::{query, Game, GAMES};
+use crate::protocols::types::CommonResponse;
+use crate::GDErrorKind::InvalidInput;
+use crate::GDResult;
+
+/// The query function of a registered game.
+pub type QueryFn = fn(&IpAddr, Option<u16>) -> GDResult<Box<dyn CommonResponse>>;
+
+struct RegisteredGame {
+    id: String,
+    definition: Game,
+    query: QueryFn,
+}
+
+/// The registered games, few enough to be looked up linearly.
+static REGISTRY: Mutex<Vec<RegisteredGame>> = Mutex::new(Vec::new());
+
+/// Register a game that isn't built in (a heavily modded server with a custom
+/// app id for example), so that it can be queried by its id with
+/// [query_by_id], `query` being used to query it.
+///
+/// Registering an id again replaces its previous registration, but the ids of
+/// the [built-in games](GAMES) can't be overridden.
+///
+/// This can be called from any thread, the registry being guarded by a
+/// mutex, which is only held while the game is looked up (and not while it is
+/// queried).
+///
+/// # Errors
+/// Returns an [InvalidInput] error if `id` is the one of a built-in game.
+pub fn register_game(id: &str, definition: Game, query: QueryFn) -> GDResult<()> {
+    if GAMES.contains_key(id) {
+        return Err(InvalidInput.context(format!("\"{id}\" is a built-in game and can't be overridden")));
+    }
+
+    let mut registry = REGISTRY.lock().unwrap();
+    let game = RegisteredGame {
+        id: id.to_string(),
+        definition,
+        query,
+    };
+    match registry.iter_mut().find(|registered| registered.id == id) {
+        Some(registered) => *registered = game,
+        None => registry.push(game),
+    }
+
+    Ok(())
+}
+
+/// Get the definition of a game registered with [register_game].
+pub fn registered_game(id: &str) -> Option<Game> {
+    REGISTRY
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|registered| registered.id == id)
+        .map(|registered| registered.definition.clone())
+}
+
+/// Query a game by its id, built-in or [registered](register_game).
+///
+/// # Errors
+/// Returns an [InvalidInput] error if there is no game with this id, or the
+/// error of the query.
+pub fn query_by_id(id: &str, address: &IpAddr, port: Option<u16>) -> GDResult<Box<dyn CommonResponse>> {
+    if let Some(game) = GAMES.get(id) {
+        return query(game, address, port);
+    }
+
+    let registered_query = REGISTRY
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|registered| registered.id == id)
+        .map(|registered| registered.query);
+
+    match registered_query {
+        Some(registered_query) => registered_query(address, port),
+        None => Err(InvalidInput.context(format!("Unknown game \"{id}\""))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::types::{ExtraRequestSettings, Protocol};
+    use crate::protocols::valve::Engine;
+    use std::net::Ipv4Addr;
+
+    fn custom_game() -> Game {
+        Game {
+            name: "My Custom Game",
+            default_port: 27015,
+            protocol: Protocol::Valve(Engine::new(4_000_000)),
+            request_settings: ExtraRequestSettings::default(),
+        }
+    }
+
+    fn custom_query(_address: &IpAddr, port: Option<u16>) -> GDResult<Box<dyn CommonResponse>> {
+        Err(InvalidInput.context(format!("custom query on {port:?}")))
+    }
+
+    #[test]
+    fn registered_game_is_queried() {
+        register_game("mycustomgame", custom_game(), custom_query).unwrap();
+
+        assert_eq!(registered_game("mycustomgame"), Some(custom_game()));
+
+        let error = query_by_id("mycustomgame", &IpAddr::V4(Ipv4Addr::LOCALHOST), Some(1))
+            .err()
+            .unwrap();
+        assert_eq!(error.source.unwrap().to_string(), "custom query on Some(1)");
+    }
+
+    #[test]
+    fn built_in_games_cant_be_overridden() {
+        assert_eq!(
+            register_game("teamfortress2", custom_game(), custom_query)
+                .unwrap_err()
+                .kind,
+            InvalidInput
+        );
+        assert_eq!(registered_game("teamfortress2"), None);
+    }
+
+    #[test]
+    fn unknown_games_are_reported() {
+        let error = query_by_id("notagame", &IpAddr::V4(Ipv4Addr::LOCALHOST), None)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, InvalidInput);
+    }
+}
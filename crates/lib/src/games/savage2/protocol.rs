@@ -15,6 +15,7 @@ pub fn query_with_timeout(
 ) -> GDResult<Response> {
     let addr = &SocketAddr::new(*address, port.unwrap_or(11235));
     let mut socket = UdpSocket::new(addr, &timeout_settings)?;
+    socket.set_protocol_hint("savage2");
     socket.send(&[0x01])?;
     let data = socket.receive(None)?;
     let mut buffer = Buffer::<LittleEndian>::new(&data);
@@ -33,5 +34,6 @@ pub fn query_with_timeout(
         game_mode: buffer.read_string::<Utf8Decoder>(None)?,
         protocol_version: buffer.read_string::<Utf8Decoder>(None)?,
         level_minimum: buffer.read::<u8>()?,
+        ping: socket.ping(),
     })
 }
@@ -0,0 +1,121 @@
+//! Squad reports its current layer and teams in the rules, and the layer in
+//! its keywords too, see [query_layer](crate::games::squad::query_layer).
+//! Servers often don't answer the players request, the query then only has
+//! the server info (and `players_details` is empty).
+//!
+//! The query port is set by `QueryPort`, 27165 by default (the game port being
+//! 7787).
+
+use crate::protocols::valve::{game, keyword_value};
+use crate::GDResult;
+use std::net::IpAddr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Default Squad query port (the default game port being 7787).
+pub const DEFAULT_PORT: u16 = 27165;
+
+/// The query response.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// The valve response, the parsed rules are removed from its rules.
+    pub server: game::Response,
+    /// The layer being played (example: "Narva RAAS v1").
+    pub layer: Option<String>,
+    /// The game mode of the layer (example: "RAAS").
+    pub game_mode: Option<String>,
+    /// The faction of the first team.
+    pub team_one: Option<String>,
+    /// The faction of the second team.
+    pub team_two: Option<String>,
+}
+
+impl Response {
+    /// Extract the layer and teams from the rules (or the keywords).
+    pub fn from_valve_response(mut server: game::Response) -> Self {
+        let layer = server.rules.remove("MapName_s").or_else(|| {
+            server
+                .keywords
+                .as_deref()
+                .and_then(|keywords| keyword_value(keywords, "layer"))
+                .map(str::to_string)
+        });
+
+        Self {
+            layer,
+            game_mode: server.rules.remove("GameMode_s"),
+            team_one: server.rules.remove("TeamOne_s"),
+            team_two: server.rules.remove("TeamTwo_s"),
+            server,
+        }
+    }
+}
+
+pub use crate::games::valve::squad::{query, query_game_port};
+
+/// Same as [query], the layer and teams being parsed out of the response.
+pub fn query_layer(address: &IpAddr, port: Option<u16>) -> GDResult<Response> {
+    Ok(Response::from_valve_response(query(address, port)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::valve::{test_server_info, ServerInfo};
+
+    fn valve_response(keywords: &str, rules: &[(&str, &str)]) -> game::Response {
+        let info = ServerInfo {
+            map: "Narva".to_string(),
+            appid: 393_380,
+            players_online: 40,
+            ..test_server_info(keywords)
+        };
+        let rules = rules
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        // The players request went unanswered.
+        game::Response::from_parts(info, None, Some(rules))
+    }
+
+    #[cfg(feature = "game_defs")]
+    #[test]
+    fn definition_has_the_query_port() {
+        let game = crate::games::GAMES.get("squad").unwrap();
+
+        assert_eq!(game.default_port, DEFAULT_PORT);
+        assert_eq!(game.query_port_for(7787), DEFAULT_PORT);
+    }
+
+    #[test]
+    fn parses_layer_and_teams() {
+        let response = Response::from_valve_response(valve_response(
+            "layer:Narva RAAS v1",
+            &[
+                ("GameMode_s", "RAAS"),
+                ("TeamOne_s", "RGF"),
+                ("TeamTwo_s", "USA"),
+                ("PLAYERCOUNT_I", "40"),
+            ],
+        ));
+
+        assert_eq!(response.layer.as_deref(), Some("Narva RAAS v1"));
+        assert_eq!(response.game_mode.as_deref(), Some("RAAS"));
+        assert_eq!(response.team_one.as_deref(), Some("RGF"));
+        assert_eq!(response.team_two.as_deref(), Some("USA"));
+        assert_eq!(response.server.rules.len(), 1);
+        assert!(response.server.players_details.is_empty());
+        assert_eq!(response.server.players_online, 40);
+
+        // The rules name the layer over the keywords.
+        let response = Response::from_valve_response(valve_response(
+            "layer:Narva RAAS v1",
+            &[("MapName_s", "Narva AAS v2")],
+        ));
+        assert_eq!(response.layer.as_deref(), Some("Narva AAS v2"));
+        assert!(response.server.rules.is_empty());
+    }
+}
@@ -18,3 +18,15 @@ pub struct Game {
     /// Request settings.
     pub request_settings: ExtraRequestSettings,
 }
+
+impl Game {
+    /// Get the query port of a server of this game given its game port, see
+    /// [Engine::query_port_for](crate::protocols::valve::Engine::query_port_for),
+    /// the games of other protocols being queried on their game port.
+    pub fn query_port_for(&self, game_port: u16) -> u16 {
+        match &self.protocol {
+            Protocol::Valve(engine) => engine.query_port_for(game_port),
+            _ => game_port,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! Unturned lists its settings in its keywords, as comma separated tags:
+//! `PVP` or `PVE`, and `key:value` pairs for the map (`map`), the game mode
+//! (`gamemode`) and the workshop file ids the server requires (`workshop`,
+//! `;` separated), see
+//! [query_settings](crate::games::unturned::query_settings).
+//!
+//! The server answers queries on its game port plus one. Servers hiding their
+//! players answer the players request with nameless entries, these are left
+//! out of `players_details` (`players_online` still counts them).
+
+use crate::protocols::valve::{game, keyword_value};
+use crate::GDResult;
+use std::net::IpAddr;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Default Unturned query port (the default game port being 27015).
+pub const DEFAULT_PORT: u16 = 27016;
+
+/// The query response.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// The valve response.
+    pub server: game::Response,
+    /// The map being played, the info's map if it isn't in the keywords.
+    pub map: String,
+    /// The game mode (example: "Normal").
+    pub game_mode: Option<String>,
+    /// Whether players can hurt each other, `None` if the keywords don't tell.
+    pub pvp: Option<bool>,
+    /// The ids of the workshop files the server requires.
+    pub workshop_file_ids: Vec<u64>,
+}
+
+impl Response {
+    /// Extract the map, game mode, PvP flag and workshop files from the
+    /// keywords.
+    pub fn from_valve_response(mut server: game::Response) -> Self {
+        let keywords = server.keywords.as_deref().unwrap_or_default();
+        let pvp = keywords.split(',').find_map(|tag| {
+            match tag.trim().to_ascii_uppercase().as_str() {
+                "PVP" => Some(true),
+                "PVE" => Some(false),
+                _ => None,
+            }
+        });
+        let workshop_file_ids = keyword_value(keywords, "workshop")
+            .map(|ids| ids.split(';').filter_map(|id| id.trim().parse().ok()).collect())
+            .unwrap_or_default();
+
+        let map = keyword_value(keywords, "map")
+            .unwrap_or(&server.map)
+            .to_string();
+        let game_mode = keyword_value(keywords, "gamemode").map(str::to_string);
+
+        server
+            .players_details
+            .retain(|player| !player.name.trim().is_empty());
+
+        Self {
+            map,
+            game_mode,
+            pvp,
+            workshop_file_ids,
+            server,
+        }
+    }
+}
+
+pub use crate::games::valve::unturned::{query, query_game_port};
+
+/// Same as [query], the settings being parsed out of the keywords (and the
+/// nameless players left out).
+pub fn query_settings(address: &IpAddr, port: Option<u16>) -> GDResult<Response> {
+    Ok(Response::from_valve_response(query(address, port)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::valve::{test_server_info, test_server_player, ServerInfo};
+
+    fn valve_response(keywords: &str, players: &[&str]) -> game::Response {
+        let info = ServerInfo {
+            map: "PEI".to_string(),
+            appid: 304_930,
+            players_online: players.len() as u8,
+            ..test_server_info(keywords)
+        };
+        let players = players.iter().map(|name| test_server_player(name)).collect();
+
+        game::Response::from_parts(info, Some(players), None)
+    }
+
+    #[test]
+    fn parses_keywords() {
+        let response = Response::from_valve_response(valve_response(
+            "PVE,gamemode:Normal,map:Washington,workshop:1753134636;2136497468;oops",
+            &["alice"],
+        ));
+
+        assert_eq!(response.pvp, Some(false));
+        assert_eq!(response.game_mode.as_deref(), Some("Normal"));
+        assert_eq!(response.map, "Washington");
+        assert_eq!(response.workshop_file_ids, [1_753_134_636, 2_136_497_468]);
+        assert_eq!(response.server.players_details.len(), 1);
+    }
+
+    #[test]
+    fn hidden_players_are_left_out() {
+        let response = Response::from_valve_response(valve_response("pvp", &["", " ", "alice"]));
+
+        assert_eq!(response.pvp, Some(true));
+        assert_eq!(response.map, "PEI");
+        assert!(response.workshop_file_ids.is_empty());
+        assert_eq!(response.server.players_online, 3);
+        assert_eq!(response.server.players_details.len(), 1);
+    }
+}
@@ -21,6 +21,7 @@ game_query_mod!(
         players: GatherToggle::Enforce,
         rules: GatherToggle::Skip,
         check_app_id: true,
+        ..GatheringSettings::default()
     }
 );
 game_query_mod!(ase, "ARK: Survival Evolved", Engine::new(346_110), 27015);
@@ -47,6 +48,7 @@ game_query_mod!(
         players: GatherToggle::Enforce,
         rules: GatherToggle::Enforce,
         check_app_id: false,
+        ..GatheringSettings::default()
     }
 );
 game_query_mod!(
@@ -80,6 +82,7 @@ game_query_mod!(
         players: GatherToggle::Skip,
         rules: GatherToggle::Enforce,
         check_app_id: true,
+        ..GatheringSettings::default()
     }
 );
 game_query_mod!(
@@ -110,6 +113,12 @@ game_query_mod!(doi, "Day of Infamy", Engine::new(447_820), 27015);
 game_query_mod!(dst, "Don't Starve Together", Engine::new(322_320), 27016);
 game_query_mod!(enshrouded, "Enshrouded", Engine::new(1_203_620), 15637);
 game_query_mod!(garrysmod, "Garry's Mod", Engine::new(4000), 27016);
+game_query_mod!(
+    groundbranch,
+    "Ground Branch",
+    Engine::new_with_dedicated(16900, 476_400),
+    27015
+);
 game_query_mod!(hl2d, "Half-Life 2 Deathmatch", Engine::new(320), 27015);
 game_query_mod!(
     hlds,
@@ -147,6 +156,7 @@ game_query_mod!(
     Engine::new(108_600),
     16261
 );
+game_query_mod!(quakelive, "Quake Live", Engine::new(282_440), 27960);
 game_query_mod!(risingworld, "Rising World", Engine::new(324_080), 4254);
 game_query_mod!(ror2, "Risk of Rain 2", Engine::new(632_360), 27016);
 game_query_mod!(rust, "Rust", Engine::new(252_490), 27015);
@@ -163,7 +173,7 @@ game_query_mod!(
 );
 game_query_mod!(theforest, "The Forest", Engine::new(556_450), 27016);
 game_query_mod!(thefront, "The Front", Engine::new(2_285_150), 27015);
-game_query_mod!(unturned, "Unturned", Engine::new(304_930), 27015);
+game_query_mod!(unturned, "Unturned", Engine::new(304_930), 27016);
 game_query_mod!(
     valheim,
     "Valheim",
@@ -173,6 +183,7 @@ game_query_mod!(
         players: GatherToggle::Enforce,
         rules: GatherToggle::Skip,
         check_app_id: true,
+        ..GatheringSettings::default()
     }
 );
 game_query_mod!(vrising, "V Rising", Engine::new(1_604_030), 27016);
@@ -0,0 +1,42 @@
+//! Vintage Story servers expose their status as a JSON document over HTTP.
+
+use crate::protocols::http_json::{self, JsonPaths};
+use crate::{GDResult, TimeoutSettings};
+use std::net::{IpAddr, SocketAddr};
+
+/// Default Vintage Story server port.
+pub const DEFAULT_PORT: u16 = 42420;
+
+/// Where the status document is served.
+const STATUS_PATH: &str = "/status";
+
+/// Fields of the status document.
+pub const JSON_PATHS: JsonPaths<'static> = JsonPaths {
+    name: Some("/serverName"),
+    description: Some("/gameDescription"),
+    game_mode: Some("/playstyle/langCode"),
+    game_version: Some("/gameVersion"),
+    map: None,
+    players_online: Some("/players"),
+    players_maximum: Some("/maxPlayers"),
+    has_password: Some("/hasPassword"),
+    players: Some("/playerList"),
+    player_name: Some("/name"),
+};
+
+pub fn query(address: &IpAddr, port: Option<u16>) -> GDResult<http_json::Response> {
+    query_with_timeout(address, port, &None)
+}
+
+pub fn query_with_timeout(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: &Option<TimeoutSettings>,
+) -> GDResult<http_json::Response> {
+    http_json::query(
+        &SocketAddr::new(*address, port.unwrap_or(DEFAULT_PORT)),
+        STATUS_PATH,
+        &JSON_PATHS,
+        timeout_settings,
+    )
+}
@@ -0,0 +1,127 @@
+//! Wreckfest server query.
+//!
+//! The server answers what it calls a GameSpy 4 query, which is a
+//! [single packet](crate::protocols::gamespy::three::query_single_packet)
+//! GameSpy 3 one, the race specific fields (the track and the number of laps)
+//! being sent along the other server variables.
+
+use crate::protocols::gamespy::three;
+use crate::protocols::types::{CommonPlayer, CommonResponse, TimeoutSettings};
+use crate::protocols::GenericResponse;
+use crate::GDErrorKind::TypeParse;
+use crate::GDResult;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Default Wreckfest query port.
+pub const DEFAULT_PORT: u16 = 27016;
+
+/// The query response.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// The GameSpy response, without the race specific variables.
+    pub server: three::Response,
+    /// The track being raced on, `None` if the server didn't report it.
+    pub track: Option<String>,
+    /// The number of laps of the race, `None` if the server didn't report it.
+    pub laps: Option<u32>,
+}
+
+impl Response {
+    /// Extract the race specific variables from a GameSpy response.
+    pub fn from_gamespy(mut server: three::Response) -> GDResult<Self> {
+        let track = server.unused_entries.remove("track");
+        let laps = match server.unused_entries.remove("laps") {
+            None => None,
+            Some(laps) => Some(laps.parse().map_err(|e| TypeParse.context(e))?),
+        };
+
+        Ok(Self { server, track, laps })
+    }
+}
+
+impl CommonResponse for Response {
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Wreckfest(self) }
+
+    fn name(&self) -> Option<&str> { Some(&self.server.name) }
+    fn map(&self) -> Option<&str> { Some(self.track.as_ref().unwrap_or(&self.server.map)) }
+    fn has_password(&self) -> Option<bool> { Some(self.server.has_password) }
+    fn game_mode(&self) -> Option<&str> { Some(&self.server.game_mode) }
+    fn game_version(&self) -> Option<&str> { Some(&self.server.game_version) }
+    fn players_maximum(&self) -> u32 { self.server.players_maximum }
+    fn players_online(&self) -> u32 { self.server.players_online }
+    fn ping(&self) -> Option<Duration> { self.server.ping }
+
+    fn players(&self) -> Option<Vec<&dyn CommonPlayer>> { self.server.players() }
+}
+
+pub fn query(address: &IpAddr, port: Option<u16>) -> GDResult<Response> { query_with_timeout(address, port, None) }
+
+pub fn query_with_timeout(
+    address: &IpAddr,
+    port: Option<u16>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let response = three::query_single_packet(
+        &SocketAddr::new(*address, port.unwrap_or(DEFAULT_PORT)),
+        timeout_settings,
+    )?;
+
+    Response::from_gamespy(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::gamespy::three::parse_response;
+
+    fn server_vars(extra: &[(&str, &str)]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (key, value) in [
+            ("hostname", "Demolition Derby"),
+            ("gamever", "1.288"),
+            ("gametype", "racing"),
+            ("mapname", "speedway2"),
+            ("numplayers", "0"),
+            ("maxplayers", "24"),
+            ("password", "0"),
+        ]
+        .iter()
+        .chain(extra)
+        {
+            data.extend_from_slice(key.as_bytes());
+            data.push(0);
+            data.extend_from_slice(value.as_bytes());
+            data.push(0);
+        }
+        data.push(0);
+
+        data
+    }
+
+    #[test]
+    fn parses_race_fields() {
+        let server = parse_response(&[server_vars(&[("track", "Speedway 2"), ("laps", "5")])]).unwrap();
+        let response = Response::from_gamespy(server).unwrap();
+
+        assert_eq!(response.track.as_deref(), Some("Speedway 2"));
+        assert_eq!(response.laps, Some(5));
+        assert_eq!(response.map(), Some("Speedway 2"));
+        assert_eq!(response.players_maximum(), 24);
+        assert!(response.server.unused_entries.is_empty());
+    }
+
+    #[test]
+    fn race_fields_are_optional() {
+        let server = parse_response(&[server_vars(&[])]).unwrap();
+        let response = Response::from_gamespy(server).unwrap();
+
+        assert_eq!(response.track, None);
+        assert_eq!(response.laps, None);
+        assert_eq!(response.map(), Some("speedway2"));
+    }
+}
@@ -8,10 +8,11 @@
 #![allow(dead_code)]
 
 use crate::GDErrorKind::{HostLookup, InvalidInput, PacketReceive, PacketSend, ProtocolFormat};
-use crate::{GDResult, TimeoutSettings};
+use crate::{socket::timeout_before, GDResult, TimeoutSettings};
 
 use std::io::Read;
 use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
+use std::time::Instant;
 
 use ureq::{Agent, AgentBuilder, Request};
 use url::{Host, Url};
@@ -35,6 +36,8 @@ pub struct HttpClient {
     client: Agent,
     address: Url,
     headers: Vec<(String, String)>,
+    /// When the requests stop waiting for the server.
+    deadline: Option<Instant>,
 }
 
 /// HttpHeaders for use with a single request.
@@ -181,6 +184,7 @@ impl HttpClient {
                 .into_iter()
                 .map(|(k, v)| (k.into(), v.into()))
                 .collect(),
+            deadline: TimeoutSettings::get_deadline_end_or_default(timeout_settings),
         })
     }
 
@@ -283,46 +287,15 @@ impl HttpClient {
     /// Internal request method, makes a request with an arbitrary HTTP method.
     #[inline]
     fn request(&mut self, method: &str, path: &str, headers: HttpHeaders) -> GDResult<Vec<u8>> {
-        // Append the path to the pre-parsed URL and create a request object.
-        self.address.set_path(path);
-        let request = self.make_request(method, headers);
-
-        // Send the request.
-        let http_response = request.call().map_err(|e| PacketSend.context(e))?;
-
-        let length = if let Some(length) = http_response.header("Content-Length") {
-            length
-                .parse::<usize>()
-                .map_err(|e| ProtocolFormat.context(e))?
-                .min(MAX_RESPONSE_LENGTH)
-        } else {
-            5012 // Sensible default allocation
-        };
-
-        let mut buffer: Vec<u8> = Vec::with_capacity(length);
-
-        let _ = http_response
-            .into_reader()
-            .take(MAX_RESPONSE_LENGTH as u64)
-            .read_to_end(&mut buffer)
-            .map_err(|e| PacketReceive.context(e))?;
-
-        Ok(buffer)
+        self.exchange(method, path, headers, RequestBody::Empty)
     }
 
     /// Send a HTTP request without any data and parse the JSON response.
     #[inline]
     fn request_json<T: DeserializeOwned>(&mut self, method: &str, path: &str, headers: HttpHeaders) -> GDResult<T> {
-        // Append the path to the pre-parsed URL and create a request object.
-        self.address.set_path(path);
-        let request = self.make_request(method, headers);
+        let data = self.request(method, path, headers)?;
 
-        // Send the request and parse the response as JSON.
-        request
-            .call()
-            .map_err(|e| PacketSend.context(e))?
-            .into_json::<T>()
-            .map_err(|e| ProtocolFormat.context(e))
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
     }
 
     /// Send a HTTP request with JSON data and parse the JSON response.
@@ -334,14 +307,10 @@ impl HttpClient {
         headers: HttpHeaders,
         data: S,
     ) -> GDResult<T> {
-        self.address.set_path(path);
-        let request = self.make_request(method, headers);
+        let body = serde_json::to_vec(&data).map_err(|e| InvalidInput.context(e))?;
+        let data = self.exchange(method, path, headers, RequestBody::Json(body))?;
 
-        request
-            .send_json(data)
-            .map_err(|e| PacketSend.context(e))?
-            .into_json::<T>()
-            .map_err(|e| ProtocolFormat.context(e))
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
     }
 
     /// Send a HTTP request with FORM data and parse the JSON response.
@@ -353,14 +322,112 @@ impl HttpClient {
         headers: HttpHeaders,
         data: &[(&str, &str)],
     ) -> GDResult<T> {
+        let body = url::form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(data)
+            .finish();
+        let data = self.exchange(method, path, headers, RequestBody::Form(body))?;
+
+        serde_json::from_slice(&data).map_err(|e| ProtocolFormat.context(e))
+    }
+
+    /// Send a request with its body and read the response's, recording the
+    /// exchange if an HTTP capture was set up.
+    fn exchange(&mut self, method: &str, path: &str, headers: HttpHeaders, body: RequestBody) -> GDResult<Vec<u8>> {
+        // Append the path to the pre-parsed URL and create a request object.
         self.address.set_path(path);
-        let request = self.make_request(method, headers);
+        let mut request = self.make_request(method, headers);
+        // Each request gets what is left of the deadline.
+        if let Some(timeout) = timeout_before(None, self.deadline).map_err(|e| PacketSend.context(e))? {
+            request = request.timeout(timeout);
+        }
 
-        request
-            .send_form(data)
-            .map_err(|e| PacketSend.context(e))?
-            .into_json::<T>()
-            .map_err(|e| ProtocolFormat.context(e))
+        if let Some(content_type) = body.content_type() {
+            if request.header("Content-Type").is_none() {
+                request = request.set("Content-Type", content_type);
+            }
+        }
+
+        #[cfg(feature = "packet_capture")]
+        let mut capture = crate::capture::har::HttpExchange::begin(&request, body.data());
+
+        // Send the request.
+        let sent = match body.data() {
+            None => request.call(),
+            Some(data) => request.send_bytes(data),
+        };
+        let http_response = match sent {
+            Ok(http_response) => http_response,
+            // Error statuses are captured too, before failing.
+            #[cfg(feature = "packet_capture")]
+            Err(ureq::Error::Status(status, http_response)) if capture.is_some() => {
+                let mut capture = capture.take().unwrap();
+                capture.respond(&http_response);
+                capture.record(&Self::read_response(http_response)?);
+
+                return Err(PacketSend.context(format!("Status code {status}")));
+            }
+            Err(e) => return Err(PacketSend.context(e)),
+        };
+
+        #[cfg(feature = "packet_capture")]
+        if let Some(capture) = capture.as_mut() {
+            capture.respond(&http_response);
+        }
+
+        let data = Self::read_response(http_response)?;
+
+        #[cfg(feature = "packet_capture")]
+        if let Some(capture) = capture {
+            capture.record(&data);
+        }
+
+        Ok(data)
+    }
+
+    fn read_response(http_response: ureq::Response) -> GDResult<Vec<u8>> {
+        let length = if let Some(length) = http_response.header("Content-Length") {
+            length
+                .parse::<usize>()
+                .map_err(|e| ProtocolFormat.context(e))?
+                .min(MAX_RESPONSE_LENGTH)
+        } else {
+            5012 // Sensible default allocation
+        };
+
+        let mut buffer: Vec<u8> = Vec::with_capacity(length);
+
+        let _ = http_response
+            .into_reader()
+            .take(MAX_RESPONSE_LENGTH as u64)
+            .read_to_end(&mut buffer)
+            .map_err(|e| PacketReceive.context(e))?;
+
+        Ok(buffer)
+    }
+}
+
+/// The data sent along a request.
+enum RequestBody {
+    Empty,
+    Json(Vec<u8>),
+    Form(String),
+}
+
+impl RequestBody {
+    fn data(&self) -> Option<&[u8]> {
+        match self {
+            Self::Empty => None,
+            Self::Json(data) => Some(data),
+            Self::Form(data) => Some(data.as_bytes()),
+        }
+    }
+
+    const fn content_type(&self) -> Option<&'static str> {
+        match self {
+            Self::Empty => None,
+            Self::Json(_) => Some("application/json"),
+            Self::Form(_) => Some("application/x-www-form-urlencoded"),
+        }
     }
 }
 
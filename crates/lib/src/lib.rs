@@ -47,6 +47,7 @@ pub mod services;
 mod buffer;
 mod http;
 mod socket;
+pub mod transport;
 mod utils;
 
 #[cfg(feature = "packet_capture")]
@@ -18,7 +18,7 @@ pub struct Response {
 }
 
 impl CommonResponse for Response {
-    fn as_original(&self) -> GenericResponse { GenericResponse::Epic(self) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Epic(self) }
     fn name(&self) -> Option<&str> { Some(&self.name) }
     fn map(&self) -> Option<&str> { Some(&self.map) }
     fn players_maximum(&self) -> u32 { self.players_maxmimum }
@@ -46,7 +46,7 @@ pub struct Player {
 }
 
 impl CommonPlayer for Player {
-    fn as_original(&self) -> GenericPlayer { GenericPlayer::Epic(self) }
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::Epic(self) }
 
     fn name(&self) -> &str { &self.name }
 }
@@ -17,18 +17,23 @@ use crate::{
 };
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Duration;
 
-/// Send status request, and parse response into HashMap.
+/// Send status request, and parse response into HashMap, along with the
+/// socket's ping.
 /// This function will retry fetch on timeouts.
 fn get_server_values(
     address: &SocketAddr,
     timeout_settings: &Option<TimeoutSettings>,
-) -> GDResult<HashMap<String, String>> {
+) -> GDResult<(HashMap<String, String>, Option<Duration>)> {
     let mut socket = UdpSocket::new(address, timeout_settings)?;
-    retry_on_timeout(
+    socket.set_protocol_hint("gamespy1");
+    let server_values = retry_on_timeout(
         TimeoutSettings::get_retries_or_default(timeout_settings),
-        move || get_server_values_impl(&mut socket),
-    )
+        || get_server_values_impl(&mut socket),
+    )?;
+
+    Ok((server_values, socket.ping()))
 }
 
 /// Send status request, and parse response into HashMap (without retry logic).
@@ -188,14 +193,14 @@ pub fn query_vars(
     address: &SocketAddr,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<HashMap<String, String>> {
-    get_server_values(address, &timeout_settings)
+    get_server_values(address, &timeout_settings).map(|(server_values, _)| server_values)
 }
 
 /// Query a server by providing the address, the port and timeout settings.
 /// Providing None to the timeout settings results in using the default values.
 /// (TimeoutSettings::[default](TimeoutSettings::default)).
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
-    let mut server_vars = query_vars(address, timeout_settings)?;
+    let (mut server_vars, ping) = get_server_values(address, &timeout_settings)?;
 
     let players_maximum: u32 = server_vars
         .remove("maxplayers")
@@ -239,5 +244,6 @@ pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) ->
             .parse()
             .map_err(|e| TypeParse.context(e))?,
         unused_entries: server_vars,
+        ping,
     })
 }
@@ -10,6 +10,7 @@ use crate::GDErrorKind::{PacketBad, TypeParse};
 use crate::{GDErrorKind, GDResult};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 const THIS_SESSION_ID: u32 = 1;
 
@@ -52,7 +53,8 @@ const DEFAULT_PAYLOAD: [u8; 4] = [0xFF, 0xFF, 0xFF, 0x01];
 
 impl GameSpy3 {
     fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = UdpSocket::new(address, &timeout_settings)?;
+        let mut socket = UdpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("gamespy3");
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
 
         Ok(Self {
@@ -69,7 +71,8 @@ impl GameSpy3 {
         payload: [u8; 4],
         single_packets: bool,
     ) -> GDResult<Self> {
-        let socket = UdpSocket::new(address, &timeout_settings)?;
+        let mut socket = UdpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("gamespy3");
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
 
         Ok(Self {
@@ -80,6 +83,9 @@ impl GameSpy3 {
         })
     }
 
+    /// The round trip time of the first request, see [Socket::ping].
+    pub(crate) fn ping(&self) -> Option<Duration> { self.socket.ping() }
+
     fn receive(&mut self, size: Option<usize>, kind: u8) -> GDResult<Vec<u8>> {
         let received = self.socket.receive(size.or(Some(PACKET_SIZE)))?;
         let mut buf = Buffer::<BigEndian>::new(&received);
@@ -145,11 +151,12 @@ impl GameSpy3 {
         let challenge = self.make_initial_handshake()?;
         self.send_data_request(challenge)?;
 
-        let mut values: Vec<Vec<u8>> = Vec::new();
-
-        let mut reached_expected_packets_size = false;
+        // The packets can arrive in any order, the one flagged as the last
+        // telling how many of them there are.
+        let mut values: Vec<Option<Vec<u8>>> = Vec::new();
+        let mut expected_packets = None;
 
-        while !reached_expected_packets_size {
+        loop {
             let received_data = self.receive(None, 0)?;
             let mut buf = Buffer::<BigEndian>::new(&received_data);
 
@@ -167,22 +174,30 @@ impl GameSpy3 {
             let packet_id = (id & 0x7f) as usize;
             buf.move_cursor(1)?; //unknown byte regarding packet no.
 
-            if is_last && packet_id + 1 != values.len() {
-                reached_expected_packets_size = true;
+            if is_last {
+                expected_packets = Some(packet_id + 1);
             }
 
             while values.len() <= packet_id {
-                values.push(Vec::new());
+                values.push(None);
             }
 
-            values[packet_id] = buf.remaining_bytes().to_vec();
-        }
+            values[packet_id] = Some(buf.remaining_bytes().to_vec());
 
-        if values.iter().any(Vec::is_empty) {
-            return Err(PacketBad.context("One (or more) packets is empty"));
+            if let Some(expected_packets) = expected_packets {
+                if values.len() > expected_packets {
+                    return Err(PacketBad.context(format!(
+                        "Got a packet past the last one ({expected_packets} expected)"
+                    )));
+                }
+
+                if values.iter().all(Option::is_some) {
+                    break;
+                }
+            }
         }
 
-        Ok(values)
+        Ok(values.into_iter().flatten().collect())
     }
 }
 
@@ -354,6 +369,30 @@ pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) ->
     let mut client = GameSpy3::new(address, timeout_settings)?;
     let packets = client.get_server_packets()?;
 
+    Ok(Response {
+        ping: client.ping(),
+        ..parse_response(&packets)?
+    })
+}
+
+/// Same as [query], for the servers answering in a single packet (that still
+/// begins with the `splitnum` padding), like the Wreckfest ones.
+///
+/// What these games call GameSpy 4 is this variant: the handshake, the
+/// challenge and the key-value layout are GameSpy 3's.
+pub fn query_single_packet(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response> {
+    let mut client = GameSpy3::new_custom(address, timeout_settings, DEFAULT_PAYLOAD, true)?;
+    let packets = client.get_server_packets()?;
+
+    Ok(Response {
+        ping: client.ping(),
+        ..parse_response(&packets)?
+    })
+}
+
+/// Parse the (reassembled) packets of a reply, the first one starting with
+/// the server variables, the ping being left unknown.
+pub(crate) fn parse_response(packets: &[Vec<u8>]) -> GDResult<Response> {
     let (mut server_vars, remaining_data) = data_to_map(packets.first().ok_or(GDErrorKind::PacketBad)?)?;
 
     let mut remaining_data_packets = vec![remaining_data];
@@ -406,5 +445,117 @@ pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) ->
             .parse()
             .map_err(|e| TypeParse.context(e))?,
         unused_entries: server_vars,
+        ping: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::types::CommonResponse;
+    use std::net::UdpSocket as StdUdpSocket;
+    use std::thread;
+
+    /// A reply packet, `id` being the split number (with the last packet
+    /// flag).
+    fn split_packet(id: u8, content: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x00];
+        packet.extend_from_slice(&THIS_SESSION_ID.to_be_bytes());
+        packet.extend_from_slice(b"splitnum\0");
+        packet.extend_from_slice(&[id, 0x00]);
+        packet.extend_from_slice(content);
+        packet
+    }
+
+    /// A server answering the handshake after `delay`, then the data request
+    /// with `packets`.
+    fn spawn_server(packets: Vec<Vec<u8>>, delay: Duration) -> SocketAddr {
+        let server = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = [0; 64];
+            let (_, client) = server.recv_from(&mut buf).unwrap();
+            thread::sleep(delay);
+            let mut challenge = vec![0x09];
+            challenge.extend_from_slice(&THIS_SESSION_ID.to_be_bytes());
+            challenge.extend_from_slice(b"1234\0");
+            server.send_to(&challenge, client).unwrap();
+
+            server.recv_from(&mut buf).unwrap();
+            for packet in packets {
+                server.send_to(&packet, client).unwrap();
+            }
+        });
+
+        address
+    }
+
+    fn query_server(packets: Vec<Vec<u8>>, single_packets: bool) -> GDResult<Vec<Vec<u8>>> {
+        let address = spawn_server(packets, Duration::ZERO);
+        let timeout_settings = TimeoutSettings::new(Some(Duration::from_millis(500)), None, None, 0).ok();
+        GameSpy3::new_custom(&address, timeout_settings, DEFAULT_PAYLOAD, single_packets)?.get_server_packets()
+    }
+
+    #[test]
+    fn split_packets_are_put_back_in_order() {
+        let packets = query_server(
+            vec![
+                split_packet(0x82, b"third"),
+                split_packet(0x00, b"first"),
+                split_packet(0x01, b"second"),
+            ],
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(packets, [&b"first"[..], b"second", b"third"]);
+    }
+
+    #[test]
+    fn packets_after_the_last_one_are_rejected() {
+        let error = query_server(
+            vec![split_packet(0x02, b"third"), split_packet(0x81, b"second")],
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(error.kind, PacketBad);
+    }
+
+    #[test]
+    fn single_packets_are_taken_whole() {
+        // The first packet is the whole reply, whatever its split number.
+        let packets = query_server(vec![split_packet(0x01, b"whole"), split_packet(0x80, b"ignored")], true).unwrap();
+
+        assert_eq!(packets, [&b"whole"[..]]);
+    }
+
+    #[test]
+    fn query_measures_the_ping() {
+        const DELAY: Duration = Duration::from_millis(100);
+
+        let mut server_vars = Vec::new();
+        for (key, value) in [
+            ("hostname", "Server"),
+            ("mapname", "map"),
+            ("gametype", "dm"),
+            ("gamever", "1.0"),
+            ("maxplayers", "8"),
+            ("password", "0"),
+        ] {
+            server_vars.extend_from_slice(key.as_bytes());
+            server_vars.push(0);
+            server_vars.extend_from_slice(value.as_bytes());
+            server_vars.push(0);
+        }
+        server_vars.push(0);
+
+        let address = spawn_server(vec![split_packet(0x80, &server_vars)], DELAY);
+        let timeout_settings = TimeoutSettings::new(Some(Duration::from_millis(500)), None, None, 0).ok();
+        let response = query_single_packet(&address, timeout_settings).unwrap();
+
+        // Measured from the handshake, the first request.
+        let ping = response.ping.unwrap();
+        assert!(ping >= DELAY && ping < Duration::from_millis(500));
+        assert_eq!(response.as_json().ping, Some(ping));
+    }
+}
@@ -2,6 +2,7 @@ use crate::protocols::gamespy::{VersionedPlayer, VersionedResponse};
 use crate::protocols::types::{CommonPlayer, CommonResponse, GenericPlayer};
 use crate::protocols::GenericResponse;
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -19,7 +20,7 @@ pub struct Player {
 }
 
 impl CommonPlayer for Player {
-    fn as_original(&self) -> crate::protocols::types::GenericPlayer {
+    fn as_original(&self) -> crate::protocols::types::GenericPlayer<'_> {
         GenericPlayer::Gamespy(VersionedPlayer::Three(self))
     }
 
@@ -51,10 +52,14 @@ pub struct Response {
     pub teams: Vec<Team>,
     pub tournament: bool,
     pub unused_entries: HashMap<String, String>,
+    /// The round trip time of the first request (to the first packet of its
+    /// reply), `None` if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ping: Option<Duration>,
 }
 
 impl CommonResponse for Response {
-    fn as_original(&self) -> GenericResponse { GenericResponse::GameSpy(VersionedResponse::Three(self)) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::GameSpy(VersionedResponse::Three(self)) }
 
     fn name(&self) -> Option<&str> { Some(&self.name) }
     fn map(&self) -> Option<&str> { Some(&self.map) }
@@ -63,6 +68,7 @@ impl CommonResponse for Response {
     fn game_version(&self) -> Option<&str> { Some(&self.game_version) }
     fn players_maximum(&self) -> u32 { self.players_maximum }
     fn players_online(&self) -> u32 { self.players_online }
+    fn ping(&self) -> Option<Duration> { self.ping }
 
     fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
         Some(
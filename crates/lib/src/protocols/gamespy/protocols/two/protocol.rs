@@ -79,7 +79,8 @@ fn data_as_table(data: &mut Buffer<BigEndian>) -> GDResult<(HashMap<String, Vec<
 
 impl GameSpy2 {
     fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = UdpSocket::new(address, &timeout_settings)?;
+        let mut socket = UdpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("gamespy2");
         let retry_count = TimeoutSettings::get_retries_or_default(&timeout_settings);
 
         Ok(Self {
@@ -206,5 +207,6 @@ pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) ->
         players_minimum,
         players,
         unused_entries: server_vars,
+        ping: client.socket.ping(),
     })
 }
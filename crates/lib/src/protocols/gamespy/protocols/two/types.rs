@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use crate::protocols::gamespy::{VersionedPlayer, VersionedResponse};
 use crate::protocols::types::{CommonPlayer, CommonResponse, GenericPlayer};
@@ -24,7 +25,7 @@ pub struct Player {
 }
 
 impl CommonPlayer for Player {
-    fn as_original(&self) -> GenericPlayer { GenericPlayer::Gamespy(VersionedPlayer::Two(self)) }
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::Gamespy(VersionedPlayer::Two(self)) }
 
     fn name(&self) -> &str { &self.name }
     fn score(&self) -> Option<i32> { Some(self.score.into()) }
@@ -42,16 +43,21 @@ pub struct Response {
     pub players_minimum: Option<u32>,
     pub players: Vec<Player>,
     pub unused_entries: HashMap<String, String>,
+    /// The round trip time of the request (to the first packet of its reply),
+    /// `None` if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ping: Option<Duration>,
 }
 
 impl CommonResponse for Response {
-    fn as_original(&self) -> GenericResponse { GenericResponse::GameSpy(VersionedResponse::Two(self)) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::GameSpy(VersionedResponse::Two(self)) }
 
     fn name(&self) -> Option<&str> { Some(&self.name) }
     fn map(&self) -> Option<&str> { Some(&self.map) }
     fn has_password(&self) -> Option<bool> { Some(self.has_password) }
     fn players_maximum(&self) -> u32 { self.players_maximum }
     fn players_online(&self) -> u32 { self.players_online }
+    fn ping(&self) -> Option<Duration> { self.ping }
 
     fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
         Some(
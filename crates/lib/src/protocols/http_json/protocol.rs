@@ -0,0 +1,155 @@
+use crate::http::{HttpClient, HttpSettings};
+use crate::protocols::http_json::types::{JsonPaths, Player, Response};
+use crate::{GDErrorKind::TypeParse, GDResult, TimeoutSettings};
+
+use serde_json::Value;
+use std::net::SocketAddr;
+
+/// Get the value at `path` as a string, numbers and booleans are converted.
+fn get_string(document: &Value, path: Option<&str>) -> Option<String> {
+    match document.pointer(path?)? {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Get the value at `path` as a number, numeric strings are parsed.
+fn get_number(document: &Value, path: Option<&str>) -> GDResult<Option<u32>> {
+    let value = match path.and_then(|path| document.pointer(path)) {
+        None | Some(Value::Null) => return Ok(None),
+        Some(value) => value,
+    };
+
+    match value {
+        Value::Number(number) => {
+            number
+                .as_u64()
+                .and_then(|number| u32::try_from(number).ok())
+                .map(Some)
+                .ok_or_else(|| TypeParse.context(format!("{} is not a valid count", number)))
+        }
+        Value::String(string) => string.parse().map(Some).map_err(|e| TypeParse.context(e)),
+        _ => Err(TypeParse.context(format!("{} is not a number", value))),
+    }
+}
+
+/// Get the value at `path` as a boolean, 0/1 numbers are accepted.
+fn get_bool(document: &Value, path: Option<&str>) -> Option<bool> {
+    match document.pointer(path?)? {
+        Value::Bool(value) => Some(*value),
+        Value::Number(value) => value.as_u64().map(|value| value != 0),
+        _ => None,
+    }
+}
+
+/// Extract the configured fields out of a JSON document.
+pub fn parse(document: Value, paths: &JsonPaths) -> GDResult<Response> {
+    let players = paths
+        .players
+        .and_then(|path| document.pointer(path))
+        .and_then(Value::as_array)
+        .map(|players| {
+            players
+                .iter()
+                // An empty pointer refers to the entry itself.
+                .filter_map(|player| get_string(player, Some(paths.player_name.unwrap_or(""))))
+                .map(|name| Player { name })
+                .collect::<Vec<_>>()
+        });
+
+    let players_online = match get_number(&document, paths.players_online)? {
+        Some(online) => online,
+        None => players.as_ref().map_or(0, |players| players.len() as u32),
+    };
+
+    Ok(Response {
+        name: get_string(&document, paths.name),
+        description: get_string(&document, paths.description),
+        game_mode: get_string(&document, paths.game_mode),
+        game_version: get_string(&document, paths.game_version),
+        map: get_string(&document, paths.map),
+        players_online,
+        players_maximum: get_number(&document, paths.players_maximum)?.unwrap_or(0),
+        has_password: get_bool(&document, paths.has_password),
+        players,
+        raw: document,
+    })
+}
+
+/// Fetch the JSON document at `path` over HTTP and extract the configured
+/// fields.
+pub fn query(
+    address: &SocketAddr,
+    path: &str,
+    paths: &JsonPaths,
+    timeout_settings: &Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let mut client = HttpClient::new(address, timeout_settings, HttpSettings::<String>::default())?;
+
+    parse(client.get_json::<Value>(path, None)?, paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PATHS: JsonPaths = JsonPaths {
+        name: Some("/server/name"),
+        description: None,
+        game_mode: Some("/mode"),
+        game_version: Some("/version"),
+        map: None,
+        players_online: Some("/players/online"),
+        players_maximum: Some("/players/max"),
+        has_password: Some("/password"),
+        players: Some("/players/list"),
+        player_name: Some("/name"),
+    };
+
+    #[test]
+    fn extracts_configured_paths() {
+        let response = parse(
+            json!({
+                "server": { "name": "gamedig" },
+                "mode": "survival",
+                "version": 1.19,
+                "password": 1,
+                "players": { "online": "2", "max": 16, "list": [{ "name": "alice" }, { "name": "bob" }] }
+            }),
+            &PATHS,
+        )
+        .unwrap();
+
+        assert_eq!(response.name.as_deref(), Some("gamedig"));
+        assert_eq!(response.game_mode.as_deref(), Some("survival"));
+        assert_eq!(response.game_version.as_deref(), Some("1.19"));
+        assert_eq!(response.description, None);
+        assert_eq!(response.has_password, Some(true));
+        assert_eq!(response.players_online, 2);
+        assert_eq!(response.players_maximum, 16);
+        assert_eq!(response.players.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn counts_players_when_online_is_missing() {
+        let paths = JsonPaths {
+            players_online: None,
+            players: Some("/players"),
+            player_name: None,
+            ..Default::default()
+        };
+
+        let response = parse(json!({ "players": ["alice", "bob", "carol"] }), &paths).unwrap();
+
+        assert_eq!(response.players_online, 3);
+        assert_eq!(response.players.unwrap()[2].name, "carol");
+    }
+
+    #[test]
+    fn rejects_invalid_counts() {
+        assert!(parse(json!({ "players": { "online": "many" } }), &PATHS).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::protocols::types::{CommonPlayer, CommonResponse, GenericPlayer};
+use crate::protocols::GenericResponse;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Where to find each field in the JSON document returned by a server, every
+/// path is a [JSON pointer](https://datatracker.ietf.org/doc/html/rfc6901)
+/// (e.g. `/server/name`), `None` meaning that the server doesn't provide it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JsonPaths<'a> {
+    pub name: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub game_mode: Option<&'a str>,
+    pub game_version: Option<&'a str>,
+    pub map: Option<&'a str>,
+    pub players_online: Option<&'a str>,
+    pub players_maximum: Option<&'a str>,
+    pub has_password: Option<&'a str>,
+    /// Path of the players array.
+    pub players: Option<&'a str>,
+    /// Path of the name inside of a player entry, `None` if the entries are
+    /// the names themselves.
+    pub player_name: Option<&'a str>,
+}
+
+/// A player extracted from the players array.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Player {
+    pub name: String,
+}
+
+impl CommonPlayer for Player {
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::HttpJson(self) }
+
+    fn name(&self) -> &str { &self.name }
+}
+
+/// The fields extracted from a JSON status document.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub game_mode: Option<String>,
+    pub game_version: Option<String>,
+    pub map: Option<String>,
+    /// Falls back to the number of players when not found.
+    pub players_online: u32,
+    pub players_maximum: u32,
+    pub has_password: Option<bool>,
+    pub players: Option<Vec<Player>>,
+    /// The whole document, for fields that aren't extracted.
+    pub raw: serde_json::Value,
+}
+
+impl CommonResponse for Response {
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::HttpJson(self) }
+
+    fn name(&self) -> Option<&str> { self.name.as_deref() }
+    fn description(&self) -> Option<&str> { self.description.as_deref() }
+    fn game_mode(&self) -> Option<&str> { self.game_mode.as_deref() }
+    fn game_version(&self) -> Option<&str> { self.game_version.as_deref() }
+    fn map(&self) -> Option<&str> { self.map.as_deref() }
+    fn players_maximum(&self) -> u32 { self.players_maximum }
+    fn players_online(&self) -> u32 { self.players_online }
+    fn has_password(&self) -> Option<bool> { self.has_password }
+
+    fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
+        self.players
+            .as_ref()
+            .map(|players| players.iter().map(|p| p as &dyn CommonPlayer).collect())
+    }
+}
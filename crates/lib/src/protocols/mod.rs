@@ -9,6 +9,9 @@
 pub mod epic;
 /// Reference: [node-GameDig](https://github.com/gamedig/node-gamedig/blob/master/protocols/gamespy1.js)
 pub mod gamespy;
+/// Generic querying of servers that expose their status as a JSON document
+/// over HTTP.
+pub mod http_json;
 /// Reference: [node-GameDig](https://github.com/gamedig/node-gamedig/blob/master/protocols/quake1.js)
 pub mod quake;
 /// General types that are used by all protocols.
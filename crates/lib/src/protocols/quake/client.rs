@@ -10,6 +10,11 @@ use crate::{GDErrorKind, GDResult};
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::slice::Iter;
+use std::time::Duration;
+
+/// The largest UDP payload, replies listing many players being larger than
+/// the usual packet size, they would otherwise be cut.
+const MAX_PACKET_SIZE: usize = 65_507;
 
 pub trait QuakeClient {
     type Player;
@@ -19,17 +24,20 @@ pub trait QuakeClient {
     fn parse_player_string(data: Iter<&str>) -> GDResult<Self::Player>;
 }
 
-/// Send request and return result buffer.
+/// Send request and return result buffer, along with the socket's ping.
 /// This function will retry fetch on timeouts.
 fn get_data<Client: QuakeClient>(
     address: &SocketAddr,
     timeout_settings: &Option<TimeoutSettings>,
-) -> GDResult<Vec<u8>> {
+) -> GDResult<(Vec<u8>, Option<Duration>)> {
     let mut socket = UdpSocket::new(address, timeout_settings)?;
-    retry_on_timeout(
+    socket.set_protocol_hint("quake");
+    let data = retry_on_timeout(
         TimeoutSettings::get_retries_or_default(timeout_settings),
-        move || get_data_impl::<Client>(&mut socket),
-    )
+        || get_data_impl::<Client>(&mut socket),
+    )?;
+
+    Ok((data, socket.ping()))
 }
 
 /// Send request and return result buffer (without retry logic).
@@ -43,7 +51,7 @@ fn get_data_impl<Client: QuakeClient>(socket: &mut UdpSocket) -> GDResult<Vec<u8
         .concat(),
     )?;
 
-    let data = socket.receive(None)?;
+    let data = socket.receive(Some(MAX_PACKET_SIZE))?;
     let mut bufferer = Buffer::<LittleEndian>::new(&data);
 
     if bufferer.read::<u32>()? != u32::MAX {
@@ -86,18 +94,52 @@ fn get_server_values(bufferer: &mut Buffer<LittleEndian>) -> GDResult<HashMap<St
     Ok(vars)
 }
 
+/// Split a player line on its spaces, but not on the ones of the quoted
+/// values (like names), which keep their quotes.
+fn split_player_string(line: &str) -> Vec<&str> {
+    let mut values = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            ' ' if !quoted => {
+                if let Some(start) = start.take() {
+                    values.push(&line[start .. index]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        start.get_or_insert(index);
+    }
+
+    if let Some(start) = start {
+        values.push(&line[start ..]);
+    }
+
+    values
+}
+
 fn get_players<Client: QuakeClient>(bufferer: &mut Buffer<LittleEndian>) -> GDResult<Vec<Client::Player>> {
     let mut players: Vec<Client::Player> = Vec::new();
 
-    // this needs to be looked at again as theres no way to check if the buffer has
-    // a remaining null byte the original code was:
-    // while !bufferer.is_remaining_empty() && bufferer.remaining_data() != [0x00]
-    while !bufferer.remaining_length() == 0 {
+    while bufferer.remaining_length() != 0 {
         let data = bufferer.read_string::<Utf8Decoder>(Some([0x0A]))?;
-        let data_split = data.split(' ').collect::<Vec<&str>>();
-        let data_iter = data_split.iter();
+        // Some servers end the reply with a null byte, or blank lines.
+        let data = data.trim_end_matches('\0').trim();
+        if data.is_empty() {
+            continue;
+        }
 
-        players.push(Client::parse_player_string(data_iter)?);
+        // A line that isn't a player (some mods add their own) is skipped,
+        // rather than losing the whole reply.
+        let data_split = split_player_string(data);
+        if let Ok(player) = Client::parse_player_string(data_split.iter()) {
+            players.push(player);
+        }
     }
 
     Ok(players)
@@ -107,8 +149,18 @@ pub fn client_query<Client: QuakeClient>(
     address: &SocketAddr,
     timeout_settings: Option<TimeoutSettings>,
 ) -> GDResult<Response<Client::Player>> {
-    let data = get_data::<Client>(address, &timeout_settings)?;
-    let mut bufferer = Buffer::<LittleEndian>::new(&data);
+    let (data, ping) = get_data::<Client>(address, &timeout_settings)?;
+
+    Ok(Response {
+        ping,
+        ..parse_response::<Client>(&data)?
+    })
+}
+
+/// Parse the reply following its header: the server values line, then a line
+/// per player, the ping being left unknown.
+pub(crate) fn parse_response<Client: QuakeClient>(data: &[u8]) -> GDResult<Response<Client::Player>> {
+    let mut bufferer = Buffer::<LittleEndian>::new(data);
 
     let mut server_vars = get_server_values(&mut bufferer)?;
     let players = get_players::<Client>(&mut bufferer)?;
@@ -134,6 +186,7 @@ pub fn client_query<Client: QuakeClient>(
             .remove("version")
             .or_else(|| server_vars.remove("*version")),
         unused_entries: server_vars,
+        ping: None,
     })
 }
 
@@ -143,3 +196,17 @@ pub fn remove_wrapping_quotes<'a>(string: &&'a str) -> &'a str {
         true => &string[1 .. string.len() - 1],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_values_keep_their_spaces() {
+        assert_eq!(
+            split_player_string("2 48 \"Big  Bad Wolf\" \"loop back\""),
+            ["2", "48", "\"Big  Bad Wolf\"", "\"loop back\""]
+        );
+        assert_eq!(split_player_string(" 0  0 \"\" "), ["0", "0", "\"\""]);
+    }
+}
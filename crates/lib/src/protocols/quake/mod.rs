@@ -1,6 +1,7 @@
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+pub mod netquake;
 pub mod one;
 pub mod three;
 pub mod two;
@@ -14,6 +15,8 @@ mod client;
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum QuakeVersion {
+    /// The original Quake servers, see [netquake].
+    NetQuake,
     One,
     Two,
     Three,
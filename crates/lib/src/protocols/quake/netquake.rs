@@ -0,0 +1,263 @@
+//! NetQuake, the control protocol of the original (non QuakeWorld) Quake
+//! servers.
+//!
+//! Unlike the other Quake versions, these servers don't answer `status`
+//! requests: the server info and each player are requested with connectionless
+//! control messages (`CCREQ_SERVER_INFO` and `CCREQ_PLAYER_INFO`), whose 4
+//! bytes header (the control flag and the length of the whole packet) is big
+//! endian, the rest of the message being little endian.
+
+use byteorder::{BigEndian, LittleEndian};
+
+use crate::buffer::Buffer;
+use crate::protocols::quake::Response;
+use crate::protocols::types::{CommonPlayer, GenericPlayer, TimeoutSettings};
+use crate::socket::{Socket, UdpSocket};
+use crate::utils::retry_on_timeout;
+use crate::GDErrorKind::PacketBad;
+use crate::GDResult;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use super::QuakePlayerType;
+
+/// The flag set on the header of the control messages.
+const NETFLAG_CTL: u16 = 0x8000;
+/// The version of the protocol sent along the server info request.
+const NET_PROTOCOL_VERSION: u8 = 3;
+
+const CCREQ_SERVER_INFO: u8 = 0x02;
+const CCREQ_PLAYER_INFO: u8 = 0x03;
+const CCREP_REJECT: u8 = 0x82;
+const CCREP_SERVER_INFO: u8 = 0x83;
+const CCREP_PLAYER_INFO: u8 = 0x84;
+
+/// NetQuake player data.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Player {
+    /// Player's index among the connected players.
+    pub id: u8,
+    /// Player's frags.
+    pub score: i32,
+    /// Seconds since the player connected.
+    pub time: u32,
+    pub name: String,
+    /// The shirt color.
+    pub color_primary: u8,
+    /// The pants color.
+    pub color_secondary: u8,
+    /// The player's address, as reported by the server.
+    pub address: String,
+}
+
+impl QuakePlayerType for Player {
+    fn version(response: &Response<Self>) -> super::VersionedResponse<'_> {
+        super::VersionedResponse::NetQuake(response)
+    }
+}
+
+impl CommonPlayer for Player {
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::NetQuake(self) }
+
+    fn name(&self) -> &str { &self.name }
+    fn score(&self) -> Option<i32> { Some(self.score) }
+}
+
+/// A control message: its big endian header, followed by the command.
+fn control_message(command: u8, payload: &[u8]) -> Vec<u8> {
+    let length = (4 + 1 + payload.len()) as u16;
+
+    [
+        &NETFLAG_CTL.to_be_bytes()[..],
+        &length.to_be_bytes(),
+        &[command],
+        payload,
+    ]
+    .concat()
+}
+
+/// Check the header and the command of a control message, returning its
+/// content.
+fn control_content(data: &[u8], expected_command: u8) -> GDResult<&[u8]> {
+    let mut header = Buffer::<BigEndian>::new(data);
+    if header.read::<u16>()? != NETFLAG_CTL {
+        return Err(PacketBad.context("Expected a control message"));
+    }
+
+    let length = header.read::<u16>()?;
+    if usize::from(length) != data.len() {
+        return Err(PacketBad.context(format!(
+            "Control message should be {length} bytes long, got {}",
+            data.len()
+        )));
+    }
+
+    match header.read::<u8>()? {
+        command if command == expected_command => Ok(&data[5 ..]),
+        CCREP_REJECT => {
+            let mut content = Buffer::<LittleEndian>::new(header.remaining_bytes());
+            Err(PacketBad.context(format!("Request rejected: {}", read_string(&mut content)?)))
+        }
+        command => {
+            Err(PacketBad.context(format!(
+                "Expected command {expected_command:#04x}, got {command:#04x}"
+            )))
+        }
+    }
+}
+
+/// Read a null terminated string, Quake's colored characters (with their high
+/// bit set) being read as their plain counterparts.
+fn read_string(buffer: &mut Buffer<LittleEndian>) -> GDResult<String> {
+    let length = buffer
+        .remaining_bytes()
+        .iter()
+        .position(|&byte| byte == 0)
+        .ok_or_else(|| PacketBad.context("Unterminated string"))?;
+    let string = buffer.remaining_bytes()[.. length]
+        .iter()
+        .map(|&byte| char::from(byte & 0x7F))
+        .collect();
+    buffer.move_cursor(length as isize + 1)?;
+
+    Ok(string)
+}
+
+/// Parse a `CCREP_SERVER_INFO` reply, without any players.
+fn parse_server_info(data: &[u8]) -> GDResult<Response<Player>> {
+    let mut buffer = Buffer::<LittleEndian>::new(control_content(data, CCREP_SERVER_INFO)?);
+
+    // The address the server thinks it has, not of much use.
+    read_string(&mut buffer)?;
+    let name = read_string(&mut buffer)?;
+    let map = read_string(&mut buffer)?;
+    let players_online = buffer.read::<u8>()?;
+    let players_maximum = buffer.read::<u8>()?;
+    let protocol_version = buffer.read::<u8>()?;
+
+    Ok(Response {
+        name,
+        map,
+        players: Vec::with_capacity(players_online.into()),
+        players_online,
+        players_maximum,
+        game_version: None,
+        unused_entries: HashMap::from([("protocol_version".to_string(), protocol_version.to_string())]),
+        ping: None,
+    })
+}
+
+/// Parse a `CCREP_PLAYER_INFO` reply.
+fn parse_player_info(data: &[u8]) -> GDResult<Player> {
+    let mut buffer = Buffer::<LittleEndian>::new(control_content(data, CCREP_PLAYER_INFO)?);
+
+    let id = buffer.read::<u8>()?;
+    let name = read_string(&mut buffer)?;
+    let colors = buffer.read::<u32>()?;
+
+    Ok(Player {
+        id,
+        name,
+        color_primary: ((colors >> 4) & 0x0F) as u8,
+        color_secondary: (colors & 0x0F) as u8,
+        score: buffer.read::<i32>()?,
+        time: buffer.read::<u32>()?,
+        address: read_string(&mut buffer)?,
+    })
+}
+
+fn request(socket: &mut UdpSocket, retries: usize, message: &[u8]) -> GDResult<Vec<u8>> {
+    retry_on_timeout(retries, || {
+        socket.send(message)?;
+        socket.receive(None)
+    })
+}
+
+/// Query the server info, then each of the connected players in turn.
+pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response<Player>> {
+    let mut socket = UdpSocket::new(address, &timeout_settings)?;
+    socket.set_protocol_hint("netquake");
+    let retries = TimeoutSettings::get_retries_or_default(&timeout_settings);
+
+    let server_info = control_message(CCREQ_SERVER_INFO, &[b"QUAKE\0", &[NET_PROTOCOL_VERSION][..]].concat());
+    let mut response = parse_server_info(&request(&mut socket, retries, &server_info)?)?;
+
+    // Players are requested by their index among the connected ones, not by
+    // their slot.
+    for index in 0 .. response.players_online {
+        let player_info = control_message(CCREQ_PLAYER_INFO, &[index]);
+        let player = parse_player_info(&request(&mut socket, retries, &player_info)?)?;
+        response.players.push(player);
+    }
+
+    response.ping = socket.ping();
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_have_a_big_endian_header() {
+        assert_eq!(
+            control_message(CCREQ_SERVER_INFO, b"QUAKE\0\x03"),
+            b"\x80\x00\x00\x0C\x02QUAKE\0\x03"
+        );
+        assert_eq!(control_message(CCREQ_PLAYER_INFO, &[1]), [0x80, 0x00, 0x00, 0x06, 0x03, 0x01]);
+    }
+
+    #[test]
+    fn parses_server_info() {
+        let data = control_message(
+            CCREP_SERVER_INFO,
+            b"192.168.1.2:26000\0Deathmatch \xD1\xD5\xC1\xCB\xC5\0e1m1\0\x02\x10\x03",
+        );
+        let response = parse_server_info(&data).unwrap();
+
+        assert_eq!(response.name, "Deathmatch QUAKE");
+        assert_eq!(response.map, "e1m1");
+        assert_eq!(response.players_online, 2);
+        assert_eq!(response.players_maximum, 16);
+        assert_eq!(response.unused_entries["protocol_version"], "3");
+    }
+
+    #[test]
+    fn parses_player_info() {
+        let mut content = b"\x01Ranger\0".to_vec();
+        content.extend_from_slice(&0x4Du32.to_le_bytes());
+        content.extend_from_slice(&(-2i32).to_le_bytes());
+        content.extend_from_slice(&125u32.to_le_bytes());
+        content.extend_from_slice(b"10.0.0.7:26001\0");
+        let player = parse_player_info(&control_message(CCREP_PLAYER_INFO, &content)).unwrap();
+
+        assert_eq!(
+            player,
+            Player {
+                id: 1,
+                score: -2,
+                time: 125,
+                name: "Ranger".to_string(),
+                color_primary: 4,
+                color_secondary: 13,
+                address: "10.0.0.7:26001".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn bad_control_messages_are_rejected() {
+        let mut truncated = control_message(CCREP_SERVER_INFO, b"addr\0name\0map\0\x00\x10\x03");
+        truncated.pop();
+        assert!(parse_server_info(&truncated).is_err());
+
+        let rejected = parse_server_info(&control_message(CCREP_REJECT, b"Server is full.\0")).unwrap_err();
+        assert!(format!("{rejected:?}").contains("Server is full."));
+
+        assert!(parse_server_info(&control_message(CCREP_PLAYER_INFO, b"")).is_err());
+        assert!(parse_server_info(b"\x00\x00\x00\x04").is_err());
+    }
+}
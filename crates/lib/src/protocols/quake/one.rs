@@ -16,7 +16,8 @@ use super::QuakePlayerType;
 pub struct Player {
     /// Player's server id.
     pub id: u8,
-    pub score: u16,
+    /// Player's frags, which suicides can make negative.
+    pub score: i32,
     pub time: u16,
     pub ping: u16,
     pub name: String,
@@ -26,14 +27,14 @@ pub struct Player {
 }
 
 impl QuakePlayerType for Player {
-    fn version(response: &Response<Self>) -> super::VersionedResponse { super::VersionedResponse::One(response) }
+    fn version(response: &Response<Self>) -> super::VersionedResponse<'_> { super::VersionedResponse::One(response) }
 }
 
 impl CommonPlayer for Player {
-    fn as_original(&self) -> GenericPlayer { GenericPlayer::QuakeOne(self) }
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::QuakeOne(self) }
 
     fn name(&self) -> &str { &self.name }
-    fn score(&self) -> Option<i32> { Some(self.score.into()) }
+    fn score(&self) -> Option<i32> { Some(self.score) }
 }
 
 pub(crate) struct QuakeOne;
@@ -85,3 +86,24 @@ impl QuakeClient for QuakeOne {
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response<Player>> {
     client_query::<QuakeOne>(address, timeout_settings)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::quake::client::parse_response;
+
+    #[test]
+    fn parses_status_responses() {
+        let data = b"\\hostname\\Quake Server\\map\\e1m1\\maxclients\\8\n\
+            3 -2 12 50 \"Ranger\" \"base\" 4 13\n\
+            5 17 30 999 \"Big Bad Wolf\" \"base\" 0 0\n";
+        let response = parse_response::<QuakeOne>(data).unwrap();
+
+        assert_eq!(response.name, "Quake Server");
+        assert_eq!(response.players_online, 2);
+        assert_eq!(response.players[0].score, -2);
+        assert_eq!(response.players[0].color_secondary, 13);
+        assert_eq!(response.players[1].name, "Big Bad Wolf");
+        assert_eq!(response.players[1].score, 17);
+    }
+}
@@ -22,3 +22,56 @@ impl QuakeClient for QuakeThree {
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response<Player>> {
     client_query::<QuakeThree>(address, timeout_settings)
 }
+
+/// Remove the color codes (a `^` followed by a letter or a digit, like `^1`)
+/// of a name, the players' names and the server's being kept as sent.
+pub fn strip_colors(name: &str) -> String {
+    let mut stripped = String::with_capacity(name.len());
+    let mut characters = name.chars().peekable();
+
+    while let Some(character) = characters.next() {
+        if character == '^' && characters.peek().map_or(false, char::is_ascii_alphanumeric) {
+            characters.next();
+        } else {
+            stripped.push(character);
+        }
+    }
+
+    stripped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::quake::client::parse_response;
+
+    #[test]
+    fn parses_status_responses() {
+        let data = b"\\sv_hostname\\^1Red ^7Server\\mapname\\q3dm17\\sv_maxclients\\16\\g_gametype\\0\n\
+            20 50 \"^4Big ^7Blue\"\n\
+            -1 999 \"Visor\"\n\0";
+        let response = parse_response::<QuakeThree>(data).unwrap();
+
+        assert_eq!(response.name, "^1Red ^7Server");
+        assert_eq!(response.map, "q3dm17");
+        assert_eq!(response.players_maximum, 16);
+        assert_eq!(response.unused_entries["g_gametype"], "0");
+        assert_eq!(response.players_online, 2);
+        assert_eq!(response.players[0].name, "^4Big ^7Blue");
+        assert_eq!(response.players[0].score, 20);
+        assert_eq!(response.players[1].ping, 999);
+        assert_eq!(response.players[1].address, None);
+
+        let data = b"\\sv_hostname\\Server\\mapname\\q3dm1\\sv_maxclients\\8\n12 oops\n3 40 \"Sarge\"\n";
+        let response = parse_response::<QuakeThree>(data).unwrap();
+        assert_eq!(response.players_online, 1);
+        assert_eq!(response.players[0].name, "Sarge");
+    }
+
+    #[test]
+    fn color_codes_are_stripped() {
+        assert_eq!(strip_colors("^4Big ^7Blue"), "Big Blue");
+        assert_eq!(strip_colors("^^1caret^"), "^caret^");
+        assert_eq!(strip_colors("100% ^ sure"), "100% ^ sure");
+    }
+}
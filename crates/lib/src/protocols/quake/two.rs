@@ -22,13 +22,13 @@ pub struct Player {
 }
 
 impl QuakePlayerType for Player {
-    fn version(response: &Response<Self>) -> super::VersionedResponse {
+    fn version(response: &Response<Self>) -> super::VersionedResponse<'_> {
         super::VersionedResponse::TwoAndThree(response)
     }
 }
 
 impl CommonPlayer for Player {
-    fn as_original(&self) -> GenericPlayer { GenericPlayer::QuakeTwo(self) }
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::QuakeTwo(self) }
 
     fn name(&self) -> &str { &self.name }
 
@@ -65,3 +65,24 @@ impl QuakeClient for QuakeTwo {
 pub fn query(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Response<Player>> {
     client_query::<QuakeTwo>(address, timeout_settings)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::quake::client::parse_response;
+
+    #[test]
+    fn parses_status_responses() {
+        let data = b"\\hostname\\Quake II Server\\mapname\\q2dm1\\maxclients\\12\n\
+            -3 45 \"Grunt\"\n\
+            8 120 \"Stroggicide\" \"192.168.1.2:27901\"\n";
+        let response = parse_response::<QuakeTwo>(data).unwrap();
+
+        assert_eq!(response.map, "q2dm1");
+        assert_eq!(response.players_online, 2);
+        assert_eq!(response.players[0].score, -3);
+        assert_eq!(response.players[0].address, None);
+        assert_eq!(response.players[1].score, 8);
+        assert_eq!(response.players[1].address.as_deref(), Some("192.168.1.2:27901"));
+    }
+}
@@ -1,6 +1,7 @@
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
 use crate::protocols::{
     types::{CommonPlayer, CommonResponse},
@@ -25,20 +26,25 @@ pub struct Response<P> {
     pub game_version: Option<String>,
     /// Other server entries that weren't used.
     pub unused_entries: HashMap<String, String>,
+    /// The round trip time of the request (to the first packet of its reply),
+    /// `None` if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ping: Option<Duration>,
 }
 
 pub trait QuakePlayerType: Sized + CommonPlayer {
-    fn version(response: &Response<Self>) -> VersionedResponse;
+    fn version(response: &Response<Self>) -> VersionedResponse<'_>;
 }
 
 impl<P: QuakePlayerType> CommonResponse for Response<P> {
-    fn as_original(&self) -> GenericResponse { GenericResponse::Quake(P::version(self)) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Quake(P::version(self)) }
 
     fn name(&self) -> Option<&str> { Some(&self.name) }
     fn game_version(&self) -> Option<&str> { self.game_version.as_deref() }
     fn map(&self) -> Option<&str> { Some(&self.map) }
     fn players_maximum(&self) -> u32 { self.players_maximum.into() }
     fn players_online(&self) -> u32 { self.players_online.into() }
+    fn ping(&self) -> Option<Duration> { self.ping }
 
     fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
         Some(
@@ -54,6 +60,7 @@ impl<P: QuakePlayerType> CommonResponse for Response<P> {
 #[cfg_attr(feature = "serde", derive(Serialize))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum VersionedResponse<'a> {
+    NetQuake(&'a Response<crate::protocols::quake::netquake::Player>),
     One(&'a Response<crate::protocols::quake::one::Player>),
     TwoAndThree(&'a Response<crate::protocols::quake::two::Player>),
 }
@@ -2,11 +2,12 @@
 use crate::games::minecraft;
 #[cfg(feature = "tls")]
 use crate::protocols::epic;
-use crate::protocols::{gamespy, quake, unreal2, valve};
+use crate::protocols::{gamespy, http_json, quake, unreal2, valve};
 use crate::GDErrorKind::InvalidInput;
 use crate::GDResult;
 
-use std::time::Duration;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -23,6 +24,9 @@ pub enum ProprietaryProtocol {
     Savage2,
     Eco,
     Mindustry,
+    Factorio,
+    VintageStory,
+    Wreckfest,
 }
 
 /// Enumeration of all valid protocol types
@@ -47,6 +51,7 @@ pub enum GenericResponse<'a> {
     Quake(quake::VersionedResponse<'a>),
     Valve(&'a valve::Response),
     Unreal2(&'a unreal2::Response),
+    HttpJson(&'a http_json::Response),
     #[cfg(feature = "tls")]
     Epic(&'a epic::Response),
     #[cfg(feature = "games")]
@@ -63,6 +68,10 @@ pub enum GenericResponse<'a> {
     Savage2(&'a crate::games::savage2::Response),
     #[cfg(feature = "games")]
     Eco(&'a crate::games::eco::Response),
+    #[cfg(feature = "games")]
+    Factorio(&'a crate::games::factorio::Response),
+    #[cfg(feature = "games")]
+    Wreckfest(&'a crate::games::wreckfest::Response),
 }
 
 /// All player types
@@ -70,10 +79,12 @@ pub enum GenericResponse<'a> {
 #[derive(Debug, Clone, PartialEq)]
 pub enum GenericPlayer<'a> {
     Valve(&'a valve::ServerPlayer),
+    NetQuake(&'a quake::netquake::Player),
     QuakeOne(&'a quake::one::Player),
     QuakeTwo(&'a quake::two::Player),
     Gamespy(gamespy::VersionedPlayer<'a>),
     Unreal2(&'a unreal2::Player),
+    HttpJson(&'a http_json::Player),
     #[cfg(feature = "tls")]
     Epic(&'a epic::Player),
     #[cfg(feature = "games")]
@@ -84,14 +95,16 @@ pub enum GenericPlayer<'a> {
     JCMP2(&'a crate::games::jc2m::Player),
     #[cfg(feature = "games")]
     Eco(&'a crate::games::eco::Player),
+    #[cfg(feature = "games")]
+    Factorio(&'a crate::games::factorio::Player),
 }
 
 pub trait CommonResponse {
     /// Get the original response type
-    fn as_original(&self) -> GenericResponse;
+    fn as_original(&self) -> GenericResponse<'_>;
     /// Get a struct that can be stored as JSON (you don't need to override
     /// this)
-    fn as_json(&self) -> CommonResponseJson {
+    fn as_json(&self) -> CommonResponseJson<'_> {
         CommonResponseJson {
             name: self.name(),
             description: self.description(),
@@ -105,6 +118,7 @@ pub trait CommonResponse {
             players: self
                 .players()
                 .map(|players| players.iter().map(|p| p.as_json()).collect()),
+            ping: self.ping(),
         }
     }
 
@@ -128,6 +142,8 @@ pub trait CommonResponse {
     fn has_password(&self) -> Option<bool> { None }
     /// Currently connected players
     fn players(&self) -> Option<Vec<&dyn CommonPlayer>> { None }
+    /// The round trip time of the query's first request, if it was measured
+    fn ping(&self) -> Option<Duration> { None }
 }
 
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -143,14 +159,15 @@ pub struct CommonResponseJson<'a> {
     pub players_bots: Option<u32>,
     pub has_password: Option<bool>,
     pub players: Option<Vec<CommonPlayerJson<'a>>>,
+    pub ping: Option<Duration>,
 }
 
 pub trait CommonPlayer {
     /// Get the original player type
-    fn as_original(&self) -> GenericPlayer;
+    fn as_original(&self) -> GenericPlayer<'_>;
     /// Get a struct that can be stored as JSON (you don't need to override
     /// this)
-    fn as_json(&self) -> CommonPlayerJson {
+    fn as_json(&self) -> CommonPlayerJson<'_> {
         CommonPlayerJson {
             name: self.name(),
             score: self.score(),
@@ -190,6 +207,19 @@ pub struct TimeoutSettings {
     /// Number of retries per request
     #[cfg_attr(feature = "clap", arg(long, default_value = "0"))]
     retries: usize,
+    /// Time after which a socket gives up on the server, however many packets
+    /// it already received
+    #[cfg_attr(feature = "clap", arg(long, value_parser = parse_duration_secs, help = "Total query deadline (in seconds)"))]
+    #[cfg_attr(feature = "serde", serde(default))]
+    deadline: Option<Duration>,
+    /// Time waited for before each retry
+    #[cfg_attr(feature = "clap", arg(long = "retry-delay", value_parser = parse_duration_secs, help = "Delay before each retry (in seconds)", default_value = "0"))]
+    #[cfg_attr(feature = "serde", serde(default))]
+    retry_delay: Duration,
+    /// When the deadline ends, once started by [TimeoutSettings::start_deadline]
+    #[cfg_attr(feature = "clap", arg(skip))]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    deadline_end: Option<Instant>,
 }
 
 impl TimeoutSettings {
@@ -230,9 +260,65 @@ impl TimeoutSettings {
             write,
             connect,
             retries,
+            deadline: None,
+            retry_delay: Duration::ZERO,
+            deadline_end: None,
         })
     }
 
+    /// Bound the whole query (every packet of a multi-packet response, every
+    /// retry) by `deadline`, the read timeout still applying to each received
+    /// packet. `None` (the default) only relies on the read timeout.  
+    /// Passing zero Duration throws GDErrorKind::[InvalidInput].
+    pub fn with_deadline(self, deadline: Option<Duration>) -> GDResult<Self> {
+        if deadline.map_or(false, |deadline| deadline.is_zero()) {
+            return Err(InvalidInput.context("Deadline must not be 0"));
+        }
+
+        Ok(Self {
+            deadline,
+            deadline_end: None,
+            ..self
+        })
+    }
+
+    /// Start the deadline (if any) now, so that every socket opened with these
+    /// settings (by the fallbacks, the retries or the redirects of a query)
+    /// ends by the same point in time instead of counting the deadline from
+    /// when it is opened. A deadline that was already started is kept.
+    pub fn start_deadline(self) -> Self {
+        match (self.deadline, self.deadline_end) {
+            (Some(deadline), None) => {
+                Self {
+                    deadline_end: Some(Instant::now() + deadline),
+                    ..self
+                }
+            }
+            _ => self,
+        }
+    }
+
+    /// Same as [TimeoutSettings::start_deadline], for optional settings.
+    pub fn start_deadline_of(timeout_settings: Option<Self>) -> Option<Self> {
+        timeout_settings.map(Self::start_deadline)
+    }
+
+    /// Start the deadline, shortened to end by `end` at the latest.
+    #[cfg(feature = "games")]
+    pub(crate) fn start_deadline_ending_by(self, end: Instant) -> Self {
+        let started = self.start_deadline();
+        Self {
+            deadline_end: Some(started.deadline_end.map_or(end, |own| own.min(end))),
+            ..started
+        }
+    }
+
+    /// Wait for `retry_delay` before each retry of a request that got no
+    /// answer, so that a resend doesn't follow a lost packet right away. The
+    /// deadline (if any) still bounds the whole exchange. None is waited for
+    /// by default.
+    pub const fn with_retry_delay(self, retry_delay: Duration) -> Self { Self { retry_delay, ..self } }
+
     /// Get the read timeout.
     pub const fn get_read(&self) -> Option<Duration> { self.read }
 
@@ -245,6 +331,12 @@ impl TimeoutSettings {
     /// Get number of retries
     pub const fn get_retries(&self) -> usize { self.retries }
 
+    /// Get the total deadline.
+    pub const fn get_deadline(&self) -> Option<Duration> { self.deadline }
+
+    /// Get the delay before each retry.
+    pub const fn get_retry_delay(&self) -> Duration { self.retry_delay }
+
     /// Get the number of retries if there are timeout settings else fall back
     /// to the default
     pub const fn get_retries_or_default(timeout_settings: &Option<Self>) -> usize {
@@ -277,13 +369,47 @@ impl TimeoutSettings {
         }
     }
 
-    /// Default values are 4 seconds for both read and write, no retries.
+    /// Get the deadline given timeout settings or get the default.
+    pub const fn get_deadline_or_default(timeout_settings: &Option<Self>) -> Option<Duration> {
+        if let Some(timeout_settings) = timeout_settings {
+            timeout_settings.get_deadline()
+        } else {
+            Self::const_default().get_deadline()
+        }
+    }
+
+    /// Get the point in time the deadline ends at given timeout settings (or
+    /// the defaults), counting it from now if it wasn't started.
+    pub fn get_deadline_end_or_default(timeout_settings: &Option<Self>) -> Option<Instant> {
+        let timeout_settings = timeout_settings.unwrap_or_default();
+        timeout_settings.deadline_end.or_else(|| {
+            timeout_settings
+                .get_deadline()
+                .map(|deadline| Instant::now() + deadline)
+        })
+    }
+
+    /// Get the delay before each retry given timeout settings or get the
+    /// default.
+    pub const fn get_retry_delay_or_default(timeout_settings: &Option<Self>) -> Duration {
+        if let Some(timeout_settings) = timeout_settings {
+            timeout_settings.get_retry_delay()
+        } else {
+            Self::const_default().get_retry_delay()
+        }
+    }
+
+    /// Default values are 4 seconds for both read and write, no retries (nor
+    /// retry delay) and no deadline.
     pub const fn const_default() -> Self {
         Self {
             read: Some(Duration::from_secs(4)),
             write: Some(Duration::from_secs(4)),
             connect: Some(Duration::from_secs(4)),
             retries: 0,
+            deadline: None,
+            retry_delay: Duration::ZERO,
+            deadline_end: None,
         }
     }
 }
@@ -293,6 +419,67 @@ impl Default for TimeoutSettings {
     fn default() -> Self { Self::const_default() }
 }
 
+/// The addresses to announce to a proxy (like HAProxy) or a server expecting a
+/// [PROXY protocol](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt)
+/// version 2 header before the actual data.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ProxyAddr {
+    /// The address of the client the connection is made for.
+    pub source: SocketAddr,
+    /// The address the client connected to.
+    pub destination: SocketAddr,
+}
+
+/// Starts every version 2 header.
+const PROXY_V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+impl ProxyAddr {
+    pub const fn new(source: SocketAddr, destination: SocketAddr) -> Self {
+        Self {
+            source,
+            destination,
+        }
+    }
+
+    /// The binary (version 2) header of a proxied TCP connection.
+    ///
+    /// Addresses of different families can't be sent, the header then tells
+    /// that the connection is proxied without giving them (`UNSPEC`).
+    pub fn v2_header(&self) -> Vec<u8> {
+        let mut header = PROXY_V2_SIGNATURE.to_vec();
+        // Version 2, PROXY command.
+        header.push(0x21);
+
+        let mut addresses = Vec::with_capacity(36);
+        let family = match (self.source, self.destination) {
+            (SocketAddr::V4(source), SocketAddr::V4(destination)) => {
+                addresses.extend_from_slice(&source.ip().octets());
+                addresses.extend_from_slice(&destination.ip().octets());
+                // TCP over IPv4.
+                0x11
+            }
+            (SocketAddr::V6(source), SocketAddr::V6(destination)) => {
+                addresses.extend_from_slice(&source.ip().octets());
+                addresses.extend_from_slice(&destination.ip().octets());
+                // TCP over IPv6.
+                0x21
+            }
+            _ => 0x00,
+        };
+        if family != 0x00 {
+            addresses.extend_from_slice(&self.source.port().to_be_bytes());
+            addresses.extend_from_slice(&self.destination.port().to_be_bytes());
+        }
+
+        header.push(family);
+        header.extend_from_slice(&(addresses.len() as u16).to_be_bytes());
+        header.extend_from_slice(&addresses);
+
+        header
+    }
+}
+
 /// Generic extra request settings
 ///
 /// Fields of this struct may not be used depending on which protocol
@@ -474,4 +661,34 @@ mod tests {
 
         let _: valve::GatheringSettings = settings.into();
     }
+
+    #[test]
+    fn proxy_v2_header_ipv4() {
+        let proxy = ProxyAddr::new(
+            "192.168.1.2:51000".parse().unwrap(),
+            "10.0.0.1:25565".parse().unwrap(),
+        );
+
+        assert_eq!(
+            proxy.v2_header(),
+            [
+                &PROXY_V2_SIGNATURE[..],
+                &[0x21, 0x11, 0x00, 0x0C],
+                &[192, 168, 1, 2, 10, 0, 0, 1],
+                &[0xC7, 0x38, 0x63, 0xDD],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn proxy_v2_header_ipv6_and_mixed_families() {
+        let v6 = ProxyAddr::new("[::1]:1".parse().unwrap(), "[2001:db8::2]:2".parse().unwrap()).v2_header();
+        assert_eq!(v6.len(), 16 + 36);
+        assert_eq!(&v6[12 .. 16], &[0x21, 0x21, 0x00, 36]);
+        assert_eq!(&v6[48 ..], &[0x00, 0x01, 0x00, 0x02]);
+
+        let mixed = ProxyAddr::new("[::1]:1".parse().unwrap(), "10.0.0.1:2".parse().unwrap()).v2_header();
+        assert_eq!(&mixed[12 ..], &[0x21, 0x00, 0x00, 0x00]);
+    }
 }
@@ -32,7 +32,8 @@ pub(crate) struct Unreal2Protocol {
 
 impl Unreal2Protocol {
     pub fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = UdpSocket::new(address, &timeout_settings)?;
+        let mut socket = UdpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("unreal2");
         let retry_count = timeout_settings.as_ref().map_or_else(
             || TimeoutSettings::default().get_retries(),
             TimeoutSettings::get_retries,
@@ -192,6 +193,7 @@ impl Unreal2Protocol {
             server_info,
             mutators_and_rules,
             players,
+            ping: self.socket.ping(),
         })
     }
 }
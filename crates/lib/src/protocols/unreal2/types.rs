@@ -7,6 +7,7 @@ use crate::{GDError, GDResult};
 use super::Unreal2StringDecoder;
 
 use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 
 use byteorder::ByteOrder;
 
@@ -167,7 +168,7 @@ impl CommonPlayer for Player {
 
     fn score(&self) -> Option<i32> { Some(self.score) }
 
-    fn as_original(&self) -> GenericPlayer { GenericPlayer::Unreal2(self) }
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::Unreal2(self) }
 }
 
 /// Unreal 2 response.
@@ -177,10 +178,14 @@ pub struct Response {
     pub server_info: ServerInfo,
     pub mutators_and_rules: MutatorsAndRules,
     pub players: Players,
+    /// The round trip time of the server info request (to the first packet
+    /// of its reply), `None` if unknown.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ping: Option<Duration>,
 }
 
 impl CommonResponse for Response {
-    fn as_original(&self) -> GenericResponse { GenericResponse::Unreal2(self) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Unreal2(self) }
 
     fn name(&self) -> Option<&str> { Some(&self.server_info.name) }
 
@@ -194,6 +199,8 @@ impl CommonResponse for Response {
 
     fn has_password(&self) -> Option<bool> { Some(self.server_info.password) }
 
+    fn ping(&self) -> Option<Duration> { self.ping }
+
     fn players(&self) -> Option<Vec<&dyn crate::protocols::types::CommonPlayer>> {
         Some(
             self.players
@@ -0,0 +1,358 @@
+//! Querying servers without blocking, over tokio's sockets, for polling many
+//! of them concurrently without a thread per query.
+//!
+//! Only the exchanges with the server are asynchronous, the replies being
+//! parsed like the ones of the blocking
+//! [query](crate::protocols::valve::query).
+
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, Instant, SystemTime};
+
+use tokio::net::UdpSocket;
+
+use crate::protocols::types::TimeoutSettings;
+use crate::protocols::valve::protocol::{
+    answers_another_request,
+    check_info,
+    parse_players,
+    parse_rules,
+    read_challenge,
+    read_datagram,
+    with_challenge,
+    Datagram,
+    Fragments,
+    ValveProtocol,
+    CHALLENGE_KIND,
+    PACKET_SIZE,
+};
+use crate::protocols::valve::{Engine, GatheringSettings, Packet, Request, Response};
+use crate::transport::UdpTransport;
+use crate::utils::maybe_gather;
+use crate::GDErrorKind::{PacketBad, PacketReceive, PacketSend, SocketBind, SocketConnect};
+use crate::GDResult;
+
+/// Same as [query](super::query), without blocking the thread while waiting
+/// for the server.
+///
+/// The whole query is bounded by the deadline of the timeout settings (if
+/// any), each reply by the read timeout. Redirects aren't followed (nor
+/// recognized, as with [query](super::query)), and games needing a
+/// [PreProbe](super::PreProbe) can't be queried.
+pub async fn query_async(
+    address: &SocketAddr,
+    engine: Engine,
+    gather_settings: Option<GatheringSettings>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let query = query_unbounded(address, engine, gather_settings.unwrap_or_default(), &timeout_settings);
+
+    match TimeoutSettings::get_deadline_end_or_default(&timeout_settings) {
+        Some(deadline) => {
+            tokio::time::timeout_at(deadline.into(), query)
+                .await
+                .unwrap_or_else(|_| Err(timed_out("The query deadline passed")))
+        }
+        None => query.await,
+    }
+}
+
+async fn query_unbounded(
+    address: &SocketAddr,
+    engine: Engine,
+    gather_settings: GatheringSettings,
+    timeout_settings: &Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    let mut client = AsyncClient::connect(address, timeout_settings).await?;
+    if let Some(challenge) = gather_settings.challenge {
+        client.challenge = Some(challenge.to_le_bytes().to_vec());
+    }
+    client.max_challenge_retries = gather_settings.max_challenge_retries;
+
+    let packet = client
+        .request(&engine, 0, Request::Info, Request::Info.get_default_payload())
+        .await?;
+    let info = ValveProtocol::<UdpTransport>::parse_server_info(&engine, &packet, gather_settings.max_name_length)?;
+    let info = check_info(&engine, info, &gather_settings)?;
+
+    let protocol = info.protocol_version;
+
+    Ok(Response {
+        info,
+        players: maybe_gather!(
+            gather_settings.players,
+            client
+                .request_data(&engine, protocol, Request::Players)
+                .await
+                .and_then(|data| parse_players(&engine, &data))
+        ),
+        rules: maybe_gather!(
+            gather_settings.rules,
+            client
+                .request_data(&engine, protocol, Request::Rules)
+                .await
+                .and_then(|data| parse_rules(&engine, &data))
+        ),
+        response_address: Some(*address),
+        queried_at: Some(SystemTime::now()),
+        challenge: client
+            .challenge
+            .as_deref()
+            .and_then(|challenge| challenge.try_into().ok())
+            .map(i32::from_le_bytes),
+        raw: gather_settings.keep_raw.then_some(packet.payload),
+        ping: client.ping,
+    })
+}
+
+fn timed_out(message: &'static str) -> crate::GDError {
+    PacketReceive.context(io::Error::new(io::ErrorKind::TimedOut, message))
+}
+
+/// The asynchronous counterpart of the blocking Valve client, making the
+/// requests (with their challenges and retries) over a tokio socket.
+struct AsyncClient {
+    socket: UdpSocket,
+    read_timeout: Option<Duration>,
+    retry_count: usize,
+    retry_delay: Duration,
+    challenge: Option<Vec<u8>>,
+    max_challenge_retries: usize,
+    /// When the first request was sent, to measure the ping.
+    first_sent: Option<Instant>,
+    ping: Option<Duration>,
+}
+
+impl AsyncClient {
+    async fn connect(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
+        // Bind to the address family of the remote, so that IPv6 hosts can be
+        // reached too.
+        let local_address: SocketAddr = match address {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local_address)
+            .await
+            .map_err(|e| SocketBind.context(e))?;
+        socket
+            .connect(address)
+            .await
+            .map_err(|e| SocketConnect.context(e))?;
+
+        Ok(Self {
+            socket,
+            read_timeout: TimeoutSettings::get_read_and_write_or_defaults(timeout_settings).0,
+            retry_count: TimeoutSettings::get_retries_or_default(timeout_settings),
+            retry_delay: TimeoutSettings::get_retry_delay_or_default(timeout_settings),
+            challenge: None,
+            max_challenge_retries: crate::protocols::valve::DEFAULT_MAX_CHALLENGE_RETRIES,
+            first_sent: None,
+            ping: None,
+        })
+    }
+
+    async fn send(&mut self, kind: u8, payload: Vec<u8>) -> GDResult<()> {
+        self.socket
+            .send(&Packet::new(kind, payload).to_bytes())
+            .await
+            .map_err(|e| PacketSend.context(e))?;
+        self.first_sent.get_or_insert_with(Instant::now);
+
+        Ok(())
+    }
+
+    async fn receive_datagram(&mut self) -> GDResult<Vec<u8>> {
+        let mut data = vec![0; PACKET_SIZE];
+        let received = match self.read_timeout {
+            Some(read_timeout) => {
+                tokio::time::timeout(read_timeout, self.socket.recv(&mut data))
+                    .await
+                    .map_err(|_| timed_out("The server didn't answer in time"))?
+            }
+            None => self.socket.recv(&mut data).await,
+        }
+        .map_err(|e| PacketReceive.context(e))?;
+        data.truncate(received);
+
+        if self.ping.is_none() {
+            self.ping = self.first_sent.map(|sent| sent.elapsed());
+        }
+
+        Ok(data)
+    }
+
+    /// Receive a reply, waiting for all of its fragments if it is split.
+    async fn receive(&mut self, engine: &Engine, protocol: u8) -> GDResult<Packet> {
+        let data = self.receive_datagram().await?;
+
+        let mut fragments = match read_datagram(engine, protocol, &data)? {
+            Datagram::Whole(packet) => return Ok(packet),
+            Datagram::Fragment(first) => Fragments::new(first),
+        };
+        while !fragments.is_complete() {
+            let data = match self.receive_datagram().await {
+                Ok(data) => data,
+                Err(e) if e.is_retryable() => return Err(fragments.incomplete()),
+                Err(e) => return Err(e),
+            };
+            fragments.add(engine, protocol, &data)?;
+        }
+
+        fragments.assemble()
+    }
+
+    async fn request_data(&mut self, engine: &Engine, protocol: u8, kind: Request) -> GDResult<Vec<u8>> {
+        self.request(engine, protocol, kind, kind.get_default_payload())
+            .await
+            .map(|packet| packet.payload)
+    }
+
+    /// Make a request, retrying on timeouts.
+    async fn request(&mut self, engine: &Engine, protocol: u8, kind: Request, payload: Vec<u8>) -> GDResult<Packet> {
+        let mut retries = 0;
+        loop {
+            match self.request_once(engine, protocol, kind as u8, payload.clone()).await {
+                Err(e) if e.is_retryable() && retries < self.retry_count => retries += 1,
+                result => return result,
+            }
+
+            if !self.retry_delay.is_zero() {
+                tokio::time::sleep(self.retry_delay).await;
+            }
+        }
+    }
+
+    /// Make a request (without retry logic), answering the challenges the
+    /// server hands out like the blocking client does.
+    async fn request_once(&mut self, engine: &Engine, protocol: u8, kind: u8, payload: Vec<u8>) -> GDResult<Packet> {
+        let mut sent_with_challenge = self.challenge.is_some();
+        self.send(kind, with_challenge(kind, payload, self.challenge.as_deref()))
+            .await?;
+
+        let mut packet = self.receive_reply(engine, protocol, kind).await?;
+        let mut retries = 0;
+        while packet.kind == CHALLENGE_KIND {
+            if sent_with_challenge {
+                if retries == self.max_challenge_retries {
+                    return Err(PacketBad.context(format!(
+                        "Still handed out a new challenge after {retries} retries"
+                    )));
+                }
+                retries += 1;
+            }
+            sent_with_challenge = true;
+
+            let challenge = read_challenge(&packet.payload)?;
+            self.send(
+                kind,
+                with_challenge(kind, Request::Info.get_default_payload(), Some(&challenge)),
+            )
+            .await?;
+            self.challenge = Some(challenge);
+
+            packet = self.receive_reply(engine, protocol, kind).await?;
+        }
+
+        Ok(packet)
+    }
+
+    /// Receive the reply to a request (or a challenge), the replies to the
+    /// other requests that arrive before it being dropped (they are asked for
+    /// again when their request is made).
+    async fn receive_reply(&mut self, engine: &Engine, protocol: u8, kind: u8) -> GDResult<Packet> {
+        loop {
+            let packet = self.receive(engine, protocol).await?;
+            if !answers_another_request(kind, packet.kind) {
+                return Ok(packet);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::types::GatherToggle;
+    use crate::protocols::valve::protocol::TestInfoReply;
+    use std::thread;
+
+    /// A `A2S_PLAYER` reply with a single player.
+    fn players_reply() -> Vec<u8> {
+        let mut data = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 1, 0];
+        data.extend_from_slice(b"alice\0");
+        data.extend_from_slice(&5i32.to_le_bytes());
+        data.extend_from_slice(&60.5f32.to_le_bytes());
+
+        data
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn query_is_made_asynchronously() {
+        const CHALLENGE: [u8; 4] = [1, 2, 3, 4];
+
+        // Hands out a challenge for the players, then answers with them.
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let address = server.local_addr().unwrap();
+        let responder = thread::spawn(move || {
+            let mut buffer = [0; 64];
+            let mut requests = Vec::new();
+            while requests.len() < 3 {
+                let (size, from) = server.recv_from(&mut buffer).unwrap();
+                let request = buffer[.. size].to_vec();
+                let reply = match (request[4], request.ends_with(&CHALLENGE)) {
+                    (0x54, _) => TestInfoReply::default().build(),
+                    (0x55, false) => [&[0xFF, 0xFF, 0xFF, 0xFF, 0x41][..], &CHALLENGE].concat(),
+                    (0x55, true) => players_reply(),
+                    _ => unreachable!(),
+                };
+                server.send_to(&reply, from).unwrap();
+                requests.push(request);
+            }
+
+            requests
+        });
+
+        let settings = GatheringSettings {
+            players: GatherToggle::Enforce,
+            rules: GatherToggle::Skip,
+            ..GatheringSettings::default()
+        };
+        let response = runtime()
+            .block_on(query_async(&address, Engine::new(440), Some(settings), None))
+            .unwrap();
+        assert_eq!(responder.join().unwrap().len(), 3);
+
+        assert_eq!(response.info.name, "gamedig");
+        assert_eq!(response.info.folder, "tf");
+        assert_eq!(response.players.unwrap()[0].name, "alice");
+        assert_eq!(response.challenge, Some(i32::from_le_bytes(CHALLENGE)));
+        assert_eq!(response.response_address, Some(address));
+        assert!(response.ping.is_some());
+    }
+
+    #[test]
+    fn silent_servers_time_out() {
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let timeout_settings = TimeoutSettings::new(Some(Duration::from_millis(50)), None, None, 1).ok();
+
+        let start = Instant::now();
+        let error = runtime()
+            .block_on(query_async(
+                &silent.local_addr().unwrap(),
+                Engine::new(440),
+                None,
+                timeout_settings,
+            ))
+            .unwrap_err();
+        assert!(error.is_retryable());
+        // Retried once.
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -1,8 +1,15 @@
+/// Querying without blocking.
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
 /// The implementation.
 pub mod protocol;
+/// Running commands on a server.
+pub mod rcon;
 /// All types used by the implementation.
 pub mod types;
 
+#[cfg(feature = "tokio")]
+pub use asynchronous::query_async;
 pub use protocol::*;
 pub use types::*;
 
@@ -35,6 +42,7 @@ macro_rules! game_query_mod {
             };
 
             crate::protocols::valve::game_query_fn!($pretty_name, $engine, $default_port, $gathering_settings);
+            crate::protocols::valve::game_query_fn!(@game_port $engine);
         }
     };
 }
@@ -57,22 +65,34 @@ pub(crate) use game_query_mod;
 macro_rules! game_query_fn {
     ($pretty_name: expr, $engine: expr, $default_port: literal, $gathering_settings: expr) => {
         // TODO: By using $gathering_settings, also add to doc if a game doesnt respond to certain gathering settings
-        crate::protocols::valve::game_query_fn!{@gen $engine, $default_port, concat!(
+        crate::protocols::valve::game_query_fn!{@gen $pretty_name, $engine, $default_port, concat!(
             "Make a valve query for ", $pretty_name, " with default timeout settings and default extra request settings.\n\n",
             "If port is `None`, then the default port (", stringify!($default_port), ") will be used."), $gathering_settings}
     };
 
-    (@gen $engine: expr, $default_port: literal, $doc: expr, $gathering_settings: expr) => {
+    (@game_port $engine: expr) => {
+        /// Same as [query], for a server whose game port (rather than its
+        /// query port) is known, the query port being derived from it by
+        /// [Engine::query_port_for](crate::protocols::valve::Engine::query_port_for).
+        pub fn query_game_port(
+            address: &std::net::IpAddr,
+            game_port: u16,
+        ) -> crate::GDResult<crate::protocols::valve::game::Response> {
+            query(address, Some($engine.query_port_for(game_port)))
+        }
+    };
+
+    (@gen $pretty_name: expr, $engine: expr, $default_port: literal, $doc: expr, $gathering_settings: expr) => {
         #[doc = $doc]
         pub fn query(address: &std::net::IpAddr, port: Option<u16>) -> crate::GDResult<crate::protocols::valve::game::Response> {
-            let valve_response = crate::protocols::valve::query(
-                &std::net::SocketAddr::new(*address, port.unwrap_or($default_port)),
-                $engine,
-                Some($gathering_settings),
-                None,
-            )?;
+            let game = crate::games::Game {
+                name: $pretty_name,
+                default_port: $default_port,
+                protocol: crate::protocols::types::Protocol::Valve($engine),
+                request_settings: $gathering_settings.into_extra(),
+            };
 
-            Ok(crate::protocols::valve::game::Response::new_from_valve_response(valve_response))
+            crate::games::query_valve(&game, address, port)
         }
     };
 }
@@ -4,37 +4,53 @@ use crate::{
         types::TimeoutSettings,
         valve::{
             types::{
+                DEFAULT_MAX_CHALLENGE_RETRIES,
                 Environment,
                 ExtraData,
                 GatheringSettings,
+                PreProbe,
                 Request,
                 Response,
                 Server,
                 ServerInfo,
                 ServerPlayer,
                 TheShip,
+                game,
             },
             Engine,
             ModData,
         },
     },
-    socket::{Socket, UdpSocket},
-    utils::{maybe_gather, retry_on_timeout, u8_lower_upper},
-    GDErrorKind::{BadGame, Decompress, UnknownEnumCast},
+    transport::{ExchangedPacket, RecordingTransport, Transport, UdpTransport},
+    utils::{maybe_gather, retry_on_timeout_with_delay, u8_lower_upper},
+    GDErrorKind::{
+        BadGame,
+        Decompress,
+        HostLookup,
+        InvalidInput,
+        PacketBad,
+        PacketParse,
+        PacketReceive,
+        UnknownEnumCast,
+    },
+    GDError,
     GDResult,
+    PacketTooShort,
 };
 
-use bzip2_rs::decoder::Decoder;
+use bzip2_rs::DecoderReader;
 
 use crate::buffer::Utf8Decoder;
 use crate::protocols::valve::Packet;
 use byteorder::LittleEndian;
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Debug)]
 #[allow(dead_code)] //remove this later on
-struct SplitPacket {
+pub(super) struct SplitPacket {
     pub header: u32,
     pub id: u32,
     pub total: u8,
@@ -64,8 +80,10 @@ impl SplitPacket {
                     true => 1248,
                 };
 
+                // The size and the CRC32 of the decompressed payload are only
+                // sent along the first fragment of a compressed response.
                 let is_compressed = ((id >> 31) & 1u32) == 1u32;
-                let decompressed = match is_compressed {
+                let decompressed = match is_compressed && number == 0 {
                     false => None,
                     true => Some((buffer.read()?, buffer.read()?)),
                 };
@@ -74,6 +92,12 @@ impl SplitPacket {
             }
         };
 
+        if number >= total {
+            return Err(PacketBad.context(format!(
+                "Fragment number {number} is out of the {total} fragments"
+            )));
+        }
+
         Ok(Self {
             header,
             id,
@@ -86,28 +110,31 @@ impl SplitPacket {
     }
 
     fn get_payload(&self) -> GDResult<Vec<u8>> {
-        if let Some(decompressed) = self.decompressed {
-            let mut decoder = Decoder::new();
-            decoder
-                .write(&self.payload)
-                .map_err(|e| Decompress.context(e))?;
-
-            let decompressed_size = decompressed.0 as usize;
-
-            let mut decompressed_payload = vec![0; decompressed_size];
+        if let Some((decompressed_size, crc32)) = self.decompressed {
+            // The size is announced by the server, it isn't trusted with the
+            // allocation.
+            if decompressed_size > MAX_DECOMPRESSED_SIZE {
+                return Err(Decompress.context(format!(
+                    "Decompressed size {decompressed_size} is over the maximum of {MAX_DECOMPRESSED_SIZE}"
+                )));
+            }
 
-            decoder
-                .read(&mut decompressed_payload)
+            // Reading a byte more than announced tells a payload that is too
+            // big without decompressing it all.
+            let mut decompressed_payload = Vec::with_capacity(decompressed_size as usize);
+            DecoderReader::new(&self.payload[..])
+                .take(u64::from(decompressed_size) + 1)
+                .read_to_end(&mut decompressed_payload)
                 .map_err(|e| Decompress.context(e))?;
 
-            if decompressed_payload.len() != decompressed_size
-                || crc32fast::hash(&decompressed_payload) != decompressed.1
-            {
+            if decompressed_payload.len() != decompressed_size as usize {
                 Err(Decompress.context(format!(
                     "Decompressed size {} was not expected {}",
                     decompressed_payload.len(),
                     decompressed_size
                 )))
+            } else if crc32fast::hash(&decompressed_payload) != crc32 {
+                Err(Decompress.context("Decompressed payload doesn't match its CRC32"))
             } else {
                 Ok(decompressed_payload)
             }
@@ -117,57 +144,358 @@ impl SplitPacket {
     }
 }
 
-pub(crate) struct ValveProtocol {
-    socket: UdpSocket,
+/// A received datagram, a whole reply or a fragment of a split one.
+pub(super) enum Datagram {
+    Whole(Packet),
+    Fragment(SplitPacket),
+}
+
+/// Read a datagram of a reply to a request of a server speaking `protocol`.
+pub(super) fn read_datagram(engine: &Engine, protocol: u8, data: &[u8]) -> GDResult<Datagram> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
+
+    let header: u8 = buffer.read()?;
+    buffer.move_cursor(-1)?;
+    match header {
+        0xFE => SplitPacket::new(engine, protocol, &mut buffer).map(Datagram::Fragment),
+        _ => Packet::new_from_bufferer(&mut buffer).map(Datagram::Whole),
+    }
+}
+
+/// The fragments of a split reply received so far. They can arrive out of
+/// order or duplicated, so they are indexed by their number until all of them
+/// are there.
+pub(super) struct Fragments {
+    id: u32,
+    total: u8,
+    packets: BTreeMap<u8, SplitPacket>,
+}
+
+impl Fragments {
+    pub(super) fn new(first: SplitPacket) -> Self {
+        let (id, total) = (first.id, first.total);
+
+        Self {
+            id,
+            total,
+            packets: BTreeMap::from([(first.number, first)]),
+        }
+    }
+
+    /// Add the fragment held by `data`, those of another response are
+    /// dropped, and so are duplicates by keeping the first one.
+    pub(super) fn add(&mut self, engine: &Engine, protocol: u8, data: &[u8]) -> GDResult<()> {
+        let packet = SplitPacket::new(engine, protocol, &mut Buffer::<LittleEndian>::new(data))?;
+        if packet.id == self.id {
+            self.packets.entry(packet.number).or_insert(packet);
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn is_complete(&self) -> bool { self.packets.len() >= self.total as usize }
+
+    /// The error of a reply whose remaining fragments didn't arrive.
+    pub(super) fn incomplete(&self) -> GDError {
+        PacketReceive.context(format!(
+            "Incomplete fragments: got {} of {}",
+            self.packets.len(),
+            self.total
+        ))
+    }
+
+    /// Join the fragments into the reply (decompressing it if it is).
+    pub(super) fn assemble(self) -> GDResult<Packet> {
+        let mut packets = self.packets.into_values();
+        let mut main_packet = packets.next().ok_or(PacketBad)?;
+        for packet in packets {
+            main_packet.payload.extend(packet.payload);
+        }
+
+        let payload = main_packet.get_payload()?;
+        Packet::new_from_bufferer(&mut Buffer::<LittleEndian>::new(&payload))
+    }
+}
+
+pub(crate) struct ValveProtocol<T: Transport = UdpTransport> {
+    transport: T,
     retry_count: usize,
+    /// Waited for before each retry of a request that got no answer.
+    retry_delay: Duration,
+    /// When the retries stop.
+    deadline: Option<Instant>,
+    /// The token handed out by the server in reply to a [PreProbe], appended
+    /// to every request.
+    token: Option<Vec<u8>>,
+    /// The last challenge the server handed out, reused for the following
+    /// requests instead of asking for a new one.
+    challenge: Option<Vec<u8>>,
+    /// How many times a request is sent again with a new challenge, see
+    /// [GatheringSettings::max_challenge_retries].
+    max_challenge_retries: usize,
+    /// Replies that arrived while another request was being made, kept for
+    /// when their request is made.
+    early_replies: Vec<Packet>,
+}
+
+pub(super) static PACKET_SIZE: usize = 6144;
+
+/// The largest size a compressed reply can announce, far over what the
+/// replies hold, as the payload is allocated for the announced size.
+const MAX_DECOMPRESSED_SIZE: u32 = 1 << 20;
+
+/// The kind of the `S2C_CHALLENGE` reply ('A').
+pub(super) const CHALLENGE_KIND: u8 = 0x41;
+
+/// The width of the challenge number sent in a `S2C_CHALLENGE` (0x41) reply.
+const CHALLENGE_SIZE: usize = 4;
+
+/// The payload of a request of `kind` sent with `challenge`: the info request
+/// appends it to its payload, the other requests are only made of it.
+pub(super) fn with_challenge(kind: u8, payload: Vec<u8>, challenge: Option<&[u8]>) -> Vec<u8> {
+    match (challenge, kind) {
+        (Some(challenge), INFO_REQUEST) => [payload, challenge.to_vec()].concat(),
+        (Some(challenge), _) => challenge.to_vec(),
+        (None, _) => payload,
+    }
+}
+
+const INFO_REQUEST: u8 = Request::Info as u8;
+
+/// Extract the challenge number from the payload of a `S2C_CHALLENGE` reply.
+///
+/// The reply is documented to carry a 4 bytes challenge, but some builds add
+/// an extra trailing byte, so only the documented width is taken and anything
+/// after it is ignored.
+pub(super) fn read_challenge(payload: &[u8]) -> GDResult<Vec<u8>> {
+    match payload.get(.. CHALLENGE_SIZE) {
+        Some(challenge) => Ok(challenge.to_vec()),
+        None => {
+            Err(PacketTooShort {
+                expected: CHALLENGE_SIZE,
+                got: payload.len(),
+                offset: 0,
+            }
+            .into())
+        }
+    }
+}
+
+/// The kind of the Source info reply ('I').
+const SOURCE_INFO_KIND: u8 = 0x49;
+/// The kind of the obsolete GoldSrc info reply ('m').
+const GOLDSRC_INFO_KIND: u8 = 0x6D;
+
+/// Whether an info reply of `kind` has the obsolete GoldSrc layout.
+///
+/// The kind of the reply tells the layouts apart, whatever the engine: GoldSrc
+/// servers updated since 2013 answer with the Source layout ('I'), while some
+/// Source engine frontends still answer with the obsolete one ('m'). The
+/// engine is only relied upon for other kinds, a forced GoldSrc engine then
+/// meaning the obsolete layout.
+fn is_goldsrc_info(engine: &Engine, kind: u8) -> bool {
+    match kind {
+        SOURCE_INFO_KIND => false,
+        GOLDSRC_INFO_KIND => true,
+        _ => *engine == Engine::GoldSrc(true),
+    }
+}
+
+/// Read the server's name, failing if it isn't null terminated within
+/// `max_length` bytes, as the following fields would be misread otherwise.
+fn read_name(buffer: &mut Buffer<LittleEndian>, max_length: usize) -> GDResult<String> {
+    let terminated = buffer
+        .remaining_bytes()
+        .iter()
+        .take(max_length.saturating_add(1))
+        .any(|byte| *byte == 0);
+    if !terminated {
+        return Err(PacketBad.context(format!(
+            "The server name isn't null terminated within {max_length} bytes"
+        )));
+    }
+
+    buffer.read_string::<Utf8Decoder>(None)
+}
+
+/// Read the keywords up to their null terminator, whatever they hold (plugins
+/// put URLs and arbitrary text in them), with their control bytes replaced by
+/// spaces. The keywords as sent are also returned.
+fn read_keywords(buffer: &mut Buffer<LittleEndian>) -> GDResult<(String, String)> {
+    let data = buffer.remaining_bytes();
+    let length = data
+        .iter()
+        .position(|byte| *byte == 0)
+        .ok_or_else(|| PacketBad.context("The keywords aren't null terminated"))?;
+    let raw = String::from_utf8_lossy(&data[.. length]).into_owned();
+    buffer.move_cursor(length as isize + 1)?;
+
+    let keywords = raw
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+
+    Ok((keywords, raw))
+}
+
+/// The kind of the reply to a request, `None` if it isn't one of the standard
+/// requests.
+const fn reply_kind(request: u8) -> Option<u8> {
+    match request {
+        0x54 => Some(0x49), // 'T' -> 'I'
+        0x55 => Some(0x44), // 'U' -> 'D'
+        0x56 => Some(0x45), // 'V' -> 'E'
+        _ => None,
+    }
+}
+
+/// Whether a reply answers another standard request than `request` (which is
+/// standard too), as some servers send them out of the expected order.
+pub(super) fn answers_another_request(request: u8, reply: u8) -> bool {
+    match reply_kind(request) {
+        Some(expected) => expected != reply && [0x49, 0x44, 0x45].contains(&reply),
+        None => false,
+    }
+}
+
+/// The kind of the reply some server frontends send instead of the info, to
+/// point at the actual server ('R'), its payload being the `ip:port` of that
+/// server (null terminated).
+///
+/// This isn't part of the A2S protocol (the Valve Developer Wiki's "Server
+/// queries" page defines no 'R' reply) but a convention of these frontends,
+/// so it is only recognized when following redirects, an 'R' reply otherwise
+/// being parsed as an info one (and failing) like any other unexpected reply.
+pub(super) const REDIRECT_KIND: u8 = 0x52;
+
+/// A number of redirects to follow with [query_with_max_redirects] before
+/// giving up, so that servers redirecting to each other don't make it loop
+/// forever.
+pub const DEFAULT_MAX_REDIRECTS: usize = 3;
+
+/// The read timeout of each port probed by [query_port_range], when no
+/// timeout settings are given, shorter than the default one so that a range
+/// of silent ports doesn't take long to go through.
+pub const DEFAULT_PORT_PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A reply, or the address of the server that should be asked instead.
+enum Redirectable<R> {
+    Reply(R),
+    Redirect(SocketAddr),
+}
+
+impl<R> Redirectable<R> {
+    /// The reply, a redirect being a loop of no redirects, for when they aren't
+    /// followed (and so shouldn't have been recognized).
+    fn into_reply(self) -> GDResult<R> {
+        match self {
+            Self::Reply(reply) => Ok(reply),
+            Self::Redirect(address) => Err(redirect_loop(address, 0)),
+        }
+    }
+}
+
+/// The error of a query still redirected (to `address`) after following
+/// `redirects` redirects.
+pub(super) fn redirect_loop(address: SocketAddr, redirects: usize) -> GDError {
+    PacketParse.context(format!(
+        "redirect loop: still redirected (to {address}) after {redirects} redirects"
+    ))
 }
 
-static PACKET_SIZE: usize = 6144;
+/// Extract the address to query instead from the payload of a redirect reply.
+pub(super) fn read_redirect(payload: &[u8]) -> GDResult<SocketAddr> {
+    let address = Buffer::<LittleEndian>::new(payload).read_string::<Utf8Decoder>(None)?;
+
+    address
+        .parse()
+        .map_err(|_| PacketBad.context(format!("Invalid redirect address \"{address}\"")))
+}
 
 impl ValveProtocol {
     pub fn new(address: &SocketAddr, timeout_settings: Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = UdpSocket::new(address, &timeout_settings)?;
+        // The retries end by the socket's deadline.
+        let timeout_settings = TimeoutSettings::start_deadline_of(timeout_settings);
+        let transport = UdpTransport::connect(address, &timeout_settings)?;
         let retry_count = timeout_settings.as_ref().map_or_else(
             || TimeoutSettings::default().get_retries(),
             TimeoutSettings::get_retries,
         );
 
-        Ok(Self {
-            socket,
+        Ok(Self::with_transport(transport, retry_count).with_retry_delay(&timeout_settings))
+    }
+}
+
+impl<T: Transport> ValveProtocol<T> {
+    pub fn with_transport(mut transport: T, retry_count: usize) -> Self {
+        transport.set_protocol_hint("valve");
+
+        Self {
+            transport,
             retry_count,
-        })
+            retry_delay: Duration::ZERO,
+            deadline: None,
+            token: None,
+            challenge: None,
+            max_challenge_retries: DEFAULT_MAX_CHALLENGE_RETRIES,
+            early_replies: Vec::new(),
+        }
     }
 
-    fn receive(&mut self, engine: &Engine, protocol: u8, buffer_size: usize) -> GDResult<Packet> {
-        let data = self.socket.receive(Some(buffer_size))?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
-
-        let header: u8 = buffer.read()?;
-        buffer.move_cursor(-1)?;
-        if header == 0xFE {
-            // the packet is split
-            let mut main_packet = SplitPacket::new(engine, protocol, &mut buffer)?;
-            let mut chunk_packets = Vec::with_capacity((main_packet.total - 1) as usize);
-
-            for _ in 1 .. main_packet.total {
-                let new_data = self.socket.receive(Some(buffer_size))?;
-                buffer = Buffer::<LittleEndian>::new(&new_data);
-                let chunk_packet = SplitPacket::new(engine, protocol, &mut buffer)?;
-                chunk_packets.push(chunk_packet);
-            }
+    /// Wait for the retry delay of the timeout settings between the retries,
+    /// which stop at their deadline.
+    fn with_retry_delay(self, timeout_settings: &Option<TimeoutSettings>) -> Self {
+        Self {
+            retry_delay: TimeoutSettings::get_retry_delay_or_default(timeout_settings),
+            deadline: TimeoutSettings::get_deadline_end_or_default(timeout_settings),
+            ..self
+        }
+    }
 
-            chunk_packets.sort_by(|a, b| a.number.cmp(&b.number));
+    /// The round trip time of the first request, see [Transport::ping].
+    pub(crate) fn ping(&self) -> Option<Duration> { self.transport.ping() }
 
-            for chunk_packet in chunk_packets {
-                main_packet.payload.extend(chunk_packet.payload);
-            }
+    /// Send the probe and keep the token from its reply for the following
+    /// requests.
+    /// This function will retry fetch on timeouts.
+    pub fn pre_probe(&mut self, probe: &PreProbe) -> GDResult<()> {
+        let token = retry_on_timeout_with_delay(self.retry_count, self.retry_delay, self.deadline, || {
+            self.transport.send(&probe.request)?;
+            probe.read_token(&self.transport.recv_timeout(Some(PACKET_SIZE))?)
+        })?;
 
-            let payload = main_packet.get_payload()?; // Creating a non-temporary value here
-            let mut new_packet_buffer = Buffer::<LittleEndian>::new(&payload); // Using the non-temporary value here
-            Ok(Packet::new_from_bufferer(&mut new_packet_buffer)?)
-        } else {
-            Packet::new_from_bufferer(&mut buffer)
+        self.token = Some(token);
+
+        Ok(())
+    }
+
+    /// Send a request, with the probe token appended if there is one.
+    fn send_request(&mut self, kind: u8, payload: Vec<u8>) -> GDResult<()> {
+        let mut data = Packet::new(kind, payload).to_bytes();
+        if let Some(token) = &self.token {
+            data.extend_from_slice(token);
         }
+
+        self.transport.send(&data)
+    }
+
+    fn receive(&mut self, engine: &Engine, protocol: u8, buffer_size: usize) -> GDResult<Packet> {
+        let data = self.transport.recv_timeout(Some(buffer_size))?;
+
+        let mut fragments = match read_datagram(engine, protocol, &data)? {
+            Datagram::Whole(packet) => return Ok(packet),
+            Datagram::Fragment(first) => Fragments::new(first),
+        };
+        while !fragments.is_complete() {
+            let new_data = match self.transport.recv_timeout(Some(buffer_size)) {
+                Ok(data) => data,
+                Err(e) if e.is_retryable() => return Err(fragments.incomplete()),
+                Err(e) => return Err(e),
+            };
+            fragments.add(engine, protocol, &new_data)?;
+        }
+
+        fragments.assemble()
     }
 
     pub fn get_kind_request_data(&mut self, engine: &Engine, protocol: u8, kind: Request) -> GDResult<Vec<u8>> {
@@ -178,7 +506,14 @@ impl ValveProtocol {
     /// Ask for a specific request only.
     /// This function will retry fetch on timeouts.
     pub fn get_request_data(&mut self, engine: &Engine, protocol: u8, kind: u8, payload: Vec<u8>) -> GDResult<Vec<u8>> {
-        retry_on_timeout(self.retry_count, || {
+        self.get_request_packet(engine, protocol, kind, payload)
+            .map(|packet| packet.payload)
+    }
+
+    /// Same as [get_request_data](Self::get_request_data), keeping the kind
+    /// of the reply.
+    fn get_request_packet(&mut self, engine: &Engine, protocol: u8, kind: u8, payload: Vec<u8>) -> GDResult<Packet> {
+        retry_on_timeout_with_delay(self.retry_count, self.retry_delay, self.deadline, || {
             self.get_request_data_impl(engine, protocol, kind, payload.clone())
         })
     }
@@ -190,37 +525,72 @@ impl ValveProtocol {
         protocol: u8,
         kind: u8,
         payload: Vec<u8>,
-    ) -> GDResult<Vec<u8>> {
-        let request_initial_packet = Packet::new(kind, payload).to_bytes();
-        self.socket.send(&request_initial_packet)?;
+    ) -> GDResult<Packet> {
+        if let Some(index) = self
+            .early_replies
+            .iter()
+            .position(|packet| reply_kind(kind) == Some(packet.kind))
+        {
+            return Ok(self.early_replies.remove(index));
+        }
 
-        let mut packet = self.receive(engine, protocol, PACKET_SIZE)?;
-        while packet.kind == 0x41 {
-            // 'A'
-            let challenge = packet.payload;
+        // The request is first sent without a challenge, servers predating the
+        // 2020 challenge requirement answer it right away. Once the server
+        // handed one out (usually in reply to the info request, unless the
+        // caller already had one) it is sent right away with the following
+        // requests, saving the round trip of asking for it again.
+        let mut sent_with_challenge = self.challenge.is_some();
+        self.send_request(kind, with_challenge(kind, payload, self.challenge.as_deref()))?;
 
-            const INFO: u8 = Request::Info as u8;
-            let challenge_packet = Packet::new(
-                kind,
-                match kind {
-                    INFO => [Request::Info.get_default_payload(), challenge].concat(),
-                    _ => challenge,
-                },
-            )
-            .to_bytes();
+        let mut packet = self.receive_reply(engine, protocol, kind)?;
+        // A challenge in reply to a request that was sent with one replaces
+        // an expired challenge.
+        let mut retries = 0;
+        while packet.kind == CHALLENGE_KIND {
+            if sent_with_challenge {
+                if retries == self.max_challenge_retries {
+                    return Err(PacketBad.context(format!(
+                        "Still handed out a new challenge after {retries} retries"
+                    )));
+                }
+                retries += 1;
+            }
+            sent_with_challenge = true;
 
-            self.socket.send(&challenge_packet)?;
+            let challenge = read_challenge(&packet.payload)?;
+            self.send_request(
+                kind,
+                with_challenge(kind, Request::Info.get_default_payload(), Some(&challenge)),
+            )?;
+            self.challenge = Some(challenge);
 
-            packet = self.receive(engine, protocol, PACKET_SIZE)?;
+            packet = self.receive_reply(engine, protocol, kind)?;
         }
 
-        Ok(packet.payload)
+        Ok(packet)
     }
 
-    fn get_goldsrc_server_info(buffer: &mut Buffer<LittleEndian>) -> GDResult<ServerInfo> {
-        let _header: u8 = buffer.read()?; //get the header (useless info)
+    /// Receive the reply to a request (or a challenge), the replies to the
+    /// other requests that arrive before it being kept for when they are made
+    /// instead of being taken for it.
+    fn receive_reply(&mut self, engine: &Engine, protocol: u8, kind: u8) -> GDResult<Packet> {
+        loop {
+            let packet = self.receive(engine, protocol, PACKET_SIZE)?;
+            if !answers_another_request(kind, packet.kind) {
+                return Ok(packet);
+            }
+
+            // Only the latest reply of each kind is kept.
+            self.early_replies.retain(|early| early.kind != packet.kind);
+            self.early_replies.push(packet);
+        }
+    }
+
+    /// Parse an obsolete GoldSrc info reply (protocol 47 and below), which
+    /// starts with the server's address and doesn't have the app id.
+    fn get_goldsrc_server_info(buffer: &mut Buffer<LittleEndian>, max_name_length: usize) -> GDResult<ServerInfo> {
         let _address: String = buffer.read_string::<Utf8Decoder>(None)?; //get the server address (useless info)
-        let name = buffer.read_string::<Utf8Decoder>(None)?;
+        let name = read_name(buffer, max_name_length)?;
         let map = buffer.read_string::<Utf8Decoder>(None)?;
         let folder = buffer.read_string::<Utf8Decoder>(None)?;
         let game_mode = buffer.read_string::<Utf8Decoder>(None)?;
@@ -236,16 +606,20 @@ impl ValveProtocol {
         let environment_type = match buffer.read::<u8>()? {
             76 => Environment::Linux,   //'L'
             87 => Environment::Windows, //'W'
-            _ => Err(UnknownEnumCast)?,
+            value => Environment::from_gldsrc(value),
         };
         let has_password = buffer.read::<u8>()? == 1;
         let is_mod = buffer.read::<u8>()? == 1;
         let mod_data = match is_mod {
             false => None,
             true => {
+                let link = buffer.read_string::<Utf8Decoder>(None)?;
+                let download_link = buffer.read_string::<Utf8Decoder>(None)?;
+                let _null: u8 = buffer.read()?;
+
                 Some(ModData {
-                    link: buffer.read_string::<Utf8Decoder>(None)?,
-                    download_link: buffer.read_string::<Utf8Decoder>(None)?,
+                    link,
+                    download_link,
                     version: buffer.read()?,
                     size: buffer.read()?,
                     multiplayer_only: buffer.read::<u8>()? == 1,
@@ -263,6 +637,7 @@ impl ValveProtocol {
             folder,
             game_mode,
             appid: 0, // not present in the obsolete response
+            app_id_short: 0,
             players_online: players,
             players_maximum: max_players,
             players_bots: bots,
@@ -278,28 +653,56 @@ impl ValveProtocol {
         })
     }
 
-    /// Get the server information's.
+    /// Get the server information's, redirects not being followed.
+    #[cfg(test)]
     fn get_server_info(&mut self, engine: &Engine) -> GDResult<ServerInfo> {
-        let data = self.get_kind_request_data(engine, 0, Request::Info)?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
+        self.get_info_reply(engine, super::types::DEFAULT_MAX_NAME_LENGTH, false)?
+            .into_reply()
+            .map(|(info, _)| info)
+    }
 
-        if let Engine::GoldSrc(force) = engine {
-            if *force {
-                return Self::get_goldsrc_server_info(&mut buffer);
-            }
+    /// Get the server information's along with the payload they were parsed
+    /// from, or where the server redirects to if `follow_redirects`.
+    fn get_info_reply(
+        &mut self,
+        engine: &Engine,
+        max_name_length: usize,
+        follow_redirects: bool,
+    ) -> GDResult<Redirectable<(ServerInfo, Vec<u8>)>> {
+        let packet = self.get_request_packet(
+            engine,
+            0,
+            Request::Info as u8,
+            Request::Info.get_default_payload(),
+        )?;
+        if follow_redirects && packet.kind == REDIRECT_KIND {
+            return Ok(Redirectable::Redirect(read_redirect(&packet.payload)?));
+        }
+
+        let info = Self::parse_server_info(engine, &packet, max_name_length)?;
+        Ok(Redirectable::Reply((info, packet.payload)))
+    }
+
+    /// Parse the payload of an info reply.
+    pub(super) fn parse_server_info(engine: &Engine, packet: &Packet, max_name_length: usize) -> GDResult<ServerInfo> {
+        let mut buffer = Buffer::<LittleEndian>::new(&packet.payload);
+
+        if is_goldsrc_info(engine, packet.kind) {
+            return Self::get_goldsrc_server_info(&mut buffer, max_name_length);
         }
 
         let protocol = buffer.read()?;
-        let name = buffer.read_string::<Utf8Decoder>(None)?;
+        let name = read_name(&mut buffer, max_name_length)?;
         let map = buffer.read_string::<Utf8Decoder>(None)?;
         let folder = buffer.read_string::<Utf8Decoder>(None)?;
         let game_mode = buffer.read_string::<Utf8Decoder>(None)?;
-        let mut appid = buffer.read::<u16>()? as u32;
+        let app_id_short = buffer.read::<u16>()?;
+        let mut appid = app_id_short as u32;
         let players = buffer.read()?;
         let max_players = buffer.read()?;
         let bots = buffer.read()?;
         let server_type = Server::from_gldsrc(buffer.read()?)?;
-        let environment_type = Environment::from_gldsrc(buffer.read()?)?;
+        let environment_type = Environment::from_gldsrc(buffer.read()?);
         let has_password = buffer.read::<u8>()? == 1;
         let vac_secured = buffer.read::<u8>()? == 1;
         let the_ship = match *engine == Engine::new(2400) {
@@ -316,6 +719,8 @@ impl ValveProtocol {
         let extra_data = match buffer.read::<u8>() {
             Err(_) => None,
             Ok(value) => {
+                // Set along the keywords, the fields being read in order.
+                let mut raw_keywords = None;
                 Some(ExtraData {
                     port: match (value & 0x80) > 0 {
                         false => None,
@@ -335,8 +740,14 @@ impl ValveProtocol {
                     },
                     keywords: match (value & 0x20) > 0 {
                         false => None,
-                        true => Some(buffer.read_string::<Utf8Decoder>(None)?),
+                        true => {
+                            let (keywords, raw) = read_keywords(&mut buffer)?;
+                            raw_keywords = Some(raw);
+
+                            Some(keywords)
+                        }
                     },
+                    raw_keywords,
                     game_id: match (value & 0x01) > 0 {
                         false => None,
                         true => {
@@ -357,6 +768,7 @@ impl ValveProtocol {
             folder,
             game_mode,
             appid,
+            app_id_short,
             players_online: players,
             players_maximum: max_players,
             players_bots: bots,
@@ -375,54 +787,70 @@ impl ValveProtocol {
     /// Get the server player's.
     fn get_server_players(&mut self, engine: &Engine, protocol: u8) -> GDResult<Vec<ServerPlayer>> {
         let data = self.get_kind_request_data(engine, protocol, Request::Players)?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
-
-        let count = buffer.read::<u8>()? as usize;
-        let mut players: Vec<ServerPlayer> = Vec::with_capacity(count);
-
-        for _ in 0 .. count {
-            buffer.move_cursor(1)?; //skip the index byte
-
-            players.push(ServerPlayer {
-                name: buffer.read_string::<Utf8Decoder>(None)?,
-                score: buffer.read()?,
-                duration: buffer.read()?,
-                deaths: match *engine == Engine::new(2400) {
-                    false => None,
-                    true => Some(buffer.read()?),
-                },
-                money: match *engine == Engine::new(2400) {
-                    false => None,
-                    true => Some(buffer.read()?),
-                },
-            });
-        }
 
-        Ok(players)
+        parse_players(engine, &data)
     }
 
     /// Get the server's rules.
     fn get_server_rules(&mut self, engine: &Engine, protocol: u8) -> GDResult<HashMap<String, String>> {
         let data = self.get_kind_request_data(engine, protocol, Request::Rules)?;
-        let mut buffer = Buffer::<LittleEndian>::new(&data);
 
-        let count = buffer.read::<u16>()? as usize;
-        let mut rules: HashMap<String, String> = HashMap::with_capacity(count);
+        parse_rules(engine, &data)
+    }
+}
+
+/// Parse the payload of a players reply.
+pub(super) fn parse_players(engine: &Engine, data: &[u8]) -> GDResult<Vec<ServerPlayer>> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
 
-        for _ in 0 .. count {
-            let name = buffer.read_string::<Utf8Decoder>(None)?;
-            let value = buffer.read_string::<Utf8Decoder>(None)?;
+    let count = buffer.read::<u8>()? as usize;
+    let mut players: Vec<ServerPlayer> = Vec::with_capacity(count);
 
-            rules.insert(name, value);
-        }
+    for _ in 0 .. count {
+        players.push(ServerPlayer {
+            // Not necessarily 0 (nor sequential), it's only kept as is.
+            index: buffer.read()?,
+            name: buffer.read_string::<Utf8Decoder>(None)?,
+            score: buffer.read()?,
+            duration: buffer.read()?,
+            deaths: None,
+            money: None,
+        });
+    }
 
-        if *engine == Engine::new(632_360) {
-            // ROR2
-            rules.remove("Test");
+    // The Ship sends the deaths and money of the players once all of
+    // them are listed, not along each of them. Servers leaving them out
+    // still get their players.
+    if *engine == Engine::new(2400) && buffer.remaining_length() >= players.len() * 8 {
+        for player in &mut players {
+            player.deaths = Some(buffer.read()?);
+            player.money = Some(buffer.read()?);
         }
+    }
 
-        Ok(rules)
+    Ok(players)
+}
+
+/// Parse the payload of a rules reply.
+pub(super) fn parse_rules(engine: &Engine, data: &[u8]) -> GDResult<HashMap<String, String>> {
+    let mut buffer = Buffer::<LittleEndian>::new(data);
+
+    let count = buffer.read::<u16>()? as usize;
+    let mut rules: HashMap<String, String> = HashMap::with_capacity(count);
+
+    for _ in 0 .. count {
+        let name = buffer.read_string::<Utf8Decoder>(None)?;
+        let value = buffer.read_string::<Utf8Decoder>(None)?;
+
+        rules.insert(name, value);
+    }
+
+    if *engine == Engine::new(632_360) {
+        // ROR2
+        rules.remove("Test");
     }
+
+    Ok(rules)
 }
 
 /// Query a server by providing the address, the port, the app, gather and
@@ -430,45 +858,249 @@ impl ValveProtocol {
 /// default values for them
 /// (GatherSettings::[default](GatheringSettings::default),
 /// TimeoutSettings::[default](TimeoutSettings::default)).
+///
+/// Redirects to another server aren't followed, see [query_with_max_redirects]
+/// to follow them.
 pub fn query(
     address: &SocketAddr,
     engine: Engine,
     gather_settings: Option<GatheringSettings>,
     timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<Response> {
+    query_with_max_redirects(address, engine, gather_settings, timeout_settings, 0)
+}
+
+/// Same as [query], also returning the packets exchanged with the server
+/// (even if the query failed, for example because a reply couldn't be
+/// parsed), to attach them to bug reports.
+///
+/// Redirects aren't followed.
+pub fn query_debug(
+    address: &SocketAddr,
+    engine: Engine,
+    gather_settings: Option<GatheringSettings>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> (GDResult<Response>, Vec<ExchangedPacket>) {
+    let timeout_settings = TimeoutSettings::start_deadline_of(timeout_settings);
+    let transport = match RecordingTransport::<UdpTransport>::connect(address, &timeout_settings) {
+        Ok(transport) => transport,
+        Err(error) => return (Err(error), Vec::new()),
+    };
+    let mut client =
+        ValveProtocol::with_transport(transport, TimeoutSettings::get_retries_or_default(&timeout_settings))
+            .with_retry_delay(&timeout_settings);
+
+    let result = get_response(&mut client, engine, gather_settings.unwrap_or_default()).map(|mut response| {
+        response.response_address = Some(*address);
+        response
+    });
+
+    (result, client.transport.into_packets())
+}
+
+/// Same as [query], following at most `max_redirects` redirects (0 not
+/// following any), the address of the server that finally answered being
+/// the response's `response_address`.
+///
+/// Redirects point the query at any address, they should only be followed
+/// for servers known to be behind a frontend, [DEFAULT_MAX_REDIRECTS] being
+/// a sensible limit.
+///
+/// # Errors
+/// Returns a [PacketParse] error (a redirect loop) if the server that would
+/// be queried after `max_redirects` redirects redirects again.
+pub fn query_with_max_redirects(
+    address: &SocketAddr,
+    engine: Engine,
+    gather_settings: Option<GatheringSettings>,
+    timeout_settings: Option<TimeoutSettings>,
+    max_redirects: usize,
+) -> GDResult<Response> {
+    let gather_settings = gather_settings.unwrap_or_default();
+    // The servers redirected to are queried within the same deadline.
+    let timeout_settings = TimeoutSettings::start_deadline_of(timeout_settings);
+    let mut address = *address;
+
+    for _ in 0 ..= max_redirects {
+        let transport = UdpTransport::connect(&address, &timeout_settings)?;
+        let mut client =
+            ValveProtocol::with_transport(transport, TimeoutSettings::get_retries_or_default(&timeout_settings))
+                .with_retry_delay(&timeout_settings);
+
+        match get_response_or_redirect(&mut client, engine, gather_settings, max_redirects > 0)? {
+            Redirectable::Reply(mut response) => {
+                response.response_address = Some(address);
+                return Ok(response);
+            }
+            Redirectable::Redirect(redirect) => address = redirect,
+        }
+    }
+
+    Err(redirect_loop(address, max_redirects))
+}
+
+/// Query a server over the given transport, retrying `retries` times on
+/// timeouts, this allows for routing the query through a proxy or an already
+/// connected socket, or replacing the network entirely (see
+/// [MockTransport](crate::transport::MockTransport)).
+///
+/// Redirects can't be followed over the transport.
+pub fn query_with_transport<T: Transport>(
+    transport: T,
+    engine: Engine,
+    gather_settings: Option<GatheringSettings>,
+    retries: usize,
 ) -> GDResult<Response> {
     let response_gather_settings = gather_settings.unwrap_or_default();
-    get_response(address, engine, response_gather_settings, timeout_settings)
+    get_response(
+        &mut ValveProtocol::with_transport(transport, retries),
+        engine,
+        response_gather_settings,
+    )
 }
 
-fn get_response(
+/// Query a server that only answers requests carrying a token handed out in
+/// reply to an initial probe (as some anti-DDoS frontends do), the probe is
+/// sent once before the actual requests.
+/// Servers that don't need it should be queried with [query], which doesn't
+/// make this extra round trip.
+pub fn query_with_pre_probe(
     address: &SocketAddr,
     engine: Engine,
-    gather_settings: GatheringSettings,
+    gather_settings: Option<GatheringSettings>,
     timeout_settings: Option<TimeoutSettings>,
+    pre_probe: &PreProbe,
 ) -> GDResult<Response> {
     let mut client = ValveProtocol::new(address, timeout_settings)?;
+    client.pre_probe(pre_probe)?;
 
-    let info = client.get_server_info(&engine)?;
+    let mut response = get_response(&mut client, engine, gather_settings.unwrap_or_default())?;
+    response.response_address = Some(*address);
 
-    if let Engine::Source(Some(appids)) = &engine {
-        let mut is_specified_id = false;
+    Ok(response)
+}
 
-        if appids.0 == info.appid {
-            is_specified_id = true;
-        } else if let Some(dedicated_appid) = appids.1 {
-            if dedicated_appid == info.appid {
-                is_specified_id = true;
-            }
+/// Query the `ports` of a server in turn, until one of them answers, for
+/// servers that don't run on their game's default port, returning the
+/// response along with the port that answered.
+///
+/// The timeout settings apply to each port, [DEFAULT_PORT_PROBE_TIMEOUT]
+/// being the read timeout if there are none. Redirects aren't followed.
+///
+/// # Errors
+/// Returns the error of the last port if none of them answered (port 0 being
+/// an [InvalidInput] error), or an [InvalidInput] error if there were no
+/// ports to query.
+pub fn query_port_range<I: IntoIterator<Item = u16>>(
+    address: &IpAddr,
+    ports: I,
+    engine: Engine,
+    gather_settings: Option<GatheringSettings>,
+    timeout_settings: Option<TimeoutSettings>,
+) -> GDResult<(game::Response, u16)> {
+    let timeout_settings = match timeout_settings {
+        Some(timeout_settings) => timeout_settings,
+        None => TimeoutSettings::new(Some(DEFAULT_PORT_PROBE_TIMEOUT), None, None, 0)?,
+    };
+
+    let mut last_error = InvalidInput.context("No ports to query");
+    for port in ports {
+        if port == 0 {
+            last_error = InvalidInput.context("Port 0 can't be queried");
+            continue;
         }
 
-        if !is_specified_id && gather_settings.check_app_id {
-            return Err(BadGame.context(format!("AppId: {}", info.appid)));
+        let address = SocketAddr::new(*address, port);
+        match query_with_max_redirects(&address, engine, gather_settings, Some(timeout_settings), 0) {
+            Ok(response) => return Ok((game::Response::new_from_valve_response(response), port)),
+            Err(error) => last_error = error,
         }
     }
 
+    Err(last_error)
+}
+
+/// Get the address of a server from what can be copied from the Steam server
+/// browser or shared by players:
+/// - a console command: `connect 1.2.3.4:27015` (a following `; password x`
+///   being ignored)
+/// - a Steam URL: `steam://connect/1.2.3.4:27015` (a following `/password`
+///   being ignored)
+/// - a bare `host:port`
+///
+/// Hosts that aren't IP addresses are resolved, the resolution being noted in
+/// the capture (see `capture::annotate_hostname`, with the `packet_capture`
+/// feature) if one is set up.
+///
+/// # Errors
+/// Returns an [InvalidInput] error if the text is none of these, and a
+/// [HostLookup] error if the host couldn't be resolved.
+pub fn parse_connect_string(connect: &str) -> GDResult<SocketAddr> {
+    let connect = connect.trim();
+    let address = if let Some(command) = connect.strip_prefix("connect ") {
+        command.split(';').next().unwrap_or_default()
+    } else if let Some(url) = connect.strip_prefix("steam://connect/") {
+        url.split('/').next().unwrap_or_default()
+    } else {
+        connect
+    }
+    .trim();
+
+    let (host, port) = address
+        .rsplit_once(':')
+        .filter(|(host, _)| !host.is_empty() && !host.contains(char::is_whitespace))
+        .ok_or_else(|| {
+            InvalidInput.context(format!(
+                "Expected `connect host:port`, `steam://connect/host:port` or `host:port`, got \"{connect}\""
+            ))
+        })?;
+    let port: u16 = port
+        .parse()
+        .map_err(|_| InvalidInput.context(format!("Invalid port \"{port}\" in \"{connect}\"")))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let address = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| HostLookup.context(e))?
+        .next()
+        .ok_or_else(|| HostLookup.context(format!("No socket addresses found for \"{host}\"")))?;
+
+    #[cfg(feature = "packet_capture")]
+    if host.parse::<IpAddr>().is_err() {
+        crate::capture::annotate_hostname(host, &address.ip());
+    }
+
+    Ok(address)
+}
+
+fn get_response<T: Transport>(
+    client: &mut ValveProtocol<T>,
+    engine: Engine,
+    gather_settings: GatheringSettings,
+) -> GDResult<Response> {
+    get_response_or_redirect(client, engine, gather_settings, false)?.into_reply()
+}
+
+fn get_response_or_redirect<T: Transport>(
+    client: &mut ValveProtocol<T>,
+    engine: Engine,
+    gather_settings: GatheringSettings,
+    follow_redirects: bool,
+) -> GDResult<Redirectable<Response>> {
+    if let Some(challenge) = gather_settings.challenge {
+        client.challenge = Some(challenge.to_le_bytes().to_vec());
+    }
+    client.max_challenge_retries = gather_settings.max_challenge_retries;
+
+    let (info, raw_info) = match client.get_info_reply(&engine, gather_settings.max_name_length, follow_redirects)? {
+        Redirectable::Reply(reply) => reply,
+        Redirectable::Redirect(address) => return Ok(Redirectable::Redirect(address)),
+    };
+    let info = check_info(&engine, info, &gather_settings)?;
+
     let protocol = info.protocol_version;
 
-    Ok(Response {
+    Ok(Redirectable::Reply(Response {
         info,
         players: maybe_gather!(
             gather_settings.players,
@@ -478,5 +1110,1181 @@ fn get_response(
             gather_settings.rules,
             client.get_server_rules(&engine, protocol)
         ),
-    })
+        response_address: None,
+        queried_at: Some(SystemTime::now()),
+        challenge: client
+            .challenge
+            .as_deref()
+            .and_then(|challenge| challenge.try_into().ok())
+            .map(i32::from_le_bytes),
+        raw: gather_settings.keep_raw.then_some(raw_info),
+        ping: client.ping(),
+    }))
+}
+
+/// Check that the server runs the engine's app (if the settings say so), and
+/// drop what the settings don't keep from its info.
+pub(super) fn check_info(
+    engine: &Engine,
+    mut info: ServerInfo,
+    gather_settings: &GatheringSettings,
+) -> GDResult<ServerInfo> {
+    if !gather_settings.keep_raw {
+        if let Some(extra_data) = info.extra_data.as_mut() {
+            extra_data.raw_keywords = None;
+        }
+    }
+
+    if let Engine::Source(Some(appids)) = engine {
+        let mut is_specified_id = false;
+
+        if appids.0 == info.appid {
+            is_specified_id = true;
+        } else if let Some(dedicated_appid) = appids.1 {
+            if dedicated_appid == info.appid {
+                is_specified_id = true;
+            }
+        }
+
+        if !is_specified_id && gather_settings.check_app_id {
+            return Err(BadGame.context(format!("AppId: {}", info.appid)));
+        }
+    }
+
+    Ok(info)
+}
+
+/// The fields of a Source `A2S_INFO` reply, built into one by
+/// [build](Self::build), for the tests of the protocol and its users, the
+/// default ones being those of a Team Fortress 2 server.
+#[cfg(test)]
+pub(crate) struct TestInfoReply {
+    pub protocol: u8,
+    /// The name, map, folder and game, each null terminated.
+    pub strings: String,
+    pub app_id: u16,
+    /// The players, maximum players, bots, server type, environment,
+    /// visibility and VAC bytes.
+    pub counts: [u8; 7],
+    pub version: &'static str,
+    /// The extra data flags and the fields they announce, if there is extra
+    /// data.
+    pub extra_data: Option<(u8, Vec<u8>)>,
+}
+
+#[cfg(test)]
+impl Default for TestInfoReply {
+    /// With the port, steam id, keywords and game id extra data fields.
+    fn default() -> Self {
+        let mut fields = 27015u16.to_le_bytes().to_vec();
+        fields.extend_from_slice(&85_568_392_920_040_000u64.to_le_bytes());
+        fields.extend_from_slice(b"alltalk,increased_maxplayers\0");
+        fields.extend_from_slice(&440u64.to_le_bytes());
+
+        Self {
+            protocol: 17,
+            strings: "gamedig\0cp_dustbowl\0tf\0Team Fortress\0".to_string(),
+            app_id: 440,
+            counts: [2, 24, 0, b'd', b'l', 0, 1],
+            version: "8622567",
+            extra_data: Some((0x80 | 0x10 | 0x20 | 0x01, fields)),
+        }
+    }
+}
+
+#[cfg(test)]
+impl TestInfoReply {
+    pub fn build(&self) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xFF, 0xFF, 0xFF, SOURCE_INFO_KIND, self.protocol];
+        data.extend_from_slice(self.strings.as_bytes());
+        data.extend_from_slice(&self.app_id.to_le_bytes());
+        data.extend_from_slice(&self.counts);
+        data.extend_from_slice(self.version.as_bytes());
+        data.push(0);
+        if let Some((flags, fields)) = &self.extra_data {
+            data.push(*flags);
+            data.extend_from_slice(fields);
+        }
+
+        data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{MockTransport, PacketDirection};
+    use crate::GDErrorKind::{PacketSend, PacketUnderflow};
+    use std::thread;
+
+    /// A `S2C_CHALLENGE` reply carrying the documented 4 bytes challenge.
+    const CHALLENGE_REPLY: [u8; 9] = [0xFF, 0xFF, 0xFF, 0xFF, 0x41, 0x4B, 0xA1, 0x25, 0x09];
+    /// The same reply as sent by builds that append an extra byte.
+    const CHALLENGE_REPLY_EXTRA_BYTE: [u8; 10] = [0xFF, 0xFF, 0xFF, 0xFF, 0x41, 0x4B, 0xA1, 0x25, 0x09, 0x00];
+
+    fn challenge_of(data: &[u8]) -> GDResult<Vec<u8>> {
+        let packet = Packet::new_from_bufferer(&mut Buffer::<LittleEndian>::new(data))?;
+        assert_eq!(packet.kind, 0x41);
+
+        read_challenge(&packet.payload)
+    }
+
+    #[test]
+    fn challenge_4_bytes() {
+        assert_eq!(challenge_of(&CHALLENGE_REPLY).unwrap(), [0x4B, 0xA1, 0x25, 0x09]);
+    }
+
+    #[test]
+    fn challenge_5_bytes() {
+        assert_eq!(
+            challenge_of(&CHALLENGE_REPLY_EXTRA_BYTE).unwrap(),
+            [0x4B, 0xA1, 0x25, 0x09]
+        );
+    }
+
+    #[test]
+    fn challenge_too_short() {
+        assert!(challenge_of(&CHALLENGE_REPLY[.. 7]).is_err());
+    }
+
+    fn info_reply() -> Vec<u8> { TestInfoReply::default().build() }
+
+    /// A `A2S_PLAYER` reply with two players.
+    fn players_reply() -> Vec<u8> {
+        let mut data = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x44, 2];
+        for (index, name, score, duration) in [(0u8, "alice", 5i32, 60.5f32), (1, "bob", -1, 12.0)] {
+            data.push(index);
+            data.extend_from_slice(name.as_bytes());
+            data.push(0);
+            data.extend_from_slice(&score.to_le_bytes());
+            data.extend_from_slice(&duration.to_le_bytes());
+        }
+
+        data
+    }
+
+    /// A `A2S_RULES` reply with a single rule.
+    fn rules_reply() -> Vec<u8> {
+        let mut data = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45];
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(b"mp_timelimit\0");
+        data.extend_from_slice(b"30\0");
+
+        data
+    }
+
+    #[test]
+    fn query_over_mock_transport() {
+        let transport = MockTransport::new([
+            CHALLENGE_REPLY.to_vec(),
+            info_reply(),
+            CHALLENGE_REPLY_EXTRA_BYTE.to_vec(),
+            players_reply(),
+            rules_reply(),
+        ]);
+
+        let response = query_with_transport(transport, Engine::new(440), None, 0).unwrap();
+
+        assert_eq!(response.info.name, "gamedig");
+        assert_eq!(response.info.map, "cp_dustbowl");
+        assert_eq!(response.info.appid, 440);
+        assert_eq!(response.info.players_online, 2);
+        assert_eq!(response.info.server_type, Server::Dedicated);
+        assert_eq!(response.info.environment_type, Environment::Linux);
+        assert!(response.info.vac_secured);
+
+        let extra_data = response.info.extra_data.unwrap();
+        assert_eq!(extra_data.port, Some(27015));
+        assert_eq!(
+            extra_data.keywords.as_deref(),
+            Some("alltalk,increased_maxplayers")
+        );
+
+        let players = response.players.unwrap();
+        assert_eq!(players.len(), 2);
+        assert_eq!(players[0].name, "alice");
+        assert_eq!(players[1].score, -1);
+
+        assert_eq!(
+            response.rules.unwrap().get("mp_timelimit").map(String::as_str),
+            Some("30")
+        );
+    }
+
+    #[test]
+    fn challenge_is_echoed_back() {
+        let transport = MockTransport::new([CHALLENGE_REPLY.to_vec(), info_reply()]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        client.get_server_info(&Engine::new(440)).unwrap();
+
+        let sent = client.transport.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[1], [&sent[0][..], &CHALLENGE_REPLY[5 ..]].concat());
+    }
+
+    #[test]
+    fn info_challenge_is_reused() {
+        let transport = MockTransport::new([
+            CHALLENGE_REPLY.to_vec(),
+            info_reply(),
+            players_reply(),
+            rules_reply(),
+        ]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        let response = get_response(&mut client, Engine::new(440), GatheringSettings::default()).unwrap();
+        assert_eq!(response.players.unwrap().len(), 2);
+
+        // Neither the players nor the rules request asked for a new challenge.
+        let challenge = CHALLENGE_REPLY[5 ..].to_vec();
+        let sent = client.transport.sent();
+        assert_eq!(sent.len(), 4);
+        assert_eq!(sent[2], Packet::new(Request::Players as u8, challenge.clone()).to_bytes());
+        assert_eq!(sent[3], Packet::new(Request::Rules as u8, challenge).to_bytes());
+        assert_eq!(response.challenge, Some(i32::from_le_bytes([0x4B, 0xA1, 0x25, 0x09])));
+    }
+
+    #[test]
+    fn expired_challenges_are_retried() {
+        let settings = GatheringSettings {
+            players: crate::protocols::types::GatherToggle::Enforce,
+            rules: crate::protocols::types::GatherToggle::Skip,
+            max_challenge_retries: 2,
+            ..GatheringSettings::default()
+        };
+        let replies = |challenges| {
+            [CHALLENGE_REPLY.to_vec(), info_reply()]
+                .into_iter()
+                .chain(std::iter::repeat(CHALLENGE_REPLY.to_vec()).take(challenges))
+                .chain([players_reply()])
+        };
+
+        // The players request was sent with the info's challenge, which
+        // expired twice.
+        let mut client = ValveProtocol::with_transport(MockTransport::new(replies(2)), 0);
+        let response = get_response(&mut client, Engine::new(440), settings).unwrap();
+        assert_eq!(response.players.unwrap().len(), 2);
+        assert_eq!(client.transport.sent().len(), 5);
+
+        let mut client = ValveProtocol::with_transport(MockTransport::new(replies(3)), 0);
+        let error = get_response(&mut client, Engine::new(440), settings).unwrap_err();
+        assert_eq!(error.kind, PacketBad);
+        assert_eq!(client.transport.sent().len(), 5);
+    }
+
+    #[test]
+    fn supplied_challenge_skips_the_round_trip() {
+        const CHALLENGE: i32 = 0x1234_5678;
+        let settings = GatheringSettings {
+            challenge: Some(CHALLENGE),
+            ..GatheringSettings::default()
+        };
+
+        let transport = MockTransport::new([info_reply(), players_reply(), rules_reply()]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+        let response = get_response(&mut client, Engine::new(440), settings).unwrap();
+        assert_eq!(response.challenge, Some(CHALLENGE));
+
+        let sent = client.transport.sent();
+        assert_eq!(sent.len(), 3);
+        assert!(sent[0].ends_with(&CHALLENGE.to_le_bytes()));
+        assert_eq!(
+            sent[1],
+            Packet::new(Request::Players as u8, CHALLENGE.to_le_bytes().to_vec()).to_bytes()
+        );
+
+        // An expired challenge is replaced by the one the server hands out.
+        let transport = MockTransport::new([
+            CHALLENGE_REPLY.to_vec(),
+            info_reply(),
+            players_reply(),
+            rules_reply(),
+        ]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+        let response = get_response(&mut client, Engine::new(440), settings).unwrap();
+        assert_eq!(response.challenge, Some(i32::from_le_bytes([0x4B, 0xA1, 0x25, 0x09])));
+        assert_eq!(client.transport.sent().len(), 4);
+    }
+
+    /// A `A2S_PLAYER` reply of a server that puts the players' slots in the
+    /// index bytes.
+    const SLOT_INDEXED_PLAYERS_REPLY: &[u8] = b"\xFF\xFF\xFF\xFF\x44\x02\
+        \x07alice\0\x05\0\0\0\0\0\x72\x42\
+        \xC8bob\0\xFF\xFF\xFF\xFF\0\0\x40\x41";
+
+    #[test]
+    fn player_index_bytes_are_kept() {
+        let mut client = ValveProtocol::with_transport(MockTransport::new([SLOT_INDEXED_PLAYERS_REPLY.to_vec()]), 0);
+
+        let players = client.get_server_players(&Engine::new(440), 17).unwrap();
+        assert_eq!(
+            players
+                .iter()
+                .map(|player| (player.index, player.name.as_str(), player.score, player.duration))
+                .collect::<Vec<_>>(),
+            [(7, "alice", 5, 60.5), (200, "bob", -1, 12.0)]
+        );
+    }
+
+    /// A The Ship `A2S_PLAYER` reply, the deaths and money of its two players
+    /// following the list.
+    const THE_SHIP_PLAYERS_REPLY: &[u8] = b"\xFF\xFF\xFF\xFFD\x02\
+        \x00Tiny Tim\0\x03\0\0\0\0\0\xF0\x42\
+        \x01Dr. Bones\0\x00\0\0\0\0\0\x20\x41\
+        \x01\0\0\0\xE8\x03\0\0\
+        \x04\0\0\0\x10\x27\0\0";
+
+    #[test]
+    fn the_ship_player_data_follows_the_list() {
+        let mut client = ValveProtocol::with_transport(MockTransport::new([THE_SHIP_PLAYERS_REPLY.to_vec()]), 0);
+
+        let players = client.get_server_players(&Engine::new(2400), 7).unwrap();
+        assert_eq!(
+            players
+                .iter()
+                .map(|player| (player.name.as_str(), player.score, player.deaths, player.money))
+                .collect::<Vec<_>>(),
+            [
+                ("Tiny Tim", 3, Some(1), Some(1000)),
+                ("Dr. Bones", 0, Some(4), Some(10000))
+            ]
+        );
+        assert_eq!(players[0].duration, 120.0);
+
+        // Without the extra data, as other games send them.
+        let mut client = ValveProtocol::with_transport(MockTransport::new([players_reply()]), 0);
+        let players = client.get_server_players(&Engine::new(2400), 7).unwrap();
+        assert_eq!(players.len(), 2);
+        assert!(players.iter().all(|player| player.deaths.is_none()));
+    }
+
+    #[test]
+    fn early_players_reply_is_kept() {
+        // The players reply shows up before the info, during the challenge
+        // exchange of the info request.
+        let transport = MockTransport::new([
+            CHALLENGE_REPLY.to_vec(),
+            players_reply(),
+            info_reply(),
+            rules_reply(),
+        ]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        let response = get_response(&mut client, Engine::new(440), GatheringSettings::default()).unwrap();
+        assert_eq!(response.info.name, "gamedig");
+        assert_eq!(response.players.unwrap().len(), 2);
+        assert_eq!(response.rules.unwrap()["mp_timelimit"], "30");
+
+        // The players request wasn't sent, its reply being already there.
+        let sent = client.transport.sent();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[2][4], Request::Rules as u8);
+    }
+
+    #[test]
+    fn unterminated_name_is_an_error() {
+        let mut info = info_reply()[.. 6].to_vec();
+        info.extend_from_slice(&[b'x'; 300]);
+        let mut client = ValveProtocol::with_transport(MockTransport::new([info]), 0);
+        let error = client.get_server_info(&Engine::new(440)).unwrap_err();
+        assert_eq!(error.kind, PacketBad);
+
+        // A name longer than the limit is refused even if it is terminated.
+        let mut client = ValveProtocol::with_transport(MockTransport::new([info_reply()]), 0);
+        let settings = GatheringSettings {
+            max_name_length: 4,
+            ..INFO_ONLY
+        };
+        assert!(get_response(&mut client, Engine::new(440), settings).is_err());
+
+        let mut client = ValveProtocol::with_transport(MockTransport::new([info_reply()]), 0);
+        let settings = GatheringSettings {
+            max_name_length: 7,
+            ..INFO_ONLY
+        };
+        assert_eq!(
+            get_response(&mut client, Engine::new(440), settings)
+                .unwrap()
+                .info
+                .name,
+            "gamedig"
+        );
+    }
+
+    #[test]
+    fn keywords_control_bytes_are_sanitized() {
+        let mut fields = b"alltalk,web:https://example.com\x01\x7F,\xFF\0".to_vec();
+        fields.extend_from_slice(&440u64.to_le_bytes());
+        let info = TestInfoReply {
+            extra_data: Some((0x20 | 0x01, fields)),
+            ..TestInfoReply::default()
+        }
+        .build();
+
+        let mut client = ValveProtocol::with_transport(MockTransport::new([info]), 0);
+        let extra_data = client
+            .get_server_info(&Engine::new(440))
+            .unwrap()
+            .extra_data
+            .unwrap();
+
+        assert_eq!(
+            extra_data.keywords.as_deref(),
+            Some("alltalk,web:https://example.com  ,\u{FFFD}")
+        );
+        assert_eq!(
+            extra_data.raw_keywords.as_deref(),
+            Some("alltalk,web:https://example.com\u{1}\u{7F},\u{FFFD}")
+        );
+        // The game id after the keywords is read as is.
+        assert_eq!(extra_data.game_id, Some(440));
+    }
+
+    #[test]
+    fn long_appid_is_read_from_game_id() {
+        // ARK: Survival Ascended's app id doesn't fit in the 16 bits field.
+        const APPID: u32 = 2_399_830;
+
+        let data = TestInfoReply {
+            strings: "ark\0TheIsland_WP\0ark\0ARK: Survival Ascended\0".to_string(),
+            app_id: APPID as u16,
+            counts: [3, 70, 0, b'd', b'w', 0, 0],
+            version: "1.0",
+            extra_data: Some((0x01, u64::from(APPID).to_le_bytes().to_vec())),
+            ..TestInfoReply::default()
+        }
+        .build();
+
+        let transport = MockTransport::new([data]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+        let info = client.get_server_info(&Engine::new(APPID)).unwrap();
+
+        assert_eq!(info.appid, APPID);
+        assert_eq!(info.app_id_short, 40534);
+
+        let response = crate::protocols::valve::game::Response::from_parts(info, None, None);
+        assert_eq!(response.appid, APPID);
+        assert_eq!(response.app_id_short, 40534);
+        assert_eq!(response.game_id, Some(u64::from(APPID)));
+    }
+
+    #[test]
+    fn extra_data_flags_gate_their_fields() {
+        fn info_with_extra_data(flags: u8, fields: &[u8]) -> ServerInfo {
+            let data = TestInfoReply {
+                extra_data: Some((flags, fields.to_vec())),
+                ..TestInfoReply::default()
+            }
+            .build();
+
+            let mut client = ValveProtocol::with_transport(MockTransport::new([data]), 0);
+            client.get_server_info(&Engine::new(440)).unwrap()
+        }
+
+        // The SourceTV port and name, then a game id whose upper bits (set for
+        // mods) aren't part of the app id.
+        let mut fields = 27020u16.to_le_bytes().to_vec();
+        fields.extend_from_slice(b"SourceTV\0");
+        fields.extend_from_slice(&(0x8000_0000_0001_01B8u64).to_le_bytes());
+        let info = info_with_extra_data(0x40 | 0x01, &fields);
+        let extra_data = info.extra_data.clone().unwrap();
+        assert_eq!(extra_data.port, None);
+        assert_eq!(extra_data.steam_id, None);
+        assert_eq!(extra_data.tv_port, Some(27020));
+        assert_eq!(extra_data.tv_name.as_deref(), Some("SourceTV"));
+        assert_eq!(extra_data.keywords, None);
+        assert_eq!(extra_data.game_id, Some(0x8000_0000_0001_01B8));
+        assert_eq!(info.appid, 0x01_01B8);
+        assert_eq!(info.app_id_short, 440);
+
+        let response = crate::protocols::valve::game::Response::from_parts(info, None, None);
+        assert_eq!(response.tv_port, Some(27020));
+        assert_eq!(response.tv_name.as_deref(), Some("SourceTV"));
+        assert_eq!(response.game_id, Some(0x8000_0000_0001_01B8));
+
+        // Only the game port, the app id staying the 16 bits one.
+        let info = info_with_extra_data(0x80, &27015u16.to_le_bytes());
+        let extra_data = info.extra_data.clone().unwrap();
+        assert_eq!(extra_data.port, Some(27015));
+        assert_eq!(extra_data.tv_port, None);
+        assert_eq!(extra_data.game_id, None);
+        assert_eq!(info.appid, 440);
+
+        // An EDF without any flag set.
+        let extra_data = info_with_extra_data(0x00, &[]).extra_data.unwrap();
+        assert_eq!(extra_data.port, None);
+        assert_eq!(extra_data.game_id, None);
+    }
+
+    #[test]
+    fn connect_strings() {
+        let address: SocketAddr = "1.2.3.4:27015".parse().unwrap();
+
+        assert_eq!(parse_connect_string("connect 1.2.3.4:27015").unwrap(), address);
+        assert_eq!(
+            parse_connect_string("connect 1.2.3.4:27015; password secret").unwrap(),
+            address
+        );
+        assert_eq!(
+            parse_connect_string("steam://connect/1.2.3.4:27015").unwrap(),
+            address
+        );
+        assert_eq!(
+            parse_connect_string("steam://connect/1.2.3.4:27015/secret").unwrap(),
+            address
+        );
+        assert_eq!(parse_connect_string(" 1.2.3.4:27015\n").unwrap(), address);
+        assert_eq!(
+            parse_connect_string("[::1]:27015").unwrap(),
+            "[::1]:27015".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_connect_strings() {
+        for connect in ["1.2.3.4", "connect 1.2.3.4:port", "steam://run/440", "join 1.2.3.4:27015", ""] {
+            assert_eq!(parse_connect_string(connect).unwrap_err().kind, InvalidInput);
+        }
+    }
+
+    /// An obsolete GoldSrc info reply, as sent by a Counter-Strike 1.6 server
+    /// (protocol 47) running a mod.
+    const GOLDSRC_INFO_REPLY: &[u8] = b"\xFF\xFF\xFF\xFF\x6D192.168.1.10:27015\0CS 1.6 Server\0de_dust2\0cstrike\0\
+Counter-Strike\0\x0A\x20\x2FDL\x00\x01www.counter-strike.net\0\0\0\x01\x00\x00\x00\x00\x40\x9C\x00\x01\x01\x01\x02";
+
+    #[test]
+    fn goldsrc_reply_is_detected() {
+        for engine in [Engine::new_gold_src(false), Engine::new(10)] {
+            let transport = MockTransport::new([GOLDSRC_INFO_REPLY.to_vec()]);
+            let mut client = ValveProtocol::with_transport(transport, 0);
+
+            let info = client.get_server_info(&engine).unwrap();
+            assert_eq!(info.name, "CS 1.6 Server");
+            assert_eq!(info.map, "de_dust2");
+            assert_eq!(info.folder, "cstrike");
+            assert_eq!(info.game_mode, "Counter-Strike");
+            assert_eq!(info.players_online, 10);
+            assert_eq!(info.players_maximum, 32);
+            assert_eq!(info.protocol_version, 47);
+            assert_eq!(info.server_type, Server::Dedicated);
+            assert_eq!(info.environment_type, Environment::Linux);
+            assert!(!info.has_password);
+            assert!(info.is_mod);
+            assert!(info.vac_secured);
+            assert_eq!(info.players_bots, 2);
+
+            let mod_data = info.mod_data.clone().unwrap();
+            assert_eq!(mod_data.link, "www.counter-strike.net");
+            assert_eq!(mod_data.download_link, "");
+            assert_eq!(mod_data.version, 1);
+            assert_eq!(mod_data.size, 10_240_000);
+            assert!(mod_data.multiplayer_only);
+            assert!(mod_data.has_own_dll);
+
+            let response = crate::protocols::valve::game::Response::from_parts(info, None, None);
+            assert_eq!(response.name, "CS 1.6 Server");
+            assert_eq!(response.os_name(), "Linux");
+            assert_eq!(response.goldsrc_mod, Some(mod_data));
+        }
+    }
+
+    #[test]
+    fn unknown_environments_are_reported() {
+        let mut data = info_reply();
+        let environment = data.windows(2).position(|window| window == b"dl").unwrap() + 1;
+        data[environment] = b'x';
+
+        let mut client = ValveProtocol::with_transport(MockTransport::new([data]), 0);
+        let info = client.get_server_info(&Engine::new(440)).unwrap();
+        assert_eq!(info.environment_type, Environment::Unknown(b'x'));
+        assert_eq!(info.environment_type.os_name(), "Unknown");
+    }
+
+    #[test]
+    fn info_layout_follows_the_reply_kind() {
+        // A TFC server updated to answer with the Source layout, queried with
+        // the obsolete one forced.
+        let data = TestInfoReply {
+            protocol: 48,
+            strings: "tfc server\0well\0tfc\0Team Fortress Classic\0".to_string(),
+            app_id: 20,
+            counts: [4, 24, 0, b'd', b'l', 0, 1],
+            version: "1.1.2.2/Stdio",
+            extra_data: Some((0x80, 27015u16.to_le_bytes().to_vec())),
+        }
+        .build();
+
+        for engine in [Engine::new_gold_src(true), Engine::new_gold_src(false)] {
+            let mut client = ValveProtocol::with_transport(MockTransport::new([data.clone()]), 0);
+            let info = client.get_server_info(&engine).unwrap();
+            assert_eq!(info.name, "tfc server");
+            assert_eq!(info.map, "well");
+            assert_eq!(info.appid, 20);
+            assert_eq!(info.protocol_version, 48);
+            assert_eq!(info.game_version, "1.1.2.2/Stdio");
+            assert!(!info.is_mod);
+        }
+
+        // Obsolete replies are parsed as such, whatever the engine.
+        for engine in [Engine::new_gold_src(true), Engine::new(10)] {
+            let mut client = ValveProtocol::with_transport(MockTransport::new([GOLDSRC_INFO_REPLY.to_vec()]), 0);
+            assert_eq!(client.get_server_info(&engine).unwrap().protocol_version, 47);
+        }
+
+        assert!(!is_goldsrc_info(&Engine::new_gold_src(true), SOURCE_INFO_KIND));
+        assert!(is_goldsrc_info(&Engine::new(240), GOLDSRC_INFO_KIND));
+        assert!(is_goldsrc_info(&Engine::new_gold_src(true), 0x00));
+        assert!(!is_goldsrc_info(&Engine::new_gold_src(false), 0x00));
+    }
+
+    /// An obsolete GoldSrc info reply from a Natural Selection server, a mod
+    /// playable in single player too, with a download link.
+    const GOLDSRC_MOD_INFO_REPLY: &[u8] = b"\xFF\xFF\xFF\xFF\x6D10.0.0.5:27015\0NS Server\0ns_veil\0ns\0Natural Selection\0\
+\x0C\x10\x2FDW\x00\x01http://www.natural-selection.org\0http://www.natural-selection.org/download\0\0\
+\x03\x00\x00\x00\x00\x00\x20\x03\x00\x01\x00\x00";
+
+    #[test]
+    fn goldsrc_mod_section_is_parsed() {
+        let transport = MockTransport::new([GOLDSRC_MOD_INFO_REPLY.to_vec()]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        let info = client.get_server_info(&Engine::new_gold_src(false)).unwrap();
+        assert_eq!(info.folder, "ns");
+        assert_eq!(info.environment_type, Environment::Windows);
+        assert!(!info.vac_secured);
+        assert_eq!(info.players_bots, 0);
+
+        let response = crate::protocols::valve::game::Response::from_parts(info, None, None);
+        assert_eq!(
+            response.goldsrc_mod,
+            Some(ModData {
+                link: "http://www.natural-selection.org".to_string(),
+                download_link: "http://www.natural-selection.org/download".to_string(),
+                version: 3,
+                size: 52_428_800,
+                multiplayer_only: false,
+                has_own_dll: true,
+            })
+        );
+    }
+
+    /// A hand-built `A2S_INFO` reply shaped like the ones of Counter-Strike:
+    /// Source servers predating the challenge requirement, with only the port
+    /// in its extra data.
+    const PRE_CHALLENGE_INFO_REPLY: &[u8] = b"\xFF\xFF\xFF\xFF\x49\x11old css\0de_dust2\0cstrike\0Counter-Strike: Source\0\
+\xF0\x00\x05\x20\x00dw\x00\x001.0.0.34\0\x80\x87\x69";
+
+    #[test]
+    fn pre_challenge_info_is_parsed_directly() {
+        let transport = MockTransport::new([PRE_CHALLENGE_INFO_REPLY.to_vec()]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        let info = client.get_server_info(&Engine::new(240)).unwrap();
+        assert_eq!(info.name, "old css");
+        assert_eq!(info.map, "de_dust2");
+        assert_eq!(info.appid, 240);
+        assert_eq!(info.players_online, 5);
+        assert_eq!(info.players_maximum, 32);
+        assert_eq!(info.environment_type, Environment::Windows);
+        assert_eq!(info.game_version, "1.0.0.34");
+        assert_eq!(info.extra_data.unwrap().port, Some(27015));
+
+        // No challenge was waited for, nor appended to the single request.
+        let sent = client.transport.sent();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(
+            sent[0],
+            [&[0xFF, 0xFF, 0xFF, 0xFF, Request::Info as u8][..], &Request::Info.get_default_payload()].concat()
+        );
+    }
+
+    #[test]
+    fn pre_challenge_server_full_query() {
+        let transport = MockTransport::new([
+            PRE_CHALLENGE_INFO_REPLY.to_vec(),
+            CHALLENGE_REPLY.to_vec(),
+            players_reply(),
+            rules_reply(),
+        ]);
+
+        let response = query_with_transport(transport, Engine::new(240), None, 0).unwrap();
+
+        assert_eq!(response.info.name, "old css");
+        assert_eq!(response.players.unwrap().len(), 2);
+        assert_eq!(response.rules.unwrap().len(), 1);
+    }
+
+    /// Split `payload` into Source split packets of at most `size` bytes.
+    fn split_reply(payload: &[u8], size: usize) -> Vec<Vec<u8>> {
+        let chunks: Vec<&[u8]> = payload.chunks(size).collect();
+
+        chunks
+            .iter()
+            .enumerate()
+            .map(|(number, chunk)| {
+                let mut data = vec![0xFE, 0xFF, 0xFF, 0xFF];
+                data.extend_from_slice(&1234u32.to_le_bytes());
+                data.push(chunks.len() as u8);
+                data.push(number as u8);
+                data.extend_from_slice(&(size as u16).to_le_bytes());
+                data.extend_from_slice(chunk);
+
+                data
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_reply_is_reassembled() {
+        let transport = MockTransport::new(split_reply(&info_reply(), 32));
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        assert_eq!(client.get_server_info(&Engine::new(440)).unwrap().appid, 440);
+    }
+
+    #[test]
+    fn fragments_numbered_past_the_total_are_rejected() {
+        for out_of_range in [0, 1] {
+            let mut fragments = split_reply(&info_reply(), 32);
+            // The number follows the header, the id and the total.
+            fragments[out_of_range][9] = fragments[out_of_range][8];
+
+            let mut client = ValveProtocol::with_transport(MockTransport::new(fragments), 0);
+            assert_eq!(client.get_server_info(&Engine::new(440)).unwrap_err().kind, PacketBad);
+        }
+    }
+
+    /// A bzip2 compressed `A2S_RULES` reply of [many_rules_reply], generated
+    /// for the tests.
+    const COMPRESSED_RULES: [u8; 169] = [
+        0x42, 0x5A, 0x68, 0x39, 0x31, 0x41, 0x59, 0x26, 0x53, 0x59, 0x8A, 0xF0, 0x5A, 0x5C, 0x00, 0x01,
+        0x93, 0xDF, 0x80, 0xC0, 0x00, 0x00, 0x40, 0x7F, 0xE0, 0x02, 0x00, 0x00, 0x00, 0xB6, 0x07, 0x88,
+        0x00, 0x00, 0x00, 0xB0, 0x00, 0xB6, 0xD0, 0x6A, 0x9E, 0xA7, 0xEA, 0x9E, 0xD5, 0x1F, 0xAA, 0x1A,
+        0x7A, 0x80, 0x04, 0x51, 0xFF, 0xAA, 0xA3, 0x40, 0xD0, 0x0C, 0x80, 0x2A, 0x55, 0x4F, 0xC9, 0xFA,
+        0xA9, 0x1F, 0xEA, 0xA0, 0x0D, 0x3D, 0x4F, 0x49, 0xCC, 0x44, 0x59, 0x1F, 0x5D, 0x7C, 0xB0, 0x00,
+        0x00, 0x00, 0x00, 0x06, 0xF8, 0xA4, 0xFA, 0x46, 0xF3, 0xB7, 0x6B, 0x12, 0xA3, 0x56, 0xDB, 0x12,
+        0xA3, 0x56, 0xDE, 0x11, 0xAD, 0x71, 0xC3, 0x7B, 0xDB, 0x7B, 0x31, 0x04, 0x82, 0x48, 0x24, 0xC4,
+        0x13, 0x2C, 0x01, 0x70, 0x99, 0xD6, 0x95, 0xA9, 0xED, 0x4D, 0xB6, 0x92, 0x49, 0x26, 0xDB, 0x79,
+        0x6D, 0xB6, 0xE6, 0x66, 0x66, 0x67, 0x22, 0x3F, 0x84, 0x77, 0x11, 0xC8, 0x8E, 0x84, 0x74, 0x23,
+        0xB8, 0x8E, 0x84, 0x78, 0x11, 0xA1, 0x14, 0x47, 0xA0, 0x8E, 0x44, 0x78, 0x9E, 0x5F, 0xE2, 0xEE,
+        0x48, 0xA7, 0x0A, 0x12, 0x11, 0x5E, 0x0B, 0x4B, 0x80,
+    ];
+
+    /// A `A2S_RULES` reply with 40 rules, `sm_mod_NN_enabled` being `NN % 3`.
+    fn many_rules_reply() -> Vec<u8> {
+        let mut data = vec![0xFF, 0xFF, 0xFF, 0xFF, 0x45];
+        data.extend_from_slice(&40u16.to_le_bytes());
+        for number in 0 .. 40 {
+            data.extend_from_slice(format!("sm_mod_{number:02}_enabled\0{}\0", number % 3).as_bytes());
+        }
+
+        data
+    }
+
+    /// Split a compressed payload into Source split packets of at most `size`
+    /// bytes, the first one announcing the decompressed size and CRC32.
+    fn compressed_split_reply(compressed: &[u8], decompressed: &[u8], size: usize) -> Vec<Vec<u8>> {
+        let mut fragments = split_reply(compressed, size);
+        for fragment in &mut fragments {
+            fragment[7] |= 0x80;
+        }
+        let mut announced = (decompressed.len() as u32).to_le_bytes().to_vec();
+        announced.extend_from_slice(&crc32fast::hash(decompressed).to_le_bytes());
+        fragments[0].splice(12 .. 12, announced);
+
+        fragments
+    }
+
+    #[test]
+    fn compressed_split_reply_is_decompressed() {
+        let fragments = compressed_split_reply(&COMPRESSED_RULES, &many_rules_reply(), 64);
+        assert_eq!(fragments.len(), 3);
+        let transport = MockTransport::new(fragments);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        let rules = client.get_server_rules(&Engine::new(440), 17).unwrap();
+        assert_eq!(rules.len(), 40);
+        assert_eq!(rules["sm_mod_00_enabled"], "0");
+        assert_eq!(rules["sm_mod_39_enabled"], "0");
+        assert_eq!(rules["sm_mod_20_enabled"], "2");
+    }
+
+    #[test]
+    fn compressed_reply_is_checked() {
+        let mut corrupted = many_rules_reply();
+        corrupted[10] ^= 0xFF;
+        let transport = MockTransport::new(compressed_split_reply(&COMPRESSED_RULES, &corrupted, 64));
+        let mut client = ValveProtocol::with_transport(transport, 0);
+        let error = client.get_server_rules(&Engine::new(440), 17).unwrap_err();
+        assert_eq!(error.kind, Decompress);
+
+        let transport = MockTransport::new(compressed_split_reply(&COMPRESSED_RULES, &[0; 10], 64));
+        let mut client = ValveProtocol::with_transport(transport, 0);
+        let error = client.get_server_rules(&Engine::new(440), 17).unwrap_err();
+        assert_eq!(error.kind, Decompress);
+
+        // An announced size that is too big isn't allocated.
+        let mut fragments = compressed_split_reply(&COMPRESSED_RULES, &many_rules_reply(), 64);
+        fragments[0][12 .. 16].copy_from_slice(&u32::MAX.to_le_bytes());
+        let mut client = ValveProtocol::with_transport(MockTransport::new(fragments), 0);
+        let error = client.get_server_rules(&Engine::new(440), 17).unwrap_err();
+        assert_eq!(error.kind, Decompress);
+    }
+
+    #[test]
+    fn dropped_fragment_is_reported() {
+        let mut fragments = split_reply(&info_reply(), 32);
+        let total = fragments.len();
+        fragments.remove(1);
+
+        let transport = MockTransport::new(fragments);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        let error = client.get_server_info(&Engine::new(440)).unwrap_err();
+        assert_eq!(error.kind, PacketReceive);
+        assert_eq!(
+            error.source.unwrap().to_string(),
+            format!("Incomplete fragments: got {} of {}", total - 1, total)
+        );
+    }
+
+    #[test]
+    fn reordered_and_duplicated_fragments_are_reassembled() {
+        let mut fragments = split_reply(&info_reply(), 32);
+        fragments.reverse();
+        fragments.insert(1, fragments[0].clone());
+
+        let transport = MockTransport::new(fragments);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        let info = client.get_server_info(&Engine::new(440)).unwrap();
+        assert_eq!(info.appid, 440);
+        assert_eq!(info.name, "gamedig");
+        assert_eq!(client.transport.remaining(), 0);
+    }
+
+    fn token_probe() -> PreProbe {
+        PreProbe {
+            request: b"\xFF\xFF\xFF\xFFprobe".to_vec(),
+            token_offset: 4,
+            token_length: 3,
+        }
+    }
+
+    #[test]
+    fn pre_probe_token_is_appended() {
+        let transport = MockTransport::new([
+            b"\xFF\xFF\xFF\xFFtok".to_vec(),
+            CHALLENGE_REPLY.to_vec(),
+            info_reply(),
+        ]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        client.pre_probe(&token_probe()).unwrap();
+        client.get_server_info(&Engine::new(440)).unwrap();
+
+        let sent = client.transport.sent();
+        assert_eq!(sent.len(), 3);
+        assert_eq!(sent[0], token_probe().request);
+        assert!(sent[1].ends_with(b"tok"));
+        assert!(sent[2].ends_with(&[&CHALLENGE_REPLY[5 ..], b"tok"].concat()));
+    }
+
+    #[test]
+    fn pre_probe_reply_too_short() {
+        let transport = MockTransport::new([b"\xFF\xFF\xFF\xFFto".to_vec()]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        assert!(client.pre_probe(&token_probe()).is_err());
+    }
+
+    #[test]
+    fn pre_probe_token_bounds_dont_overflow() {
+        let transport = MockTransport::new([b"\xFF\xFF\xFF\xFFtok".to_vec()]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+        let probe = PreProbe {
+            token_offset: usize::MAX,
+            ..token_probe()
+        };
+
+        assert_eq!(client.pre_probe(&probe).unwrap_err().kind, PacketUnderflow);
+    }
+
+    #[test]
+    fn no_pre_probe_by_default() {
+        let transport = MockTransport::new([info_reply()]);
+        let mut client = ValveProtocol::with_transport(transport, 0);
+
+        client.get_server_info(&Engine::new(440)).unwrap();
+
+        let request = Packet::new(Request::Info as u8, Request::Info.get_default_payload()).to_bytes();
+        assert_eq!(client.transport.sent(), &[request]);
+    }
+
+    /// A socket answering every request with `reply(its own address)`, until
+    /// none comes for a second, the number of requests being returned on join.
+    fn udp_server(reply: impl Fn(SocketAddr) -> Vec<u8> + Send + 'static) -> (SocketAddr, thread::JoinHandle<usize>) {
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+        let address = socket.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut buffer = [0; PACKET_SIZE];
+            let mut requests = 0;
+            while let Ok((_, from)) = socket.recv_from(&mut buffer) {
+                requests += 1;
+                socket.send_to(&reply(address), from).unwrap();
+            }
+
+            requests
+        });
+
+        (address, server)
+    }
+
+    #[test]
+    fn debug_query_keeps_the_packets_of_a_failure() {
+        // An info reply cut short, that can't be parsed.
+        let (server, _) = udp_server(|_| info_reply()[.. 20].to_vec());
+
+        let (result, packets) = query_debug(&server, Engine::new(440), Some(INFO_ONLY), None);
+        assert!(result.is_err());
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].direction, PacketDirection::Sent);
+        assert_eq!(packets[0].data[4], Request::Info as u8);
+        assert_eq!(packets[1].direction, PacketDirection::Received);
+        assert_eq!(packets[1].data, info_reply()[.. 20]);
+
+        let (server, _) = udp_server(|_| info_reply());
+        let (result, packets) = query_debug(&server, Engine::new(440), Some(INFO_ONLY), None);
+        let response = result.unwrap();
+        assert_eq!(response.info.name, "gamedig");
+        assert!(response.ping.is_some());
+        assert_eq!(packets.len(), 2);
+    }
+
+    #[test]
+    fn lost_requests_are_resent_after_the_retry_delay() {
+        const DELAY: Duration = Duration::from_millis(150);
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(1))).unwrap();
+        let address = socket.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut buffer = [0; PACKET_SIZE];
+            // The first request is lost.
+            socket.recv_from(&mut buffer).unwrap();
+            let lost = std::time::Instant::now();
+            let (_, from) = socket.recv_from(&mut buffer).unwrap();
+            socket.send_to(&info_reply(), from).unwrap();
+
+            lost.elapsed()
+        });
+
+        let timeout_settings = TimeoutSettings::new(Some(Duration::from_millis(100)), None, None, 1)
+            .unwrap()
+            .with_retry_delay(DELAY);
+        let response = query(&address, Engine::new(440), Some(INFO_ONLY), Some(timeout_settings)).unwrap();
+        assert_eq!(response.info.name, "gamedig");
+        // The read timeout elapsed, then the delay was waited for.
+        assert!(server.join().unwrap() >= DELAY);
+    }
+
+    /// A socket connected by the caller, as a proxy's would be.
+    struct ConnectedSocket(std::net::UdpSocket);
+
+    impl Transport for ConnectedSocket {
+        fn send(&mut self, data: &[u8]) -> GDResult<()> {
+            self.0.send(data).map_err(|e| PacketSend.context(e))?;
+
+            Ok(())
+        }
+
+        fn recv_timeout(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+            let mut buffer = vec![0; size.unwrap_or(PACKET_SIZE)];
+            let length = self.0.recv(&mut buffer).map_err(|e| PacketReceive.context(e))?;
+            buffer.truncate(length);
+
+            Ok(buffer)
+        }
+    }
+
+    #[test]
+    fn query_over_a_supplied_transport() {
+        let (server, _) = udp_server(|_| info_reply());
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(server).unwrap();
+        socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+
+        let response = query_with_transport(ConnectedSocket(socket), Engine::new(440), Some(INFO_ONLY), 0).unwrap();
+        assert_eq!(response.info.name, "gamedig");
+        // The supplied transport doesn't measure it.
+        assert_eq!(response.ping, None);
+        assert_eq!(
+            ConnectedSocket::connect(&server, &None).err().unwrap().kind,
+            InvalidInput
+        );
+    }
+
+    fn redirect_reply(to: SocketAddr) -> Vec<u8> {
+        [&[0xFF, 0xFF, 0xFF, 0xFF, REDIRECT_KIND], format!("{to}\0").as_bytes()].concat()
+    }
+
+    const INFO_ONLY: GatheringSettings = GatheringSettings {
+        players: crate::protocols::types::GatherToggle::Skip,
+        rules: crate::protocols::types::GatherToggle::Skip,
+        check_app_id: false,
+        ..GatheringSettings::default()
+    };
+
+    #[test]
+    fn port_range_stops_at_the_first_answer() {
+        let silent = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (server, server_thread) = udp_server(|_| info_reply());
+        let (skipped, skipped_thread) = udp_server(|_| info_reply());
+        let timeout_settings = TimeoutSettings::new(Some(std::time::Duration::from_millis(100)), None, None, 0).ok();
+
+        let (response, port) = query_port_range(
+            &server.ip(),
+            [0, silent.local_addr().unwrap().port(), server.port(), skipped.port()],
+            Engine::new(440),
+            Some(INFO_ONLY),
+            timeout_settings,
+        )
+        .unwrap();
+        assert_eq!(port, server.port());
+        assert_eq!(response.name, "gamedig");
+        assert_eq!(server_thread.join().unwrap(), 1);
+        assert_eq!(skipped_thread.join().unwrap(), 0);
+
+        let error = query_port_range(&server.ip(), [0], Engine::new(440), None, None).unwrap_err();
+        assert_eq!(error.kind, InvalidInput);
+        let error = query_port_range(&server.ip(), [], Engine::new(440), None, None).unwrap_err();
+        assert_eq!(error.kind, InvalidInput);
+    }
+
+    #[cfg(feature = "games")]
+    #[test]
+    fn ipv6_servers_are_queried() {
+        // Skipped where IPv6 isn't available.
+        let Ok(server) = std::net::UdpSocket::bind("[::1]:0") else {
+            return;
+        };
+        let address = server.local_addr().unwrap();
+        let responder = thread::spawn(move || {
+            let mut buffer = [0; PACKET_SIZE];
+            for _ in 0 .. 3 {
+                let (_, client) = server.recv_from(&mut buffer).unwrap();
+                let reply = match buffer[4] {
+                    0x54 => info_reply(),
+                    0x55 => players_reply(),
+                    _ => rules_reply(),
+                };
+                server.send_to(&reply, client).unwrap();
+            }
+        });
+
+        // The games' query functions take any address, through the same path.
+        let response = crate::games::teamfortress2::query(&address.ip(), Some(address.port()));
+        responder.join().unwrap();
+        let response = response.unwrap();
+        assert_eq!(response.name, "gamedig");
+        assert_eq!(response.players_details.len(), 2);
+    }
+
+    #[test]
+    fn folder_is_parsed_apart_from_the_game() {
+        let transport = MockTransport::new([info_reply()]);
+        let response = query_with_transport(transport, Engine::new(440), Some(INFO_ONLY), 0).unwrap();
+
+        let response = game::Response::new_from_valve_response(response);
+        assert_eq!(response.folder, "tf");
+        assert_eq!(response.game, "Team Fortress");
+    }
+
+    #[test]
+    fn raw_info_is_kept_on_demand() {
+        let transport = MockTransport::new([info_reply()]);
+        let response = query_with_transport(transport, Engine::new(440), Some(INFO_ONLY), 0).unwrap();
+        assert_eq!(response.raw, None);
+        assert_eq!(response.info.extra_data.unwrap().raw_keywords, None);
+
+        let settings = GatheringSettings {
+            keep_raw: true,
+            ..INFO_ONLY
+        };
+        let transport = MockTransport::new([info_reply()]);
+        let response = query_with_transport(transport, Engine::new(440), Some(settings), 0).unwrap();
+        assert_eq!(response.raw.as_deref(), Some(&info_reply()[5 ..]));
+        // Kept even if they didn't need sanitizing.
+        let raw_keywords = response.info.extra_data.as_ref().unwrap().raw_keywords.as_deref();
+        assert_eq!(raw_keywords, Some("alltalk,increased_maxplayers"));
+
+        let response = game::Response::new_from_valve_response(response);
+        assert_eq!(response.raw.as_deref(), Some(&info_reply()[5 ..]));
+    }
+
+    #[test]
+    fn redirect_is_followed() {
+        let (server, _) = udp_server(|_| info_reply());
+        let (frontend, frontend_thread) = udp_server(move |_| redirect_reply(server));
+
+        // Only recognized when asked to follow them, failing to parse as an
+        // info reply otherwise.
+        let error = query(&frontend, Engine::new(440), Some(INFO_ONLY), None).unwrap_err();
+        assert_eq!(error.kind, PacketUnderflow);
+
+        let response =
+            query_with_max_redirects(&frontend, Engine::new(440), Some(INFO_ONLY), None, DEFAULT_MAX_REDIRECTS);
+        let response = response.unwrap();
+        assert_eq!(response.info.name, "gamedig");
+        assert_eq!(response.response_address, Some(server));
+        assert_eq!(frontend_thread.join().unwrap(), 2);
+
+        // Not followed when the server is queried over a given transport.
+        let transport = MockTransport::new([redirect_reply(server)]);
+        let error = query_with_transport(transport, Engine::new(440), Some(INFO_ONLY), 0).unwrap_err();
+        assert_eq!(error.kind, PacketUnderflow);
+    }
+
+    #[test]
+    fn query_records_when_it_completed() {
+        let (server, _) = udp_server(|_| info_reply());
+
+        let before = SystemTime::now();
+        let response = game::Response::new_from_valve_response(
+            query(&server, Engine::new(440), Some(INFO_ONLY), None).unwrap(),
+        );
+        let queried_at = response.queried_at.unwrap();
+        assert!(before <= queried_at && queried_at <= SystemTime::now());
+
+        // The players join times are relative to it.
+        let settings = GatheringSettings {
+            players: crate::protocols::types::GatherToggle::Enforce,
+            ..INFO_ONLY
+        };
+        let transport = MockTransport::new([info_reply(), players_reply()]);
+        let response = query_with_transport(transport, Engine::new(440), Some(settings), 0).unwrap();
+        let response = game::Response::new_from_valve_response(response);
+        let queried_at = response.queried_at.unwrap();
+        assert_eq!(
+            response.players_details[0].connected_since,
+            Some(queried_at - std::time::Duration::from_secs_f32(60.5))
+        );
+    }
+
+    #[test]
+    fn redirect_loop_is_reported() {
+        let (looping, looping_thread) = udp_server(redirect_reply);
+
+        let error = query_with_max_redirects(&looping, Engine::new(440), Some(INFO_ONLY), None, 2).unwrap_err();
+        assert_eq!(error.kind, PacketParse);
+        assert!(error.to_string().contains("redirect loop"));
+        assert_eq!(looping_thread.join().unwrap(), 3);
+    }
 }
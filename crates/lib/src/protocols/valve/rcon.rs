@@ -0,0 +1,271 @@
+//! The [Source RCON protocol](https://developer.valvesoftware.com/wiki/Source_RCON_Protocol),
+//! to run commands on a server once authenticated with its `rcon_password`.
+//!
+//! Every packet is little endian: its size (not counting the size field), an
+//! id that the replies carry back, its type and a null terminated body,
+//! followed by an empty string.
+//!
+//! The server splitting long outputs over many packets without telling how
+//! many, an empty `SERVERDATA_RESPONSE_VALUE` packet is sent after each
+//! command: the server answers it with an empty packet of the same id once
+//! the whole output was sent.
+
+use byteorder::LittleEndian;
+
+use crate::buffer::Buffer;
+use crate::protocols::types::TimeoutSettings;
+use crate::socket::{Socket, TcpSocket};
+use crate::GDErrorKind::{BadPassword, PacketBad};
+use crate::GDResult;
+use std::net::SocketAddr;
+
+const SERVERDATA_AUTH: i32 = 3;
+const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+const SERVERDATA_EXECCOMMAND: i32 = 2;
+const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// The id, the type and the two null bytes of the smallest packet.
+const MIN_PACKET_SIZE: i32 = 10;
+/// Servers don't send bodies as long, a larger size means the data isn't a
+/// packet.
+const MAX_PACKET_SIZE: i32 = 1 << 16;
+
+/// A packet of the protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Packet {
+    id: i32,
+    kind: i32,
+    body: Vec<u8>,
+}
+
+impl Packet {
+    fn to_bytes(&self) -> Vec<u8> {
+        let size = MIN_PACKET_SIZE + self.body.len() as i32;
+
+        [
+            &size.to_le_bytes()[..],
+            &self.id.to_le_bytes(),
+            &self.kind.to_le_bytes(),
+            &self.body,
+            &[0x00, 0x00],
+        ]
+        .concat()
+    }
+}
+
+/// A connection to a server, authenticated with its RCON password.
+pub struct RconClient {
+    socket: TcpSocket,
+    /// The id of the last packet sent.
+    last_id: i32,
+}
+
+impl RconClient {
+    /// Connect to the server and authenticate, with the default timeout
+    /// settings.
+    ///
+    /// A wrong password is a [BadPassword]
+    /// error.
+    pub fn connect(address: &SocketAddr, password: &str) -> GDResult<Self> {
+        Self::connect_with_timeout(address, password, None)
+    }
+
+    /// Same as [connect](Self::connect), the read timeout applying to each
+    /// received packet.
+    pub fn connect_with_timeout(
+        address: &SocketAddr,
+        password: &str,
+        timeout_settings: Option<TimeoutSettings>,
+    ) -> GDResult<Self> {
+        let mut socket = TcpSocket::new(address, &timeout_settings)?;
+        socket.set_protocol_hint("valve rcon");
+
+        let mut client = Self {
+            socket,
+            last_id: 0,
+        };
+        client.authenticate(password)?;
+
+        Ok(client)
+    }
+
+    fn send(&mut self, kind: i32, body: &[u8]) -> GDResult<i32> {
+        self.last_id = self.last_id.wrapping_add(1).max(1);
+        self.socket.send(
+            &Packet {
+                id: self.last_id,
+                kind,
+                body: body.to_vec(),
+            }
+            .to_bytes(),
+        )?;
+
+        Ok(self.last_id)
+    }
+
+    fn receive(&mut self) -> GDResult<Packet> {
+        let size = Buffer::<LittleEndian>::new(&self.socket.receive_exact(4)?).read::<i32>()?;
+        if !(MIN_PACKET_SIZE ..= MAX_PACKET_SIZE).contains(&size) {
+            return Err(PacketBad.context(format!("Invalid packet size: {size}")));
+        }
+
+        let data = self.socket.receive_exact(size as usize)?;
+        let mut buffer = Buffer::<LittleEndian>::new(&data);
+        let id = buffer.read::<i32>()?;
+        let kind = buffer.read::<i32>()?;
+        // The body and the empty string, their null bytes aren't part of it.
+        let body = buffer.remaining_bytes();
+        let body = &body[.. body.len() - 2];
+
+        Ok(Packet {
+            id,
+            kind,
+            body: body.to_vec(),
+        })
+    }
+
+    fn authenticate(&mut self, password: &str) -> GDResult<()> {
+        let id = self.send(SERVERDATA_AUTH, password.as_bytes())?;
+
+        // An empty SERVERDATA_RESPONSE_VALUE comes first, from most servers.
+        loop {
+            let packet = self.receive()?;
+            if packet.kind != SERVERDATA_AUTH_RESPONSE {
+                continue;
+            }
+
+            return match packet.id {
+                -1 => Err(BadPassword.into()),
+                packet_id if packet_id == id => Ok(()),
+                packet_id => {
+                    Err(PacketBad.context(format!(
+                        "Expected the authentication response {id}, got {packet_id}"
+                    )))
+                }
+            };
+        }
+    }
+
+    /// Run a command, returning its whole output.
+    pub fn exec(&mut self, command: &str) -> GDResult<String> {
+        let id = self.send(SERVERDATA_EXECCOMMAND, command.as_bytes())?;
+        let end_id = self.send(SERVERDATA_RESPONSE_VALUE, &[])?;
+
+        let mut output = Vec::new();
+        loop {
+            let packet = self.receive()?;
+            match packet.id {
+                packet_id if packet_id == end_id => break,
+                packet_id if packet_id == id => output.extend_from_slice(&packet.body),
+                // What's left of a previous command, like the extra packet
+                // some servers send after the empty one.
+                _ => {}
+            }
+        }
+
+        Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    const PASSWORD: &str = "hunter2";
+
+    /// Read a packet from the client, `None` once it disconnected.
+    fn read_packet(stream: &mut TcpStream) -> Option<Packet> {
+        let mut size = [0; 4];
+        stream.read_exact(&mut size).ok()?;
+        let mut data = vec![0; i32::from_le_bytes(size) as usize];
+        stream.read_exact(&mut data).ok()?;
+
+        Some(Packet {
+            id: i32::from_le_bytes(data[.. 4].try_into().unwrap()),
+            kind: i32::from_le_bytes(data[4 .. 8].try_into().unwrap()),
+            body: data[8 .. data.len() - 2].to_vec(),
+        })
+    }
+
+    fn write_packet(stream: &mut TcpStream, id: i32, kind: i32, body: &[u8]) {
+        stream
+            .write_all(
+                &Packet {
+                    id,
+                    kind,
+                    body: body.to_vec(),
+                }
+                .to_bytes(),
+            )
+            .unwrap();
+    }
+
+    /// A server answering each command with an output split over two
+    /// packets, and the empty packet with the extra one srcds sends after it.
+    fn rcon_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let auth = read_packet(&mut stream).unwrap();
+            write_packet(&mut stream, auth.id, SERVERDATA_RESPONSE_VALUE, b"");
+            let auth_id = if auth.body == PASSWORD.as_bytes() {
+                auth.id
+            } else {
+                -1
+            };
+            write_packet(&mut stream, auth_id, SERVERDATA_AUTH_RESPONSE, b"");
+
+            while let Some(packet) = read_packet(&mut stream) {
+                if packet.kind == SERVERDATA_EXECCOMMAND {
+                    let output = format!(
+                        "{} output, long enough to be split",
+                        String::from_utf8_lossy(&packet.body)
+                    );
+                    let (first, second) = output.as_bytes().split_at(10);
+                    write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, first);
+                    write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, second);
+                } else {
+                    write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, b"");
+                    write_packet(&mut stream, packet.id, SERVERDATA_RESPONSE_VALUE, &[0x00, 0x01, 0x00, 0x00]);
+                }
+            }
+        });
+
+        address
+    }
+
+    #[test]
+    fn packets_are_laid_out() {
+        let packet = Packet {
+            id: 7,
+            kind: SERVERDATA_EXECCOMMAND,
+            body: b"status".to_vec(),
+        };
+
+        assert_eq!(
+            packet.to_bytes(),
+            b"\x10\x00\x00\x00\x07\x00\x00\x00\x02\x00\x00\x00status\x00\x00"
+        );
+    }
+
+    #[test]
+    fn commands_get_their_whole_output() {
+        let mut client = RconClient::connect(&rcon_server(), PASSWORD).unwrap();
+
+        assert_eq!(client.exec("status").unwrap(), "status output, long enough to be split");
+        // The extra packet of the previous command isn't mistaken for output.
+        assert_eq!(client.exec("users").unwrap(), "users output, long enough to be split");
+    }
+
+    #[test]
+    fn wrong_passwords_are_refused() {
+        let error = RconClient::connect(&rcon_server(), "guess").err().unwrap();
+
+        assert_eq!(error.kind, BadPassword);
+    }
+}
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
 
 use crate::protocols::types::{CommonPlayer, CommonResponse, ExtraRequestSettings, GatherToggle, GenericPlayer};
-use crate::GDErrorKind::UnknownEnumCast;
-use crate::GDResult;
+use crate::GDErrorKind::{PacketUnderflow, UnknownEnumCast};
+use crate::{GDResult, PacketTooShort};
 use crate::{buffer::Buffer, protocols::GenericResponse};
 use byteorder::LittleEndian;
 #[cfg(feature = "serde")]
@@ -35,16 +37,29 @@ pub enum Environment {
     Linux,
     Windows,
     Mac,
+    /// An identifier that isn't documented, as sent by the server.
+    Unknown(u8),
 }
 
 impl Environment {
-    pub(crate) fn from_gldsrc(value: u8) -> GDResult<Self> {
-        Ok(match value {
+    pub(crate) const fn from_gldsrc(value: u8) -> Self {
+        match value {
             108 => Self::Linux,     //'l'
             119 => Self::Windows,   //'w'
             109 | 111 => Self::Mac, //'m' or 'o'
-            _ => Err(UnknownEnumCast)?,
-        })
+            value => Self::Unknown(value),
+        }
+    }
+
+    /// The display name of the operating system ("Linux", "Windows",
+    /// "macOS" or "Unknown").
+    pub const fn os_name(&self) -> &'static str {
+        match self {
+            Self::Linux => "Linux",
+            Self::Windows => "Windows",
+            Self::Mac => "macOS",
+            Self::Unknown(_) => "Unknown",
+        }
     }
 }
 
@@ -55,10 +70,30 @@ pub struct Response {
     pub info: ServerInfo,
     pub players: Option<Vec<ServerPlayer>>,
     pub rules: Option<HashMap<String, String>>,
+    /// The address that answered, the one of the server redirected to if
+    /// the queried one redirected, `None` if unknown (when the query was made
+    /// over a custom transport).
+    pub response_address: Option<SocketAddr>,
+    /// When the query completed, to get the time the players joined at (see
+    /// [ServerPlayer::connected_since]), `None` if unknown.
+    pub queried_at: Option<SystemTime>,
+    /// The last challenge the server handed out (or the given one, if it
+    /// wasn't rejected), for the next queries to reuse, see
+    /// [GatheringSettings::challenge]. `None` if the server didn't ask for one.
+    pub challenge: Option<i32>,
+    /// The payload of the info reply (following its header and kind byte),
+    /// for the fields that aren't parsed to be read from it, `None` unless
+    /// [GatheringSettings::keep_raw] is set. Serialized as a base64 string.
+    #[cfg_attr(feature = "serde", serde(default, with = "crate::utils::base64_bytes"))]
+    pub raw: Option<Vec<u8>>,
+    /// The round trip time of the first request (to the first packet of its
+    /// reply), `None` if unknown (when the transport doesn't measure it).
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ping: Option<Duration>,
 }
 
 impl CommonResponse for Response {
-    fn as_original(&self) -> GenericResponse { GenericResponse::Valve(self) }
+    fn as_original(&self) -> GenericResponse<'_> { GenericResponse::Valve(self) }
 
     fn name(&self) -> Option<&str> { Some(&self.info.name) }
     fn game_mode(&self) -> Option<&str> { Some(&self.info.game_mode) }
@@ -68,6 +103,7 @@ impl CommonResponse for Response {
     fn players_online(&self) -> u32 { self.info.players_online.into() }
     fn players_bots(&self) -> Option<u32> { Some(self.info.players_bots.into()) }
     fn has_password(&self) -> Option<bool> { Some(self.info.has_password) }
+    fn ping(&self) -> Option<Duration> { self.ping }
 
     fn players(&self) -> Option<Vec<&dyn CommonPlayer>> {
         self.players
@@ -92,6 +128,10 @@ pub struct ServerInfo {
     pub game_mode: String,
     /// [Steam Application ID](https://developer.valvesoftware.com/wiki/Steam_Application_ID) of game.
     pub appid: u32,
+    /// The app id as sent in its 16 bits field, which differs from
+    /// [appid](Self::appid) for app ids that don't fit in it, the one of the
+    /// extra data's game id being preferred when present.
+    pub app_id_short: u16,
     /// Number of players on the server.
     pub players_online: u8,
     /// Maximum number of players the server reports it can hold.
@@ -122,6 +162,9 @@ pub struct ServerInfo {
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct ServerPlayer {
+    /// The index byte leading the player's entry, which should be 0 but some
+    /// servers set to the player's slot (it isn't checked either way).
+    pub index: u8,
     /// Player's name.
     pub name: String,
     /// General score.
@@ -134,8 +177,31 @@ pub struct ServerPlayer {
     pub money: Option<u32>, // the_ship
 }
 
+impl ServerPlayer {
+    /// The time the player joined at, `query_completed_at` (usually the
+    /// response's `queried_at`) minus the `duration`, which the server computed
+    /// when it generated the reply.
+    ///
+    /// Unlike the durations, the join times of a player can be compared
+    /// across polls (to tell who joined in the last minute...). Negative or
+    /// invalid durations count as 0, and join times before the earliest time
+    /// the platform can represent are clamped to the Unix epoch.
+    pub fn connected_since(&self, query_completed_at: SystemTime) -> SystemTime {
+        let duration = match self.duration {
+            duration if duration.is_finite() && duration > 0.0 => {
+                Duration::from_secs_f32(duration.min(u32::MAX as f32))
+            }
+            _ => Duration::ZERO,
+        };
+
+        query_completed_at
+            .checked_sub(duration)
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
 impl CommonPlayer for ServerPlayer {
-    fn as_original(&self) -> GenericPlayer { GenericPlayer::Valve(self) }
+    fn as_original(&self) -> GenericPlayer<'_> { GenericPlayer::Valve(self) }
     fn name(&self) -> &str { &self.name }
     fn score(&self) -> Option<i32> { Some(self.score) }
 }
@@ -161,8 +227,12 @@ pub struct ExtraData {
     pub tv_port: Option<u16>,
     /// SourceTV's name.
     pub tv_name: Option<String>,
-    /// Keywords that describe the server according to it.
+    /// Keywords that describe the server according to it, with the control
+    /// bytes some plugins put in them replaced by spaces.
     pub keywords: Option<String>,
+    /// The keywords as sent (invalid UTF-8 aside, see [Response::raw] for
+    /// the exact bytes), only kept if [GatheringSettings::keep_raw] is set.
+    pub raw_keywords: Option<String>,
     /// The server's 64-bit GameID.
     pub game_id: Option<u64>,
 }
@@ -228,6 +298,35 @@ impl Packet {
     }
 }
 
+/// Get the value of a `key:value` keyword, keywords being comma separated.
+#[cfg(feature = "games")]
+pub(crate) fn keyword_value<'a>(keywords: &'a str, key: &str) -> Option<&'a str> {
+    keywords.split(',').find_map(|keyword| {
+        let (name, value) = keyword.split_once(':')?;
+        (name.trim() == key).then(|| value.trim())
+    })
+}
+
+/// The tickrate keyword patterns most games use, see [keyword_number].
+pub const TICKRATE_PATTERNS: &[&str] = &["tickrate_{}"];
+
+/// Get the number of the first comma separated keyword matching one of the
+/// patterns, a pattern being the keyword with `{}` in place of the number
+/// (`"tickrate_{}"` matching `tickrate_128`, `"{}tick"` matching `128tick`).
+pub fn keyword_number(keywords: &str, patterns: &[&str]) -> Option<u16> {
+    keywords.split(',').map(str::trim).find_map(|keyword| {
+        patterns.iter().find_map(|pattern| {
+            let (prefix, suffix) = pattern.split_once("{}")?;
+
+            keyword
+                .strip_prefix(prefix)?
+                .strip_suffix(suffix)?
+                .parse()
+                .ok()
+        })
+    })
+}
+
 /// The type of the request, see the [protocol](https://developer.valvesoftware.com/wiki/Server_queries).
 #[derive(Eq, PartialEq, Copy, Clone)]
 #[repr(u8)]
@@ -277,8 +376,122 @@ impl Engine {
     pub const fn new_with_dedicated(appid: u32, dedicated_appid: u32) -> Self {
         Self::Source(Some((appid, Some(dedicated_appid))))
     }
+
+    /// Get the query port of a server given its game port, according to the
+    /// app's entry in [QUERY_PORTS], servers of apps that aren't listed (and
+    /// GoldSrc ones) are queried on their game port.
+    pub fn query_port_for(&self, game_port: u16) -> u16 {
+        let query_port = match self {
+            Self::Source(Some((appid, _))) => {
+                QUERY_PORTS
+                    .iter()
+                    .find(|(id, _)| id == appid)
+                    .map(|(_, query_port)| *query_port)
+            }
+            _ => None,
+        };
+
+        match query_port {
+            None | Some(QueryPort::SameAsGame) => game_port,
+            Some(QueryPort::Offset(offset)) => u16::try_from(i32::from(game_port) + offset).unwrap_or(game_port),
+            Some(QueryPort::Fixed(port)) => port,
+        }
+    }
+}
+
+/// How the query port of a game relates to its game port.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum QueryPort {
+    /// The server answers queries on its game port.
+    SameAsGame,
+    /// The query port is the game port plus this offset.
+    Offset(i32),
+    /// The query port is configured apart from the game port, this being its
+    /// default value.
+    Fixed(u16),
+}
+
+/// The query ports of the apps that aren't queried on their game port, by
+/// steam app id.
+pub const QUERY_PORTS: &[(u32, QueryPort)] = &[
+    // Ground Branch: set by `QueryPort`, defaults to 27015.
+    (16900, QueryPort::Fixed(27015)),
+    // ARMA 2: Operation Arrowhead: 2302 -> 2303.
+    (33930, QueryPort::Offset(1)),
+    // DayZ: set by `steamQueryPort`, defaults to 27016.
+    (221_100, QueryPort::Fixed(27016)),
+    // Creativerse: 26900 -> 26901.
+    (280_790, QueryPort::Offset(1)),
+    // Unturned: 27015 -> 27016.
+    (304_930, QueryPort::Offset(1)),
+    // Rising World: 4255 -> 4254.
+    (324_080, QueryPort::Offset(-1)),
+    // ARK: Survival Evolved: set by `QueryPort`, defaults to 27015.
+    (346_110, QueryPort::Fixed(27015)),
+    // Squad: set by `QueryPort`, defaults to 27165.
+    (393_380, QueryPort::Fixed(27165)),
+    // Conan Exiles: set by `QueryPort`, defaults to 27015.
+    (440_900, QueryPort::Fixed(27015)),
+    // Battalion 1944: 7777 -> 7780.
+    (489_940, QueryPort::Offset(3)),
+    // The Forest: 27015 -> 27016.
+    (556_450, QueryPort::Offset(1)),
+    // Insurgency: Sandstorm: set by `-QueryPort`, defaults to 27131.
+    (581_320, QueryPort::Fixed(27131)),
+    // Barotrauma: 27015 -> 27016.
+    (602_960, QueryPort::Offset(1)),
+    // Risk of Rain 2: 27015 -> 27016.
+    (632_360, QueryPort::Offset(1)),
+    // ATLAS: 5761 -> 57561.
+    (834_910, QueryPort::Offset(51800)),
+    // Valheim: 2456 -> 2457.
+    (892_970, QueryPort::Offset(1)),
+];
+
+/// A probe sent once before the actual requests, for servers behind anti-DDoS
+/// frontends that only answer requests which echo a token handed out in reply
+/// to the probe.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PreProbe {
+    /// The datagram sent as the probe.
+    pub request: Vec<u8>,
+    /// Where the token starts in the reply.
+    pub token_offset: usize,
+    /// The token length, it is appended to every following request.
+    pub token_length: usize,
+}
+
+impl PreProbe {
+    /// Extract the token from a reply to the probe.
+    pub(crate) fn read_token(&self, reply: &[u8]) -> GDResult<Vec<u8>> {
+        let token_end = self
+            .token_offset
+            .checked_add(self.token_length)
+            .ok_or_else(|| PacketUnderflow.context("The probe token ends past any reply"))?;
+
+        reply
+            .get(self.token_offset .. token_end)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| {
+                PacketTooShort {
+                    expected: self.token_length,
+                    got: reply.len().saturating_sub(self.token_offset),
+                    offset: self.token_offset,
+                }
+                .into()
+            })
+    }
 }
 
+/// The default [max_name_length](GatheringSettings::max_name_length).
+pub const DEFAULT_MAX_NAME_LENGTH: usize = 256;
+
+/// The default
+/// [max_challenge_retries](GatheringSettings::max_challenge_retries).
+pub const DEFAULT_MAX_CHALLENGE_RETRIES: usize = 3;
+
 /// What data to gather, purely used only with the query function.
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -286,16 +499,38 @@ pub struct GatheringSettings {
     pub players: GatherToggle,
     pub rules: GatherToggle,
     pub check_app_id: bool,
+    /// The maximum length (in bytes) of the server's name, a name that isn't
+    /// null terminated within it failing the query instead of being read into
+    /// the following fields (as sent by some malformed servers).
+    pub max_name_length: usize,
+    /// A challenge the server already handed out (see
+    /// [Response::challenge]), sent right away with the requests to skip the
+    /// round trip of asking for one. A challenge the server rejected (by
+    /// handing out a new one) is replaced.
+    pub challenge: Option<i32>,
+    /// How many times a request is sent again with a new challenge, when the
+    /// one it was sent with expired (busy servers handing out another one
+    /// instead of the reply), before failing the request.
+    pub max_challenge_retries: usize,
+    /// Keep the payload of the info reply in [Response::raw], and the
+    /// keywords as sent in [ExtraData::raw_keywords].
+    pub keep_raw: bool,
 }
 
 impl GatheringSettings {
     /// Default values are try to gather but don't fail on timeout for both
-    /// players and rules.
+    /// players and rules, [DEFAULT_MAX_NAME_LENGTH] for the name, no
+    /// challenge, [DEFAULT_MAX_CHALLENGE_RETRIES] and not keeping the raw
+    /// info.
     pub const fn default() -> Self {
         Self {
             players: GatherToggle::Try,
             rules: GatherToggle::Try,
             check_app_id: true,
+            max_name_length: DEFAULT_MAX_NAME_LENGTH,
+            challenge: None,
+            max_challenge_retries: DEFAULT_MAX_CHALLENGE_RETRIES,
+            keep_raw: false,
         }
     }
 
@@ -321,6 +556,10 @@ impl From<ExtraRequestSettings> for GatheringSettings {
             players: value.gather_players.unwrap_or(default.players),
             rules: value.gather_rules.unwrap_or(default.rules),
             check_app_id: value.check_app_id.unwrap_or(default.check_app_id),
+            max_name_length: default.max_name_length,
+            challenge: default.challenge,
+            max_challenge_retries: default.max_challenge_retries,
+            keep_raw: default.keep_raw,
         }
     }
 }
@@ -329,13 +568,21 @@ impl From<ExtraRequestSettings> for GatheringSettings {
 /// ones, but without the unnecessary bits (example: the **The Ship**-only
 /// fields).
 pub mod game {
-    use super::{Server, ServerPlayer};
+    use super::{keyword_number, Environment, ModData, Server, ServerInfo, ServerPlayer, TICKRATE_PATTERNS};
     use crate::protocols::valve::types::get_optional_extracted_data;
     use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::time::{Duration, SystemTime};
 
     #[cfg(feature = "serde")]
     use serde::{Deserialize, Serialize};
 
+    /// How many more players the info can report than the player list holds
+    /// before [Response::likely_fake_players] suspects them of being fake:
+    /// players joining or leaving between the requests make honest servers
+    /// differ by a few.
+    pub const FAKE_PLAYERS_MARGIN: u8 = 2;
+
     /// A player's details.
     #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -346,6 +593,10 @@ pub mod game {
         pub score: i32,
         /// How long a player has been in the server (seconds).
         pub duration: f32,
+        /// The time the player joined at, `None` if the time of the query is
+        /// unknown, see [ServerPlayer::connected_since].
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub connected_since: Option<SystemTime>,
     }
 
     impl Player {
@@ -354,11 +605,27 @@ pub mod game {
                 name: player.name.clone(),
                 score: player.score,
                 duration: player.duration,
+                connected_since: None,
+            }
+        }
+
+        /// Same as [Player::from_valve_response], the join time being
+        /// computed from when the query completed.
+        pub fn from_valve_response_at(player: &ServerPlayer, queried_at: Option<SystemTime>) -> Self {
+            Self {
+                connected_since: queried_at.map(|queried_at| player.connected_since(queried_at)),
+                ..Self::from_valve_response(player)
             }
         }
     }
 
     /// The query response.
+    ///
+    /// With the `serde` feature, it is serialized with its field names as the
+    /// keys (and its players', the enums as their variant names, like
+    /// `"Dedicated"`), missing optional data being `null`. These names are
+    /// part of the API, see `Response::to_gamedig_json` for the shape of the
+    /// Node.js library instead.
     #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
     #[derive(Debug, Clone, PartialEq)]
     pub struct Response {
@@ -368,20 +635,38 @@ pub mod game {
         pub name: String,
         /// Map name.
         pub map: String,
-        /// The name of the game.
+        /// The folder (mod directory) of the game (example: `cstrike`), to
+        /// tell the game apart.
+        pub folder: String,
+        /// The name of the game (example: `Counter-Strike`), for display.
         pub game: String,
-        /// Server's app id.
+        /// Server's app id, the one of the game id when present.
         pub appid: u32,
+        /// Server's app id as sent in its 16 bits field.
+        pub app_id_short: u16,
+        /// The full 64 bits game id, if the server sent it.
+        pub game_id: Option<u64>,
         /// Number of players on the server.
         pub players_online: u8,
-        /// Details about the server's players (not all players necessarily).
+        /// Details about the server's players (not all players necessarily),
+        /// without the SourceTV spectators.
         pub players_details: Vec<Player>,
+        /// Whether the player list was gathered, `players_details` being empty
+        /// otherwise.
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub players_gathered: bool,
+        /// Number of SourceTV spectators in the player list, `None` if the
+        /// players weren't gathered or the server didn't report its SourceTV
+        /// name.
+        pub spectators: Option<u8>,
         /// Maximum number of players the server reports it can hold.
         pub players_maximum: u8,
         /// Number of bots on the server.
         pub players_bots: u8,
         /// Dedicated, NonDedicated or SourceTV
         pub server_type: Server,
+        /// The Operating System that the server is on.
+        pub environment_type: Environment,
         /// Indicates whether the server requires a password.
         pub has_password: bool,
         /// Indicated whether the server uses VAC.
@@ -398,30 +683,96 @@ pub mod game {
         pub tv_name: Option<String>,
         /// Keywords that describe the server according to it.
         pub keywords: Option<String>,
+        /// The tickrate reported in the keywords, `None` if none matches the
+        /// game's tickrate patterns.
+        pub tickrate: Option<u16>,
+        /// The mod an obsolete GoldSrc server runs, `None` if it doesn't run
+        /// one (or isn't such a server).
+        pub goldsrc_mod: Option<ModData>,
         /// Server's rules.
         pub rules: HashMap<String, String>,
+        /// The address that answered the query, which isn't the queried one
+        /// if it redirected to another server, `None` if unknown.
+        pub response_address: Option<SocketAddr>,
+        /// The payload of the info reply, if the query kept it (see
+        /// [GatheringSettings::keep_raw](super::GatheringSettings::keep_raw)).
+        /// Serialized as a base64 string.
+        #[cfg_attr(feature = "serde", serde(default, with = "crate::utils::base64_bytes"))]
+        pub raw: Option<Vec<u8>>,
+        /// The round trip time of the first request, `None` if unknown.
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub ping: Option<Duration>,
+        /// When the query completed, `None` if unknown.
+        #[cfg_attr(feature = "serde", serde(default))]
+        pub queried_at: Option<SystemTime>,
     }
 
     impl Response {
         pub fn new_from_valve_response(response: super::Response) -> Self {
+            Self::new_from_valve_response_with_tickrate(response, TICKRATE_PATTERNS)
+        }
+
+        /// Assemble a response from the results of the separate requests, for
+        /// when they were made manually (or replayed from a capture), missing
+        /// players or rules being left empty.
+        pub fn from_parts(
+            info: ServerInfo,
+            players: Option<Vec<ServerPlayer>>,
+            rules: Option<HashMap<String, String>>,
+        ) -> Self {
+            Self::new_from_valve_response(super::Response {
+                info,
+                players,
+                rules,
+                response_address: None,
+                queried_at: None,
+                challenge: None,
+                raw: None,
+                ping: None,
+            })
+        }
+
+        /// Same as [Response::new_from_valve_response], for games whose
+        /// keywords report the tickrate in another form, see
+        /// [keyword_number] for the patterns.
+        pub fn new_from_valve_response_with_tickrate(response: super::Response, tickrate_patterns: &[&str]) -> Self {
+            let game_id = response.info.extra_data.as_ref().and_then(|data| data.game_id);
             let (port, steam_id, tv_port, tv_name, keywords) = get_optional_extracted_data(response.info.extra_data);
+            let tickrate = keywords
+                .as_deref()
+                .and_then(|keywords| keyword_number(keywords, tickrate_patterns));
+
+            // SourceTV proxies show up in the player list under the SourceTV
+            // name, they are counted apart from the players.
+            let players_gathered = response.players.is_some();
+            let mut players = response.players.unwrap_or_default();
+            let spectators = tv_name.as_ref().filter(|_| players_gathered).map(|tv_name| {
+                let online = players.len();
+                players.retain(|player| &player.name != tv_name);
+
+                (online - players.len()) as u8
+            });
 
             Self {
                 protocol: response.info.protocol_version,
                 name: response.info.name,
                 map: response.info.map,
+                folder: response.info.folder,
                 game: response.info.game_mode,
                 appid: response.info.appid,
+                app_id_short: response.info.app_id_short,
+                game_id,
                 players_online: response.info.players_online,
-                players_details: response
-                    .players
-                    .unwrap_or_default()
+                players_details: players
                     .iter()
-                    .map(Player::from_valve_response)
+                    .map(|player| Player::from_valve_response_at(player, response.queried_at))
                     .collect(),
+                players_gathered,
+                spectators,
                 players_maximum: response.info.players_maximum,
                 players_bots: response.info.players_bots,
                 server_type: response.info.server_type,
+                environment_type: response.info.environment_type,
                 has_password: response.info.has_password,
                 vac_secured: response.info.vac_secured,
                 version: response.info.game_version,
@@ -430,8 +781,552 @@ pub mod game {
                 tv_port,
                 tv_name,
                 keywords,
+                tickrate,
+                goldsrc_mod: response.info.mod_data,
                 rules: response.rules.unwrap_or_default(),
+                response_address: response.response_address,
+                raw: response.raw,
+                ping: response.ping,
+                queried_at: response.queried_at,
             }
         }
+
+        /// The name of the server's operating system, for display, see
+        /// [Environment::os_name]. Both of the GoldSrc Mac identifiers (`m`
+        /// and `o`) map to "macOS", and unknown identifiers to "Unknown".
+        pub const fn os_name(&self) -> &'static str { self.environment_type.os_name() }
+
+        /// The keywords split into tags, on commas and whitespace (the
+        /// delimiters games use), empty if there are no keywords.
+        pub fn keyword_tags(&self) -> Vec<&str> {
+            self.keywords
+                .as_deref()
+                .unwrap_or_default()
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|tag| !tag.is_empty())
+                .collect()
+        }
+
+        /// The number of players the info reports beyond the named players of
+        /// the player list (and the SourceTV spectators), when it exceeds
+        /// [FAKE_PLAYERS_MARGIN]: boosted servers pad the reported count
+        /// with players that don't exist, or list them without a name.
+        ///
+        /// This is a heuristic, `None` telling that nothing looks suspicious,
+        /// or that the player list wasn't gathered, as there is nothing to
+        /// compare to. An empty list that was gathered leaves all the reported
+        /// players unaccounted for.
+        pub fn likely_fake_players(&self) -> Option<u8> {
+            if !self.players_gathered {
+                return None;
+            }
+
+            let named = self
+                .players_details
+                .iter()
+                .filter(|player| !player.name.trim().is_empty())
+                .count();
+            let listed = (named + usize::from(self.spectators.unwrap_or(0))).min(u8::MAX.into()) as u8;
+
+            Some(self.players_online.saturating_sub(listed)).filter(|missing| *missing > FAKE_PLAYERS_MARGIN)
+        }
+
+        /// A fingerprint of the server, to tell that responses from different
+        /// addresses (IPv4, IPv6, hostnames, several master servers...) are of
+        /// the same logical server.
+        ///
+        /// It is computed from the stable fields only: the name, the steam id
+        /// (when present), the game id (or app id) and the maximum number of
+        /// players. Volatile fields, like the number of players or the current
+        /// map, are deliberately left out, so that it doesn't change between
+        /// queries. The hash (64 bits FNV-1a) is the same across runs, builds
+        /// and platforms, so fingerprints can be stored.
+        pub fn fingerprint(&self) -> u64 {
+            const OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+            const PRIME: u64 = 0x0000_0100_0000_01B3;
+
+            let mut data = self.name.as_bytes().to_vec();
+            // Separates the name from the following fields.
+            data.push(0);
+            match self.steam_id {
+                Some(steam_id) => data.extend_from_slice(&steam_id.to_le_bytes()),
+                None => data.extend_from_slice(&[0xFF; 8]),
+            }
+            data.extend_from_slice(&self.game_id.unwrap_or(self.appid.into()).to_le_bytes());
+            data.push(self.players_maximum);
+
+            data.iter()
+                .fold(OFFSET_BASIS, |hash, byte| (hash ^ u64::from(*byte)).wrapping_mul(PRIME))
+        }
+
+        /// Render the response in the JSON shape of the Node.js GameDig
+        /// library, so that tools built for it can switch over.
+        ///
+        /// - `name`, `map`, `password`, `maxplayers`, `numplayers` and
+        ///   `version` are the server's name, map, `has_password`,
+        ///   `players_maximum`, `players_online` and `version`.
+        /// - `players` are the `players_details`, as `{"name", "raw": {"score",
+        ///   "time"}}`, `time` being the duration in seconds.
+        /// - `bots` is always empty, as the players don't tell whether they
+        ///   are bots (their number is in `raw.numbots`).
+        /// - `ping` is in milliseconds, `null` if unknown, and `connect` is the
+        ///   [response_address](Self::response_address), `null` if unknown.
+        /// - `raw` holds every other field: `protocol`, `game`, `appId`,
+        ///   `numbots`, `listentype` (`"d"`, `"l"` or `"p"`), `environment`
+        ///   (`"l"`, `"w"` or `"m"`), `secure`, `port`, `steamid` (as a
+        ///   string), `tv_port`, `tv_name`, `tags` (the keywords), `tickrate`,
+        ///   `spectators` and `rules`.
+        ///
+        /// Missing optional data is always present as `null`, never omitted.
+        #[cfg(feature = "serde")]
+        pub fn to_gamedig_json(&self) -> serde_json::Value {
+            serde_json::json!({
+                "name": self.name,
+                "map": self.map,
+                "password": self.has_password,
+                "maxplayers": self.players_maximum,
+                "numplayers": self.players_online,
+                "players": self.players_details.iter().map(|player| serde_json::json!({
+                    "name": player.name,
+                    "raw": {
+                        "score": player.score,
+                        "time": player.duration,
+                    },
+                })).collect::<Vec<_>>(),
+                "bots": [],
+                "version": self.version,
+                "ping": self.ping.map(|ping| ping.as_millis() as u64),
+                "connect": self.response_address.map(|address| address.to_string()),
+                "raw": {
+                    "protocol": self.protocol,
+                    "game": self.game,
+                    "appId": self.appid,
+                    "numbots": self.players_bots,
+                    "listentype": match self.server_type {
+                        Server::Dedicated => "d",
+                        Server::NonDedicated => "l",
+                        Server::TV => "p",
+                    },
+                    "environment": match self.environment_type {
+                        Environment::Linux => 'l',
+                        Environment::Windows => 'w',
+                        Environment::Mac => 'm',
+                        Environment::Unknown(value) => char::from(value),
+                    },
+                    "secure": self.vac_secured,
+                    "port": self.port,
+                    "steamid": self.steam_id.map(|steam_id| steam_id.to_string()),
+                    "tv_port": self.tv_port,
+                    "tv_name": self.tv_name,
+                    "tags": self.keywords,
+                    "tickrate": self.tickrate,
+                    "spectators": self.spectators,
+                    "rules": self.rules,
+                },
+            })
+        }
+
+        /// Render the response as metrics in the Prometheus text exposition
+        /// format, every sample being labeled with `labels` (their values are
+        /// escaped, their names must be valid label names):
+        /// - `gamedig_up`: always 1, as the server answered.
+        /// - `gamedig_players`: number of players on the server.
+        /// - `gamedig_max_players`: maximum number of players.
+        /// - `gamedig_ping_ms`: the [ping](Self::ping) in milliseconds, only
+        ///   when it was measured.
+        #[cfg(feature = "prometheus")]
+        pub fn to_prometheus(&self, labels: &[(&str, &str)]) -> String {
+            let labels = prometheus_labels(labels);
+
+            let mut metrics = vec![
+                ("gamedig_up", "Whether the server answered the query.", 1.to_string()),
+                (
+                    "gamedig_players",
+                    "Number of players on the server.",
+                    self.players_online.to_string(),
+                ),
+                (
+                    "gamedig_max_players",
+                    "Maximum number of players the server reports it can hold.",
+                    self.players_maximum.to_string(),
+                ),
+            ];
+            if let Some(ping) = self.ping {
+                metrics.push((
+                    "gamedig_ping_ms",
+                    "Round trip time of the query's first request, in milliseconds.",
+                    (ping.as_secs_f64() * 1000.0).to_string(),
+                ));
+            }
+
+            metrics
+                .iter()
+                .map(|(name, help, value)| {
+                    format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name}{labels} {value}\n")
+                })
+                .collect()
+        }
+    }
+
+    /// Render labels as `{name="value",...}`, escaping backslashes, double
+    /// quotes and line feeds in the values.
+    #[cfg(feature = "prometheus")]
+    fn prometheus_labels(labels: &[(&str, &str)]) -> String {
+        if labels.is_empty() {
+            return String::new();
+        }
+
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(name, value)| {
+                let value = value
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"")
+                    .replace('\n', "\\n");
+                format!("{name}=\"{value}\"")
+            })
+            .collect();
+
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+/// The info of a dedicated server with `keywords`, for the tests parsing
+/// responses.
+#[cfg(test)]
+pub(crate) fn test_server_info(keywords: &str) -> ServerInfo {
+    ServerInfo {
+        protocol_version: 17,
+        name: "gamedig".to_string(),
+        map: "cp_dustbowl".to_string(),
+        folder: "tf".to_string(),
+        game_mode: "Team Fortress".to_string(),
+        appid: 440,
+        app_id_short: 440,
+        players_online: 1,
+        players_maximum: 24,
+        players_bots: 0,
+        server_type: Server::Dedicated,
+        environment_type: Environment::Linux,
+        has_password: false,
+        vac_secured: true,
+        the_ship: None,
+        game_version: "8622567".to_string(),
+        extra_data: Some(ExtraData {
+            port: Some(27015),
+            steam_id: None,
+            tv_port: None,
+            tv_name: None,
+            keywords: Some(keywords.to_string()),
+            raw_keywords: None,
+            game_id: None,
+        }),
+        is_mod: false,
+        mod_data: None,
+    }
+}
+
+/// A player named `name`, for the tests parsing responses.
+#[cfg(test)]
+pub(crate) fn test_server_player(name: &str) -> ServerPlayer {
+    ServerPlayer {
+        index: 0,
+        name: name.to_string(),
+        score: 5,
+        duration: 60.5,
+        deaths: None,
+        money: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "games")]
+    #[test]
+    fn keyword_value() {
+        let keywords = "level:Ministry, mode:TDM,alltalk";
+
+        assert_eq!(super::keyword_value(keywords, "level"), Some("Ministry"));
+        assert_eq!(super::keyword_value(keywords, "mode"), Some("TDM"));
+        assert_eq!(super::keyword_value(keywords, "alltalk"), None);
+    }
+
+    #[test]
+    fn keyword_number() {
+        let keywords = "alltalk, tickrate_128,64tick";
+
+        assert_eq!(super::keyword_number(keywords, TICKRATE_PATTERNS), Some(128));
+        assert_eq!(super::keyword_number(keywords, &["{}tick"]), Some(64));
+        assert_eq!(super::keyword_number(keywords, &["tick_{}"]), None);
+        assert_eq!(super::keyword_number("tickrate_fast", TICKRATE_PATTERNS), None);
+    }
+
+    fn server_info(tv_name: Option<&str>) -> ServerInfo {
+        let mut info = test_server_info("alltalk,tickrate_66");
+        if let Some(extra_data) = info.extra_data.as_mut() {
+            extra_data.tv_name = tv_name.map(str::to_string);
+        }
+
+        info
+    }
+
+    #[test]
+    fn from_parts() {
+        let response = game::Response::from_parts(server_info(None), Some(vec![test_server_player("alice")]), None);
+        assert_eq!(response.name, "gamedig");
+        assert_eq!(response.port, Some(27015));
+        assert_eq!(response.tickrate, Some(66));
+        assert_eq!(response.players_details.len(), 1);
+        assert_eq!(response.players_details[0].name, "alice");
+        assert_eq!(response.spectators, None);
+        assert!(response.rules.is_empty());
+    }
+
+    #[test]
+    fn folder_and_game_are_apart() {
+        let info = ServerInfo {
+            folder: "cstrike".to_string(),
+            game_mode: "Counter-Strike".to_string(),
+            ..server_info(None)
+        };
+
+        let response = game::Response::from_parts(info, None, None);
+        assert_eq!(response.folder, "cstrike");
+        assert_eq!(response.game, "Counter-Strike");
+    }
+
+    #[test]
+    fn sourcetv_spectators_are_counted_apart() {
+        let players = vec![
+            test_server_player("SourceTV"),
+            test_server_player("alice"),
+            test_server_player("SourceTV"),
+        ];
+
+        let response = game::Response::from_parts(server_info(Some("SourceTV")), Some(players.clone()), None);
+        assert_eq!(response.spectators, Some(2));
+        assert_eq!(response.players_details.len(), 1);
+        assert_eq!(response.players_details[0].name, "alice");
+
+        let response = game::Response::from_parts(server_info(Some("GOTV")), Some(players), None);
+        assert_eq!(response.spectators, Some(0));
+        assert_eq!(response.players_details.len(), 3);
+
+        // There are no spectators to count without the player list.
+        let response = game::Response::from_parts(server_info(Some("SourceTV")), None, None);
+        assert_eq!(response.spectators, None);
+    }
+
+    #[test]
+    fn likely_fake_players() {
+        let response = |players_online, names: &[&str]| {
+            let info = ServerInfo {
+                players_online,
+                ..server_info(Some("SourceTV"))
+            };
+            let players = names.iter().map(|name| test_server_player(name)).collect();
+
+            game::Response::from_parts(info, Some(players), None)
+        };
+
+        assert_eq!(response(3, &["alice", "bob", "SourceTV"]).likely_fake_players(), None);
+        // Players joining between the requests.
+        assert_eq!(response(4, &["alice", "SourceTV"]).likely_fake_players(), None);
+        assert_eq!(response(24, &["alice", "", " ", "SourceTV"]).likely_fake_players(), Some(22));
+        assert_eq!(response(24, &[]).likely_fake_players(), Some(24));
+        assert_eq!(response(2, &[]).likely_fake_players(), None);
+
+        let response = game::Response::from_parts(server_info(None), None, None);
+        assert_eq!(response.likely_fake_players(), None);
+    }
+
+    #[test]
+    fn keyword_tags() {
+        let mut response = game::Response::from_parts(server_info(None), None, None);
+        assert_eq!(response.keyword_tags(), ["alltalk", "tickrate_66"]);
+
+        response.keywords = Some(" empty,, secure  hltv\tcoop ".to_string());
+        assert_eq!(response.keyword_tags(), ["empty", "secure", "hltv", "coop"]);
+
+        response.keywords = Some(" \t".to_string());
+        assert!(response.keyword_tags().is_empty());
+        response.keywords = None;
+        assert!(response.keyword_tags().is_empty());
+    }
+
+    #[test]
+    fn connected_since() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let mut player = test_server_player("alice");
+
+        player.duration = 60.5;
+        assert_eq!(player.connected_since(now), now - Duration::from_secs_f32(60.5));
+
+        for duration in [-1.0, f32::NAN] {
+            player.duration = duration;
+            assert_eq!(player.connected_since(now), now);
+        }
+        // Longer than any duration the servers send, but doesn't overflow.
+        player.duration = f32::MAX;
+        assert!(player.connected_since(now) < now);
+    }
+
+    #[test]
+    fn fingerprint() {
+        let mut info = server_info(None);
+        let response = game::Response::from_parts(info.clone(), None, None);
+        // Doesn't change across runs (or versions).
+        assert_eq!(response.fingerprint(), 0x2C4A_B4BB_C851_9A34);
+
+        // Volatile fields don't change it.
+        info.map = "de_nuke".to_string();
+        info.players_online += 1;
+        let later = game::Response::from_parts(info.clone(), Some(vec![test_server_player("alice")]), None);
+        assert_eq!(response.fingerprint(), later.fingerprint());
+
+        info.name.push('!');
+        assert_ne!(
+            response.fingerprint(),
+            game::Response::from_parts(info, None, None).fingerprint()
+        );
+    }
+
+    #[test]
+    fn os_name() {
+        assert_eq!(Environment::from_gldsrc(b'o').os_name(), "macOS");
+        assert_eq!(Environment::from_gldsrc(b'm').os_name(), "macOS");
+        assert_eq!(Environment::from_gldsrc(b'l').os_name(), "Linux");
+        assert_eq!(Environment::Windows.os_name(), "Windows");
+        assert_eq!(Environment::from_gldsrc(b'x'), Environment::Unknown(b'x'));
+        assert_eq!(Environment::from_gldsrc(b'x').os_name(), "Unknown");
+    }
+
+    #[cfg(any(feature = "serde", feature = "prometheus"))]
+    fn game_response() -> game::Response {
+        game::Response {
+            protocol: 17,
+            name: "A \"quoted\" server".to_string(),
+            map: "cp_badlands".to_string(),
+            folder: "tf".to_string(),
+            game: "Team Fortress".to_string(),
+            appid: 440,
+            app_id_short: 440,
+            game_id: None,
+            players_online: 12,
+            players_details: Vec::new(),
+            players_gathered: false,
+            spectators: None,
+            players_maximum: 24,
+            players_bots: 0,
+            server_type: Server::Dedicated,
+            environment_type: Environment::Linux,
+            has_password: false,
+            vac_secured: true,
+            version: "8622567".to_string(),
+            port: None,
+            steam_id: None,
+            tv_port: None,
+            tv_name: None,
+            keywords: None,
+            tickrate: None,
+            goldsrc_mod: None,
+            rules: HashMap::new(),
+            response_address: None,
+            raw: None,
+            ping: None,
+            queried_at: None,
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn to_gamedig_json() {
+        let mut response = game_response();
+        response.players_details.push(game::Player {
+            name: "alice".to_string(),
+            score: 5,
+            duration: 60.5,
+            connected_since: None,
+        });
+
+        let json = response.to_gamedig_json();
+        assert_eq!(json["maxplayers"], 24);
+        assert_eq!(json["players"][0]["name"], "alice");
+        assert_eq!(json["players"][0]["raw"]["time"], 60.5);
+        assert_eq!(json["raw"]["listentype"], "d");
+        assert_eq!(json["raw"]["environment"], "l");
+        assert!(json["raw"]["steamid"].is_null());
+        assert!(json.get("connect").unwrap().is_null());
+        assert!(json.get("ping").unwrap().is_null());
+
+        response.ping = Some(std::time::Duration::from_micros(42_700));
+        response.response_address = Some("127.0.0.1:27015".parse().unwrap());
+        let json = response.to_gamedig_json();
+        assert_eq!(json["ping"], 42);
+        assert_eq!(json["connect"], "127.0.0.1:27015");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialized_field_names() {
+        let mut response = game_response();
+        response.raw = Some(vec![0x11, 0xFF]);
+
+        let json = serde_json::to_value(&response).unwrap();
+        assert_eq!(json["players_maximum"], 24);
+        assert_eq!(json["server_type"], "Dedicated");
+        assert_eq!(json["environment_type"], "Linux");
+        assert!(json["steam_id"].is_null());
+        assert_eq!(json["raw"], "Ef8=");
+        assert_eq!(serde_json::from_value::<game::Response>(json).unwrap(), response);
+
+        // Clients may leave the raw payload out.
+        let mut json = serde_json::to_value(game_response()).unwrap();
+        json.as_object_mut().unwrap().remove("raw");
+        assert_eq!(serde_json::from_value::<game::Response>(json).unwrap().raw, None);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn to_prometheus() {
+        let response = game_response();
+        let metrics = response.to_prometheus(&[("game", "tf2"), ("name", &response.name)]);
+        assert!(metrics.contains("gamedig_up{game=\"tf2\",name=\"A \\\"quoted\\\" server\"} 1\n"));
+        assert!(metrics.contains("# TYPE gamedig_players gauge\n"));
+        assert!(metrics.contains("gamedig_players{game=\"tf2\",name=\"A \\\"quoted\\\" server\"} 12\n"));
+        assert!(metrics.contains("gamedig_max_players{game=\"tf2\",name=\"A \\\"quoted\\\" server\"} 24\n"));
+
+        assert!(response.to_prometheus(&[]).contains("\ngamedig_up 1\n"));
+        // Not measured.
+        assert!(!metrics.contains("gamedig_ping_ms"));
+
+        let response = game::Response {
+            ping: Some(std::time::Duration::from_micros(12_500)),
+            ..game_response()
+        };
+        let metrics = response.to_prometheus(&[]);
+        assert!(metrics.contains("# TYPE gamedig_ping_ms gauge\n"));
+        assert!(metrics.contains("\ngamedig_ping_ms 12.5\n"));
+    }
+
+    #[test]
+    fn query_port_for() {
+        // Valheim
+        assert_eq!(Engine::new(892_970).query_port_for(2456), 2457);
+        // Rising World
+        assert_eq!(Engine::new(324_080).query_port_for(4255), 4254);
+        // ARK: Survival Evolved
+        assert_eq!(Engine::new(346_110).query_port_for(7777), 27015);
+        // Team Fortress 2
+        assert_eq!(Engine::new(440).query_port_for(27015), 27015);
+        assert_eq!(Engine::new_gold_src(false).query_port_for(27015), 27015);
+        assert_eq!(Engine::new(892_970).query_port_for(u16::MAX), u16::MAX);
+    }
+
+    #[test]
+    fn query_ports_are_sorted_by_app_id() {
+        assert!(QUERY_PORTS.windows(2).all(|pair| pair[0].0 < pair[1].0));
     }
 }
@@ -1,12 +1,12 @@
 use crate::{
     buffer::Buffer,
-    socket::{Socket, UdpSocket},
+    transport::{Transport, UdpTransport},
     valve_master_server::{Region, SearchFilters},
     GDErrorKind::PacketBad,
     GDResult,
 };
 
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 use byteorder::BigEndian;
 
@@ -15,6 +15,25 @@ pub fn default_master_address() -> SocketAddr {
     SocketAddr::new(IpAddr::V4(Ipv4Addr::new(208, 64, 201, 194)), 27011) // hl2master.steampowered.com
 }
 
+/// The reply header (`\xFF\xFF\xFF\xFFf`) is followed by `\n` when the
+/// servers are listed as IPv4 addresses (4 bytes each, then the port).
+const REPLY_IPV4: u8 = 0x0A;
+/// Newer master servers follow it by `\x0B` instead when they list IPv6
+/// addresses (16 bytes each, then the port).
+const REPLY_IPV6: u8 = 0x0B;
+
+/// Whether the address is the one that marks the end of the list.
+fn is_end_marker((ip, port): &(IpAddr, u16)) -> bool { ip.is_unspecified() && *port == 0 }
+
+/// The representation of an address in a request, IPv6 ones being bracketed
+/// so that they can be told apart from the port.
+fn address_string(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("[{ip}]"),
+    }
+}
+
 fn construct_payload(region: Region, filters: &Option<SearchFilters>, last_ip: &str, last_port: u16) -> Vec<u8> {
     let filters_bytes: Vec<u8> = filters
         .as_ref()
@@ -30,7 +49,7 @@ fn construct_payload(region: Region, filters: &Option<SearchFilters>, last_ip: &
         // The last fetched ip as a string
         last_ip.as_bytes(),
         // Followed by an ':'
-        &[b':'],
+        b":",
         // And the port, as a string
         last_port.to_string().as_bytes(),
         // Which needs to end with a NULL byte
@@ -42,20 +61,32 @@ fn construct_payload(region: Region, filters: &Option<SearchFilters>, last_ip: &
 }
 
 /// The implementation, use this if you want to keep the same socket.
-pub struct ValveMasterServer {
-    socket: UdpSocket,
+pub struct ValveMasterServer<T: Transport = UdpTransport> {
+    transport: T,
 }
 
 impl ValveMasterServer {
     /// Construct a new struct.
     pub fn new(master_address: &SocketAddr) -> GDResult<Self> {
-        let socket = UdpSocket::new(master_address, &None)?;
+        let transport = UdpTransport::connect(master_address, &None)?;
+
+        Ok(Self::with_transport(transport))
+    }
+}
+
+impl<T: Transport> ValveMasterServer<T> {
+    /// Construct a new struct talking to the master over the given transport
+    /// (see [MockTransport](crate::transport::MockTransport)).
+    pub fn with_transport(mut transport: T) -> Self {
+        transport.set_protocol_hint("valve master server");
 
-        Ok(Self { socket })
+        Self { transport }
     }
 
     /// Make just a single query, providing `0.0.0.0` as the last ip and `0` as
-    /// the last port will give the initial packet.
+    /// the last port will give the initial packet (an IPv6 last ip has to be
+    /// bracketed).
+    /// The listed servers can be a mix of IPv4 and IPv6 ones.
     pub fn query_specific(
         &mut self,
         region: Region,
@@ -64,24 +95,28 @@ impl ValveMasterServer {
         last_address_port: u16,
     ) -> GDResult<Vec<(IpAddr, u16)>> {
         let payload = construct_payload(region, search_filters, last_address_ip, last_address_port);
-        self.socket.send(&payload)?;
+        self.transport.send(&payload)?;
 
-        let received_data = self.socket.receive(Some(1400))?;
+        let received_data = self.transport.recv_timeout(Some(1400))?;
         let mut buf = Buffer::<BigEndian>::new(&received_data);
 
-        if buf.read::<u32>()? != u32::MAX || buf.read::<u16>()? != 26122 {
-            return Err(PacketBad.context("Expected 4294967295 followed by 26122"));
+        if buf.read::<u32>()? != u32::MAX || buf.read::<u8>()? != b'f' {
+            return Err(PacketBad.context("Expected 4294967295 followed by 'f'"));
         }
 
+        let is_ipv6 = match buf.read::<u8>()? {
+            REPLY_IPV4 => false,
+            REPLY_IPV6 => true,
+            kind => return Err(PacketBad.context(format!("Unknown address list type {kind:#04x}"))),
+        };
+
         let mut ips: Vec<(IpAddr, u16)> = Vec::new();
 
         while buf.remaining_length() > 0 {
-            let ip = IpAddr::V4(Ipv4Addr::new(
-                buf.read::<u8>()?,
-                buf.read::<u8>()?,
-                buf.read::<u8>()?,
-                buf.read::<u8>()?,
-            ));
+            let ip = match is_ipv6 {
+                false => IpAddr::V4(Ipv4Addr::from(buf.read::<u32>()?)),
+                true => IpAddr::V6(Ipv6Addr::from(buf.read::<u128>()?)),
+            };
             let port = buf.read::<u16>()?;
 
             ips.push((ip, port));
@@ -95,26 +130,28 @@ impl ValveMasterServer {
         let mut ips: Vec<(IpAddr, u16)> = Vec::new();
 
         let mut exit_fetching = false;
-        let mut last_ip: String = "0.0.0.0".to_string();
-        let mut last_port: u16 = 0;
+        let mut last_address: (IpAddr, u16) = (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
 
         while !exit_fetching {
-            let new_ips = self.query_specific(region, &search_filters, last_ip.as_str(), last_port)?;
+            let new_ips = self.query_specific(
+                region,
+                &search_filters,
+                &address_string(&last_address.0),
+                last_address.1,
+            )?;
 
             match new_ips.last() {
                 None => exit_fetching = true,
-                Some((latest_ip, latest_port)) => {
+                Some(latest_address) => {
                     let mut remove_last = false;
 
-                    let latest_ip_string = latest_ip.to_string();
-                    if latest_ip_string == "0.0.0.0" && *latest_port == 0 {
+                    if is_end_marker(latest_address) {
                         exit_fetching = true;
                         remove_last = true;
-                    } else if latest_ip_string == last_ip && *latest_port == last_port {
+                    } else if *latest_address == last_address {
                         exit_fetching = true;
                     } else {
-                        last_ip = latest_ip_string;
-                        last_port = *latest_port;
+                        last_address = *latest_address;
                     }
 
                     ips.extend(new_ips);
@@ -137,10 +174,8 @@ pub fn query_singular(region: Region, search_filters: Option<SearchFilters>) ->
 
     let mut ips = master_server.query_specific(region, &search_filters, "0.0.0.0", 0)?;
 
-    if let Some((last_ip, last_port)) = ips.last() {
-        if last_ip.to_string() == "0.0.0.0" && *last_port == 0 {
-            ips.pop();
-        }
+    if ips.last().map_or(false, is_end_marker) {
+        ips.pop();
     }
 
     Ok(ips)
@@ -152,3 +187,108 @@ pub fn query(region: Region, search_filters: Option<SearchFilters>) -> GDResult<
 
     master_server.query(region, search_filters)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocols::{
+        types::GatherToggle,
+        valve::{self, Engine, GatheringSettings, TestInfoReply},
+    };
+    use crate::transport::MockTransport;
+    use std::{net::UdpSocket, thread};
+
+    /// A master reply listing the given addresses, which have to be of the
+    /// same family.
+    fn master_reply(addresses: &[SocketAddr]) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xFF, 0xFF, 0xFF, b'f'];
+        data.push(match addresses[0] {
+            SocketAddr::V4(_) => REPLY_IPV4,
+            SocketAddr::V6(_) => REPLY_IPV6,
+        });
+
+        for address in addresses {
+            match address.ip() {
+                IpAddr::V4(ip) => data.extend_from_slice(&ip.octets()),
+                IpAddr::V6(ip) => data.extend_from_slice(&ip.octets()),
+            }
+            data.extend_from_slice(&address.port().to_be_bytes());
+        }
+
+        data
+    }
+
+    /// Start a server answering a single `A2S_INFO` request (without a
+    /// challenge), named after its address.
+    fn start_server(local_address: &str) -> (SocketAddr, thread::JoinHandle<()>) {
+        let socket = UdpSocket::bind(local_address).unwrap();
+        let address = socket.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let mut request = [0; 64];
+            let (_, client) = socket.recv_from(&mut request).unwrap();
+
+            let reply = TestInfoReply {
+                strings: format!("{address}\0de_dust2\0cstrike\0Counter-Strike: Source\0"),
+                app_id: 240,
+                counts: [0, 32, 0, b'd', b'l', 0, 1],
+                version: "1.0.0.0",
+                extra_data: None,
+                ..TestInfoReply::default()
+            };
+            socket.send_to(&reply.build(), client).unwrap();
+        });
+
+        (address, server)
+    }
+
+    #[test]
+    fn ipv4_and_ipv6_servers_are_listed_and_queried() {
+        let (ipv4_server, ipv4_thread) = start_server("127.0.0.1:0");
+        let (ipv6_server, ipv6_thread) = start_server("[::1]:0");
+        let end_marker = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
+
+        let transport = MockTransport::new([
+            master_reply(&[ipv4_server]),
+            master_reply(&[ipv6_server, end_marker]),
+        ]);
+        let mut master = ValveMasterServer::with_transport(transport);
+
+        let servers = master.query(Region::Others, None).unwrap();
+        assert_eq!(
+            servers,
+            [
+                (ipv4_server.ip(), ipv4_server.port()),
+                (ipv6_server.ip(), ipv6_server.port())
+            ]
+        );
+
+        // The second page is asked starting from the last listed server.
+        let sent = master.transport.sent();
+        assert!(sent[1].ends_with(format!("{ipv4_server}\0\0").as_bytes()));
+
+        let gather_settings = GatheringSettings {
+            players: GatherToggle::Skip,
+            rules: GatherToggle::Skip,
+            check_app_id: false,
+            ..GatheringSettings::default()
+        };
+        for (ip, port) in servers {
+            let address = SocketAddr::new(ip, port);
+            let response = valve::query(&address, Engine::new(240), Some(gather_settings), None).unwrap();
+
+            assert_eq!(response.info.name, address.to_string());
+        }
+
+        ipv4_thread.join().unwrap();
+        ipv6_thread.join().unwrap();
+    }
+
+    #[test]
+    fn ipv6_last_address_is_bracketed() {
+        let ip = IpAddr::V6(Ipv6Addr::LOCALHOST);
+        let payload = construct_payload(Region::Others, &None, &address_string(&ip), 27015);
+
+        assert!(payload.ends_with(b"[::1]:27015\0\0"));
+    }
+}
@@ -1,16 +1,76 @@
 use crate::{
+    errors::PacketTooShort,
     protocols::types::TimeoutSettings,
     GDErrorKind::{PacketReceive, PacketSend, SocketBind, SocketConnect},
     GDResult,
 };
 
 use std::{
-    io::{Read, Write},
-    net::{self, SocketAddr},
+    cell::Cell,
+    io::{self, Read, Write},
+    net::{self, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::{Duration, Instant},
 };
 
 const DEFAULT_PACKET_SIZE: usize = 1024;
 
+thread_local! {
+    /// The number of bytes sent and received by the sockets of the thread,
+    /// queries being made on the thread calling them.
+    static EXCHANGED_BYTES: Cell<(usize, usize)> = const { Cell::new((0, 0)) };
+}
+
+/// The number of bytes sent and received so far by the sockets of the current
+/// thread (wrapping around), to be compared with a previous count.
+#[cfg_attr(not(feature = "games"), allow(dead_code))]
+pub(crate) fn exchanged_bytes() -> (usize, usize) { EXCHANGED_BYTES.with(Cell::get) }
+
+fn count_exchanged_bytes(sent: usize, received: usize) {
+    EXCHANGED_BYTES.with(|bytes| {
+        let (total_sent, total_received) = bytes.get();
+        bytes.set((total_sent.wrapping_add(sent), total_received.wrapping_add(received)));
+    });
+}
+
+/// A timeout (of connecting, or of the next read): the socket's own timeout,
+/// shortened to what is left of the deadline. Once the deadline passed, it
+/// times out straight away.
+pub(crate) fn timeout_before(timeout: Option<Duration>, deadline: Option<Instant>) -> io::Result<Option<Duration>> {
+    let Some(deadline) = deadline else {
+        return Ok(timeout);
+    };
+
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, "The query deadline passed"));
+    }
+
+    Ok(Some(timeout.map_or(remaining, |timeout| timeout.min(remaining))))
+}
+
+/// The round trip time of the first exchange over a socket: from the last
+/// data sent before the first data received, so that a lost (and resent)
+/// request isn't counted.
+#[derive(Debug, Clone, Copy, Default)]
+struct PingTimer {
+    last_sent: Option<Instant>,
+    ping: Option<Duration>,
+}
+
+impl PingTimer {
+    fn sent(&mut self) {
+        if self.ping.is_none() {
+            self.last_sent = Some(Instant::now());
+        }
+    }
+
+    fn received(&mut self) {
+        if self.ping.is_none() {
+            self.ping = self.last_sent.map(|sent| sent.elapsed());
+        }
+    }
+}
+
 /// A trait defining the basic functionalities of a network socket.
 pub trait Socket {
     /// Create a new socket and connect to the remote address.
@@ -51,6 +111,29 @@ pub trait Socket {
     /// A result containing received data or an error.
     fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>>;
 
+    /// Receive exactly `size` bytes from the socket, for stream protocols
+    /// whose connection stays open once the response is read.
+    ///
+    /// # Arguments
+    /// * `size` - Size of the data to receive.
+    ///
+    /// # Returns
+    /// A result containing received data or an error, getting less data being
+    /// a [PacketUnderflow](crate::GDErrorKind::PacketUnderflow).
+    fn receive_exact(&mut self, size: usize) -> GDResult<Vec<u8>> {
+        let data = self.receive(Some(size))?;
+        if data.len() != size {
+            return Err(PacketTooShort {
+                expected: size,
+                got: data.len(),
+                offset: 0,
+            }
+            .into());
+        }
+
+        Ok(data)
+    }
+
     /// Get the remote port of the socket.
     ///
     /// # Returns
@@ -61,7 +144,25 @@ pub trait Socket {
     ///
     /// # Returns
     /// The local SocketAddr.
+    #[cfg_attr(not(feature = "packet_capture"), allow(dead_code))]
     fn local_addr(&self) -> std::io::Result<SocketAddr>;
+
+    /// The round trip time of the first exchange: from sending the request to
+    /// the end of the first receive of its response, which for TCP is either
+    /// the bytes asked for by [receive_exact](Socket::receive_exact) or the
+    /// whole response, read until the server closes the connection.
+    ///
+    /// # Returns
+    /// The time, `None` until something was both sent and received.
+    fn ping(&self) -> Option<Duration> { None }
+
+    /// Name the game protocol spoken over the socket (`valve`, `quake`...),
+    /// for the captured packets to be annotated with, sockets that aren't
+    /// captured ignoring it.
+    ///
+    /// # Arguments
+    /// * `hint` - The name of the protocol.
+    fn set_protocol_hint(&mut self, _hint: &'static str) {}
 }
 
 /// Implementation of a TCP socket.
@@ -70,18 +171,32 @@ pub struct TcpSocketImpl {
     socket: net::TcpStream,
     /// The address of the remote host.
     address: SocketAddr,
+    /// The read timeout of the settings, see [timeout_before].
+    read_timeout: Option<Duration>,
+    /// When the socket stops waiting for the server.
+    deadline: Option<Instant>,
+    ping: PingTimer,
 }
 
 impl Socket for TcpSocketImpl {
     fn new(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = TimeoutSettings::get_connect_or_default(timeout_settings).map_or_else(
-            || net::TcpStream::connect(address),
-            |timeout| net::TcpStream::connect_timeout(address, timeout),
+        let deadline = TimeoutSettings::get_deadline_end_or_default(timeout_settings);
+        // Connecting counts towards the deadline too.
+        let socket = timeout_before(TimeoutSettings::get_connect_or_default(timeout_settings), deadline).and_then(
+            |timeout| {
+                match timeout {
+                    Some(timeout) => net::TcpStream::connect_timeout(address, timeout),
+                    None => net::TcpStream::connect(address),
+                }
+            },
         );
 
         let socket = Self {
             socket: socket.map_err(|e| SocketConnect.context(e))?,
             address: *address,
+            read_timeout: TimeoutSettings::get_read_and_write_or_defaults(timeout_settings).0,
+            deadline,
+            ping: PingTimer::default(),
         };
 
         socket.apply_timeout(timeout_settings)?;
@@ -98,21 +213,52 @@ impl Socket for TcpSocketImpl {
     }
 
     fn send(&mut self, data: &[u8]) -> GDResult<()> {
-        self.socket.write(data).map_err(|e| PacketSend.context(e))?;
+        let sent = self.socket.write(data).map_err(|e| PacketSend.context(e))?;
+        count_exchanged_bytes(sent, 0);
+        self.ping.sent();
+
         Ok(())
     }
 
     fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        if self.deadline.is_some() {
+            let timeout = timeout_before(self.read_timeout, self.deadline).map_err(|e| PacketReceive.context(e))?;
+            self.socket
+                .set_read_timeout(timeout)
+                .map_err(|e| PacketReceive.context(e))?;
+        }
+
         let mut buf = Vec::with_capacity(size.unwrap_or(DEFAULT_PACKET_SIZE));
         self.socket
             .read_to_end(&mut buf)
             .map_err(|e| PacketReceive.context(e))?;
+        count_exchanged_bytes(0, buf.len());
+        self.ping.received();
+
+        Ok(buf)
+    }
+
+    fn receive_exact(&mut self, size: usize) -> GDResult<Vec<u8>> {
+        if self.deadline.is_some() {
+            let timeout = timeout_before(self.read_timeout, self.deadline).map_err(|e| PacketReceive.context(e))?;
+            self.socket
+                .set_read_timeout(timeout)
+                .map_err(|e| PacketReceive.context(e))?;
+        }
+
+        let mut buf = vec![0; size];
+        self.socket
+            .read_exact(&mut buf)
+            .map_err(|e| PacketReceive.context(e))?;
+        count_exchanged_bytes(0, size);
+        self.ping.received();
 
         Ok(buf)
     }
 
     fn port(&self) -> u16 { self.address.port() }
     fn local_addr(&self) -> std::io::Result<SocketAddr> { self.socket.local_addr() }
+    fn ping(&self) -> Option<Duration> { self.ping.ping }
 }
 
 /// Implementation of a UDP socket.
@@ -121,15 +267,29 @@ pub struct UdpSocketImpl {
     socket: net::UdpSocket,
     /// The address of the remote host.
     address: SocketAddr,
+    /// The read timeout of the settings, see [timeout_before].
+    read_timeout: Option<Duration>,
+    /// When the socket stops waiting for the server.
+    deadline: Option<Instant>,
+    ping: PingTimer,
 }
 
 impl Socket for UdpSocketImpl {
     fn new(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
-        let socket = net::UdpSocket::bind("0.0.0.0:0").map_err(|e| SocketBind.context(e))?;
+        // Bind to the address family of the remote, so that IPv6 hosts can be
+        // reached too.
+        let local_address: SocketAddr = match address {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = net::UdpSocket::bind(local_address).map_err(|e| SocketBind.context(e))?;
 
         let socket = Self {
             socket,
             address: *address,
+            read_timeout: TimeoutSettings::get_read_and_write_or_defaults(timeout_settings).0,
+            deadline: TimeoutSettings::get_deadline_end_or_default(timeout_settings),
+            ping: PingTimer::default(),
         };
 
         socket.apply_timeout(timeout_settings)?;
@@ -146,25 +306,40 @@ impl Socket for UdpSocketImpl {
     }
 
     fn send(&mut self, data: &[u8]) -> GDResult<()> {
-        self.socket
+        let sent = self
+            .socket
             .send_to(data, self.address)
             .map_err(|e| PacketSend.context(e))?;
+        count_exchanged_bytes(sent, 0);
+        self.ping.sent();
 
         Ok(())
     }
 
     fn receive(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        // Each packet gets the whole read timeout, as long as the deadline
+        // isn't closer.
+        if self.deadline.is_some() {
+            let timeout = timeout_before(self.read_timeout, self.deadline).map_err(|e| PacketReceive.context(e))?;
+            self.socket
+                .set_read_timeout(timeout)
+                .map_err(|e| PacketReceive.context(e))?;
+        }
+
         let mut buf: Vec<u8> = vec![0; size.unwrap_or(DEFAULT_PACKET_SIZE)];
         let (number_of_bytes_received, _) = self
             .socket
             .recv_from(&mut buf)
             .map_err(|e| PacketReceive.context(e))?;
+        count_exchanged_bytes(0, number_of_bytes_received);
+        self.ping.received();
 
         Ok(buf[.. number_of_bytes_received].to_vec())
     }
 
     fn port(&self) -> u16 { self.address.port() }
     fn local_addr(&self) -> std::io::Result<SocketAddr> { self.socket.local_addr() }
+    fn ping(&self) -> Option<Duration> { self.ping.ping }
 }
 
 #[cfg(not(feature = "packet_capture"))]
@@ -179,9 +354,10 @@ pub(crate) type TcpSocket = crate::capture::socket::CapturedTcpSocket;
 
 #[cfg(test)]
 mod tests {
-    use std::thread;
+    use std::{thread, time::Duration};
 
     use super::*;
+    use crate::TimeoutPhase;
 
     #[test]
     fn test_tcp_socket_send_and_receive() {
@@ -245,4 +421,128 @@ mod tests {
 
         assert_eq!(message, &received_message[..]);
     }
+
+    #[test]
+    fn ping_is_measured_from_the_last_request() {
+        const DELAY: Duration = Duration::from_millis(100);
+
+        let server = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let bound_address = server.local_addr().unwrap();
+        let server_thread = thread::spawn(move || {
+            let mut buf = [0; 16];
+            // The first request is taken as lost, the resent one is answered
+            // after the delay.
+            server.recv_from(&mut buf).unwrap();
+            let (_, src_addr) = server.recv_from(&mut buf).unwrap();
+            thread::sleep(DELAY);
+            server.send_to(b"first", src_addr).unwrap();
+            server.send_to(b"second", src_addr).unwrap();
+        });
+
+        let mut socket = UdpSocket::new(&bound_address, &None).unwrap();
+        assert_eq!(socket.ping(), None);
+        socket.send(b"lost").unwrap();
+        thread::sleep(DELAY * 4);
+        socket.send(b"resent").unwrap();
+        assert_eq!(socket.ping(), None);
+
+        socket.receive(None).unwrap();
+        let ping = socket.ping().unwrap();
+        assert!(ping >= DELAY && ping < DELAY * 4, "{ping:?}");
+
+        // Only the first exchange is measured.
+        socket.receive(None).unwrap();
+        assert_eq!(socket.ping(), Some(ping));
+        server_thread.join().unwrap();
+    }
+
+    #[test]
+    fn refused_connect_is_not_a_timeout() {
+        // Nothing listens on the port once the listener is dropped.
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let error = TcpSocket::new(&address, &None).err().unwrap();
+        assert_eq!(error.kind, SocketConnect);
+        assert_eq!(error.timeout_phase(), None);
+    }
+
+    #[test]
+    #[ignore = "Needs the traffic to a non-routable address to be dropped"]
+    fn filtered_connect_times_out() {
+        let timeout_settings = TimeoutSettings::new(None, None, Some(Duration::from_millis(200)), 0).ok();
+        let address = SocketAddr::from(([10, 255, 255, 1], 27015));
+
+        let error = TcpSocket::new(&address, &timeout_settings).err().unwrap();
+        assert_eq!(error.timeout_phase(), Some(TimeoutPhase::Connect));
+    }
+
+    #[test]
+    fn silent_server_times_out_reading() {
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let timeout_settings = TimeoutSettings::new(Some(Duration::from_millis(50)), None, None, 0).ok();
+
+        let mut socket = TcpSocket::new(&listener.local_addr().unwrap(), &timeout_settings).unwrap();
+        let (_stream, _) = listener.accept().unwrap();
+
+        let error = socket.receive(None).err().unwrap();
+        assert_eq!(error.timeout_phase(), Some(TimeoutPhase::Read));
+    }
+
+    #[test]
+    fn deadline_bounds_every_packet() {
+        const DEADLINE: Duration = Duration::from_millis(300);
+
+        let server = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let timeout_settings = TimeoutSettings::new(Some(Duration::from_millis(200)), None, None, 0)
+            .unwrap()
+            .with_deadline(Some(DEADLINE))
+            .ok();
+        let mut socket = UdpSocket::new(&server.local_addr().unwrap(), &timeout_settings).unwrap();
+        socket.send(b"start").unwrap();
+
+        // Sends a packet well within each read timeout, for longer than the
+        // deadline.
+        let mut buf = [0; 16];
+        let (_, client) = server.recv_from(&mut buf).unwrap();
+        thread::spawn(move || {
+            for _ in 0 .. 20 {
+                let _ = server.send_to(b"fragment", client);
+                thread::sleep(Duration::from_millis(50));
+            }
+        });
+
+        let start = Instant::now();
+        let error = loop {
+            if let Err(error) = socket.receive(None) {
+                break error;
+            }
+        };
+        assert_eq!(error.timeout_phase(), Some(TimeoutPhase::Read));
+        assert!(start.elapsed() < DEADLINE * 2);
+        assert!(TimeoutSettings::default().with_deadline(Some(Duration::ZERO)).is_err());
+    }
+
+    #[test]
+    fn started_deadline_is_shared_by_the_sockets() {
+        let server = net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let timeout_settings = TimeoutSettings::start_deadline_of(
+            TimeoutSettings::default()
+                .with_deadline(Some(Duration::from_millis(100)))
+                .ok(),
+        );
+        thread::sleep(Duration::from_millis(150));
+
+        // Opened once the deadline passed, the socket doesn't wait at all.
+        let start = Instant::now();
+        let mut socket = UdpSocket::new(&server.local_addr().unwrap(), &timeout_settings).unwrap();
+        let error = socket.receive(None).err().unwrap();
+        assert_eq!(error.timeout_phase(), Some(TimeoutPhase::Read));
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let error = TcpSocket::new(&listener.local_addr().unwrap(), &timeout_settings).err().unwrap();
+        assert_eq!(error.timeout_phase(), Some(TimeoutPhase::Connect));
+    }
 }
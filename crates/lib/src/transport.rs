@@ -0,0 +1,254 @@
+//! Pluggable transports used by the protocol queries.
+//!
+//! Queries that accept a [Transport] can be driven by something else than a
+//! real socket, [MockTransport] replays scripted responses so that the whole
+//! query and parsing flow can be tested without touching the network.
+
+use crate::{
+    protocols::types::TimeoutSettings,
+    socket::{Socket, TcpSocket, UdpSocket},
+    GDErrorKind::{InvalidInput, PacketReceive},
+    GDResult,
+};
+
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    time::{Duration, SystemTime},
+};
+
+/// A connection to a game server that data can be exchanged over.
+///
+/// Queries can be routed through something else than the sockets the library
+/// opens (a proxy, an already connected socket...) by implementing it, only
+/// [send](Transport::send) and [recv_timeout](Transport::recv_timeout) being
+/// needed for a transport that is handed over already connected.
+pub trait Transport {
+    /// Open a new transport to the remote address.
+    ///
+    /// Transports that can't be opened by address fail with an
+    /// [InvalidInput] error, which is the default.
+    ///
+    /// # Arguments
+    /// * `address` - The address to connect to.
+    /// * `timeout_settings` - Optional timeout settings for the transport.
+    fn connect(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self>
+    where Self: Sized {
+        let _ = timeout_settings;
+        Err(InvalidInput.context(format!("This transport can't be opened to {address}")))
+    }
+
+    /// Send data to the remote.
+    ///
+    /// # Arguments
+    /// * `data` - Data to be sent.
+    fn send(&mut self, data: &[u8]) -> GDResult<()>;
+
+    /// Receive data from the remote, waiting at most for the read timeout the
+    /// transport was connected with.
+    ///
+    /// # Arguments
+    /// * `size` - Optional maximum size of data to receive.
+    fn recv_timeout(&mut self, size: Option<usize>) -> GDResult<Vec<u8>>;
+
+    /// Name the game protocol spoken over the transport (`valve`,
+    /// `quake`...), for the captured packets to be annotated with, transports
+    /// that aren't captured sockets ignoring it.
+    fn set_protocol_hint(&mut self, _hint: &'static str) {}
+
+    /// The round trip time of the first exchange (from sending the first
+    /// request to receiving the first reply), `None` (the default) if the
+    /// transport doesn't measure it.
+    fn ping(&self) -> Option<Duration> { None }
+}
+
+/// A transport over an UDP socket.
+pub struct UdpTransport(UdpSocket);
+
+impl Transport for UdpTransport {
+    fn connect(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
+        UdpSocket::new(address, timeout_settings).map(Self)
+    }
+
+    fn send(&mut self, data: &[u8]) -> GDResult<()> { self.0.send(data) }
+    fn recv_timeout(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { self.0.receive(size) }
+    fn set_protocol_hint(&mut self, hint: &'static str) { self.0.set_protocol_hint(hint) }
+    fn ping(&self) -> Option<Duration> { self.0.ping() }
+}
+
+/// A transport over a TCP stream.
+pub struct TcpTransport(TcpSocket);
+
+impl Transport for TcpTransport {
+    fn connect(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
+        TcpSocket::new(address, timeout_settings).map(Self)
+    }
+
+    fn send(&mut self, data: &[u8]) -> GDResult<()> { self.0.send(data) }
+    fn recv_timeout(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> { self.0.receive(size) }
+    fn set_protocol_hint(&mut self, hint: &'static str) { self.0.set_protocol_hint(hint) }
+    fn ping(&self) -> Option<Duration> { self.0.ping() }
+}
+
+/// A transport that replays scripted responses, one per receive, and records
+/// everything that is sent to it.
+///
+/// Connecting one results in a transport without any scripted response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MockTransport {
+    responses: VecDeque<Vec<u8>>,
+    sent: Vec<Vec<u8>>,
+}
+
+impl MockTransport {
+    /// Create a transport that will reply with the given responses, in order.
+    pub fn new<I: IntoIterator<Item = Vec<u8>>>(responses: I) -> Self {
+        Self {
+            responses: responses.into_iter().collect(),
+            sent: Vec::new(),
+        }
+    }
+
+    /// The data sent so far, in order.
+    pub fn sent(&self) -> &[Vec<u8>] { &self.sent }
+
+    /// The number of scripted responses that weren't received yet.
+    pub fn remaining(&self) -> usize { self.responses.len() }
+}
+
+impl Transport for MockTransport {
+    fn connect(_address: &SocketAddr, _timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
+        Ok(Self::default())
+    }
+
+    fn send(&mut self, data: &[u8]) -> GDResult<()> {
+        self.sent.push(data.to_vec());
+
+        Ok(())
+    }
+
+    /// Pops the next scripted response, truncating it to `size` like a
+    /// datagram socket would. Running out of responses behaves as a timeout.
+    fn recv_timeout(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        let mut response = self
+            .responses
+            .pop_front()
+            .ok_or_else(|| PacketReceive.context("No scripted response left"))?;
+
+        if let Some(size) = size {
+            response.truncate(size);
+        }
+
+        Ok(response)
+    }
+}
+
+/// Whether a [ExchangedPacket] was sent or received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PacketDirection {
+    Sent,
+    Received,
+}
+
+/// A packet recorded by a [RecordingTransport].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExchangedPacket {
+    pub direction: PacketDirection,
+    /// When the packet was sent or received.
+    pub timestamp: SystemTime,
+    pub data: Vec<u8>,
+}
+
+/// A transport that records the packets exchanged over another one, whether
+/// the query succeeds or not, to attach them to bug reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordingTransport<T> {
+    inner: T,
+    packets: Vec<ExchangedPacket>,
+}
+
+impl<T> RecordingTransport<T> {
+    /// Record the packets exchanged over `inner`.
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            packets: Vec::new(),
+        }
+    }
+
+    /// The packets exchanged so far, in order.
+    pub fn packets(&self) -> &[ExchangedPacket] { &self.packets }
+
+    /// Take the packets exchanged so far.
+    pub fn into_packets(self) -> Vec<ExchangedPacket> { self.packets }
+
+    fn record(&mut self, direction: PacketDirection, data: &[u8]) {
+        self.packets.push(ExchangedPacket {
+            direction,
+            timestamp: SystemTime::now(),
+            data: data.to_vec(),
+        });
+    }
+}
+
+impl<T: Transport> Transport for RecordingTransport<T> {
+    fn connect(address: &SocketAddr, timeout_settings: &Option<TimeoutSettings>) -> GDResult<Self> {
+        T::connect(address, timeout_settings).map(Self::new)
+    }
+
+    fn send(&mut self, data: &[u8]) -> GDResult<()> {
+        self.inner.send(data)?;
+        self.record(PacketDirection::Sent, data);
+
+        Ok(())
+    }
+
+    fn recv_timeout(&mut self, size: Option<usize>) -> GDResult<Vec<u8>> {
+        let data = self.inner.recv_timeout(size)?;
+        self.record(PacketDirection::Received, &data);
+
+        Ok(data)
+    }
+
+    fn set_protocol_hint(&mut self, hint: &'static str) { self.inner.set_protocol_hint(hint) }
+    fn ping(&self) -> Option<Duration> { self.inner.ping() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mock_replays_in_order() {
+        let mut transport = MockTransport::new([vec![1, 2, 3], vec![4]]);
+
+        transport.send(&[9]).unwrap();
+        assert_eq!(transport.recv_timeout(Some(2)).unwrap(), vec![1, 2]);
+        assert_eq!(transport.recv_timeout(None).unwrap(), vec![4]);
+        assert!(transport.recv_timeout(None).is_err());
+        assert_eq!(transport.sent(), &[vec![9]]);
+        assert_eq!(transport.remaining(), 0);
+    }
+
+    #[test]
+    fn recording_keeps_the_exchange() {
+        let mut transport = RecordingTransport::new(MockTransport::new([vec![1, 2]]));
+
+        transport.send(&[9]).unwrap();
+        transport.recv_timeout(None).unwrap();
+        assert!(transport.recv_timeout(None).is_err());
+
+        let packets: Vec<_> = transport
+            .into_packets()
+            .into_iter()
+            .map(|packet| (packet.direction, packet.data))
+            .collect();
+        assert_eq!(
+            packets,
+            [
+                (PacketDirection::Sent, vec![9]),
+                (PacketDirection::Received, vec![1, 2])
+            ]
+        );
+    }
+}
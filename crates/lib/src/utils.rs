@@ -1,30 +1,67 @@
-use crate::GDErrorKind::{PacketOverflow, PacketReceive, PacketSend, PacketUnderflow};
+#[cfg(feature = "games")]
+use crate::GDErrorKind::PacketOverflow;
+use crate::GDErrorKind::PacketReceive;
 use crate::GDResult;
+#[cfg(feature = "games")]
+use crate::PacketTooShort;
+#[cfg(feature = "games")]
 use std::cmp::Ordering;
+use std::thread;
+use std::time::{Duration, Instant};
 
+#[cfg(feature = "games")]
 pub fn error_by_expected_size(expected: usize, size: usize) -> GDResult<()> {
     match size.cmp(&expected) {
         Ordering::Greater => Err(PacketOverflow.into()),
-        Ordering::Less => Err(PacketUnderflow.into()),
+        Ordering::Less => {
+            Err(PacketTooShort {
+                expected,
+                got: size,
+                offset: 0,
+            }
+            .into())
+        }
         Ordering::Equal => Ok(()),
     }
 }
 
 pub const fn u8_lower_upper(n: u8) -> (u8, u8) { (n & 15, n >> 4) }
 
-/// Run a closure `retry_count+1` times while it returns [PacketReceive] or
-/// [PacketSend] errors, returning the first success, other Error, or after
-/// `retry_count+1` tries the last [PacketReceive] or [PacketSend] error.
-pub fn retry_on_timeout<T>(mut retry_count: usize, mut fetch: impl FnMut() -> GDResult<T>) -> GDResult<T> {
+/// Run a closure `retry_count+1` times while it returns
+/// [retryable](crate::GDError::is_retryable) errors, returning the first
+/// success, the first permanent error, or after `retry_count+1` tries the last
+/// retryable error.
+pub fn retry_on_timeout<T>(retry_count: usize, fetch: impl FnMut() -> GDResult<T>) -> GDResult<T> {
+    retry_on_timeout_with_delay(retry_count, Duration::ZERO, None, fetch)
+}
+
+/// Same as [retry_on_timeout], waiting for `delay` before each retry. Once
+/// `deadline` passed no retry is made, the delay being cut short by it.
+pub fn retry_on_timeout_with_delay<T>(
+    mut retry_count: usize,
+    delay: Duration,
+    deadline: Option<Instant>,
+    mut fetch: impl FnMut() -> GDResult<T>,
+) -> GDResult<T> {
     let mut last_err = PacketReceive.context("Retry count was 0");
     retry_count += 1;
     while retry_count > 0 {
         last_err = match fetch() {
             Ok(r) => return Ok(r),
-            Err(e) if e.kind == PacketReceive || e.kind == PacketSend => e,
+            Err(e) if e.is_retryable() => e,
             Err(e) => return Err(e),
         };
         retry_count -= 1;
+
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining.map_or(false, |remaining| remaining.is_zero()) {
+            break;
+        }
+
+        let delay = remaining.map_or(delay, |remaining| delay.min(remaining));
+        if retry_count > 0 && !delay.is_zero() {
+            thread::sleep(delay);
+        }
     }
     Err(last_err)
 }
@@ -78,6 +115,27 @@ macro_rules! maybe_gather {
 
 pub(crate) use maybe_gather;
 
+/// (De)serialize optional bytes (like the raw payloads kept by the responses)
+/// as a base64 string rather than an array of numbers, with
+/// `#[serde(default, with = "crate::utils::base64_bytes")]`.
+#[cfg(feature = "serde")]
+pub(crate) mod base64_bytes {
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    const ENGINE: base64::engine::GeneralPurpose = base64::engine::general_purpose::STANDARD;
+
+    pub(crate) fn serialize<S: Serializer>(bytes: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+        bytes.as_ref().map(|bytes| ENGINE.encode(bytes)).serialize(serializer)
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|encoded| ENGINE.decode(encoded).map_err(serde::de::Error::custom))
+            .transpose()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::retry_on_timeout;
@@ -93,6 +151,7 @@ mod tests {
         assert_eq!(super::u8_lower_upper(171), (11, 10));
     }
 
+    #[cfg(feature = "games")]
     #[test]
     fn error_by_expected_size() {
         assert!(super::error_by_expected_size(69, 69).is_ok());
@@ -142,6 +201,34 @@ mod tests {
         assert_eq!(r.unwrap_err().kind, PacketReceive);
     }
 
+    #[test]
+    fn retry_stops_at_the_deadline() {
+        const DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+        let mut tries = 0;
+        let start = std::time::Instant::now();
+        let deadline = start + std::time::Duration::from_millis(100);
+        let r: GDResult<()> = super::retry_on_timeout_with_delay(5, DELAY, Some(deadline), || {
+            tries += 1;
+            Err(PacketReceive.context("test"))
+        });
+        assert_eq!(r.unwrap_err().kind, PacketReceive);
+        // The delay was cut short by the deadline, after which no retry was made.
+        assert!(start.elapsed() < DELAY);
+        assert_eq!(tries, 2);
+    }
+
+    #[test]
+    fn retry_fails_fast_on_permanent_io_error() {
+        let mut i = 0u8;
+        let r: GDResult<()> = retry_on_timeout(50, || {
+            i += 1;
+            Err(PacketReceive.context(std::io::Error::from(std::io::ErrorKind::ConnectionRefused)))
+        });
+        assert_eq!(r.unwrap_err().kind, PacketReceive);
+        assert_eq!(i, 1);
+    }
+
     #[test]
     fn retry_with_non_timeout_error() {
         let mut i = 0u8;
@@ -157,6 +244,24 @@ mod tests {
         assert_eq!(r.unwrap_err().kind, PacketBad);
     }
 
+    #[test]
+    fn retries_wait_for_the_delay() {
+        const DELAY: std::time::Duration = std::time::Duration::from_millis(20);
+
+        let start = std::time::Instant::now();
+        let mut i = 0u8;
+        let r: GDResult<()> = super::retry_on_timeout_with_delay(2, DELAY, None, || {
+            i += 1;
+            Err(PacketReceive.context("test"))
+        });
+        assert_eq!(r.unwrap_err().kind, PacketReceive);
+        assert_eq!(i, 3);
+        // Not waited for after the last try.
+        let elapsed = start.elapsed();
+        assert!(elapsed >= DELAY * 2);
+        assert!(elapsed < DELAY * 3 + std::time::Duration::from_millis(500));
+    }
+
     fn gather_success(n: i32) -> GDResult<i32> { Ok(n) }
 
     fn gather_fail(err: &'static str) -> GDResult<i32> { Err(GDErrorKind::PacketSend.context(err)) }
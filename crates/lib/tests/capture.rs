@@ -0,0 +1,169 @@
+#![cfg(all(test, feature = "packet_capture"))]
+
+use std::{
+    io::Write,
+    net::UdpSocket,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use gamedig::{
+    capture::{capture_to_writer, finish_capture, with_capture, CaptureOptions},
+    protocols::valve::{self, Engine},
+    TimeoutSettings,
+};
+use pcap_file::pcapng::{
+    blocks::{name_resolution::Record, section_header::SectionHeaderOption},
+    Block,
+    PcapNgReader,
+};
+
+/// An in-memory capture, that can still be read once handed to the capture.
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> { self.0.lock().unwrap().write(data) }
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+}
+
+#[test]
+fn udp_exchange_is_captured_in_memory() {
+    const REPLY: &[u8] = b"\xFF\xFF\xFF\xFFI\x11";
+
+    let (address, responder) = one_shot_server(REPLY);
+
+    let buffer = SharedBuffer::default();
+    capture_to_writer(buffer.clone()).unwrap();
+    let timeout_settings = TimeoutSettings::new(Some(Duration::from_secs(1)), None, None, 0).ok();
+    // The truncated reply can't be parsed, but is captured all the same.
+    assert!(valve::query(&address, Engine::new(440), None, timeout_settings).is_err());
+    finish_capture().unwrap();
+    responder.join().unwrap();
+
+    let packets = captured_packets(&buffer.0.lock().unwrap());
+
+    assert_eq!(packets.len(), 2);
+    assert!(contains(&packets[0], b"Source Engine Query"));
+    assert!(contains(&packets[1], REPLY));
+}
+
+/// A server answering a single query with `reply`.
+fn one_shot_server(reply: &'static [u8]) -> (std::net::SocketAddr, thread::JoinHandle<()>) {
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    let responder = thread::spawn(move || {
+        let mut buffer = [0; 1400];
+        let (_, client) = server.recv_from(&mut buffer).unwrap();
+        server.send_to(reply, client).unwrap();
+    });
+
+    (address, responder)
+}
+
+fn captured_packets(data: &[u8]) -> Vec<Vec<u8>> {
+    let mut reader = PcapNgReader::new(data).unwrap();
+    let mut packets = Vec::new();
+    while let Some(block) = reader.next_block() {
+        if let Block::EnhancedPacket(packet) = block.unwrap() {
+            packets.push(packet.data.to_vec());
+        }
+    }
+
+    packets
+}
+
+#[test]
+fn scoped_captures_are_kept_apart() {
+    let queries: Vec<_> = [&b"\xFF\xFF\xFF\xFFIfirst"[..], b"\xFF\xFF\xFF\xFFIsecond"]
+        .into_iter()
+        .map(|reply| {
+            thread::spawn(move || {
+                let (address, responder) = one_shot_server(reply);
+                let buffer = SharedBuffer::default();
+                let timeout_settings = TimeoutSettings::new(Some(Duration::from_secs(1)), None, None, 0).ok();
+
+                let result = with_capture(buffer.clone(), CaptureOptions::default(), || {
+                    valve::query(&address, Engine::new(440), None, timeout_settings)
+                })
+                .unwrap();
+                assert!(result.is_err());
+                responder.join().unwrap();
+
+                let data = buffer.0.lock().unwrap().clone();
+                (reply, captured_packets(&data))
+            })
+        })
+        .collect();
+
+    for query in queries {
+        let (reply, packets) = query.join().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert!(contains(&packets[1], reply));
+    }
+}
+
+#[test]
+fn resolved_connect_strings_are_named() {
+    // Never answers, the query is only made for its stream to be captured.
+    let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = server.local_addr().unwrap().port();
+    let buffer = SharedBuffer::default();
+    let options = CaptureOptions {
+        name_resolution: true,
+        annotate_hostnames: true,
+        ..Default::default()
+    };
+
+    let address = with_capture(buffer.clone(), options, || {
+        let address = valve::parse_connect_string(&format!("connect localhost:{port}")).unwrap();
+        let timeout_settings = TimeoutSettings::new(Some(Duration::from_millis(100)), None, None, 0).ok();
+        assert!(valve::query(&address, Engine::new(440), None, timeout_settings).is_err());
+
+        address
+    })
+    .unwrap();
+
+    let data = buffer.0.lock().unwrap().clone();
+    let mut reader = PcapNgReader::new(&data[..]).unwrap();
+    let (mut names, mut comments) = (Vec::new(), Vec::new());
+    while let Some(block) = reader.next_block() {
+        match block.unwrap() {
+            Block::NameResolution(block) => {
+                for record in block.records {
+                    match record {
+                        Record::Ipv4(record) => names.extend(record.names.iter().map(|name| name.to_string())),
+                        Record::Ipv6(record) => names.extend(record.names.iter().map(|name| name.to_string())),
+                        _ => {}
+                    }
+                }
+            }
+            Block::EnhancedPacket(packet) => {
+                comments.extend(packet.options.iter().map(|option| format!("{option:?}")));
+            }
+            _ => {}
+        }
+    }
+
+    assert_eq!(names, ["localhost"]);
+    assert!(comments
+        .iter()
+        .any(|comment| comment.contains(&format!("querying localhost (resolved {})", address.ip()))));
+}
+
+#[test]
+fn section_header_names_the_application() {
+    let buffer = SharedBuffer::default();
+    with_capture(buffer.clone(), CaptureOptions::default(), || {}).unwrap();
+
+    let data = buffer.0.lock().unwrap().clone();
+    let reader = PcapNgReader::new(&data[..]).unwrap();
+    let options = &reader.section().options;
+    assert!(options.contains(&SectionHeaderOption::UserApplication(
+        format!("rust-gamedig {}", env!("CARGO_PKG_VERSION")).into()
+    )));
+    assert!(options.contains(&SectionHeaderOption::OS(std::env::consts::OS.into())));
+}
+
+fn contains(packet: &[u8], payload: &[u8]) -> bool { packet.windows(payload.len()).any(|window| window == payload) }